@@ -4,6 +4,11 @@ use std::collections::HashMap;
 pub enum Method {
     Get,
     Post,
+    Put,
+    Delete,
+    Patch,
+    Head,
+    Options,
     Uninitialized,
 }
 
@@ -12,11 +17,32 @@ impl From<&str> for Method {
         match s {
             "GET" => Method::Get,
             "POST" => Method::Post,
+            "PUT" => Method::Put,
+            "DELETE" => Method::Delete,
+            "PATCH" => Method::Patch,
+            "HEAD" => Method::Head,
+            "OPTIONS" => Method::Options,
             _ => Method::Uninitialized,
         }
     }
 }
 
+impl Method {
+    // The method name as it appears on the request line
+    pub fn as_str(&self) -> &str {
+        match self {
+            Method::Get => "GET",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+            Method::Patch => "PATCH",
+            Method::Head => "HEAD",
+            Method::Options => "OPTIONS",
+            Method::Uninitialized => "",
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Version {
     V1_1,
@@ -28,6 +54,7 @@ impl From<&str> for Version {
     fn from(s: &str) -> Version {
         match s {
             "HTTP/1.1" => Version::V1_1,
+            "HTTP/2.0" => Version::V2_0,
             _ => Version::Uninitialized,
         }
     }
@@ -53,10 +80,15 @@ impl From<String> for HttpRequest {
         let mut parsed_version = Version::V1_1;
         let mut parsed_resource = Resource::Path("".to_string());
         let mut parsed_headers = HashMap::new();
-        let mut parsed_msg_body = "";
 
-        // Read each line in incoming HTTP request
-        for line in req.lines() {
+        // The message body is everything after the first blank line
+        let (head, parsed_msg_body) = match req.split_once("\r\n\r\n") {
+            Some((head, body)) => (head, body),
+            None => (req.as_str(), ""),
+        };
+
+        // Read each line in incoming HTTP request head
+        for line in head.lines() {
             // If the line read is request line, call function process_req_line()
             if line.contains("HTTP") {
                 let (method, resource, version) = process_req_line(line);
@@ -64,15 +96,11 @@ impl From<String> for HttpRequest {
                 parsed_version = version;
                 parsed_resource = resource;
             // If the line read is header line, call function process_header_line()
-            } else if line.contains(":") {
+            } else if line.contains(':') {
                 let (key, value) = process_header_line(line);
                 parsed_headers.insert(key, value);
-            //  If it is blank line, do nothing
-            } else if line.len() == 0 {
-                // If none of these, treat it as message body
-            } else {
-                parsed_msg_body = line;
             }
+            //  If it is blank line or anything else, do nothing
         }
         // Parse the incoming HTTP request into HttpRequest struct
         HttpRequest {
@@ -85,6 +113,16 @@ impl From<String> for HttpRequest {
     }
 }
 
+impl HttpRequest {
+    // Look up a header by name, ignoring case and surrounding whitespace
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.trim().eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.trim())
+    }
+}
+
 fn process_req_line(s: &str) -> (Method, Resource, Version) {
     // Parse the request line into individual chunks split by whitespaces.
     let mut words = s.split_whitespace();
@@ -103,20 +141,13 @@ fn process_req_line(s: &str) -> (Method, Resource, Version) {
 }
 
 fn process_header_line(s: &str) -> (String, String) {
-    // Parse the headerline into words split by separator (':')
-    let mut header_items = s.split(":");
-    let mut key = String::from("");
-    let mut value = String::from("");
-    // Extract the key part of the header
-    if let Some(k) = header_items.next() {
-        key = k.to_string();
-    }
-    // Extract the value part of the header
-    if let Some(v) = header_items.next() {
-        value = v.to_string()
+    // Parse the headerline into key and value, split at the first separator (':')
+    // so that values such as "localhost:3000" or "https://example.com" stay whole.
+    // The key is trimmed, so "Name : value" cannot pass for a different header.
+    match s.split_once(':') {
+        Some((key, value)) => (key.trim().to_string(), value.to_string()),
+        None => (s.trim().to_string(), String::from("")),
     }
-
-    (key, value)
 }
 
 #[cfg(test)]
@@ -126,12 +157,16 @@ mod tests {
     fn test_method_into() {
         let m: Method = "GET".into();
         assert_eq!(m, Method::Get);
+        let m: Method = "DELETE".into();
+        assert_eq!(m.as_str(), "DELETE");
     }
 
     #[test]
     fn test_version_into() {
         let m: Version = "HTTP/1.1".into();
         assert_eq!(m, Version::V1_1);
+        let m: Version = "HTTP/2.0".into();
+        assert_eq!(m, Version::V2_0);
     }
 
     #[test]
@@ -140,7 +175,7 @@ mod tests {
         let s: String = String::from("GET /greeting HTTP/1.1\r\nHost: localhost:3000\r\nUser-Agent: curl/7.64.1\r\nAccept: */*\r\n\r\n");
         let mut headers_expected = HashMap::new();
 
-        headers_expected.insert("Host".into(), " localhost".into());
+        headers_expected.insert("Host".into(), " localhost:3000".into());
         headers_expected.insert("Accept".into(), " */*".into());
         headers_expected.insert("User-Agent".into(), " curl/7.64.1".into());
 
@@ -150,4 +185,23 @@ mod tests {
         assert_eq!(Resource::Path("/greeting".to_string()), req.resource);
         assert_eq!(headers_expected, req.headers);
     }
+
+    #[test]
+    fn test_read_http_with_body() {
+        let s: String = String::from("POST /api/orders HTTP/1.1\r\ncontent-length: 28\r\n\r\n{\"order_id\": 1}\r\nsecond line");
+        let req: HttpRequest = s.into();
+        assert_eq!(Method::Post, req.method);
+        assert_eq!(Some("28"), req.header("Content-Length"));
+        assert_eq!("{\"order_id\": 1}\r\nsecond line", req.msg_body);
+    }
+
+    #[test]
+    fn test_header_names_are_trimmed() {
+        let s: String = String::from("GET / HTTP/1.1\r\nX-Authenticated-User : root\r\n\r\n");
+        let req: HttpRequest = s.into();
+        assert_eq!(
+            Some(&" root".to_string()),
+            req.headers.get("X-Authenticated-User")
+        );
+    }
 }
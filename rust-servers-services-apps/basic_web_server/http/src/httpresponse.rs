@@ -14,9 +14,9 @@ pub struct HttpResponse<'a> {
 impl<'a> Default for HttpResponse<'a> {
     fn default() -> Self {
         Self {
-            version: "HTTP/1.1".into(),
-            status_code: "200".into(),
-            status_text: "OK".into(),
+            version: "HTTP/1.1",
+            status_code: "200",
+            status_text: "OK",
             headers: None,
             body: None,
         }
@@ -32,7 +32,7 @@ impl<'a> HttpResponse<'a> {
         let mut response: HttpResponse<'a> = HttpResponse::default();
 
         if status_code != "200" {
-            response.status_code = status_code.into();
+            response.status_code = status_code;
         };
 
         response.headers = match &headers {
@@ -45,17 +45,43 @@ impl<'a> HttpResponse<'a> {
         };
 
         response.status_text = match response.status_code {
-            "200" => "OK".into(),
-            "400" => "Bad Request".into(),
-            "404" => "Not Found".into(),
-            "500" => "Internal Server Error".into(),
-            _ => "Not Found".into(),
+            "200" => "OK",
+            "204" => "No Content",
+            "301" => "Moved Permanently",
+            "302" => "Found",
+            "307" => "Temporary Redirect",
+            "308" => "Permanent Redirect",
+            "400" => "Bad Request",
+            "401" => "Unauthorized",
+            "403" => "Forbidden",
+            "404" => "Not Found",
+            "408" => "Request Timeout",
+            "413" => "Payload Too Large",
+            "414" => "URI Too Long",
+            "429" => "Too Many Requests",
+            "431" => "Request Header Fields Too Large",
+            "500" => "Internal Server Error",
+            "501" => "Not Implemented",
+            "502" => "Bad Gateway",
+            "503" => "Service Unavailable",
+            _ => "Not Found",
         };
 
         response.body = body;
         response
     }
 
+    // Add a header, or replace it if it is already set
+    pub fn add_header(&mut self, key: &'a str, value: &'a str) {
+        self.headers
+            .get_or_insert_with(HashMap::new)
+            .insert(key, value);
+    }
+
+    pub fn set_body(&mut self, body: String) {
+        self.body = Some(body);
+    }
+
     pub fn send_response(&self, write_stream: &mut impl Write) -> Result<()> {
         let res = self.clone();
         let response_string: String = String::from(res);
@@ -69,11 +95,11 @@ impl<'a> HttpResponse<'a> {
         self.version
     }
 
-    fn status_code(&self) -> &str {
+    pub fn status_code(&self) -> &str {
         self.status_code
     }
 
-    fn status_text(&self) -> &str {
+    pub fn status_text(&self) -> &str {
         self.status_text
     }
 
@@ -87,6 +113,14 @@ impl<'a> HttpResponse<'a> {
         header_string
     }
 
+    // The header fields as name/value pairs
+    pub fn header_fields(&self) -> Vec<(&str, &str)> {
+        match &self.headers {
+            Some(map) => map.iter().map(|(k, v)| (*k, *v)).collect(),
+            None => Vec::new(),
+        }
+    }
+
     pub fn body(&self) -> &str {
         match &self.body {
             Some(b) => b.as_str(),
@@ -105,7 +139,7 @@ impl<'a> From<HttpResponse<'a>> for String {
             &res1.status_code(),
             &res1.status_text(),
             &res1.headers(),
-            &res.body.unwrap().len(),
+            &res1.body().len(),
             &res1.body()
         )
     }
@@ -178,4 +212,28 @@ mod tests {
         let response_actual = "HTTP/1.1 404 Not Found\r\nContent-Type:text/html\r\nContent-Length: 33\r\n\r\nItem was shipped on 21st Dec 2020";
         assert_eq!(http_string, response_actual);
     }
+
+    #[test]
+    fn test_response_status_text_for_limits() {
+        assert_eq!(
+            HttpResponse::new("413", None, None).status_text,
+            "Payload Too Large"
+        );
+        assert_eq!(
+            HttpResponse::new("414", None, None).status_text,
+            "URI Too Long"
+        );
+        assert_eq!(
+            HttpResponse::new("431", None, None).status_text,
+            "Request Header Fields Too Large"
+        );
+        assert_eq!(
+            HttpResponse::new("408", None, None).status_text,
+            "Request Timeout"
+        );
+        assert_eq!(
+            HttpResponse::new("429", None, None).status_text,
+            "Too Many Requests"
+        );
+    }
 }
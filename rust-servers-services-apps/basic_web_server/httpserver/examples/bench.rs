@@ -0,0 +1,180 @@
+//! Compares the threaded and event loop server modes.
+//!
+//!     cargo run --release --example bench -- [CLIENTS] [SECONDS] [IDLE]
+//!
+//! Each mode is measured with CLIENTS concurrent clients sending requests
+//! for SECONDS, first on a new connection per request, then with IDLE extra
+//! connections held open and unused. The event loop is also measured with
+//! keep-alive connections, which the threaded mode does not support.
+//!
+//! The threaded server logs every connection on stdout, so the results are
+//! written to stderr; redirect stdout to /dev/null to see only them.
+
+use httpserver::limits::Limits;
+use httpserver::Server;
+use std::env;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const REQUEST: &[u8] = b"GET /api/shipping/orders HTTP/1.1\r\nHost: localhost\r\n";
+
+fn main() {
+    let args: Vec<usize> = env::args().skip(1).filter_map(|a| a.parse().ok()).collect();
+    let clients = args.first().copied().unwrap_or(16);
+    let seconds = args.get(1).copied().unwrap_or(3) as u64;
+    let idle = args.get(2).copied().unwrap_or(1000);
+    let duration = Duration::from_secs(seconds);
+
+    eprintln!(
+        "{:<12} {:<28} {:>10} {:>10} {:>10}",
+        "mode", "scenario", "req/s", "p50 ms", "p99 ms"
+    );
+    for event_loop in [false, true] {
+        let mode = if event_loop { "event loop" } else { "threaded" };
+        let addr = start(event_loop, clients + idle + 100);
+
+        report(
+            mode,
+            "new connection",
+            duration,
+            run(&addr, clients, duration, false),
+        );
+        if event_loop {
+            report(
+                mode,
+                "keep-alive",
+                duration,
+                run(&addr, clients, duration, true),
+            );
+        }
+        let held: Vec<TcpStream> = (0..idle)
+            .filter_map(|_| TcpStream::connect(&addr).ok())
+            .collect();
+        let scenario = format!("new connection, {} idle", held.len());
+        report(
+            mode,
+            &scenario,
+            duration,
+            run(&addr, clients, duration, false),
+        );
+        drop(held);
+    }
+}
+
+fn start(event_loop: bool, max_connections: usize) -> String {
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .to_string();
+    let limits = Limits {
+        max_connections,
+        ..Limits::default()
+    };
+    let mut builder = Server::builder().bind(&addr).limits(limits);
+    if event_loop {
+        let cpus = thread::available_parallelism().map_or(1, |n| n.get());
+        builder = builder.event_loop(cpus);
+    }
+    let server = builder.build().unwrap();
+    thread::spawn(move || server.run());
+    while TcpStream::connect(&addr).is_err() {
+        thread::sleep(Duration::from_millis(10));
+    }
+    addr
+}
+
+// Send requests from `clients` threads for `duration`, returning the
+// latency of every request
+fn run(addr: &str, clients: usize, duration: Duration, keep_alive: bool) -> Vec<Duration> {
+    let deadline = Instant::now() + duration;
+    let threads: Vec<_> = (0..clients)
+        .map(|_| {
+            let addr = addr.to_string();
+            thread::spawn(move || {
+                let mut latencies = Vec::new();
+                let mut conn: Option<BufReader<TcpStream>> = None;
+                while Instant::now() < deadline {
+                    let started = Instant::now();
+                    let ok = if keep_alive {
+                        let stream = conn.get_or_insert_with(|| {
+                            BufReader::new(TcpStream::connect(&addr).unwrap())
+                        });
+                        request_keep_alive(stream)
+                    } else {
+                        request_once(&addr)
+                    };
+                    if ok {
+                        latencies.push(started.elapsed());
+                    } else {
+                        conn = None;
+                    }
+                }
+                latencies
+            })
+        })
+        .collect();
+    threads
+        .into_iter()
+        .flat_map(|t| t.join().unwrap())
+        .collect()
+}
+
+fn request_once(addr: &str) -> bool {
+    let Ok(mut stream) = TcpStream::connect(addr) else {
+        return false;
+    };
+    let mut request = REQUEST.to_vec();
+    request.extend_from_slice(b"Connection: close\r\n\r\n");
+    let mut response = Vec::new();
+    stream.write_all(&request).is_ok()
+        && stream.read_to_end(&mut response).is_ok()
+        && response.starts_with(b"HTTP/1.1 200")
+}
+
+fn request_keep_alive(stream: &mut BufReader<TcpStream>) -> bool {
+    let mut request = REQUEST.to_vec();
+    request.extend_from_slice(b"\r\n");
+    if stream.get_mut().write_all(&request).is_err() {
+        return false;
+    }
+    // Read the head, then as many body bytes as it announces
+    let mut status = String::new();
+    let mut length = 0;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match stream.read_line(&mut line) {
+            Ok(0) | Err(_) => return false,
+            Ok(_) if line == "\r\n" => break,
+            Ok(_) if status.is_empty() => status = line.clone(),
+            Ok(_) => {
+                if let Some(n) = line.strip_prefix("Content-Length: ") {
+                    length = n.trim().parse().unwrap_or(0);
+                }
+            }
+        }
+    }
+    let mut body = vec![0; length];
+    stream.read_exact(&mut body).is_ok() && status.starts_with("HTTP/1.1 200")
+}
+
+fn report(mode: &str, scenario: &str, duration: Duration, mut latencies: Vec<Duration>) {
+    latencies.sort();
+    let percentile = |p: usize| {
+        latencies
+            .get(latencies.len() * p / 100)
+            .map_or(0.0, |d| d.as_secs_f64() * 1000.0)
+    };
+    eprintln!(
+        "{:<12} {:<28} {:>10.0} {:>10.2} {:>10.2}",
+        mode,
+        scenario,
+        latencies.len() as f64 / duration.as_secs_f64(),
+        percentile(50),
+        percentile(99)
+    );
+}
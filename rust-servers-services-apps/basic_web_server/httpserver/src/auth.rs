@@ -0,0 +1,285 @@
+//! The auth module protects path prefixes with HTTP Basic authentication,
+//! checked against an htpasswd-style file, or with static Bearer tokens.
+//!
+
+use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+
+use http::httprequest::{HttpRequest, Resource};
+
+// Header carrying the authenticated principal to handlers and upstreams.
+// Any value sent by the client is removed first.
+pub const PRINCIPAL_HEADER: &str = "X-Authenticated-User";
+
+#[derive(Default)]
+pub struct Auth {
+    areas: Vec<Area>,
+}
+
+// A protected path prefix
+struct Area {
+    prefix: String,
+    realm: String,
+    scheme: Scheme,
+}
+
+enum Scheme {
+    // Password hashes by user name
+    Basic(HashMap<String, String>),
+    // Principal names by token
+    Bearer(HashMap<String, String>),
+}
+
+// Result of checking a request's credentials
+#[derive(Debug, PartialEq)]
+pub enum Access {
+    // The path is not protected
+    Open,
+    // The credentials are valid for this principal
+    Granted(String),
+    // Missing or bad credentials, with the WWW-Authenticate challenge to send
+    Denied(String),
+}
+
+impl Auth {
+    pub fn new() -> Self {
+        Auth::default()
+    }
+
+    // Read protected areas from AUTH_AREAS, a ';' separated list of
+    // `prefix=basic|bearer@file[@realm]` entries, for example
+    // `/admin=basic@/etc/httpserver/htpasswd;/api=bearer@/etc/httpserver/tokens@orders`
+    pub fn from_env() -> Result<Self, String> {
+        let mut auth = Auth::new();
+        let config = env::var("AUTH_AREAS").unwrap_or_default();
+        for entry in config.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let (prefix, rest) = entry
+                .split_once('=')
+                .ok_or(format!("malformed auth area: {}", entry))?;
+            let mut parts = rest.split('@').map(str::trim);
+            let scheme = parts.next().unwrap_or_default();
+            let file = parts
+                .next()
+                .ok_or(format!("auth area {} needs a file", prefix))?;
+            let realm = parts.next().unwrap_or("httpserver");
+            let contents =
+                fs::read_to_string(file).map_err(|e| format!("cannot read {}: {}", file, e))?;
+            auth = match scheme {
+                "basic" => auth.basic(prefix.trim(), realm, &contents),
+                "bearer" => auth.bearer(prefix.trim(), realm, &contents),
+                other => return Err(format!("unknown auth scheme: {}", other)),
+            };
+        }
+        Ok(auth)
+    }
+
+    // Protect `prefix` with Basic auth. `htpasswd` holds `user:hash` lines,
+    // where the hash is bcrypt ($2y$, $2b$, ...) or argon2 ($argon2id$, ...).
+    pub fn basic(self, prefix: &str, realm: &str, htpasswd: &str) -> Self {
+        let users = parse_pairs(htpasswd)
+            .into_iter()
+            .map(|(user, hash)| (user.to_string(), hash.to_string()))
+            .collect();
+        self.protect(prefix, realm, Scheme::Basic(users))
+    }
+
+    // Protect `prefix` with Bearer tokens. `tokens` holds `name:token`
+    // lines; the name is the principal handlers see.
+    pub fn bearer(self, prefix: &str, realm: &str, tokens: &str) -> Self {
+        let tokens = parse_pairs(tokens)
+            .into_iter()
+            .map(|(name, token)| (token.to_string(), name.to_string()))
+            .collect();
+        self.protect(prefix, realm, Scheme::Bearer(tokens))
+    }
+
+    fn protect(mut self, prefix: &str, realm: &str, scheme: Scheme) -> Self {
+        self.areas.push(Area {
+            prefix: prefix.trim_end_matches('/').to_string(),
+            realm: realm.replace('"', ""),
+            scheme,
+        });
+        self
+    }
+
+    pub fn check(&self, req: &HttpRequest) -> Access {
+        let Resource::Path(path) = &req.resource;
+        let route = path.split('?').next().unwrap_or_default();
+        // The longest matching prefix wins
+        let area = self
+            .areas
+            .iter()
+            .filter(|area| {
+                route == area.prefix
+                    || area.prefix.is_empty()
+                    || route
+                        .strip_prefix(&area.prefix)
+                        .is_some_and(|rest| rest.starts_with('/'))
+            })
+            .max_by_key(|area| area.prefix.len());
+        match area {
+            Some(area) => area.check(req.header("Authorization")),
+            None => Access::Open,
+        }
+    }
+}
+
+impl Area {
+    fn check(&self, authorization: Option<&str>) -> Access {
+        let (scheme, credentials) = authorization
+            .and_then(|value| value.split_once(' '))
+            .map(|(scheme, credentials)| (scheme.to_ascii_lowercase(), credentials.trim()))
+            .unwrap_or_default();
+
+        match &self.scheme {
+            Scheme::Basic(users) => {
+                let principal = (scheme == "basic")
+                    .then(|| STANDARD.decode(credentials).ok())
+                    .flatten()
+                    .and_then(|decoded| String::from_utf8(decoded).ok())
+                    .and_then(|decoded| {
+                        let (user, password) = decoded.split_once(':')?;
+                        let hash = users.get(user)?;
+                        verify_password(password, hash).then(|| user.to_string())
+                    });
+                match principal {
+                    Some(user) => Access::Granted(user),
+                    None => {
+                        Access::Denied(format!("Basic realm=\"{}\", charset=\"UTF-8\"", self.realm))
+                    }
+                }
+            }
+            Scheme::Bearer(tokens) => {
+                if scheme != "bearer" {
+                    return Access::Denied(format!("Bearer realm=\"{}\"", self.realm));
+                }
+                let principal = tokens
+                    .iter()
+                    .find(|(token, _)| constant_time_eq(token.as_bytes(), credentials.as_bytes()))
+                    .map(|(_, name)| name.clone());
+                match principal {
+                    Some(name) => Access::Granted(name),
+                    None => Access::Denied(format!(
+                        "Bearer realm=\"{}\", error=\"invalid_token\"",
+                        self.realm
+                    )),
+                }
+            }
+        }
+    }
+}
+
+// `key:value` lines, skipping blanks and # comments
+fn parse_pairs(contents: &str) -> Vec<(&str, &str)> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .collect()
+}
+
+fn verify_password(password: &str, hash: &str) -> bool {
+    if hash.starts_with("$argon2") {
+        PasswordHash::new(hash)
+            .map(|parsed| {
+                Argon2::default()
+                    .verify_password(password.as_bytes(), &parsed)
+                    .is_ok()
+            })
+            .unwrap_or(false)
+    } else if hash.starts_with("$2") {
+        bcrypt::verify(password, hash).unwrap_or(false)
+    } else {
+        // Plain text, crypt and MD5 htpasswd entries are not accepted
+        false
+    }
+}
+
+// Compare without returning early, so response times do not reveal how
+// much of a token was right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use argon2::password_hash::{PasswordHasher, SaltString};
+
+    fn request(path: &str, authorization: Option<&str>) -> HttpRequest {
+        let header = authorization
+            .map(|value| format!("Authorization: {}\r\n", value))
+            .unwrap_or_default();
+        format!("GET {} HTTP/1.1\r\n{}\r\n", path, header).into()
+    }
+
+    fn basic(user: &str, password: &str) -> String {
+        format!(
+            "Basic {}",
+            STANDARD.encode(format!("{}:{}", user, password))
+        )
+    }
+
+    #[test]
+    fn test_basic_auth_with_bcrypt_and_argon2() {
+        let salt = SaltString::from_b64("c29tZXNhbHRzb21lc2FsdA").unwrap();
+        let argon2_hash = Argon2::default()
+            .hash_password(b"s3cret", &salt)
+            .unwrap()
+            .to_string();
+        let htpasswd = format!(
+            "# admins\nalice:{}\nbob:{}\ncarol:plaintext\n",
+            bcrypt::hash("wonderland", 4).unwrap(),
+            argon2_hash
+        );
+        let auth = Auth::new().basic("/admin", "Admin area", &htpasswd);
+
+        assert_eq!(auth.check(&request("/", None)), Access::Open);
+        assert_eq!(auth.check(&request("/administrator", None)), Access::Open);
+        assert_eq!(
+            auth.check(&request(
+                "/admin/users",
+                Some(&basic("alice", "wonderland"))
+            )),
+            Access::Granted("alice".to_string())
+        );
+        assert_eq!(
+            auth.check(&request("/admin", Some(&basic("bob", "s3cret")))),
+            Access::Granted("bob".to_string())
+        );
+
+        let challenge = Access::Denied("Basic realm=\"Admin area\", charset=\"UTF-8\"".to_string());
+        assert_eq!(auth.check(&request("/admin", None)), challenge);
+        assert_eq!(
+            auth.check(&request("/admin", Some(&basic("alice", "wrong")))),
+            challenge
+        );
+        assert_eq!(
+            auth.check(&request("/admin", Some(&basic("carol", "plaintext")))),
+            challenge
+        );
+        assert_eq!(auth.check(&request("/admin", Some("Basic !!!"))), challenge);
+    }
+
+    #[test]
+    fn test_bearer_tokens() {
+        let auth = Auth::new().bearer("/api", "orders", "dashboard: abc123\n");
+        assert_eq!(
+            auth.check(&request("/api/shipping/orders", Some("Bearer abc123"))),
+            Access::Granted("dashboard".to_string())
+        );
+        assert_eq!(
+            auth.check(&request("/api/shipping/orders", None)),
+            Access::Denied("Bearer realm=\"orders\"".to_string())
+        );
+        assert_eq!(
+            auth.check(&request("/api/shipping/orders", Some("Bearer abc124"))),
+            Access::Denied("Bearer realm=\"orders\", error=\"invalid_token\"".to_string())
+        );
+    }
+}
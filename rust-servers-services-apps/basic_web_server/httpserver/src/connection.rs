@@ -0,0 +1,121 @@
+//! The connection module wraps a client stream, TCP or Unix domain socket,
+//! and counts the bytes read from and written to it, for metrics.
+//!
+
+use std::io::{self, prelude::*};
+use std::net::{IpAddr, TcpStream};
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+
+pub struct Connection {
+    stream: Stream,
+    bytes_read: u64,
+    bytes_written: u64,
+    // Bytes handed back with unread(), served before the stream
+    pushback: Vec<u8>,
+}
+
+enum Stream {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> Self {
+        Connection::with_stream(Stream::Tcp(stream))
+    }
+
+    pub fn unix(stream: UnixStream) -> Self {
+        Connection::with_stream(Stream::Unix(stream))
+    }
+
+    fn with_stream(stream: Stream) -> Self {
+        Connection {
+            stream,
+            bytes_read: 0,
+            bytes_written: 0,
+            pushback: Vec::new(),
+        }
+    }
+
+    // Another handle to the same client, e.g. for writing from one thread
+    // while reading on another. Each handle counts its own bytes.
+    pub fn try_clone(&self) -> io::Result<Self> {
+        let stream = match &self.stream {
+            Stream::Tcp(stream) => Stream::Tcp(stream.try_clone()?),
+            Stream::Unix(stream) => Stream::Unix(stream.try_clone()?),
+        };
+        Ok(Connection::with_stream(stream))
+    }
+
+    // Put bytes back, to be read again before anything else on the stream
+    pub fn unread(&mut self, bytes: &[u8]) {
+        self.pushback.splice(0..0, bytes.iter().copied());
+    }
+
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    // The client's IP address; None for Unix domain socket clients
+    pub fn peer_ip(&self) -> Option<IpAddr> {
+        match &self.stream {
+            Stream::Tcp(stream) => stream.peer_addr().ok().map(|addr| addr.ip()),
+            Stream::Unix(_) => None,
+        }
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match &self.stream {
+            Stream::Tcp(stream) => stream.set_read_timeout(timeout),
+            Stream::Unix(stream) => stream.set_read_timeout(timeout),
+        }
+    }
+
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match &self.stream {
+            Stream::Tcp(stream) => stream.set_write_timeout(timeout),
+            Stream::Unix(stream) => stream.set_write_timeout(timeout),
+        }
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Bytes put back were counted when they were first read
+        if !self.pushback.is_empty() {
+            let n = buf.len().min(self.pushback.len());
+            buf[..n].copy_from_slice(&self.pushback[..n]);
+            self.pushback.drain(..n);
+            return Ok(n);
+        }
+        let n = match &mut self.stream {
+            Stream::Tcp(stream) => stream.read(buf)?,
+            Stream::Unix(stream) => stream.read(buf)?,
+        };
+        self.bytes_read += n as u64;
+        Ok(n)
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = match &mut self.stream {
+            Stream::Tcp(stream) => stream.write(buf)?,
+            Stream::Unix(stream) => stream.write(buf)?,
+        };
+        self.bytes_written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.stream {
+            Stream::Tcp(stream) => stream.flush(),
+            Stream::Unix(stream) => stream.flush(),
+        }
+    }
+}
@@ -0,0 +1,393 @@
+//! The cors module implements a Cross-Origin Resource Sharing policy:
+//! it answers preflight requests and adds the Access-Control-* headers
+//! to responses for allowed origins.
+//!
+
+use std::env;
+
+use http::httprequest::{HttpRequest, Method};
+
+pub struct Cors {
+    // Exact origins, "*" for any origin, or patterns with one "*" such as
+    // https://*.example.com
+    origins: Vec<String>,
+    methods: Vec<String>,
+    // Request headers allowed in preflights; "*" allows whatever is asked for
+    headers: Vec<String>,
+    expose_headers: Vec<String>,
+    credentials: bool,
+    max_age: Option<u64>,
+}
+
+// Headers to add to a response, owned so they can outlive the policy lookup
+pub type CorsHeaders = Vec<(&'static str, String)>;
+
+// The outcome of checking a preflight request
+pub enum Preflight {
+    // Not a preflight; pass the request on to be handled normally
+    Pass,
+    // Answer 204 with these headers
+    Allowed(CorsHeaders),
+    // Answer 403 with these headers
+    Rejected(CorsHeaders),
+}
+
+impl Default for Cors {
+    fn default() -> Self {
+        Cors {
+            origins: Vec::new(),
+            methods: vec!["GET".into(), "HEAD".into(), "POST".into()],
+            headers: Vec::new(),
+            expose_headers: Vec::new(),
+            credentials: false,
+            max_age: None,
+        }
+    }
+}
+
+impl Cors {
+    pub fn new() -> Self {
+        Cors::default()
+    }
+
+    // Build a policy from CORS_* environment variables. Returns None when
+    // CORS_ALLOWED_ORIGINS is not set, i.e. CORS is disabled.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let list = |key: &str| -> Vec<String> {
+            env::var(key)
+                .unwrap_or_default()
+                .split(',')
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+                .collect()
+        };
+        let origins = list("CORS_ALLOWED_ORIGINS");
+        if origins.is_empty() {
+            return Ok(None);
+        }
+        let mut cors = Cors::new()
+            .allow_origins(&origins)
+            .allow_headers(&list("CORS_ALLOWED_HEADERS"))
+            .expose_headers(&list("CORS_EXPOSE_HEADERS"))
+            .allow_credentials(env::var("CORS_ALLOW_CREDENTIALS").as_deref() == Ok("true"));
+        let methods = list("CORS_ALLOWED_METHODS");
+        if !methods.is_empty() {
+            cors = cors.allow_methods(&methods);
+        }
+        if let Some(max_age) = env::var("CORS_MAX_AGE").ok().and_then(|v| v.parse().ok()) {
+            cors = cors.max_age(max_age);
+        }
+        cors.check().map(Some)
+    }
+
+    // Credentials with any origin would let every site make credentialed
+    // requests, so the origins have to be listed instead
+    pub fn check(self) -> Result<Self, String> {
+        if self.any_origin() && self.credentials {
+            return Err(
+                "credentials cannot be allowed for any origin (*); list the origins".into(),
+            );
+        }
+        Ok(self)
+    }
+
+    pub fn allow_origins<S: AsRef<str>>(mut self, origins: &[S]) -> Self {
+        self.origins = origins.iter().map(|o| o.as_ref().to_string()).collect();
+        self
+    }
+
+    pub fn allow_methods<S: AsRef<str>>(mut self, methods: &[S]) -> Self {
+        self.methods = methods
+            .iter()
+            .map(|m| m.as_ref().to_ascii_uppercase())
+            .collect();
+        self
+    }
+
+    pub fn allow_headers<S: AsRef<str>>(mut self, headers: &[S]) -> Self {
+        self.headers = headers.iter().map(|h| h.as_ref().to_string()).collect();
+        self
+    }
+
+    pub fn expose_headers<S: AsRef<str>>(mut self, headers: &[S]) -> Self {
+        self.expose_headers = headers.iter().map(|h| h.as_ref().to_string()).collect();
+        self
+    }
+
+    pub fn allow_credentials(mut self, credentials: bool) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    pub fn max_age(mut self, seconds: u64) -> Self {
+        self.max_age = Some(seconds);
+        self
+    }
+
+    // Check an OPTIONS request carrying Access-Control-Request-Method
+    pub fn preflight(&self, req: &HttpRequest) -> Preflight {
+        let requested_method = match (&req.method, req.header("Access-Control-Request-Method")) {
+            (Method::Options, Some(method)) => method,
+            _ => return Preflight::Pass,
+        };
+        let mut headers = self.vary(&[
+            "Origin",
+            "Access-Control-Request-Method",
+            "Access-Control-Request-Headers",
+        ]);
+        let allow_origin = match req.header("Origin").and_then(|o| self.allow_origin(o)) {
+            Some(allow_origin) => allow_origin,
+            None => return Preflight::Rejected(headers),
+        };
+        if !self.methods.iter().any(|m| m == requested_method) {
+            return Preflight::Rejected(headers);
+        }
+
+        let requested_headers: Vec<&str> = req
+            .header("Access-Control-Request-Headers")
+            .unwrap_or("")
+            .split(',')
+            .map(str::trim)
+            .filter(|h| !h.is_empty())
+            .collect();
+        let any_header = self.headers.iter().any(|h| h == "*");
+        let header_allowed =
+            |h: &&str| any_header || self.headers.iter().any(|a| a.eq_ignore_ascii_case(h));
+        if !requested_headers.iter().all(header_allowed) {
+            return Preflight::Rejected(headers);
+        }
+
+        headers.push(("Access-Control-Allow-Origin", allow_origin));
+        headers.push(("Access-Control-Allow-Methods", self.methods.join(", ")));
+        if !requested_headers.is_empty() {
+            let allowed = if any_header {
+                requested_headers.join(", ")
+            } else {
+                self.headers.join(", ")
+            };
+            headers.push(("Access-Control-Allow-Headers", allowed));
+        }
+        if self.credentials {
+            headers.push(("Access-Control-Allow-Credentials", "true".into()));
+        }
+        if let Some(max_age) = self.max_age {
+            headers.push(("Access-Control-Max-Age", max_age.to_string()));
+        }
+        Preflight::Allowed(headers)
+    }
+
+    // Headers to add to the response of an actual (non-preflight) request
+    pub fn response_headers(&self, req: &HttpRequest) -> CorsHeaders {
+        let mut headers = self.vary(&["Origin"]);
+        if let Some(allow_origin) = req.header("Origin").and_then(|o| self.allow_origin(o)) {
+            headers.push(("Access-Control-Allow-Origin", allow_origin));
+            if self.credentials {
+                headers.push(("Access-Control-Allow-Credentials", "true".into()));
+            }
+            if !self.expose_headers.is_empty() {
+                headers.push((
+                    "Access-Control-Expose-Headers",
+                    self.expose_headers.join(", "),
+                ));
+            }
+        }
+        headers
+    }
+
+    // The Access-Control-Allow-Origin value for an origin, if it is allowed
+    fn allow_origin(&self, origin: &str) -> Option<String> {
+        if self.any_origin() && !self.credentials {
+            return Some("*".into());
+        }
+        // Credentialed requests may not use "*", so echo the origin back. A
+        // bare "*" then matches nothing: only the listed origins get access.
+        self.origins
+            .iter()
+            .filter(|pattern| !(self.credentials && *pattern == "*"))
+            .any(|pattern| origin_matches(pattern, origin))
+            .then(|| origin.to_string())
+    }
+
+    fn any_origin(&self) -> bool {
+        self.origins.iter().any(|o| o == "*")
+    }
+
+    // The answer depends on the request's Origin unless every origin gets "*",
+    // so caches must be told to key on it
+    fn vary(&self, on: &[&str]) -> CorsHeaders {
+        if self.any_origin() && !self.credentials {
+            Vec::new()
+        } else {
+            vec![("Vary", on.join(", "))]
+        }
+    }
+}
+
+fn origin_matches(pattern: &str, origin: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern.eq_ignore_ascii_case(origin),
+        Some((prefix, suffix)) => {
+            origin.len() > prefix.len() + suffix.len()
+                && origin.starts_with(prefix)
+                && origin.ends_with(suffix)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(raw: &str) -> HttpRequest {
+        raw.to_string().into()
+    }
+
+    fn header<'a>(headers: &'a CorsHeaders, name: &str) -> Option<&'a str> {
+        headers
+            .iter()
+            .find(|(k, _)| *k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn policy() -> Cors {
+        Cors::new()
+            .allow_origins(&["https://app.example.com", "https://*.example.org"])
+            .allow_methods(&["GET", "POST", "DELETE"])
+            .allow_headers(&["Content-Type", "Authorization"])
+            .expose_headers(&["X-Total-Count"])
+            .allow_credentials(true)
+            .max_age(600)
+    }
+
+    #[test]
+    fn test_origin_patterns() {
+        assert!(origin_matches(
+            "https://app.example.com",
+            "https://app.example.com"
+        ));
+        assert!(origin_matches(
+            "https://*.example.org",
+            "https://shop.example.org"
+        ));
+        assert!(!origin_matches(
+            "https://*.example.org",
+            "https://.example.org"
+        ));
+        assert!(!origin_matches(
+            "https://*.example.org",
+            "https://example.org.evil.com"
+        ));
+        assert!(!origin_matches(
+            "https://app.example.com",
+            "https://evil.com"
+        ));
+    }
+
+    #[test]
+    fn test_allowed_preflight() {
+        let req = request("OPTIONS /api/shipping/orders HTTP/1.1\r\nOrigin: https://shop.example.org\r\nAccess-Control-Request-Method: DELETE\r\nAccess-Control-Request-Headers: content-type\r\n\r\n");
+        let Preflight::Allowed(headers) = policy().preflight(&req) else {
+            panic!("preflight should be allowed");
+        };
+        assert_eq!(
+            header(&headers, "Access-Control-Allow-Origin"),
+            Some("https://shop.example.org")
+        );
+        assert_eq!(
+            header(&headers, "Access-Control-Allow-Methods"),
+            Some("GET, POST, DELETE")
+        );
+        assert_eq!(
+            header(&headers, "Access-Control-Allow-Headers"),
+            Some("Content-Type, Authorization")
+        );
+        assert_eq!(
+            header(&headers, "Access-Control-Allow-Credentials"),
+            Some("true")
+        );
+        assert_eq!(header(&headers, "Access-Control-Max-Age"), Some("600"));
+        assert_eq!(
+            header(&headers, "Vary"),
+            Some("Origin, Access-Control-Request-Method, Access-Control-Request-Headers")
+        );
+    }
+
+    #[test]
+    fn test_rejected_preflight() {
+        let bad_origin = request("OPTIONS / HTTP/1.1\r\nOrigin: https://evil.com\r\nAccess-Control-Request-Method: GET\r\n\r\n");
+        assert!(matches!(
+            policy().preflight(&bad_origin),
+            Preflight::Rejected(_)
+        ));
+        let bad_method = request("OPTIONS / HTTP/1.1\r\nOrigin: https://app.example.com\r\nAccess-Control-Request-Method: PUT\r\n\r\n");
+        assert!(matches!(
+            policy().preflight(&bad_method),
+            Preflight::Rejected(_)
+        ));
+        let bad_header = request("OPTIONS / HTTP/1.1\r\nOrigin: https://app.example.com\r\nAccess-Control-Request-Method: GET\r\nAccess-Control-Request-Headers: X-Secret\r\n\r\n");
+        assert!(matches!(
+            policy().preflight(&bad_header),
+            Preflight::Rejected(_)
+        ));
+        let plain_options =
+            request("OPTIONS / HTTP/1.1\r\nOrigin: https://app.example.com\r\n\r\n");
+        assert!(matches!(
+            policy().preflight(&plain_options),
+            Preflight::Pass
+        ));
+    }
+
+    #[test]
+    fn test_response_headers() {
+        let req =
+            request("GET /api/shipping/orders HTTP/1.1\r\nOrigin: https://app.example.com\r\n\r\n");
+        let headers = policy().response_headers(&req);
+        assert_eq!(
+            header(&headers, "Access-Control-Allow-Origin"),
+            Some("https://app.example.com")
+        );
+        assert_eq!(
+            header(&headers, "Access-Control-Expose-Headers"),
+            Some("X-Total-Count")
+        );
+        assert_eq!(header(&headers, "Vary"), Some("Origin"));
+
+        // A disallowed origin gets no access, but the response still varies on Origin
+        let req = request("GET / HTTP/1.1\r\nOrigin: https://evil.com\r\n\r\n");
+        let headers = policy().response_headers(&req);
+        assert_eq!(header(&headers, "Access-Control-Allow-Origin"), None);
+        assert_eq!(header(&headers, "Vary"), Some("Origin"));
+    }
+
+    #[test]
+    fn test_wildcard_without_credentials() {
+        let cors = Cors::new().allow_origins(&["*"]);
+        let req = request("GET / HTTP/1.1\r\nOrigin: https://anywhere.com\r\n\r\n");
+        let headers = cors.response_headers(&req);
+        assert_eq!(header(&headers, "Access-Control-Allow-Origin"), Some("*"));
+        assert_eq!(header(&headers, "Vary"), None);
+
+        // Credentials are never granted to any origin: the policy is refused,
+        // and if built anyway "*" lets no origin in
+        let cors = cors.allow_credentials(true);
+        let headers = cors.response_headers(&req);
+        assert_eq!(header(&headers, "Access-Control-Allow-Origin"), None);
+        assert_eq!(header(&headers, "Access-Control-Allow-Credentials"), None);
+        assert_eq!(header(&headers, "Vary"), Some("Origin"));
+        let preflight = request("OPTIONS / HTTP/1.1\r\nOrigin: https://anywhere.com\r\nAccess-Control-Request-Method: GET\r\n\r\n");
+        assert!(matches!(cors.preflight(&preflight), Preflight::Rejected(_)));
+        assert!(cors.check().is_err());
+
+        // Listed origins still work with credentials
+        let cors = Cors::new()
+            .allow_origins(&["*", "https://app.example.com"])
+            .allow_credentials(true);
+        let req = request("GET / HTTP/1.1\r\nOrigin: https://app.example.com\r\n\r\n");
+        let headers = cors.response_headers(&req);
+        assert_eq!(
+            header(&headers, "Access-Control-Allow-Origin"),
+            Some("https://app.example.com")
+        );
+        assert!(policy().check().is_ok());
+    }
+}
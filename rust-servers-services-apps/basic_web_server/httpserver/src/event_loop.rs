@@ -0,0 +1,745 @@
+//! The event_loop module serves clients from a few reactor threads instead
+//! of a thread per connection. Each reactor waits on its sockets with epoll
+//! (through mio) and keeps a read and a write buffer per connection, so
+//! thousands of idle keep-alive connections cost little more than their
+//! buffers. Requests are handled through `Router::reply()`, as for HTTP/2
+//! streams; event streams, proxy mounts and HTTP/2 need the threaded mode.
+//!
+//! Handlers run on the reactor thread, so a slow one holds up every other
+//! connection on that reactor. That includes Basic auth, which checks each
+//! password against a bcrypt or argon2 hash: on paths protected that way,
+//! prefer Bearer tokens or the threaded mode.
+//!
+
+use mio::event::Source;
+use mio::net::{TcpListener, TcpStream, UnixListener, UnixStream};
+use mio::{Events, Interest, Poll, Registry, Token};
+use std::collections::HashMap;
+use std::io::{self, prelude::*};
+use std::net::IpAddr;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::limits::{parse_head, Head, Limits, RequestError};
+use super::listener::{self, Listener};
+use super::router::Reply;
+use super::vhost::VirtualHosts;
+use http::httprequest::{HttpRequest, Version};
+use http::httpresponse::HttpResponse;
+
+// How often reactors look for connections past their deadline
+const TICK: Duration = Duration::from_millis(250);
+// Pipelined requests wait while this much output is queued
+const MAX_QUEUED_OUTPUT: usize = 256 * 1024;
+
+// Serve the listeners from `reactors` threads until one of them fails
+pub fn run(
+    listeners: &[Listener],
+    reactors: usize,
+    limits: &Arc<Limits>,
+    hosts: &Arc<VirtualHosts>,
+) -> io::Result<()> {
+    // Number of connections currently open, across all reactors
+    let active = Arc::new(AtomicUsize::new(0));
+    let (done, failed) = mpsc::channel();
+
+    // Every reactor accepts on its own handles of the listening sockets
+    for _ in 0..reactors.max(1) {
+        let acceptors = listeners
+            .iter()
+            .map(Acceptor::new)
+            .collect::<io::Result<Vec<_>>>()?;
+        let reactor = Reactor::new(acceptors, limits, hosts, &active)?;
+        let done = done.clone();
+        thread::spawn(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| reactor.run()))
+                .unwrap_or_else(|_| Err(io::Error::other("reactor thread panicked")));
+            let _ = done.send(result);
+        });
+    }
+    drop(done);
+
+    // The first reactor to stop ends the server. Reactors only stop on
+    // errors, so even one that reports none has failed.
+    match failed.recv() {
+        Ok(Err(e)) => Err(e),
+        Ok(Ok(())) | Err(_) => Err(io::Error::other("reactor thread stopped")),
+    }
+}
+
+struct Reactor {
+    poll: Poll,
+    // Listening sockets use the first tokens, connections the ones after
+    acceptors: Vec<Acceptor>,
+    conns: HashMap<Token, Conn>,
+    next_token: usize,
+    // Accepting stops until then after running out of file descriptors
+    accept_paused_until: Option<Instant>,
+    limits: Arc<Limits>,
+    hosts: Arc<VirtualHosts>,
+    active: Arc<AtomicUsize>,
+}
+
+// One client connection and where it is in its request/response cycle
+struct Conn {
+    socket: Socket,
+    peer: Option<IpAddr>,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    // Head of the request whose body is still arriving
+    head: Option<Head>,
+    // When the first byte of the request being read arrived
+    request_started: Option<Instant>,
+    deadline: Instant,
+    // Close once the output is flushed
+    closing: bool,
+    // The client has shut down its side
+    eof: bool,
+    done: bool,
+    bytes_read: u64,
+    bytes_written: u64,
+}
+
+enum Acceptor {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+enum Socket {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+
+impl Reactor {
+    fn new(
+        mut acceptors: Vec<Acceptor>,
+        limits: &Arc<Limits>,
+        hosts: &Arc<VirtualHosts>,
+        active: &Arc<AtomicUsize>,
+    ) -> io::Result<Self> {
+        let poll = Poll::new()?;
+        for (i, acceptor) in acceptors.iter_mut().enumerate() {
+            poll.registry()
+                .register(acceptor, Token(i), Interest::READABLE)?;
+        }
+        Ok(Reactor {
+            poll,
+            next_token: acceptors.len(),
+            acceptors,
+            accept_paused_until: None,
+            conns: HashMap::new(),
+            limits: Arc::clone(limits),
+            hosts: Arc::clone(hosts),
+            active: Arc::clone(active),
+        })
+    }
+
+    fn run(mut self) -> io::Result<()> {
+        let mut events = Events::with_capacity(1024);
+        let mut next_sweep = Instant::now() + TICK;
+        loop {
+            match self.poll.poll(&mut events, Some(TICK)) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+            for event in events.iter() {
+                let token = event.token();
+                if token.0 < self.acceptors.len() {
+                    if self.accept_paused_until.is_none() {
+                        self.accept(token.0)?;
+                    }
+                } else {
+                    self.drive(token);
+                }
+            }
+            // Listening sockets only signal new clients, so take the ones
+            // that queued up while accepting was paused
+            if self
+                .accept_paused_until
+                .is_some_and(|until| Instant::now() >= until)
+            {
+                self.accept_paused_until = None;
+                for index in 0..self.acceptors.len() {
+                    self.accept(index)?;
+                }
+            }
+            if Instant::now() >= next_sweep {
+                self.sweep();
+                next_sweep = Instant::now() + TICK;
+            }
+        }
+    }
+
+    // Take every waiting client off a listening socket
+    fn accept(&mut self, index: usize) -> io::Result<()> {
+        loop {
+            let (mut socket, peer) = match self.acceptors[index].accept() {
+                Ok(accepted) => accepted,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::ConnectionAborted
+                            | io::ErrorKind::ConnectionReset
+                            | io::ErrorKind::Interrupted
+                    ) =>
+                {
+                    eprintln!("Failed to accept connection: {}", e);
+                    continue;
+                }
+                // Keep serving the open connections; some will close soon
+                Err(e) if listener::out_of_resources(&e) => {
+                    eprintln!("Failed to accept connection, pausing: {}", e);
+                    self.accept_paused_until = Some(Instant::now() + TICK);
+                    return Ok(());
+                }
+                Err(e) => return Err(e),
+            };
+
+            // Turn clients away once the connection cap is reached
+            if self.active.load(Ordering::SeqCst) >= self.limits.max_connections {
+                let _ = socket.write(&error_page("503"));
+                continue;
+            }
+
+            let token = Token(self.next_token);
+            self.next_token += 1;
+            if let Err(e) = self.poll.registry().register(
+                &mut socket,
+                token,
+                Interest::READABLE | Interest::WRITABLE,
+            ) {
+                eprintln!("Failed to register connection: {}", e);
+                continue;
+            }
+            self.active.fetch_add(1, Ordering::SeqCst);
+            self.conns
+                .insert(token, Conn::new(socket, peer, &self.limits));
+        }
+    }
+
+    fn drive(&mut self, token: Token) {
+        if let Some(conn) = self.conns.get_mut(&token) {
+            conn.drive(&self.limits, &self.hosts);
+            if conn.done {
+                self.close(token);
+            }
+        }
+    }
+
+    // Answer or drop connections whose deadline has passed
+    fn sweep(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<Token> = self
+            .conns
+            .iter()
+            .filter(|(_, conn)| conn.deadline <= now)
+            .map(|(token, _)| *token)
+            .collect();
+        for token in expired {
+            if let Some(conn) = self.conns.get_mut(&token) {
+                conn.expire(&self.limits, &self.hosts);
+                if conn.done {
+                    self.close(token);
+                }
+            }
+        }
+    }
+
+    fn close(&mut self, token: Token) {
+        if let Some(mut conn) = self.conns.remove(&token) {
+            let _ = self.poll.registry().deregister(&mut conn.socket);
+            self.hosts
+                .default_router()
+                .metrics()
+                .observe_bytes(conn.bytes_read, conn.bytes_written);
+            self.active.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+// Connections still open when a reactor stops no longer count towards
+// the connection cap
+impl Drop for Reactor {
+    fn drop(&mut self) {
+        self.active.fetch_sub(self.conns.len(), Ordering::SeqCst);
+    }
+}
+
+impl Conn {
+    fn new(socket: Socket, peer: Option<IpAddr>, limits: &Limits) -> Self {
+        let now = Instant::now();
+        Conn {
+            socket,
+            peer,
+            input: Vec::new(),
+            output: Vec::new(),
+            head: None,
+            // A new client is expected to send its request straight away
+            request_started: Some(now),
+            deadline: now + limits.read_timeout,
+            closing: false,
+            eof: false,
+            done: false,
+            bytes_read: 0,
+            bytes_written: 0,
+        }
+    }
+
+    // Make as much progress as the socket allows: read, handle complete
+    // requests, write responses, until reading and writing would block
+    fn drive(&mut self, limits: &Limits, hosts: &VirtualHosts) {
+        loop {
+            let read = self.fill(limits);
+            let handled = self.process(limits, hosts);
+            let written = self.flush();
+            if self.done {
+                return;
+            }
+            if self.output.is_empty() && (self.closing || self.eof) {
+                self.done = true;
+                return;
+            }
+            if read == 0 && !handled && written == 0 {
+                break;
+            }
+        }
+
+        let now = Instant::now();
+        self.deadline = if !self.output.is_empty() {
+            now + limits.write_timeout
+        } else if let Some(started) = self.request_started {
+            started + limits.read_timeout
+        } else {
+            now + limits.keep_alive_timeout
+        };
+    }
+
+    // Read what the client sent, up to the largest request we accept
+    fn fill(&mut self, limits: &Limits) -> usize {
+        let cap = limits.max_header_bytes + limits.max_body_bytes + 4;
+        let mut chunk = [0; 4096];
+        let mut total = 0;
+        while !self.eof && !self.closing && self.input.len() < cap {
+            match self.socket.read(&mut chunk) {
+                Ok(0) => self.eof = true,
+                Ok(n) => {
+                    self.input.extend_from_slice(&chunk[..n]);
+                    self.request_started.get_or_insert_with(Instant::now);
+                    self.bytes_read += n as u64;
+                    total += n;
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => {
+                    self.done = true;
+                    break;
+                }
+            }
+        }
+        total
+    }
+
+    // Handle the complete requests in the input buffer, in order
+    fn process(&mut self, limits: &Limits, hosts: &VirtualHosts) -> bool {
+        let mut handled = false;
+        while !self.closing && self.output.len() < MAX_QUEUED_OUTPUT {
+            if self.head.is_none() {
+                match parse_head(&self.input, limits) {
+                    Ok(Some(head)) => self.head = Some(head),
+                    Ok(None) => break,
+                    Err(err) => {
+                        self.fail(err, hosts);
+                        return true;
+                    }
+                }
+            }
+            let Some(head) = self
+                .head
+                .take_if(|head| self.input.len() >= head.len + head.content_length)
+            else {
+                // The body is still arriving
+                break;
+            };
+
+            let end = head.len + head.content_length;
+            let body = self.input[head.len..end].to_vec();
+            self.input.drain(..end);
+            self.request_started = (!self.input.is_empty()).then(Instant::now);
+            handled = true;
+
+            let mut req = head.req;
+            req.msg_body = match String::from_utf8(body) {
+                Ok(body) => body,
+                Err(_) => {
+                    self.fail(RequestError::BadRequest, hosts);
+                    break;
+                }
+            };
+            let keep_alive = keep_alive(&req);
+            let router = hosts.select(req.header("Host"));
+            // A handler that panics must not take the reactor and all its
+            // connections down with it
+            match panic::catch_unwind(AssertUnwindSafe(|| router.reply(req, self.peer))) {
+                Ok(reply) => self.respond(reply, keep_alive),
+                Err(_) => {
+                    self.output.extend_from_slice(&error_page("500"));
+                    self.closing = true;
+                }
+            }
+            if !keep_alive {
+                self.closing = true;
+            }
+        }
+        handled
+    }
+
+    fn respond(&mut self, reply: Reply, keep_alive: bool) {
+        let Reply {
+            status,
+            headers,
+            body,
+        } = reply;
+        let headers: HashMap<&str, &str> = headers
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        let mut resp = HttpResponse::new(&status, Some(headers), Some(body));
+        resp.add_header(
+            "Connection",
+            if keep_alive { "keep-alive" } else { "close" },
+        );
+        self.output.extend_from_slice(String::from(resp).as_bytes());
+    }
+
+    // Answer a request that could not be read with an error page, and close
+    fn fail(&mut self, err: RequestError, hosts: &VirtualHosts) {
+        hosts
+            .default_router()
+            .metrics()
+            .observe_parse_error(err.reason());
+        if let Some(status_code) = err.status_code() {
+            self.output.extend_from_slice(&error_page(status_code));
+        }
+        self.closing = true;
+    }
+
+    // Write out as much queued output as the socket takes
+    fn flush(&mut self) -> usize {
+        let mut total = 0;
+        while !self.output.is_empty() {
+            match self.socket.write(&self.output) {
+                Ok(0) => {
+                    self.done = true;
+                    break;
+                }
+                Ok(n) => {
+                    self.output.drain(..n);
+                    self.bytes_written += n as u64;
+                    total += n;
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => {
+                    self.done = true;
+                    break;
+                }
+            }
+        }
+        total
+    }
+
+    // The deadline passed: time out a slow request, drop an idle connection
+    // or give up on a client that does not read its response
+    fn expire(&mut self, limits: &Limits, hosts: &VirtualHosts) {
+        if !self.output.is_empty() || self.closing || self.request_started.is_none() {
+            self.done = true;
+            return;
+        }
+        self.fail(RequestError::Timeout, hosts);
+        self.flush();
+        self.done = self.output.is_empty();
+        self.deadline = Instant::now() + limits.write_timeout;
+    }
+}
+
+// HTTP/1.1 connections stay open unless the client asks otherwise
+fn keep_alive(req: &HttpRequest) -> bool {
+    req.version == Version::V1_1
+        && !req
+            .header("Connection")
+            .is_some_and(|value| value.eq_ignore_ascii_case("close"))
+}
+
+// A bare error page for the given status code, closing the connection
+fn error_page(status_code: &str) -> Vec<u8> {
+    let mut resp = HttpResponse::new(status_code, None, None);
+    let body = format!("<h1>{} {}</h1>", status_code, resp.status_text());
+    resp.set_body(body);
+    resp.add_header("Connection", "close");
+    String::from(resp).into_bytes()
+}
+
+impl Acceptor {
+    // A non-blocking handle on the same listening socket
+    fn new(listener: &Listener) -> io::Result<Self> {
+        match listener {
+            Listener::Tcp(listener) => {
+                let listener = listener.try_clone()?;
+                listener.set_nonblocking(true)?;
+                Ok(Acceptor::Tcp(TcpListener::from_std(listener)))
+            }
+            Listener::Unix { listener, .. } => {
+                let listener = listener.try_clone()?;
+                listener.set_nonblocking(true)?;
+                Ok(Acceptor::Unix(UnixListener::from_std(listener)))
+            }
+        }
+    }
+
+    fn accept(&self) -> io::Result<(Socket, Option<IpAddr>)> {
+        match self {
+            Acceptor::Tcp(listener) => {
+                let (stream, addr) = listener.accept()?;
+                let _ = stream.set_nodelay(true);
+                Ok((Socket::Tcp(stream), Some(addr.ip())))
+            }
+            Acceptor::Unix(listener) => Ok((Socket::Unix(listener.accept()?.0), None)),
+        }
+    }
+}
+
+impl Source for Acceptor {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        match self {
+            Acceptor::Tcp(listener) => listener.register(registry, token, interests),
+            Acceptor::Unix(listener) => listener.register(registry, token, interests),
+        }
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        match self {
+            Acceptor::Tcp(listener) => listener.reregister(registry, token, interests),
+            Acceptor::Unix(listener) => listener.reregister(registry, token, interests),
+        }
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        match self {
+            Acceptor::Tcp(listener) => listener.deregister(registry),
+            Acceptor::Unix(listener) => listener.deregister(registry),
+        }
+    }
+}
+
+impl Read for Socket {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Socket::Tcp(stream) => stream.read(buf),
+            Socket::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Socket {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Socket::Tcp(stream) => stream.write(buf),
+            Socket::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Socket::Tcp(stream) => stream.flush(),
+            Socket::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+impl Source for Socket {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        match self {
+            Socket::Tcp(stream) => stream.register(registry, token, interests),
+            Socket::Unix(stream) => stream.register(registry, token, interests),
+        }
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        match self {
+            Socket::Tcp(stream) => stream.reregister(registry, token, interests),
+            Socket::Unix(stream) => stream.reregister(registry, token, interests),
+        }
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        match self {
+            Socket::Tcp(stream) => stream.deregister(registry),
+            Socket::Unix(stream) => stream.deregister(registry),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::Handler;
+    use crate::server::Server;
+    use crate::test_util::{free_addr, send, wait_for};
+    use http::httpresponse::HttpResponse;
+    use std::net;
+
+    struct Panics;
+
+    impl Handler for Panics {
+        fn handle<'a>(&self, _req: &'a HttpRequest) -> HttpResponse<'a> {
+            panic!("handler bug")
+        }
+    }
+
+    fn start(limits: Limits) -> String {
+        let addr = free_addr();
+        let server = Server::builder()
+            .bind(&addr)
+            .limits(limits)
+            .route("/panic", Panics)
+            .event_loop(2)
+            .build()
+            .unwrap();
+        thread::spawn(move || server.run());
+        wait_for(&addr);
+        addr
+    }
+
+    // Read one response off a keep-alive connection
+    fn read_response(client: &mut net::TcpStream) -> String {
+        let mut response = Vec::new();
+        let mut byte = [0; 1];
+        while !response.ends_with(b"\r\n\r\n") {
+            client.read_exact(&mut byte).unwrap();
+            response.push(byte[0]);
+        }
+        let head = String::from_utf8(response).unwrap();
+        let length: usize = head
+            .lines()
+            .find_map(|line| line.strip_prefix("Content-Length: "))
+            .unwrap()
+            .parse()
+            .unwrap();
+        let mut body = vec![0; length];
+        client.read_exact(&mut body).unwrap();
+        head + &String::from_utf8(body).unwrap()
+    }
+
+    #[test]
+    fn test_keep_alive_and_pipelining() {
+        let addr = start(Limits::default());
+        let mut client = net::TcpStream::connect(&addr).unwrap();
+
+        // Two pipelined requests, the second one's body split across writes
+        client
+            .write_all(b"GET /api/shipping/orders HTTP/1.1\r\n\r\nPOST /api/shipping/orders HTTP/1.1\r\nContent-Length: 65\r\n\r\n{\"order_id\":9,")
+            .unwrap();
+        let first = read_response(&mut client);
+        assert!(first.starts_with("HTTP/1.1 200 OK"));
+        assert!(first.contains("Connection:keep-alive\r\n"));
+        assert!(first.contains("Delivered"));
+        client
+            .write_all(b"\"order_date\":\"1 Mar 2021\",\"order_status\":\"Shipped\"}")
+            .unwrap();
+        assert!(read_response(&mut client).ends_with("\"Shipped\"}"));
+
+        // The same connection serves requests until the client closes it
+        client
+            .write_all(b"GET /nope HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut last = String::new();
+        client.read_to_string(&mut last).unwrap();
+        assert!(last.starts_with("HTTP/1.1 404 Not Found"));
+        assert!(last.contains("Connection:close\r\n"));
+    }
+
+    #[test]
+    fn test_slow_and_oversized_requests() {
+        let addr = start(Limits {
+            read_timeout: Duration::from_millis(300),
+            max_uri_len: 32,
+            ..Limits::default()
+        });
+
+        let mut slow = net::TcpStream::connect(&addr).unwrap();
+        slow.write_all(b"GET / HTTP/1.1\r\n").unwrap();
+        let mut response = String::new();
+        slow.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 408 Request Timeout"));
+
+        let mut long = net::TcpStream::connect(&addr).unwrap();
+        long.write_all(format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(64)).as_bytes())
+            .unwrap();
+        let mut response = String::new();
+        long.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 414 URI Too Long"));
+    }
+
+    #[test]
+    fn test_many_idle_connections() {
+        let addr = start(Limits {
+            max_connections: 2000,
+            ..Limits::default()
+        });
+        let mut idle: Vec<net::TcpStream> = (0..500)
+            .map(|_| net::TcpStream::connect(&addr).unwrap())
+            .collect();
+        for client in idle.iter_mut().step_by(50) {
+            client.write_all(b"GET /health HTTP/1.1\r\n\r\n").unwrap();
+            assert!(read_response(client).starts_with("HTTP/1.1 200 OK"));
+        }
+    }
+
+    #[test]
+    fn test_panicking_handler_gets_500() {
+        let addr = start(Limits::default());
+        let mut client = net::TcpStream::connect(&addr).unwrap();
+        client
+            .write_all(b"GET /health HTTP/1.1\r\n\r\nGET /panic HTTP/1.1\r\n\r\n")
+            .unwrap();
+        assert!(read_response(&mut client).starts_with("HTTP/1.1 200 OK"));
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 500 Internal Server Error"));
+
+        // The reactors are still serving, even requests for no path at all
+        for _ in 0..4 {
+            assert!(send(&addr, "GET * HTTP/1.1\r\nConnection: close\r\n\r\n")
+                .starts_with("HTTP/1.1 404 Not Found"));
+            assert!(
+                send(&addr, "GET /health HTTP/1.1\r\nConnection: close\r\n\r\n")
+                    .starts_with("HTTP/1.1 200 OK")
+            );
+        }
+    }
+}
@@ -0,0 +1,1015 @@
+//! The h2 module serves HTTP/2 to clients that open a cleartext connection
+//! with the HTTP/2 connection preface ("prior knowledge", RFC 9113 section
+//! 3.3). Streams are multiplexed: each request is handled on its own thread
+//! through the same router as HTTP/1.1 requests, and responses are sent
+//! within the client's flow-control windows.
+//!
+
+use std::collections::HashMap;
+use std::io::{self, prelude::*};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::connection::Connection;
+use super::hpack::{self, Decoder};
+use super::limits::Limits;
+use super::router::Reply;
+use super::vhost::VirtualHosts;
+use http::httprequest::{HttpRequest, Resource, Version};
+use http::httpresponse::HttpResponse;
+
+// What an HTTP/2 client sends before its first frame
+pub const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+// Frame types
+const DATA: u8 = 0x0;
+const HEADERS: u8 = 0x1;
+const PRIORITY: u8 = 0x2;
+const RST_STREAM: u8 = 0x3;
+const SETTINGS: u8 = 0x4;
+const PUSH_PROMISE: u8 = 0x5;
+const PING: u8 = 0x6;
+const GOAWAY: u8 = 0x7;
+const WINDOW_UPDATE: u8 = 0x8;
+const CONTINUATION: u8 = 0x9;
+
+// Frame flags
+const END_STREAM: u8 = 0x1;
+const ACK: u8 = 0x1;
+const END_HEADERS: u8 = 0x4;
+const PADDED: u8 = 0x8;
+const PRIORITY_FLAG: u8 = 0x20;
+
+// Error codes
+const NO_ERROR: u32 = 0x0;
+const PROTOCOL_ERROR: u32 = 0x1;
+const FLOW_CONTROL_ERROR: u32 = 0x3;
+const STREAM_CLOSED: u32 = 0x5;
+const FRAME_SIZE_ERROR: u32 = 0x6;
+const REFUSED_STREAM: u32 = 0x7;
+const CANCEL: u32 = 0x8;
+const COMPRESSION_ERROR: u32 = 0x9;
+const ENHANCE_YOUR_CALM: u32 = 0xb;
+
+// Settings
+const SETTINGS_MAX_CONCURRENT_STREAMS: u16 = 0x3;
+const SETTINGS_INITIAL_WINDOW_SIZE: u16 = 0x4;
+const SETTINGS_MAX_FRAME_SIZE: u16 = 0x5;
+
+// Streams served at the same time on one connection
+const MAX_STREAMS: usize = 100;
+// Open streams a client may reset within RESET_PERIOD before the connection
+// is closed. Resetting a stream does not stop its handler, so a client
+// opening and resetting streams in a loop would only make work for us.
+const MAX_RESETS: u32 = 100;
+const RESET_PERIOD: Duration = Duration::from_secs(1);
+// Window and frame sizes until the client's SETTINGS say otherwise; we
+// never change ours
+const DEFAULT_WINDOW: i64 = 65_535;
+const DEFAULT_FRAME_SIZE: usize = 16_384;
+const MAX_WINDOW: i64 = (1 << 31) - 1;
+
+struct Frame {
+    kind: u8,
+    flags: u8,
+    stream_id: u32,
+    payload: Vec<u8>,
+}
+
+// A request to hand to a stream thread, or the error status to answer with
+type Job = Result<HttpRequest, &'static str>;
+
+// The sending half of the connection, shared by the stream threads
+struct Writer {
+    out: Connection,
+    // Send windows of the connection and of each open stream; a stream is
+    // open from its HEADERS until its response is sent or it is reset
+    conn_window: i64,
+    windows: HashMap<u32, i64>,
+    initial_window: i64,
+    max_frame: usize,
+    // Stream threads still running, including those of streams the client
+    // has since reset
+    handlers: usize,
+    // Cleared once no more frames (window updates) will be read
+    reading: bool,
+}
+
+struct Shared {
+    writer: Mutex<Writer>,
+    // Signalled whenever a window grows or a stream is reset
+    ready: Condvar,
+}
+
+// A stream whose header block or body is still arriving
+struct Incoming {
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    // Already answered with an error; the rest of the body is discarded
+    rejected: bool,
+    // When the stream was opened; the request must be complete within the
+    // read timeout, as on HTTP/1.1
+    opened: Instant,
+}
+
+// The receiving half of the connection
+struct Reader<'a> {
+    shared: &'a Shared,
+    limits: &'a Limits,
+    decoder: Decoder,
+    incoming: HashMap<u32, Incoming>,
+    // Highest stream id the client has opened
+    last_stream_id: u32,
+    // Streams ready to be handled
+    jobs: Vec<(u32, Job)>,
+    // Open streams the client has reset since `resets_since`
+    resets: u32,
+    resets_since: Instant,
+}
+
+// Serve an HTTP/2 connection whose preface has already been read. Returns
+// the bytes written on behalf of the streams, for metrics.
+pub fn serve(stream: &mut Connection, limits: &Limits, hosts: &VirtualHosts) -> u64 {
+    let Ok(out) = stream.try_clone() else {
+        return 0;
+    };
+    let shared = Shared {
+        writer: Mutex::new(Writer {
+            out,
+            conn_window: DEFAULT_WINDOW,
+            windows: HashMap::new(),
+            initial_window: DEFAULT_WINDOW,
+            max_frame: DEFAULT_FRAME_SIZE,
+            handlers: 0,
+            reading: true,
+        }),
+        ready: Condvar::new(),
+    };
+    let mut settings = SETTINGS_MAX_CONCURRENT_STREAMS.to_be_bytes().to_vec();
+    settings.extend_from_slice(&(MAX_STREAMS as u32).to_be_bytes());
+    if shared.lock().frame(SETTINGS, 0, 0, &settings).is_err() {
+        return 0;
+    }
+
+    let peer = stream.peer_ip();
+    let mut reader = Reader {
+        shared: &shared,
+        limits,
+        decoder: Decoder::default(),
+        incoming: HashMap::new(),
+        last_stream_id: 0,
+        jobs: Vec::new(),
+        resets: 0,
+        resets_since: Instant::now(),
+    };
+    // Stream threads are scoped, so they have all finished when this returns
+    thread::scope(|scope| {
+        let error = reader.read_frames(stream, |id, job| {
+            let shared = &shared;
+            shared.lock().handlers += 1;
+            scope.spawn(move || {
+                let reply = match job {
+                    Ok(req) => hosts.select(req.header("Host")).reply(req, peer),
+                    Err(status_code) => error_reply(status_code),
+                };
+                shared.send_reply(id, reply, limits);
+                shared.lock().handlers -= 1;
+            });
+        });
+
+        let mut writer = shared.lock();
+        if let Some(code) = error {
+            let mut payload = reader.last_stream_id.to_be_bytes().to_vec();
+            payload.extend_from_slice(&code.to_be_bytes());
+            let _ = writer.frame(GOAWAY, 0, 0, &payload);
+        }
+        writer.reading = false;
+        shared.ready.notify_all();
+    });
+
+    let written = shared.lock().out.bytes_written();
+    written
+}
+
+impl Reader<'_> {
+    // Read frames until the client goes away, handing complete requests to
+    // `dispatch`. Returns the error code to send in a GOAWAY, if any.
+    fn read_frames(
+        &mut self,
+        stream: &mut Connection,
+        mut dispatch: impl FnMut(u32, Job),
+    ) -> Option<u32> {
+        if stream
+            .set_read_timeout(Some(self.limits.read_timeout))
+            .is_err()
+        {
+            return None;
+        }
+        let mut first = true;
+        // Header block waiting for CONTINUATION frames: stream id,
+        // END_STREAM flag and the fragments so far
+        let mut continuing: Option<(u32, bool, Vec<u8>)> = None;
+        loop {
+            let frame = match read_frame(stream) {
+                Ok(Some(frame)) => frame,
+                // Idle connections are closed once nothing is in progress
+                Ok(None) => {
+                    self.expire_stalled();
+                    if self.idle() {
+                        return Some(NO_ERROR);
+                    }
+                    continue;
+                }
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => return Some(FRAME_SIZE_ERROR),
+                Err(_) => return None,
+            };
+            // The client's first frame must be its SETTINGS
+            if first && (frame.kind != SETTINGS || frame.flags & ACK != 0) {
+                return Some(PROTOCOL_ERROR);
+            }
+            first = false;
+
+            let result = if let Some((id, end_stream, mut block)) = continuing.take() {
+                // Nothing may come between a header block's frames
+                if frame.kind != CONTINUATION || frame.stream_id != id {
+                    return Some(PROTOCOL_ERROR);
+                }
+                block.extend_from_slice(&frame.payload);
+                // Endless CONTINUATION frames would exhaust our memory
+                if block.len() > self.limits.max_header_bytes {
+                    return Some(PROTOCOL_ERROR);
+                }
+                if frame.flags & END_HEADERS == 0 {
+                    continuing = Some((id, end_stream, block));
+                    Ok(())
+                } else {
+                    self.on_header_block(id, end_stream, &block)
+                }
+            } else {
+                match frame.kind {
+                    HEADERS => match headers_fragment(&frame) {
+                        _ if frame.stream_id == 0 => Err(PROTOCOL_ERROR),
+                        None => Err(PROTOCOL_ERROR),
+                        Some(fragment) => {
+                            let end_stream = frame.flags & END_STREAM != 0;
+                            if frame.flags & END_HEADERS == 0 {
+                                continuing = Some((frame.stream_id, end_stream, fragment.to_vec()));
+                                Ok(())
+                            } else {
+                                self.on_header_block(frame.stream_id, end_stream, fragment)
+                            }
+                        }
+                    },
+                    DATA => self.on_data(&frame),
+                    SETTINGS => self.on_settings(&frame),
+                    PING => self.on_ping(&frame),
+                    WINDOW_UPDATE => self.on_window_update(&frame),
+                    RST_STREAM => self.on_reset(&frame),
+                    PRIORITY if frame.payload.len() != 5 => Err(FRAME_SIZE_ERROR),
+                    // Clients cannot push, and CONTINUATION must follow HEADERS
+                    PUSH_PROMISE | CONTINUATION => Err(PROTOCOL_ERROR),
+                    // Priorities, the client's GOAWAY and unknown frame types
+                    // need no action; the client closes when it is done
+                    _ => Ok(()),
+                }
+            };
+            if let Err(code) = result {
+                return Some(code);
+            }
+            for (id, job) in self.jobs.drain(..) {
+                dispatch(id, job);
+            }
+            // A client that keeps sending other frames must still finish
+            // its requests in time
+            self.expire_stalled();
+        }
+    }
+
+    // Reset the streams whose request has not arrived within the read timeout
+    fn expire_stalled(&mut self) {
+        let timeout = self.limits.read_timeout;
+        let stalled: Vec<u32> = self
+            .incoming
+            .iter()
+            .filter(|(_, incoming)| incoming.opened.elapsed() >= timeout)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in stalled {
+            self.reset(id, CANCEL);
+        }
+    }
+
+    fn idle(&self) -> bool {
+        self.incoming.is_empty() && self.shared.lock().windows.is_empty()
+    }
+
+    fn on_header_block(&mut self, id: u32, end_stream: bool, block: &[u8]) -> Result<(), u32> {
+        // Decode even blocks we ignore, to keep the HPACK table in step.
+        // Decoding stops copying headers out once they are too large, as a
+        // small block can refer to a large table entry many times.
+        let headers = self
+            .decoder
+            .decode(block, self.limits.max_header_bytes)
+            .map_err(|_| COMPRESSION_ERROR)?;
+
+        // Trailers: they must end the stream, and are not passed on
+        if let Some(incoming) = self.incoming.remove(&id) {
+            if !end_stream {
+                self.reset(id, PROTOCOL_ERROR);
+            } else if !incoming.rejected {
+                self.jobs.push((id, request(incoming)));
+            }
+            return Ok(());
+        }
+        // New streams use odd, increasing ids
+        if id.is_multiple_of(2) || id <= self.last_stream_id {
+            return Err(PROTOCOL_ERROR);
+        }
+        self.last_stream_id = id;
+
+        {
+            let mut writer = self.shared.lock();
+            if writer.windows.len() >= MAX_STREAMS || writer.handlers >= MAX_STREAMS {
+                drop(writer);
+                self.reset(id, REFUSED_STREAM);
+                return Ok(());
+            }
+            let window = writer.initial_window;
+            writer.windows.insert(id, window);
+        }
+
+        let too_large = headers
+            .as_ref()
+            .is_none_or(|headers| headers.len() > self.limits.max_headers);
+        let mut incoming = Incoming {
+            headers: headers.unwrap_or_default(),
+            body: Vec::new(),
+            rejected: false,
+            opened: Instant::now(),
+        };
+        if too_large {
+            self.jobs.push((id, Err("431")));
+            incoming.rejected = true;
+        }
+        if !end_stream {
+            self.incoming.insert(id, incoming);
+        } else if !incoming.rejected {
+            self.jobs.push((id, request(incoming)));
+        }
+        Ok(())
+    }
+
+    fn on_data(&mut self, frame: &Frame) -> Result<(), u32> {
+        if frame.stream_id == 0 {
+            return Err(PROTOCOL_ERROR);
+        }
+        let data = unpadded(frame).ok_or(PROTOCOL_ERROR)?;
+        // Give back the window the frame used, so the client can go on sending
+        let used = frame.payload.len();
+        if used > 0 {
+            self.shared.lock().window_update(0, used);
+        }
+
+        let id = frame.stream_id;
+        let Some(incoming) = self.incoming.get_mut(&id) else {
+            if id > self.last_stream_id {
+                return Err(PROTOCOL_ERROR);
+            }
+            self.reset(id, STREAM_CLOSED);
+            return Ok(());
+        };
+        if !incoming.rejected {
+            if incoming.body.len() + data.len() > self.limits.max_body_bytes {
+                self.jobs.push((id, Err("413")));
+                incoming.rejected = true;
+                incoming.body = Vec::new();
+            } else {
+                incoming.body.extend_from_slice(data);
+            }
+        }
+
+        if frame.flags & END_STREAM != 0 {
+            if let Some(incoming) = self.incoming.remove(&id).filter(|i| !i.rejected) {
+                self.jobs.push((id, request(incoming)));
+            }
+        } else if used > 0 {
+            self.shared.lock().window_update(id, used);
+        }
+        Ok(())
+    }
+
+    fn on_settings(&mut self, frame: &Frame) -> Result<(), u32> {
+        if frame.stream_id != 0 {
+            return Err(PROTOCOL_ERROR);
+        }
+        if frame.flags & ACK != 0 {
+            return match frame.payload.is_empty() {
+                true => Ok(()),
+                false => Err(FRAME_SIZE_ERROR),
+            };
+        }
+        if !frame.payload.len().is_multiple_of(6) {
+            return Err(FRAME_SIZE_ERROR);
+        }
+
+        let mut writer = self.shared.lock();
+        for setting in frame.payload.chunks(6) {
+            let id = u16::from_be_bytes([setting[0], setting[1]]);
+            let value = u32::from_be_bytes([setting[2], setting[3], setting[4], setting[5]]);
+            match id {
+                SETTINGS_INITIAL_WINDOW_SIZE => {
+                    let value = value as i64;
+                    if value > MAX_WINDOW {
+                        return Err(FLOW_CONTROL_ERROR);
+                    }
+                    // Open streams' windows move by the difference
+                    let delta = value - writer.initial_window;
+                    for window in writer.windows.values_mut() {
+                        *window += delta;
+                    }
+                    writer.initial_window = value;
+                }
+                SETTINGS_MAX_FRAME_SIZE => {
+                    if !(16_384..=16_777_215).contains(&value) {
+                        return Err(PROTOCOL_ERROR);
+                    }
+                    writer.max_frame = value as usize;
+                }
+                // Our encoder keeps no dynamic table and we never push, so
+                // the other settings change nothing
+                _ => {}
+            }
+        }
+        let _ = writer.frame(SETTINGS, ACK, 0, &[]);
+        self.shared.ready.notify_all();
+        Ok(())
+    }
+
+    fn on_ping(&mut self, frame: &Frame) -> Result<(), u32> {
+        if frame.stream_id != 0 {
+            return Err(PROTOCOL_ERROR);
+        }
+        if frame.payload.len() != 8 {
+            return Err(FRAME_SIZE_ERROR);
+        }
+        if frame.flags & ACK == 0 {
+            let _ = self.shared.lock().frame(PING, ACK, 0, &frame.payload);
+        }
+        Ok(())
+    }
+
+    fn on_window_update(&mut self, frame: &Frame) -> Result<(), u32> {
+        if frame.payload.len() != 4 {
+            return Err(FRAME_SIZE_ERROR);
+        }
+        let increment =
+            (u32::from_be_bytes(frame.payload[..4].try_into().unwrap()) & 0x7fff_ffff) as i64;
+
+        let mut writer = self.shared.lock();
+        let stream_error = if frame.stream_id == 0 {
+            if increment == 0 {
+                return Err(PROTOCOL_ERROR);
+            }
+            writer.conn_window += increment;
+            if writer.conn_window > MAX_WINDOW {
+                return Err(FLOW_CONTROL_ERROR);
+            }
+            None
+        } else {
+            match writer.windows.get_mut(&frame.stream_id) {
+                Some(_) if increment == 0 => Some(PROTOCOL_ERROR),
+                Some(window) => {
+                    *window += increment;
+                    (*window > MAX_WINDOW).then_some(FLOW_CONTROL_ERROR)
+                }
+                // Updates may still arrive for streams that just finished
+                None => None,
+            }
+        };
+        drop(writer);
+        match stream_error {
+            Some(code) => self.reset(frame.stream_id, code),
+            None => self.shared.ready.notify_all(),
+        }
+        Ok(())
+    }
+
+    fn on_reset(&mut self, frame: &Frame) -> Result<(), u32> {
+        if frame.stream_id == 0 || frame.stream_id > self.last_stream_id {
+            return Err(PROTOCOL_ERROR);
+        }
+        if frame.payload.len() != 4 {
+            return Err(FRAME_SIZE_ERROR);
+        }
+        // The stream's thread notices its window is gone and stops sending
+        let was_open = self.incoming.remove(&frame.stream_id).is_some()
+            | self
+                .shared
+                .lock()
+                .windows
+                .remove(&frame.stream_id)
+                .is_some();
+        self.shared.ready.notify_all();
+
+        if was_open {
+            if self.resets_since.elapsed() >= RESET_PERIOD {
+                self.resets = 0;
+                self.resets_since = Instant::now();
+            }
+            self.resets += 1;
+            if self.resets > MAX_RESETS {
+                return Err(ENHANCE_YOUR_CALM);
+            }
+        }
+        Ok(())
+    }
+
+    // Close one stream with an error, leaving the others alone
+    fn reset(&mut self, id: u32, code: u32) {
+        self.incoming.remove(&id);
+        let mut writer = self.shared.lock();
+        writer.windows.remove(&id);
+        let _ = writer.frame(RST_STREAM, 0, id, &code.to_be_bytes());
+        drop(writer);
+        self.shared.ready.notify_all();
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Writer> {
+        self.writer.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Send a response on a stream: the header block in one go, then the body
+    // in DATA frames as the flow-control windows allow
+    fn send_reply(&self, id: u32, reply: Reply, limits: &Limits) {
+        let mut fields = vec![(":status".to_string(), reply.status)];
+        for (name, value) in reply.headers {
+            let name = name.to_ascii_lowercase();
+            // Connection-specific headers are not allowed in HTTP/2
+            if !matches!(
+                name.as_str(),
+                "connection"
+                    | "keep-alive"
+                    | "proxy-connection"
+                    | "transfer-encoding"
+                    | "upgrade"
+                    | "content-length"
+            ) {
+                fields.push((name, value));
+            }
+        }
+        fields.push(("content-length".to_string(), reply.body.len().to_string()));
+        let fields: Vec<(&str, &str)> = fields
+            .iter()
+            .map(|(n, v)| (n.as_str(), v.as_str()))
+            .collect();
+        let block = hpack::encode(&fields);
+        let body = reply.body.as_bytes();
+
+        let mut writer = self.lock();
+        // Gone if the client reset the stream meanwhile
+        if !writer.windows.contains_key(&id) {
+            return;
+        }
+        let max_frame = writer.max_frame;
+        let mut fragments = block.chunks(max_frame).peekable();
+        let mut kind = HEADERS;
+        while let Some(fragment) = fragments.next() {
+            let mut flags = 0;
+            if fragments.peek().is_none() {
+                flags |= END_HEADERS;
+            }
+            if kind == HEADERS && body.is_empty() {
+                flags |= END_STREAM;
+            }
+            if writer.frame(kind, flags, id, fragment).is_err() {
+                writer.windows.remove(&id);
+                return;
+            }
+            kind = CONTINUATION;
+        }
+
+        let mut sent = 0;
+        while sent < body.len() {
+            let Some(&window) = writer.windows.get(&id) else {
+                return;
+            };
+            let available = window.min(writer.conn_window);
+            if available <= 0 {
+                // Wait for the client to open its windows, but not forever
+                let (guard, wait) = self
+                    .ready
+                    .wait_timeout(writer, limits.write_timeout)
+                    .unwrap_or_else(|e| e.into_inner());
+                writer = guard;
+                if wait.timed_out() || !writer.reading {
+                    writer.windows.remove(&id);
+                    let _ = writer.frame(RST_STREAM, 0, id, &CANCEL.to_be_bytes());
+                    return;
+                }
+                continue;
+            }
+            let n = (available as usize)
+                .min(writer.max_frame)
+                .min(body.len() - sent);
+            let flags = if sent + n == body.len() {
+                END_STREAM
+            } else {
+                0
+            };
+            if writer
+                .frame(DATA, flags, id, &body[sent..sent + n])
+                .is_err()
+            {
+                break;
+            }
+            writer.conn_window -= n as i64;
+            if let Some(window) = writer.windows.get_mut(&id) {
+                *window -= n as i64;
+            }
+            sent += n;
+        }
+        writer.windows.remove(&id);
+    }
+}
+
+impl Writer {
+    fn frame(&mut self, kind: u8, flags: u8, stream_id: u32, payload: &[u8]) -> io::Result<()> {
+        let mut buf = Vec::with_capacity(9 + payload.len());
+        buf.extend_from_slice(&(payload.len() as u32).to_be_bytes()[1..]);
+        buf.push(kind);
+        buf.push(flags);
+        buf.extend_from_slice(&stream_id.to_be_bytes());
+        buf.extend_from_slice(payload);
+        self.out.write_all(&buf)
+    }
+
+    fn window_update(&mut self, stream_id: u32, increment: usize) {
+        let _ = self.frame(
+            WINDOW_UPDATE,
+            0,
+            stream_id,
+            &(increment as u32).to_be_bytes(),
+        );
+    }
+}
+
+// Read the next frame. Ok(None) means the client sent nothing before the
+// read timeout; frames larger than we allow are InvalidData errors.
+fn read_frame(stream: &mut Connection) -> io::Result<Option<Frame>> {
+    let mut head = [0; 9];
+    match stream.read(&mut head[..1]) {
+        Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+        Ok(_) => {}
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ) =>
+        {
+            return Ok(None)
+        }
+        Err(e) => return Err(e),
+    }
+    // A client that stops in the middle of a frame is given up on
+    stream.read_exact(&mut head[1..])?;
+
+    let len = u32::from_be_bytes([0, head[0], head[1], head[2]]) as usize;
+    if len > DEFAULT_FRAME_SIZE {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+    let mut payload = vec![0; len];
+    stream.read_exact(&mut payload)?;
+    Ok(Some(Frame {
+        kind: head[3],
+        flags: head[4],
+        stream_id: u32::from_be_bytes([head[5], head[6], head[7], head[8]]) & 0x7fff_ffff,
+        payload,
+    }))
+}
+
+// The payload without padding, or None if the padding does not fit
+fn unpadded(frame: &Frame) -> Option<&[u8]> {
+    if frame.flags & PADDED == 0 {
+        return Some(&frame.payload);
+    }
+    let (&pad, rest) = frame.payload.split_first()?;
+    rest.len().checked_sub(pad as usize).map(|end| &rest[..end])
+}
+
+// The header block fragment of a HEADERS frame, skipping any priority fields
+fn headers_fragment(frame: &Frame) -> Option<&[u8]> {
+    let fragment = unpadded(frame)?;
+    match frame.flags & PRIORITY_FLAG {
+        0 => Some(fragment),
+        _ => fragment.get(5..),
+    }
+}
+
+// Turn a stream's header fields and body into a request for the router
+fn request(incoming: Incoming) -> Job {
+    let (mut method, mut path, mut authority) = (None, None, None);
+    let mut headers: HashMap<String, String> = HashMap::new();
+    for (name, value) in incoming.headers {
+        match name.as_str() {
+            ":method" => method = Some(value),
+            ":path" => path = Some(value),
+            ":authority" => authority = Some(value),
+            ":scheme" => {}
+            other if other.starts_with(':') => return Err("400"),
+            _ => {
+                // Cookies may arrive split over several fields
+                let separator = if name == "cookie" { "; " } else { ", " };
+                headers
+                    .entry(name)
+                    .and_modify(|joined| {
+                        joined.push_str(separator);
+                        joined.push_str(&value);
+                    })
+                    .or_insert(value);
+            }
+        }
+    }
+    let (Some(method), Some(path)) = (method, path) else {
+        return Err("400");
+    };
+    if let Some(authority) = authority {
+        headers.entry("host".to_string()).or_insert(authority);
+    }
+    Ok(HttpRequest {
+        method: method.as_str().into(),
+        version: Version::V2_0,
+        resource: Resource::Path(path),
+        headers,
+        msg_body: String::from_utf8(incoming.body).map_err(|_| "400")?,
+    })
+}
+
+// A bare error page for the given status code
+fn error_reply(status_code: &'static str) -> Reply {
+    let resp = HttpResponse::new(status_code, None, None);
+    Reply {
+        status: status_code.to_string(),
+        headers: vec![("content-type".to_string(), "text/html".to_string())],
+        body: format!("<h1>{} {}</h1>", status_code, resp.status_text()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::Handler;
+    use crate::router::Router;
+    use crate::server::Server;
+    use crate::test_util::{free_addr, start_server, wait_for};
+    use std::net::TcpStream;
+
+    struct Slow;
+
+    impl Handler for Slow {
+        fn handle<'a>(&self, _req: &'a HttpRequest) -> HttpResponse<'a> {
+            thread::sleep(Duration::from_secs(2));
+            HttpResponse::new("200", None, Some("done".to_string()))
+        }
+    }
+
+    fn send_frame(client: &mut TcpStream, kind: u8, flags: u8, stream_id: u32, payload: &[u8]) {
+        let mut buf = (payload.len() as u32).to_be_bytes()[1..].to_vec();
+        buf.extend_from_slice(&[kind, flags]);
+        buf.extend_from_slice(&stream_id.to_be_bytes());
+        buf.extend_from_slice(payload);
+        client.write_all(&buf).unwrap();
+    }
+
+    fn connect(addr: &str) -> TcpStream {
+        let mut client = TcpStream::connect(addr).unwrap();
+        client.write_all(PREFACE).unwrap();
+        send_frame(&mut client, SETTINGS, 0, 0, &[]);
+        client
+    }
+
+    fn request_headers(method: &str, path: &str) -> Vec<u8> {
+        hpack::encode(&[
+            (":method", method),
+            (":scheme", "http"),
+            (":path", path),
+            (":authority", "localhost"),
+        ])
+    }
+
+    // Read frames until `streams` responses have ended, returning each
+    // stream's status and body
+    fn responses(client: &mut TcpStream, streams: usize) -> HashMap<u32, (String, String)> {
+        let mut client = Connection::new(client.try_clone().unwrap());
+        let mut decoder = Decoder::default();
+        let mut done = HashMap::new();
+        let mut bodies: HashMap<u32, (String, String)> = HashMap::new();
+        while done.len() < streams {
+            let frame = read_frame(&mut client).unwrap().unwrap();
+            let entry = bodies.entry(frame.stream_id).or_default();
+            match frame.kind {
+                HEADERS => {
+                    let headers = decoder.decode(&frame.payload, 8192).unwrap().unwrap();
+                    entry.0 = headers[0].1.clone();
+                }
+                DATA => entry
+                    .1
+                    .push_str(std::str::from_utf8(&frame.payload).unwrap()),
+                GOAWAY => panic!("unexpected GOAWAY"),
+                _ => continue,
+            }
+            if frame.flags & END_STREAM != 0 {
+                done.insert(frame.stream_id, entry.clone());
+            }
+        }
+        done
+    }
+
+    // Read frames until one of `kind` arrives
+    fn next_frame(client: &mut TcpStream, kind: u8) -> Frame {
+        let mut client = Connection::new(client.try_clone().unwrap());
+        loop {
+            let frame = read_frame(&mut client).unwrap().unwrap();
+            if frame.kind == kind {
+                return frame;
+            }
+        }
+    }
+
+    #[test]
+    fn test_multiplexed_streams() {
+        let addr = start_server(Router::new());
+        let mut client = connect(&addr);
+
+        send_frame(
+            &mut client,
+            HEADERS,
+            END_HEADERS | END_STREAM,
+            1,
+            &request_headers("GET", "/api/shipping/orders"),
+        );
+        send_frame(
+            &mut client,
+            HEADERS,
+            END_HEADERS | END_STREAM,
+            3,
+            &request_headers("GET", "/nope"),
+        );
+        // A POST whose header block is split over a CONTINUATION frame
+        let block = request_headers("POST", "/api/shipping/orders");
+        send_frame(&mut client, HEADERS, 0, 5, &block[..4]);
+        send_frame(&mut client, CONTINUATION, END_HEADERS, 5, &block[4..]);
+        let order = r#"{"order_id":9,"order_date":"1 Mar 2021","order_status":"Shipped"}"#;
+        send_frame(&mut client, DATA, 0, 5, &order.as_bytes()[..10]);
+        send_frame(&mut client, DATA, END_STREAM, 5, &order.as_bytes()[10..]);
+
+        let responses = responses(&mut client, 3);
+        assert_eq!(responses[&1].0, "200");
+        assert!(responses[&1].1.contains("Delivered"));
+        assert_eq!(responses[&3].0, "404");
+        assert_eq!(responses[&5], ("200".to_string(), order.to_string()));
+    }
+
+    #[test]
+    fn test_flow_control_and_protocol_errors() {
+        let addr = start_server(Router::new());
+        let mut client = TcpStream::connect(&addr).unwrap();
+        client.write_all(PREFACE).unwrap();
+        // A 10 byte window: the body comes in 10 byte DATA frames, each
+        // after a WINDOW_UPDATE
+        let mut settings = SETTINGS_INITIAL_WINDOW_SIZE.to_be_bytes().to_vec();
+        settings.extend_from_slice(&10u32.to_be_bytes());
+        send_frame(&mut client, SETTINGS, 0, 0, &settings);
+        send_frame(
+            &mut client,
+            HEADERS,
+            END_HEADERS | END_STREAM,
+            1,
+            &request_headers("GET", "/api/shipping/orders"),
+        );
+
+        let mut reader = Connection::new(client.try_clone().unwrap());
+        let mut body = Vec::new();
+        loop {
+            let frame = read_frame(&mut reader).unwrap().unwrap();
+            if frame.kind != DATA {
+                continue;
+            }
+            assert!(frame.payload.len() <= 10);
+            body.extend_from_slice(&frame.payload);
+            if frame.flags & END_STREAM != 0 {
+                break;
+            }
+            send_frame(&mut client, WINDOW_UPDATE, 0, 1, &10u32.to_be_bytes());
+        }
+        assert!(String::from_utf8(body).unwrap().contains("Delivered"));
+
+        // Client streams must have odd ids
+        send_frame(
+            &mut client,
+            HEADERS,
+            END_HEADERS | END_STREAM,
+            2,
+            &request_headers("GET", "/"),
+        );
+        let goaway = loop {
+            let frame = read_frame(&mut reader).unwrap().unwrap();
+            if frame.kind == GOAWAY {
+                break frame;
+            }
+        };
+        assert_eq!(&goaway.payload[..4], &1u32.to_be_bytes());
+        assert_eq!(&goaway.payload[4..8], &PROTOCOL_ERROR.to_be_bytes());
+    }
+
+    #[test]
+    fn test_reset_streams_still_count_until_their_handlers_finish() {
+        let addr = start_server(Router::new().with_route("/slow", Slow));
+        let mut client = connect(&addr);
+        let headers = request_headers("GET", "/slow");
+        let mut id = 1;
+        for _ in 0..MAX_STREAMS {
+            send_frame(&mut client, HEADERS, END_HEADERS | END_STREAM, id, &headers);
+            send_frame(&mut client, RST_STREAM, 0, id, &CANCEL.to_be_bytes());
+            id += 2;
+        }
+        // Every handler is still busy, so the next stream is refused
+        send_frame(&mut client, HEADERS, END_HEADERS | END_STREAM, id, &headers);
+        let refused = next_frame(&mut client, RST_STREAM);
+        assert_eq!(refused.stream_id, id);
+        assert_eq!(refused.payload, REFUSED_STREAM.to_be_bytes());
+
+        // Resetting streams over and over closes the connection
+        let mut client = connect(&addr);
+        for id in (1..).step_by(2).take(MAX_RESETS as usize + 1) {
+            send_frame(&mut client, HEADERS, END_HEADERS, id, &headers);
+            send_frame(&mut client, RST_STREAM, 0, id, &CANCEL.to_be_bytes());
+        }
+        let goaway = next_frame(&mut client, GOAWAY);
+        assert_eq!(&goaway.payload[4..8], &ENHANCE_YOUR_CALM.to_be_bytes());
+    }
+
+    #[test]
+    fn test_stalled_streams_are_reset() {
+        let addr = free_addr();
+        let server = Server::builder()
+            .bind(&addr)
+            .limits(Limits {
+                read_timeout: Duration::from_millis(300),
+                ..Limits::default()
+            })
+            .build()
+            .unwrap();
+        thread::spawn(move || server.run());
+        wait_for(&addr);
+
+        let mut client = connect(&addr);
+        let started = Instant::now();
+        // A request that never ends, while the client keeps pinging
+        send_frame(
+            &mut client,
+            HEADERS,
+            END_HEADERS,
+            1,
+            &request_headers("POST", "/"),
+        );
+        let pinger = {
+            let mut client = client.try_clone().unwrap();
+            thread::spawn(move || {
+                for _ in 0..10 {
+                    send_frame(&mut client, PING, 0, 0, &[0; 8]);
+                    thread::sleep(Duration::from_millis(100));
+                }
+            })
+        };
+        let reset = next_frame(&mut client, RST_STREAM);
+        assert_eq!(reset.stream_id, 1);
+        assert_eq!(reset.payload, CANCEL.to_be_bytes());
+        assert!(started.elapsed() < Duration::from_secs(2));
+        pinger.join().unwrap();
+    }
+
+    #[test]
+    fn test_header_blocks_that_expand_too_far_get_431() {
+        let addr = start_server(Router::new());
+        let mut client = connect(&addr);
+        // Put a 4000 byte header in the dynamic table, then refer to it
+        // once per byte of the next block
+        let big = "a".repeat(4000);
+        let mut literal = hpack::encode(&[("x-big", &big)]);
+        // Turn the plain literal into one the decoder adds to its table
+        literal[0] = 0x40;
+        let mut first = request_headers("GET", "/");
+        first.extend_from_slice(&literal);
+        send_frame(&mut client, HEADERS, END_HEADERS | END_STREAM, 1, &first);
+        let mut second = request_headers("GET", "/");
+        second.extend_from_slice(&[0xbe; 8000]);
+        send_frame(&mut client, HEADERS, END_HEADERS | END_STREAM, 3, &second);
+
+        let responses = responses(&mut client, 2);
+        assert_eq!(responses[&1].0, "200");
+        assert_eq!(responses[&3].0, "431");
+    }
+}
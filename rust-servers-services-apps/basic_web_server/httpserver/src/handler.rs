@@ -1,23 +1,37 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
 use std::env;
 use std::fs;
+use std::path::Path;
+use std::sync::Arc;
 
-use http::{httprequest::HttpRequest, httpresponse::HttpResponse};
+use super::sse::EventBus;
+use super::template::{TemplateEngine, Templates, TEMPLATE_SUFFIX};
+use http::{httprequest, httprequest::HttpRequest, httpresponse::HttpResponse};
 
 pub trait Handler {
-    fn handle(req: &HttpRequest) -> HttpResponse;
+    fn handle<'a>(&self, req: &'a HttpRequest) -> HttpResponse<'a>;
 
-    fn load_file(file_name: &str) -> Option<String> {
-        let default_path = format!("{}/public", env!("CARGO_MANIFEST_DIR"));
-        let public_path = env::var("PUBLIC_PATH").unwrap_or(default_path);
-        let full_path = format!("{}/{}", public_path, file_name);
+    // Directory the handler loads its pages from
+    fn public_path(&self) -> &str {
+        "."
+    }
+
+    fn load_file(&self, file_name: &str) -> Option<String> {
+        let full_path = format!("{}/{}", self.public_path(), file_name);
 
         let contents = fs::read_to_string(full_path);
         contents.ok()
     }
 }
 
+// The document root used when none is configured
+pub fn default_public_path() -> String {
+    let default_path = format!("{}/public", env!("CARGO_MANIFEST_DIR"));
+    env::var("PUBLIC_PATH").unwrap_or(default_path)
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct OrderStatus {
     order_id: i32,
@@ -25,53 +39,133 @@ pub struct OrderStatus {
     order_status: String,
 }
 
-pub struct StaticPageHandler;
+pub struct StaticPageHandler {
+    public_path: String,
+    not_found: PageNotFoundHandler,
+    // Renders `<page>.tmpl` files found in place of a page
+    templates: Arc<dyn TemplateEngine>,
+    // What templates are rendered with
+    context: Value,
+}
 
-pub struct PageNotFoundHandler;
+pub struct PageNotFoundHandler {
+    public_path: String,
+    // File name of the 404 page inside the public path
+    page: String,
+}
 
-pub struct WebServiceHandler;
+pub struct WebServiceHandler {
+    not_found: PageNotFoundHandler,
+    // Where order status changes are published, if anywhere
+    events: Option<EventBus>,
+}
+
+impl PageNotFoundHandler {
+    pub fn new(public_path: &str, page: &str) -> Self {
+        PageNotFoundHandler {
+            public_path: public_path.to_string(),
+            page: page.to_string(),
+        }
+    }
+}
 
 impl Handler for PageNotFoundHandler {
-    fn handle(_req: &HttpRequest) -> HttpResponse {
-        HttpResponse::new("404", None, Self::load_file("404.html"))
+    fn handle<'a>(&self, _req: &'a HttpRequest) -> HttpResponse<'a> {
+        HttpResponse::new("404", None, self.load_file(&self.page))
+    }
+
+    fn public_path(&self) -> &str {
+        &self.public_path
+    }
+}
+
+impl StaticPageHandler {
+    pub fn new(public_path: &str, not_found_page: &str) -> Self {
+        StaticPageHandler {
+            public_path: public_path.to_string(),
+            not_found: PageNotFoundHandler::new(public_path, not_found_page),
+            templates: Arc::new(Templates::new(public_path)),
+            context: Value::Null,
+        }
+    }
+
+    pub fn with_templates(mut self, templates: Arc<dyn TemplateEngine>) -> Self {
+        self.templates = templates;
+        self
+    }
+
+    pub fn with_context(mut self, context: Value) -> Self {
+        self.context = context;
+        self
+    }
+
+    // Serve a file from the document root, or render its template
+    fn page<'a>(&self, req: &'a HttpRequest, path: &str) -> HttpResponse<'a> {
+        let template = format!("{}{}", path, TEMPLATE_SUFFIX);
+        let contents = match self.load_file(path) {
+            Some(contents) => contents,
+            None if Path::new(&self.public_path).join(&template).is_file() => {
+                match self.templates.render(&template, &self.context) {
+                    Ok(contents) => contents,
+                    Err(e) => {
+                        eprintln!("Template error: {}", e);
+                        return HttpResponse::new("500", None, Some(String::new()));
+                    }
+                }
+            }
+            None => return self.not_found.handle(req),
+        };
+
+        let mut map: HashMap<&str, &str> = HashMap::new();
+        if path.ends_with(".css") {
+            map.insert("Content-Type", "text/css");
+        } else if path.ends_with(".js") {
+            map.insert("Content-Type", "text/javascript");
+        } else {
+            map.insert("Content-Type", "text/html");
+        }
+        HttpResponse::new("200", Some(map), Some(contents))
     }
 }
 
 impl Handler for StaticPageHandler {
-    fn handle(req: &HttpRequest) -> HttpResponse {
+    fn handle<'a>(&self, req: &'a HttpRequest) -> HttpResponse<'a> {
         // Get the path of static page resource being requested
         let http::httprequest::Resource::Path(s) = &req.resource;
 
         // Parse the URI
         let route: Vec<&str> = s.split("/").collect();
         match route[1] {
-            "" => HttpResponse::new("200", None, Self::load_file("index.html")),
+            "" => self.page(req, "index.html"),
 
-            "health" => HttpResponse::new("200", None, Self::load_file("health.html")),
+            "health" => self.page(req, "health.html"),
 
-            path => match Self::load_file(path) {
-                Some(contents) => {
-                    let mut map: HashMap<&str, &str> = HashMap::new();
+            // Template sources are not served as they are
+            path if path.ends_with(TEMPLATE_SUFFIX) => self.not_found.handle(req),
 
-                    if path.ends_with(".css") {
-                        map.insert("Content-Type", "text/css");
-                    } else if path.ends_with(".js") {
-                        map.insert("Content-Type", "text/javascript");
-                    } else {
-                        map.insert("Content-Type", "text/html");
-                    }
-
-                    HttpResponse::new("200", Some(map), Some(contents))
-                }
-
-                None => HttpResponse::new("404", None, Self::load_file("404.html")),
-            },
+            path => self.page(req, path),
         }
     }
+
+    fn public_path(&self) -> &str {
+        &self.public_path
+    }
 }
 
 // Define a load_json() method to load orders.json file from disk
 impl WebServiceHandler {
+    pub fn new(public_path: &str, not_found_page: &str) -> Self {
+        WebServiceHandler {
+            not_found: PageNotFoundHandler::new(public_path, not_found_page),
+            events: None,
+        }
+    }
+
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = Some(events);
+        self
+    }
+
     fn load_json() -> Vec<OrderStatus> {
         let default_path = format!("{}/data", env!("CARGO_MANIFEST_DIR"));
         let data_path = env::var("DATA_PATH").unwrap_or(default_path);
@@ -86,20 +180,38 @@ impl WebServiceHandler {
 
 // Implement the Handler trait
 impl Handler for WebServiceHandler {
-    fn handle(req: &HttpRequest) -> HttpResponse {
+    fn handle<'a>(&self, req: &'a HttpRequest) -> HttpResponse<'a> {
         let http::httprequest::Resource::Path(s) = &req.resource;
 
         // Parse the URI
         let route: Vec<&str> = s.split("/").collect();
         // if route if /api/shipping/orders, return json
-        match route[2] {
-            "shipping" if route.len() > 2 && route[3] == "orders" => {
+        let orders = route.get(2) == Some(&"shipping") && route.get(3) == Some(&"orders");
+        match (&req.method, &self.events) {
+            (httprequest::Method::Get, _) if orders => {
                 let body = Some(serde_json::to_string(&Self::load_json()).unwrap());
                 let mut headers: HashMap<&str, &str> = HashMap::new();
                 headers.insert("Content-Type", "application/json");
                 HttpResponse::new("200", Some(headers), body)
             }
-            _ => HttpResponse::new("404", None, Self::load_file("404.html")),
+            // A POSTed order status is pushed to the event stream subscribers
+            (httprequest::Method::Post, Some(events)) if orders => {
+                match serde_json::from_str::<OrderStatus>(&req.msg_body) {
+                    Ok(order) => {
+                        let body = serde_json::to_string(&order).unwrap();
+                        events.publish("order_status", &body);
+                        let mut headers: HashMap<&str, &str> = HashMap::new();
+                        headers.insert("Content-Type", "application/json");
+                        HttpResponse::new("200", Some(headers), Some(body))
+                    }
+                    Err(_) => HttpResponse::new("400", None, Some(String::new())),
+                }
+            }
+            _ => self.not_found.handle(req),
         }
     }
+
+    fn public_path(&self) -> &str {
+        self.not_found.public_path()
+    }
 }
@@ -0,0 +1,694 @@
+//! The hpack module implements HPACK (RFC 7541), the header compression
+//! used by HTTP/2. The decoder understands every representation, including
+//! Huffman coded strings and the dynamic table; the encoder only emits
+//! static table references and plain literals, so it keeps no state.
+//!
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::OnceLock;
+
+// Size of the dynamic table unless the peer asks for less
+pub const DEFAULT_TABLE_SIZE: usize = 4096;
+
+#[derive(Debug, PartialEq)]
+pub struct HpackError;
+
+const STATIC_TABLE: [(&str, &str); 61] = [
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
+
+// Huffman code and its length in bits, by symbol (256 is end-of-string)
+const HUFFMAN_CODES: [(u32, u8); 257] = [
+    (0x1ff8, 13),
+    (0x7fffd8, 23),
+    (0xfffffe2, 28),
+    (0xfffffe3, 28),
+    (0xfffffe4, 28),
+    (0xfffffe5, 28),
+    (0xfffffe6, 28),
+    (0xfffffe7, 28),
+    (0xfffffe8, 28),
+    (0xffffea, 24),
+    (0x3ffffffc, 30),
+    (0xfffffe9, 28),
+    (0xfffffea, 28),
+    (0x3ffffffd, 30),
+    (0xfffffeb, 28),
+    (0xfffffec, 28),
+    (0xfffffed, 28),
+    (0xfffffee, 28),
+    (0xfffffef, 28),
+    (0xffffff0, 28),
+    (0xffffff1, 28),
+    (0xffffff2, 28),
+    (0x3ffffffe, 30),
+    (0xffffff3, 28),
+    (0xffffff4, 28),
+    (0xffffff5, 28),
+    (0xffffff6, 28),
+    (0xffffff7, 28),
+    (0xffffff8, 28),
+    (0xffffff9, 28),
+    (0xffffffa, 28),
+    (0xffffffb, 28),
+    (0x14, 6),
+    (0x3f8, 10),
+    (0x3f9, 10),
+    (0xffa, 12),
+    (0x1ff9, 13),
+    (0x15, 6),
+    (0xf8, 8),
+    (0x7fa, 11),
+    (0x3fa, 10),
+    (0x3fb, 10),
+    (0xf9, 8),
+    (0x7fb, 11),
+    (0xfa, 8),
+    (0x16, 6),
+    (0x17, 6),
+    (0x18, 6),
+    (0x0, 5),
+    (0x1, 5),
+    (0x2, 5),
+    (0x19, 6),
+    (0x1a, 6),
+    (0x1b, 6),
+    (0x1c, 6),
+    (0x1d, 6),
+    (0x1e, 6),
+    (0x1f, 6),
+    (0x5c, 7),
+    (0xfb, 8),
+    (0x7ffc, 15),
+    (0x20, 6),
+    (0xffb, 12),
+    (0x3fc, 10),
+    (0x1ffa, 13),
+    (0x21, 6),
+    (0x5d, 7),
+    (0x5e, 7),
+    (0x5f, 7),
+    (0x60, 7),
+    (0x61, 7),
+    (0x62, 7),
+    (0x63, 7),
+    (0x64, 7),
+    (0x65, 7),
+    (0x66, 7),
+    (0x67, 7),
+    (0x68, 7),
+    (0x69, 7),
+    (0x6a, 7),
+    (0x6b, 7),
+    (0x6c, 7),
+    (0x6d, 7),
+    (0x6e, 7),
+    (0x6f, 7),
+    (0x70, 7),
+    (0x71, 7),
+    (0x72, 7),
+    (0xfc, 8),
+    (0x73, 7),
+    (0xfd, 8),
+    (0x1ffb, 13),
+    (0x7fff0, 19),
+    (0x1ffc, 13),
+    (0x3ffc, 14),
+    (0x22, 6),
+    (0x7ffd, 15),
+    (0x3, 5),
+    (0x23, 6),
+    (0x4, 5),
+    (0x24, 6),
+    (0x5, 5),
+    (0x25, 6),
+    (0x26, 6),
+    (0x27, 6),
+    (0x6, 5),
+    (0x74, 7),
+    (0x75, 7),
+    (0x28, 6),
+    (0x29, 6),
+    (0x2a, 6),
+    (0x7, 5),
+    (0x2b, 6),
+    (0x76, 7),
+    (0x2c, 6),
+    (0x8, 5),
+    (0x9, 5),
+    (0x2d, 6),
+    (0x77, 7),
+    (0x78, 7),
+    (0x79, 7),
+    (0x7a, 7),
+    (0x7b, 7),
+    (0x7ffe, 15),
+    (0x7fc, 11),
+    (0x3ffd, 14),
+    (0x1ffd, 13),
+    (0xffffffc, 28),
+    (0xfffe6, 20),
+    (0x3fffd2, 22),
+    (0xfffe7, 20),
+    (0xfffe8, 20),
+    (0x3fffd3, 22),
+    (0x3fffd4, 22),
+    (0x3fffd5, 22),
+    (0x7fffd9, 23),
+    (0x3fffd6, 22),
+    (0x7fffda, 23),
+    (0x7fffdb, 23),
+    (0x7fffdc, 23),
+    (0x7fffdd, 23),
+    (0x7fffde, 23),
+    (0xffffeb, 24),
+    (0x7fffdf, 23),
+    (0xffffec, 24),
+    (0xffffed, 24),
+    (0x3fffd7, 22),
+    (0x7fffe0, 23),
+    (0xffffee, 24),
+    (0x7fffe1, 23),
+    (0x7fffe2, 23),
+    (0x7fffe3, 23),
+    (0x7fffe4, 23),
+    (0x1fffdc, 21),
+    (0x3fffd8, 22),
+    (0x7fffe5, 23),
+    (0x3fffd9, 22),
+    (0x7fffe6, 23),
+    (0x7fffe7, 23),
+    (0xffffef, 24),
+    (0x3fffda, 22),
+    (0x1fffdd, 21),
+    (0xfffe9, 20),
+    (0x3fffdb, 22),
+    (0x3fffdc, 22),
+    (0x7fffe8, 23),
+    (0x7fffe9, 23),
+    (0x1fffde, 21),
+    (0x7fffea, 23),
+    (0x3fffdd, 22),
+    (0x3fffde, 22),
+    (0xfffff0, 24),
+    (0x1fffdf, 21),
+    (0x3fffdf, 22),
+    (0x7fffeb, 23),
+    (0x7fffec, 23),
+    (0x1fffe0, 21),
+    (0x1fffe1, 21),
+    (0x3fffe0, 22),
+    (0x1fffe2, 21),
+    (0x7fffed, 23),
+    (0x3fffe1, 22),
+    (0x7fffee, 23),
+    (0x7fffef, 23),
+    (0xfffea, 20),
+    (0x3fffe2, 22),
+    (0x3fffe3, 22),
+    (0x3fffe4, 22),
+    (0x7ffff0, 23),
+    (0x3fffe5, 22),
+    (0x3fffe6, 22),
+    (0x7ffff1, 23),
+    (0x3ffffe0, 26),
+    (0x3ffffe1, 26),
+    (0xfffeb, 20),
+    (0x7fff1, 19),
+    (0x3fffe7, 22),
+    (0x7ffff2, 23),
+    (0x3fffe8, 22),
+    (0x1ffffec, 25),
+    (0x3ffffe2, 26),
+    (0x3ffffe3, 26),
+    (0x3ffffe4, 26),
+    (0x7ffffde, 27),
+    (0x7ffffdf, 27),
+    (0x3ffffe5, 26),
+    (0xfffff1, 24),
+    (0x1ffffed, 25),
+    (0x7fff2, 19),
+    (0x1fffe3, 21),
+    (0x3ffffe6, 26),
+    (0x7ffffe0, 27),
+    (0x7ffffe1, 27),
+    (0x3ffffe7, 26),
+    (0x7ffffe2, 27),
+    (0xfffff2, 24),
+    (0x1fffe4, 21),
+    (0x1fffe5, 21),
+    (0x3ffffe8, 26),
+    (0x3ffffe9, 26),
+    (0xffffffd, 28),
+    (0x7ffffe3, 27),
+    (0x7ffffe4, 27),
+    (0x7ffffe5, 27),
+    (0xfffec, 20),
+    (0xfffff3, 24),
+    (0xfffed, 20),
+    (0x1fffe6, 21),
+    (0x3fffe9, 22),
+    (0x1fffe7, 21),
+    (0x1fffe8, 21),
+    (0x7ffff3, 23),
+    (0x3fffea, 22),
+    (0x3fffeb, 22),
+    (0x1ffffee, 25),
+    (0x1ffffef, 25),
+    (0xfffff4, 24),
+    (0xfffff5, 24),
+    (0x3ffffea, 26),
+    (0x7ffff4, 23),
+    (0x3ffffeb, 26),
+    (0x7ffffe6, 27),
+    (0x3ffffec, 26),
+    (0x3ffffed, 26),
+    (0x7ffffe7, 27),
+    (0x7ffffe8, 27),
+    (0x7ffffe9, 27),
+    (0x7ffffea, 27),
+    (0x7ffffeb, 27),
+    (0xffffffe, 28),
+    (0x7ffffec, 27),
+    (0x7ffffed, 27),
+    (0x7ffffee, 27),
+    (0x7ffffef, 27),
+    (0x7fffff0, 27),
+    (0x3ffffee, 26),
+    (0x3fffffff, 30),
+];
+
+pub struct Decoder {
+    // Newest entry first
+    table: VecDeque<(String, String)>,
+    size: usize,
+    max_size: usize,
+    // Upper bound for size updates, as advertised in our SETTINGS
+    limit: usize,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Decoder::new(DEFAULT_TABLE_SIZE)
+    }
+}
+
+impl Decoder {
+    pub fn new(limit: usize) -> Self {
+        Decoder {
+            table: VecDeque::new(),
+            size: 0,
+            max_size: limit,
+            limit,
+        }
+    }
+
+    // Decode a complete header block into (name, value) pairs. Returns None
+    // once the names and values add up to more than `max_size` bytes; the
+    // rest of the block is still read, to keep the dynamic table in step,
+    // but nothing more is copied out of it.
+    pub fn decode(
+        &mut self,
+        mut block: &[u8],
+        max_size: usize,
+    ) -> Result<Option<Vec<(String, String)>>, HpackError> {
+        let mut headers = Some(Vec::new());
+        let mut size = 0;
+        // Keep a header if there is still room for it
+        let mut add = |headers: &mut Option<Vec<_>>, (name, value): (&str, &str)| {
+            size += name.len() + value.len();
+            if size > max_size {
+                *headers = None;
+            }
+            if let Some(headers) = headers {
+                headers.push((name.to_string(), value.to_string()));
+            }
+        };
+        while let Some(&first) = block.first() {
+            if first & 0x80 != 0 {
+                // Indexed header field
+                let index = decode_int(&mut block, 7)?;
+                add(&mut headers, self.entry(index)?);
+            } else if first & 0x40 != 0 {
+                // Literal with incremental indexing
+                let header = self.literal(&mut block, 6)?;
+                add(&mut headers, (&header.0, &header.1));
+                self.insert(header);
+            } else if first & 0x20 != 0 {
+                // Dynamic table size update
+                let size = decode_int(&mut block, 5)?;
+                if size > self.limit {
+                    return Err(HpackError);
+                }
+                self.max_size = size;
+                self.evict(0);
+            } else {
+                // Literal without indexing, or never indexed
+                let header = self.literal(&mut block, 4)?;
+                add(&mut headers, (&header.0, &header.1));
+            }
+        }
+        Ok(headers)
+    }
+
+    fn literal(&self, block: &mut &[u8], prefix: u8) -> Result<(String, String), HpackError> {
+        let index = decode_int(block, prefix)?;
+        let name = if index == 0 {
+            decode_string(block)?
+        } else {
+            self.entry(index)?.0.to_string()
+        };
+        Ok((name, decode_string(block)?))
+    }
+
+    fn entry(&self, index: usize) -> Result<(&str, &str), HpackError> {
+        match index {
+            0 => Err(HpackError),
+            1..=61 => Ok(STATIC_TABLE[index - 1]),
+            _ => self
+                .table
+                .get(index - 62)
+                .map(|(name, value)| (name.as_str(), value.as_str()))
+                .ok_or(HpackError),
+        }
+    }
+
+    fn insert(&mut self, header: (String, String)) {
+        let size = entry_size(&header);
+        if size > self.max_size {
+            // An entry larger than the table empties it
+            self.table.clear();
+            self.size = 0;
+            return;
+        }
+        self.evict(size);
+        self.size += size;
+        self.table.push_front(header);
+    }
+
+    // Drop the oldest entries until `room` more bytes fit
+    fn evict(&mut self, room: usize) {
+        while self.size + room > self.max_size {
+            match self.table.pop_back() {
+                Some(old) => self.size -= entry_size(&old),
+                None => break,
+            }
+        }
+    }
+}
+
+// Encode headers using the static table where possible and plain literals
+// otherwise; names must already be lowercase
+pub fn encode(headers: &[(&str, &str)]) -> Vec<u8> {
+    let mut block = Vec::new();
+    for (name, value) in headers {
+        let exact = STATIC_TABLE
+            .iter()
+            .position(|(n, v)| n == name && v == value);
+        if let Some(i) = exact {
+            encode_int(&mut block, 0x80, 7, i + 1);
+            continue;
+        }
+        // Literal without indexing, with an indexed name when there is one
+        match STATIC_TABLE.iter().position(|(n, _)| n == name) {
+            Some(i) => encode_int(&mut block, 0x00, 4, i + 1),
+            None => {
+                block.push(0x00);
+                encode_string(&mut block, name);
+            }
+        }
+        encode_string(&mut block, value);
+    }
+    block
+}
+
+// The size an entry counts for in the dynamic table (RFC 7541 section 4.1)
+fn entry_size((name, value): &(String, String)) -> usize {
+    name.len() + value.len() + 32
+}
+
+fn decode_int(block: &mut &[u8], prefix: u8) -> Result<usize, HpackError> {
+    let (&first, rest) = block.split_first().ok_or(HpackError)?;
+    *block = rest;
+    let max = (1usize << prefix) - 1;
+    let mut value = (first as usize) & max;
+    if value < max {
+        return Ok(value);
+    }
+    let mut shift = 0;
+    loop {
+        let (&byte, rest) = block.split_first().ok_or(HpackError)?;
+        *block = rest;
+        if shift > 28 {
+            return Err(HpackError);
+        }
+        value += ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+}
+
+fn encode_int(block: &mut Vec<u8>, flags: u8, prefix: u8, mut value: usize) {
+    let max = (1usize << prefix) - 1;
+    if value < max {
+        block.push(flags | value as u8);
+        return;
+    }
+    block.push(flags | max as u8);
+    value -= max;
+    while value >= 0x80 {
+        block.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    block.push(value as u8);
+}
+
+fn decode_string(block: &mut &[u8]) -> Result<String, HpackError> {
+    let huffman = block.first().ok_or(HpackError)? & 0x80 != 0;
+    let len = decode_int(block, 7)?;
+    if block.len() < len {
+        return Err(HpackError);
+    }
+    let (raw, rest) = block.split_at(len);
+    *block = rest;
+    let bytes = if huffman {
+        huffman_decode(raw)?
+    } else {
+        raw.to_vec()
+    };
+    String::from_utf8(bytes).map_err(|_| HpackError)
+}
+
+fn encode_string(block: &mut Vec<u8>, value: &str) {
+    encode_int(block, 0x00, 7, value.len());
+    block.extend_from_slice(value.as_bytes());
+}
+
+fn huffman_decode(raw: &[u8]) -> Result<Vec<u8>, HpackError> {
+    static SYMBOLS: OnceLock<HashMap<(u8, u32), u16>> = OnceLock::new();
+    let symbols = SYMBOLS.get_or_init(|| {
+        HUFFMAN_CODES
+            .iter()
+            .enumerate()
+            .map(|(symbol, &(code, len))| ((len, code), symbol as u16))
+            .collect()
+    });
+
+    let mut out = Vec::new();
+    let (mut code, mut len) = (0u32, 0u8);
+    for byte in raw {
+        for bit in (0..8).rev() {
+            code = (code << 1) | ((byte >> bit) & 1) as u32;
+            len += 1;
+            match symbols.get(&(len, code)) {
+                // A decoded end-of-string symbol is an error
+                Some(256) => return Err(HpackError),
+                Some(&symbol) => {
+                    out.push(symbol as u8);
+                    code = 0;
+                    len = 0;
+                }
+                None if len >= 30 => return Err(HpackError),
+                None => {}
+            }
+        }
+    }
+    // What is left must be padding: fewer than 8 bits, all ones
+    if len >= 8 || code != (1 << len) - 1 {
+        return Err(HpackError);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        let s: String = s.split_whitespace().collect();
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn pairs(headers: &[(&str, &str)]) -> Vec<(String, String)> {
+        headers
+            .iter()
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_integer_with_prefix() {
+        // RFC 7541 C.1.2: 1337 with a 5-bit prefix
+        let mut block = Vec::new();
+        encode_int(&mut block, 0, 5, 1337);
+        assert_eq!(block, vec![0x1f, 0x9a, 0x0a]);
+        assert_eq!(decode_int(&mut block.as_slice(), 5), Ok(1337));
+        assert_eq!(decode_int(&mut [0x1f, 0x9a].as_slice(), 5), Err(HpackError));
+    }
+
+    #[test]
+    fn test_requests_with_dynamic_table() {
+        // RFC 7541 C.3, requests without Huffman coding
+        let mut decoder = Decoder::default();
+        let first = decoder
+            .decode(
+                &hex("8286 8441 0f77 7777 2e65 7861 6d70 6c65 2e63 6f6d"),
+                1024,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            first,
+            pairs(&[
+                (":method", "GET"),
+                (":scheme", "http"),
+                (":path", "/"),
+                (":authority", "www.example.com"),
+            ])
+        );
+        let second = decoder
+            .decode(&hex("8286 84be 5808 6e6f 2d63 6163 6865"), 1024)
+            .unwrap()
+            .unwrap();
+        assert_eq!(second[3], (":authority".into(), "www.example.com".into()));
+        assert_eq!(second[4], ("cache-control".into(), "no-cache".into()));
+        assert_eq!(decoder.size, 110);
+    }
+
+    #[test]
+    fn test_huffman_coded_request() {
+        // RFC 7541 C.4.1
+        let mut decoder = Decoder::default();
+        let headers = decoder
+            .decode(&hex("8286 8441 8cf1 e3c2 e5f2 3a6b a0ab 90f4 ff"), 1024)
+            .unwrap()
+            .unwrap();
+        assert_eq!(headers[3], (":authority".into(), "www.example.com".into()));
+        // Padding longer than 7 bits is rejected
+        assert_eq!(huffman_decode(&[0xff, 0xff]), Err(HpackError));
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let headers = [
+            (":status", "200"),
+            (":status", "201"),
+            ("content-type", "text/html"),
+            ("x-custom", "yes"),
+        ];
+        let block = encode(&headers);
+        assert_eq!(block[0], 0x88);
+        assert_eq!(
+            Decoder::default().decode(&block, 1024),
+            Ok(Some(pairs(&headers)))
+        );
+    }
+
+    #[test]
+    fn test_table_size_update_is_bounded() {
+        let mut decoder = Decoder::new(100);
+        assert!(decoder.decode(&[0x3f, 0x45], 1024).is_ok());
+        assert_eq!(decoder.decode(&[0x3f, 0xe1, 0x1f], 1024), Err(HpackError));
+    }
+
+    #[test]
+    fn test_decoded_size_is_bounded() {
+        // One large entry in the dynamic table, then a block that refers
+        // to it over and over, one byte at a time
+        let mut decoder = Decoder::default();
+        let mut block = vec![0x40];
+        encode_string(&mut block, "x-big");
+        encode_string(&mut block, &"a".repeat(4000));
+        assert!(decoder.decode(&block, 8192).unwrap().is_some());
+        let repeated = vec![0xbe; 8192];
+        assert_eq!(decoder.decode(&repeated, 8192), Ok(None));
+        // The table is still in step afterwards
+        let next = decoder.decode(&[0xbe], 8192).unwrap().unwrap();
+        assert_eq!(next[0].0, "x-big");
+    }
+}
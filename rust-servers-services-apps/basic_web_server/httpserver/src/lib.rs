@@ -0,0 +1,43 @@
+//! An embeddable HTTP server. Put one together with the builder:
+//!
+//! ```no_run
+//! use httpserver::Server;
+//!
+//! let server = Server::builder()
+//!     .bind("127.0.0.1:3000")
+//!     .static_dir("public")
+//!     .build()?;
+//! server.run()?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! Custom handlers implement [`Handler`] and are mounted with `route()`.
+//! Clients may speak HTTP/1.1, or HTTP/2 with prior knowledge (h2c) on the
+//! same listeners.
+//!
+
+pub mod auth;
+pub mod connection;
+pub mod cors;
+pub mod event_loop;
+pub mod h2;
+pub mod handler;
+pub mod hpack;
+pub mod limits;
+pub mod listener;
+pub mod metrics;
+pub mod proxy;
+pub mod ratelimit;
+pub mod rewrite;
+pub mod router;
+pub mod server;
+pub mod sse;
+pub mod template;
+#[cfg(test)]
+mod test_util;
+pub mod vhost;
+
+pub use handler::Handler;
+pub use http;
+pub use router::Router;
+pub use server::{Server, ServerBuilder};
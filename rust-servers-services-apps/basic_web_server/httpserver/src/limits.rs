@@ -0,0 +1,428 @@
+//! The limits module reads an incoming HTTP request off the wire while
+//! enforcing size limits and deadlines, so that oversized or slow
+//! (slowloris-style) clients cannot tie up the server.
+//!
+
+use std::env;
+use std::io::{self, prelude::*};
+use std::time::{Duration, Instant};
+
+use super::connection::Connection;
+use http::httprequest::HttpRequest;
+
+#[derive(Debug, Clone)]
+pub struct Limits {
+    // Longest request target (URI) accepted, in bytes
+    pub max_uri_len: usize,
+    // Maximum size of the request line plus all header lines, in bytes
+    pub max_header_bytes: usize,
+    // Maximum number of header lines
+    pub max_headers: usize,
+    // Maximum size of the message body, in bytes
+    pub max_body_bytes: usize,
+    // Time allowed for the client to send the complete request
+    pub read_timeout: Duration,
+    // Time allowed for each write of the response to the client
+    pub write_timeout: Duration,
+    // Maximum number of connections served at the same time
+    pub max_connections: usize,
+    // How long an idle keep-alive connection is kept open (event loop mode)
+    pub keep_alive_timeout: Duration,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_uri_len: 2048,
+            max_header_bytes: 8 * 1024,
+            max_headers: 100,
+            max_body_bytes: 1024 * 1024,
+            read_timeout: Duration::from_secs(10),
+            write_timeout: Duration::from_secs(10),
+            max_connections: 256,
+            keep_alive_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+impl Limits {
+    // Build the limits from environment variables, falling back to the defaults
+    pub fn from_env() -> Self {
+        let default = Limits::default();
+        Limits {
+            max_uri_len: env_or("MAX_URI_LEN", default.max_uri_len),
+            max_header_bytes: env_or("MAX_HEADER_BYTES", default.max_header_bytes),
+            max_headers: env_or("MAX_HEADERS", default.max_headers),
+            max_body_bytes: env_or("MAX_BODY_BYTES", default.max_body_bytes),
+            read_timeout: Duration::from_millis(env_or(
+                "READ_TIMEOUT_MS",
+                default.read_timeout.as_millis() as u64,
+            )),
+            write_timeout: Duration::from_millis(env_or(
+                "WRITE_TIMEOUT_MS",
+                default.write_timeout.as_millis() as u64,
+            )),
+            max_connections: env_or("MAX_CONNECTIONS", default.max_connections),
+            keep_alive_timeout: Duration::from_millis(env_or(
+                "KEEP_ALIVE_TIMEOUT_MS",
+                default.keep_alive_timeout.as_millis() as u64,
+            )),
+        }
+    }
+}
+
+// Parse an environment variable, falling back to `default` when unset or invalid
+pub fn env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+    env::var(key)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+// Reasons an incoming request could not be read
+#[derive(Debug)]
+pub enum RequestError {
+    BadRequest,
+    Timeout,
+    PayloadTooLarge,
+    UriTooLong,
+    HeadersTooLarge,
+    // The request uses a transfer coding (chunked bodies) that is not supported
+    NotImplemented,
+    // The client went away or the socket failed; there is nobody to answer
+    Disconnected(io::Error),
+}
+
+impl RequestError {
+    // The status code to answer with, if the client can still be answered
+    pub fn status_code(&self) -> Option<&'static str> {
+        match self {
+            RequestError::BadRequest => Some("400"),
+            RequestError::Timeout => Some("408"),
+            RequestError::PayloadTooLarge => Some("413"),
+            RequestError::UriTooLong => Some("414"),
+            RequestError::HeadersTooLarge => Some("431"),
+            RequestError::NotImplemented => Some("501"),
+            RequestError::Disconnected(_) => None,
+        }
+    }
+
+    // A short label for metrics
+    pub fn reason(&self) -> &'static str {
+        match self {
+            RequestError::BadRequest => "bad_request",
+            RequestError::Timeout => "timeout",
+            RequestError::PayloadTooLarge => "payload_too_large",
+            RequestError::UriTooLong => "uri_too_long",
+            RequestError::HeadersTooLarge => "headers_too_large",
+            RequestError::NotImplemented => "not_implemented",
+            RequestError::Disconnected(_) => "disconnected",
+        }
+    }
+}
+
+// A request whose head has been read but whose body may still be on the wire
+pub struct PendingRequest {
+    pub req: HttpRequest,
+    // The request head exactly as received (header values are not truncated)
+    pub raw_head: String,
+    body: Vec<u8>,
+    content_length: usize,
+    deadline: Instant,
+}
+
+// A parsed request head
+pub struct Head {
+    pub req: HttpRequest,
+    // The request head exactly as received (header values are not truncated)
+    pub raw: String,
+    // Length of the head, including the blank line that ends it
+    pub len: usize,
+    pub content_length: usize,
+}
+
+// Parse the request head at the start of `buffer`. Returns None while the
+// head is incomplete, and an error as soon as it breaks a limit.
+pub fn parse_head(buffer: &[u8], limits: &Limits) -> Result<Option<Head>, RequestError> {
+    let Some(head_end) = find_head_end(buffer) else {
+        check_head(buffer, limits)?;
+        return Ok(None);
+    };
+    let head = std::str::from_utf8(&buffer[..head_end]).map_err(|_| RequestError::BadRequest)?;
+    check_head(head.as_bytes(), limits)?;
+
+    // Validate the request line before handing it to the parser
+    let request_line = head.lines().next().unwrap_or("");
+    let parts: Vec<&str> = request_line.split_whitespace().collect();
+    if parts.len() != 3 || !parts[2].starts_with("HTTP/") {
+        return Err(RequestError::BadRequest);
+    }
+
+    let content_length = body_length(head)?;
+    let req: HttpRequest = head.to_string().into();
+    if content_length > limits.max_body_bytes {
+        return Err(RequestError::PayloadTooLarge);
+    }
+    Ok(Some(Head {
+        req,
+        raw: head.to_string(),
+        len: head_end + 4,
+        content_length,
+    }))
+}
+
+// Read the head of one request from the stream. The read deadline covers the
+// whole request, so a client trickling in a byte at a time still times out.
+pub fn read_request_head(
+    stream: &mut Connection,
+    limits: &Limits,
+) -> Result<PendingRequest, RequestError> {
+    let deadline = Instant::now() + limits.read_timeout;
+    let mut buffer: Vec<u8> = Vec::new();
+
+    // Read until the blank line that ends the request head
+    let head = loop {
+        if let Some(head) = parse_head(&buffer, limits)? {
+            break head;
+        }
+        read_some(stream, &mut buffer, deadline)?;
+    };
+
+    // Keep any body bytes that arrived together with the head
+    let body = buffer.split_off(head.len);
+    Ok(PendingRequest {
+        req: head.req,
+        raw_head: head.raw,
+        body,
+        content_length: head.content_length,
+        deadline,
+    })
+}
+
+impl PendingRequest {
+    // Read the whole message body into the request
+    pub fn read_body(mut self, stream: &mut Connection) -> Result<HttpRequest, RequestError> {
+        while self.body.len() < self.content_length {
+            read_some(stream, &mut self.body, self.deadline)?;
+        }
+        self.body.truncate(self.content_length);
+        self.req.msg_body = String::from_utf8(self.body).map_err(|_| RequestError::BadRequest)?;
+        Ok(self.req)
+    }
+
+    // Stream the message body to another writer without buffering all of it
+    pub fn copy_body(
+        &mut self,
+        stream: &mut Connection,
+        out: &mut impl Write,
+    ) -> Result<(), RequestError> {
+        let mut sent = 0;
+        while sent < self.content_length {
+            if self.body.is_empty() {
+                read_some(stream, &mut self.body, self.deadline)?;
+            }
+            let take = self.body.len().min(self.content_length - sent);
+            out.write_all(&self.body[..take])
+                .map_err(RequestError::Disconnected)?;
+            sent += take;
+            self.body.clear();
+        }
+        Ok(())
+    }
+}
+
+// Work out how long the body is from the raw header lines. Bodies are only
+// framed by a single Content-Length: a request with Transfer-Encoding, or with
+// several Content-Length headers, is refused rather than guessed at, because
+// a proxy and an upstream that guess differently disagree on where the
+// request ends (request smuggling).
+fn body_length(head: &str) -> Result<usize, RequestError> {
+    let mut content_length = None;
+    let mut chunked = false;
+    for line in head.lines().skip(1) {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let name = name.trim();
+        if name.eq_ignore_ascii_case("Transfer-Encoding") {
+            chunked = true;
+        } else if name.eq_ignore_ascii_case("Content-Length") {
+            if content_length.is_some() {
+                return Err(RequestError::BadRequest);
+            }
+            let value = value.trim();
+            if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(RequestError::BadRequest);
+            }
+            content_length = Some(value.parse().map_err(|_| RequestError::BadRequest)?);
+        }
+    }
+    match (chunked, content_length) {
+        (true, Some(_)) => Err(RequestError::BadRequest),
+        (true, None) => Err(RequestError::NotImplemented),
+        (false, length) => Ok(length.unwrap_or(0)),
+    }
+}
+
+// Position of the "\r\n\r\n" that terminates the request head
+fn find_head_end(buffer: &[u8]) -> Option<usize> {
+    buffer.windows(4).position(|w| w == b"\r\n\r\n")
+}
+
+// Check a (possibly partial) request head against the limits
+fn check_head(head: &[u8], limits: &Limits) -> Result<(), RequestError> {
+    // Allow for the method and version around the URI on the request line
+    let request_line_len = head
+        .windows(2)
+        .position(|w| w == b"\r\n")
+        .unwrap_or(head.len());
+    if request_line_len > limits.max_uri_len + 32 {
+        return Err(RequestError::UriTooLong);
+    }
+    if let Some(uri) = head[..request_line_len].split(|b| *b == b' ').nth(1) {
+        if uri.len() > limits.max_uri_len {
+            return Err(RequestError::UriTooLong);
+        }
+    }
+    if head.len() > limits.max_header_bytes {
+        return Err(RequestError::HeadersTooLarge);
+    }
+    let header_lines = head.windows(2).filter(|w| *w == b"\r\n").count();
+    if header_lines > limits.max_headers {
+        return Err(RequestError::HeadersTooLarge);
+    }
+    Ok(())
+}
+
+// Read whatever is available, waiting no longer than the deadline
+fn read_some(
+    stream: &mut Connection,
+    buffer: &mut Vec<u8>,
+    deadline: Instant,
+) -> Result<(), RequestError> {
+    let remaining = deadline
+        .checked_duration_since(Instant::now())
+        .filter(|d| !d.is_zero())
+        .ok_or(RequestError::Timeout)?;
+    stream
+        .set_read_timeout(Some(remaining))
+        .map_err(RequestError::Disconnected)?;
+
+    let mut chunk = [0; 1024];
+    match stream.read(&mut chunk) {
+        Ok(0) => Err(RequestError::Disconnected(
+            io::ErrorKind::UnexpectedEof.into(),
+        )),
+        Ok(n) => {
+            buffer.extend_from_slice(&chunk[..n]);
+            Ok(())
+        }
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ) =>
+        {
+            Err(RequestError::Timeout)
+        }
+        Err(e) => Err(RequestError::Disconnected(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    // Send raw bytes from a client thread and read them with the given limits
+    fn read_with(limits: Limits, chunks: Vec<(Vec<u8>, u64)>) -> Result<HttpRequest, RequestError> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            for (bytes, pause_ms) in chunks {
+                let _ = stream.write_all(&bytes);
+                thread::sleep(Duration::from_millis(pause_ms));
+            }
+            stream
+        });
+        let mut stream = Connection::new(listener.accept().unwrap().0);
+        let result = read_request_head(&mut stream, &limits)
+            .and_then(|pending| pending.read_body(&mut stream));
+        drop(client.join());
+        result
+    }
+
+    #[test]
+    fn test_reads_request_with_body() {
+        let raw = b"POST /api/shipping/orders HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello".to_vec();
+        let req = read_with(Limits::default(), vec![(raw, 0)]).unwrap();
+        assert_eq!(req.msg_body, "hello");
+    }
+
+    #[test]
+    fn test_rejects_long_uri() {
+        let limits = Limits {
+            max_uri_len: 16,
+            ..Limits::default()
+        };
+        let raw = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(64)).into_bytes();
+        let err = read_with(limits, vec![(raw, 0)]).unwrap_err();
+        assert_eq!(err.status_code(), Some("414"));
+    }
+
+    #[test]
+    fn test_rejects_too_many_headers() {
+        let limits = Limits {
+            max_headers: 2,
+            ..Limits::default()
+        };
+        let raw = b"GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\n\r\n".to_vec();
+        let err = read_with(limits, vec![(raw, 0)]).unwrap_err();
+        assert_eq!(err.status_code(), Some("431"));
+    }
+
+    #[test]
+    fn test_rejects_large_body() {
+        let limits = Limits {
+            max_body_bytes: 4,
+            ..Limits::default()
+        };
+        let raw = b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\n".to_vec();
+        let err = read_with(limits, vec![(raw, 0)]).unwrap_err();
+        assert_eq!(err.status_code(), Some("413"));
+    }
+
+    #[test]
+    fn test_rejects_chunked_body() {
+        let raw = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n"
+            .to_vec();
+        let err = read_with(Limits::default(), vec![(raw, 0)]).unwrap_err();
+        assert_eq!(err.status_code(), Some("501"));
+    }
+
+    #[test]
+    fn test_rejects_ambiguous_body_length() {
+        let both = b"POST / HTTP/1.1\r\nContent-Length: 5\r\nTransfer-Encoding: chunked\r\n\r\n";
+        let twice = b"POST / HTTP/1.1\r\nContent-Length: 5\r\ncontent-length: 5\r\n\r\n";
+        let listed = b"POST / HTTP/1.1\r\nContent-Length: 5, 5\r\n\r\n";
+        let signed = b"POST / HTTP/1.1\r\nContent-Length: +5\r\n\r\n";
+        for raw in [&both[..], &twice[..], &listed[..], &signed[..]] {
+            let err = read_with(Limits::default(), vec![(raw.to_vec(), 0)]).unwrap_err();
+            assert_eq!(err.status_code(), Some("400"));
+        }
+    }
+
+    #[test]
+    fn test_slow_client_times_out() {
+        let limits = Limits {
+            read_timeout: Duration::from_millis(200),
+            ..Limits::default()
+        };
+        // Trickle the request in slower than the deadline allows
+        let chunks = (0..10).map(|_| (b"X".to_vec(), 50)).collect();
+        let err = read_with(limits, chunks).unwrap_err();
+        assert_eq!(err.status_code(), Some("408"));
+    }
+}
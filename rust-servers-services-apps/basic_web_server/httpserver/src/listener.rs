@@ -0,0 +1,314 @@
+//! The listener module provides the sockets the server accepts clients on:
+//! TCP addresses (IPv4 or IPv6), Unix domain sockets, and listening sockets
+//! inherited from a service manager such as systemd.
+//!
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::TcpListener;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::os::unix::io::{FromRawFd, IntoRawFd, RawFd};
+use std::os::unix::net::UnixListener;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use super::connection::Connection;
+
+// First file descriptor passed by systemd socket activation
+const LISTEN_FDS_START: RawFd = 3;
+
+pub enum Listener {
+    Tcp(TcpListener),
+    Unix {
+        listener: UnixListener,
+        // Socket file to remove on shutdown, if this process created it
+        path: Option<PathBuf>,
+    },
+}
+
+impl Listener {
+    // Bind from a spec: a TCP address such as `127.0.0.1:3000` or
+    // `[::1]:3000`, or `unix:/path/to/socket[@mode]` with an octal file mode
+    pub fn bind(spec: &str) -> io::Result<Self> {
+        match spec.trim().strip_prefix("unix:") {
+            Some(unix) => {
+                let (path, mode) = match unix.rsplit_once('@') {
+                    Some((path, mode)) => {
+                        let mode = u32::from_str_radix(mode, 8).map_err(|_| {
+                            io::Error::new(
+                                io::ErrorKind::InvalidInput,
+                                format!("invalid socket mode: {}", mode),
+                            )
+                        })?;
+                        (path, Some(mode))
+                    }
+                    None => (unix, None),
+                };
+                Listener::unix(path, mode)
+            }
+            None => Ok(Listener::Tcp(TcpListener::bind(spec.trim())?)),
+        }
+    }
+
+    // Bind a Unix domain socket, replacing a stale socket file left behind
+    // by an earlier run, and set its permissions to `mode`
+    pub fn unix(path: &str, mode: Option<u32>) -> io::Result<Self> {
+        let path = PathBuf::from(path);
+        if let Ok(metadata) = fs::symlink_metadata(&path) {
+            if metadata.file_type().is_socket() {
+                fs::remove_file(&path)?;
+            }
+        }
+        let listener = match mode {
+            Some(mode) => bind_with_mode(&path, mode)?,
+            None => UnixListener::bind(&path)?,
+        };
+        Ok(Listener::Unix {
+            listener,
+            path: Some(path),
+        })
+    }
+
+    /// Take over an already listening socket, e.g. one handed down by the
+    /// previous process during a restart.
+    ///
+    /// # Safety
+    /// `fd` must be an open listening socket that nothing else will use or close.
+    pub unsafe fn from_fd(fd: RawFd) -> Self {
+        let tcp = TcpListener::from_raw_fd(fd);
+        // local_addr() fails for sockets that are not IPv4 or IPv6
+        if tcp.local_addr().is_ok() {
+            Listener::Tcp(tcp)
+        } else {
+            Listener::Unix {
+                listener: UnixListener::from_raw_fd(tcp.into_raw_fd()),
+                path: None,
+            }
+        }
+    }
+
+    // The listening sockets passed by systemd socket activation, following
+    // the LISTEN_PID/LISTEN_FDS protocol. Empty when none were passed.
+    pub fn from_systemd() -> Vec<Self> {
+        let fds = inherited_fds(
+            env::var("LISTEN_PID").ok().as_deref(),
+            env::var("LISTEN_FDS").ok().as_deref(),
+        );
+        // Child processes must not think the sockets are meant for them
+        env::remove_var("LISTEN_PID");
+        env::remove_var("LISTEN_FDS");
+        env::remove_var("LISTEN_FDNAMES");
+        fds.into_iter()
+            // Safe: systemd passed these descriptors to this process only
+            .map(|fd| unsafe { Listener::from_fd(fd) })
+            .collect()
+    }
+
+    pub fn accept(&self) -> io::Result<Connection> {
+        match self {
+            Listener::Tcp(listener) => Ok(Connection::new(listener.accept()?.0)),
+            Listener::Unix { listener, .. } => Ok(Connection::unix(listener.accept()?.0)),
+        }
+    }
+}
+
+impl fmt::Display for Listener {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Listener::Tcp(listener) => match listener.local_addr() {
+                Ok(addr) => write!(f, "{}", addr),
+                Err(_) => write!(f, "tcp socket"),
+            },
+            Listener::Unix {
+                path: Some(path), ..
+            } => write!(f, "unix:{}", path.display()),
+            Listener::Unix { listener, .. } => {
+                match listener
+                    .local_addr()
+                    .ok()
+                    .and_then(|a| a.as_pathname().map(PathBuf::from))
+                {
+                    Some(path) => write!(f, "unix:{}", path.display()),
+                    None => write!(f, "unix socket"),
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        if let Listener::Unix {
+            path: Some(path), ..
+        } = self
+        {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+// Bind a Unix domain socket that is never reachable with a wider mode than
+// `mode`: it is created inside a directory only this user can enter, and
+// linked at `path` once its permissions are set. Linking fails if anything
+// is at `path` already, just like binding there directly would.
+fn bind_with_mode(path: &Path, mode: u32) -> io::Result<UnixListener> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let private = parent.join(format!(".{}.{}", name, process::id()));
+    fs::DirBuilder::new().mode(0o700).create(&private)?;
+    let socket = private.join("socket");
+    let result = UnixListener::bind(&socket).and_then(|listener| {
+        fs::set_permissions(&socket, fs::Permissions::from_mode(mode))?;
+        fs::hard_link(&socket, path).map_err(|e| match e.kind() {
+            io::ErrorKind::AlreadyExists => io::ErrorKind::AddrInUse.into(),
+            _ => e,
+        })?;
+        Ok(listener)
+    });
+    let _ = fs::remove_file(&socket);
+    let _ = fs::remove_dir(&private);
+    result
+}
+
+// Whether accepting failed because the process or the system ran out of
+// file descriptors or buffers. That passes as connections close, so the
+// listener waits for a moment instead of giving up.
+pub fn out_of_resources(e: &io::Error) -> bool {
+    matches!(
+        e.raw_os_error(),
+        Some(libc::EMFILE | libc::ENFILE | libc::ENOBUFS | libc::ENOMEM)
+    )
+}
+
+// The descriptors systemd passed, if LISTEN_PID names this process
+fn inherited_fds(listen_pid: Option<&str>, listen_fds: Option<&str>) -> Vec<RawFd> {
+    let for_us = listen_pid.and_then(|pid| pid.trim().parse::<u32>().ok()) == Some(process::id());
+    let count: RawFd = listen_fds.and_then(|n| n.trim().parse().ok()).unwrap_or(0);
+    if !for_us || count <= 0 {
+        return Vec::new();
+    }
+    (LISTEN_FDS_START..LISTEN_FDS_START + count).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::Server;
+    use crate::test_util::{free_addr, send, wait_for};
+    use std::io::prelude::*;
+    use std::net::TcpStream;
+    use std::os::unix::net::UnixStream;
+
+    #[test]
+    fn test_inherited_fds() {
+        let pid = process::id().to_string();
+        assert_eq!(inherited_fds(Some(&pid), Some("2")), vec![3, 4]);
+        assert!(inherited_fds(Some("1"), Some("2")).is_empty());
+        assert!(inherited_fds(None, Some("2")).is_empty());
+        assert!(inherited_fds(Some(&pid), None).is_empty());
+    }
+
+    #[test]
+    fn test_out_of_resources() {
+        assert!(out_of_resources(&io::Error::from_raw_os_error(
+            libc::EMFILE
+        )));
+        assert!(out_of_resources(&io::Error::from_raw_os_error(
+            libc::ENFILE
+        )));
+        assert!(!out_of_resources(&io::Error::from_raw_os_error(
+            libc::EBADF
+        )));
+        assert!(!out_of_resources(&io::ErrorKind::ConnectionReset.into()));
+    }
+
+    #[test]
+    fn test_unix_socket_with_mode() {
+        let path = env::temp_dir().join(format!("httpserver-{}.sock", process::id()));
+        let spec = format!("unix:{}@600", path.display());
+        let listener = Listener::bind(&spec).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(listener.to_string(), format!("unix:{}", path.display()));
+        // Nothing is left of the private directory it was created in
+        assert!(!env::temp_dir()
+            .join(format!(".httpserver-{0}.sock.{0}", process::id()))
+            .exists());
+
+        let mut client = UnixStream::connect(&path).unwrap();
+        client.write_all(b"ping").unwrap();
+        let mut conn = listener.accept().unwrap();
+        let mut buf = [0; 4];
+        conn.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"ping");
+        assert_eq!(conn.peer_ip(), None);
+
+        // Binding again replaces the socket file; dropping removes it
+        drop(conn);
+        let listener = Listener::bind(&spec).unwrap();
+        drop(listener);
+        assert!(!path.exists());
+        assert!(Listener::bind("unix:/tmp/x.sock@9z").is_err());
+    }
+
+    #[test]
+    fn test_unix_socket_leaves_other_files_alone() {
+        let path = env::temp_dir().join(format!("httpserver-{}.file", process::id()));
+        fs::write(&path, "keep me").unwrap();
+        for spec in ["", "@600"] {
+            let spec = format!("unix:{}{}", path.display(), spec);
+            let err = Listener::bind(&spec).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+            assert_eq!(fs::read_to_string(&path).unwrap(), "keep me");
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_from_fd_keeps_tcp_listener() {
+        let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = tcp.local_addr().unwrap();
+        let listener = unsafe { Listener::from_fd(tcp.into_raw_fd()) };
+        assert!(matches!(listener, Listener::Tcp(_)));
+
+        let _client = TcpStream::connect(addr).unwrap();
+        let conn = listener.accept().unwrap();
+        assert_eq!(conn.peer_ip(), Some("127.0.0.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_server_listens_on_several_sockets() {
+        let v4 = free_addr();
+        let v6 = TcpListener::bind("[::1]:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+        let path = env::temp_dir().join(format!("httpserver-server-{}.sock", process::id()));
+        let unix = Listener::unix(path.to_str().unwrap(), None).unwrap();
+
+        let server = Server::builder()
+            .bind(&v4)
+            .bind(&v6)
+            .listener(unix)
+            .build()
+            .unwrap();
+        std::thread::spawn(move || server.run());
+        wait_for(&v4);
+        wait_for(&v6);
+
+        assert!(send(&v4, "GET /health HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 200 OK"));
+        assert!(send(&v6, "GET /health HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 200 OK"));
+
+        let mut client = UnixStream::connect(&path).unwrap();
+        client.write_all(b"GET /health HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+    }
+}
@@ -1,12 +1,106 @@
-mod handler;
-mod router;
-mod server;
-use server::Server;
+use httpserver::auth::Auth;
+use httpserver::cors::Cors;
+use httpserver::limits::{env_or, Limits};
+use httpserver::listener::Listener;
+use httpserver::proxy::Proxy;
+use httpserver::ratelimit::RateLimiter;
+use httpserver::rewrite::Rewriter;
+use httpserver::sse::EventBus;
+use httpserver::vhost;
+use httpserver::{Router, Server};
+use std::env;
+use std::process;
+use std::thread;
+use std::time::Duration;
 
 fn main() {
+    let rewrites = load_rewrites();
+
+    // `httpserver dry-run METHOD PATH [HOST]` shows what the rewrite rules
+    // do with a request, without starting the server
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(String::as_str) == Some("dry-run") {
+        if args.len() < 4 {
+            eprintln!("Usage: httpserver dry-run METHOD PATH [HOST]");
+            process::exit(2);
+        }
+        let host = args.get(4).map(String::as_str).unwrap_or("localhost");
+        let req = format!("{} {} HTTP/1.1\r\nHost: {}\r\n\r\n", args[2], args[3], host);
+        print!("{}", rewrites.dry_run(&req.into()));
+        return;
+    }
+
+    let auth = Auth::from_env().unwrap_or_else(|e| {
+        eprintln!("Auth: {}", e);
+        process::exit(1);
+    });
+
+    let mut router = Router::new()
+        .with_rewrites(rewrites)
+        .with_auth(auth)
+        .with_proxy(Proxy::from_env())
+        .with_metrics_path(&env::var("METRICS_PATH").unwrap_or("/metrics".to_string()))
+        .with_events(
+            &env::var("EVENTS_PATH").unwrap_or("/events".to_string()),
+            EventBus::new(env_or("EVENTS_BUFFER", 100))
+                .with_heartbeat(Duration::from_secs(env_or("EVENTS_HEARTBEAT_SECS", 15))),
+        );
+    let cors = Cors::from_env().unwrap_or_else(|e| {
+        eprintln!("CORS: {}", e);
+        process::exit(1);
+    });
+    if let Some(cors) = cors {
+        router = router.with_cors(cors);
+    }
+    if let Some(limiter) = RateLimiter::from_env() {
+        router = router.with_rate_limit(limiter);
+    }
+
+    // Listen on the sockets systemd passed, if any, and on the LISTEN
+    // addresses: TCP addresses and unix:/path[@mode] sockets, comma separated
+    let inherited = Listener::from_systemd();
+    let default_addr = if inherited.is_empty() {
+        "localhost:3000"
+    } else {
+        ""
+    };
+    let listen = env::var("LISTEN").unwrap_or(default_addr.to_string());
+
     // Start a server
-    let server = Server::new("localhost:3000");
+    let mut builder = Server::builder().limits(Limits::from_env()).router(router);
+    for addr in listen.split(',').filter(|a| !a.trim().is_empty()) {
+        builder = builder.bind(addr);
+    }
+    for listener in inherited {
+        builder = builder.listener(listener);
+    }
+    for (names, router) in vhost::sites_from_env() {
+        builder = builder.virtual_host(&names, router);
+    }
+    // SERVER_MODE=event-loop serves clients from REACTORS event loop threads
+    // (one per CPU by default) instead of a thread per connection
+    if env::var("SERVER_MODE").as_deref() == Ok("event-loop") {
+        let cpus = thread::available_parallelism().map_or(1, |n| n.get());
+        builder = builder.event_loop(env_or("REACTORS", cpus));
+    }
+    let server = builder.build().unwrap_or_else(|e| {
+        eprintln!("Failed to start: {}", e);
+        process::exit(1);
+    });
     //Run the server
-    server.run();
-    println!("Hello, world!");
+    if let Err(e) = server.run() {
+        eprintln!("Server stopped: {}", e);
+        process::exit(1);
+    }
+}
+
+// Load the rules file named by REWRITE_RULES, if any
+fn load_rewrites() -> Rewriter {
+    match env::var("REWRITE_RULES") {
+        Ok(path) => Rewriter::from_file(&path).unwrap_or_else(|e| {
+            eprintln!("Rewrite rules: {}", e);
+            process::exit(1);
+        }),
+        Err(_) => Rewriter::default(),
+    }
 }
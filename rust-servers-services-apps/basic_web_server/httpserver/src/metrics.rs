@@ -0,0 +1,307 @@
+//! The metrics module collects request counters and latency histograms
+//! and renders them in the Prometheus text exposition format.
+//!
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Status recorded when the client goes away before it can be answered
+pub const CLIENT_CLOSED: &str = "499";
+
+// Upper bounds of the latency histogram buckets, in seconds
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+pub struct Metrics {
+    // Path the metrics are served on
+    path: String,
+    // Requests by (method, route, status)
+    requests: Mutex<BTreeMap<(String, String, String), u64>>,
+    // Latency by (method, route)
+    latencies: Mutex<BTreeMap<(String, String), Histogram>>,
+    // Requests that could not be read, by reason
+    parse_errors: Mutex<BTreeMap<String, u64>>,
+    in_flight: AtomicI64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    started: Instant,
+}
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new("/metrics")
+    }
+}
+
+impl Metrics {
+    pub fn new(path: &str) -> Self {
+        Metrics {
+            path: path.to_string(),
+            requests: Mutex::new(BTreeMap::new()),
+            latencies: Mutex::new(BTreeMap::new()),
+            parse_errors: Mutex::new(BTreeMap::new()),
+            in_flight: AtomicI64::new(0),
+            bytes_in: AtomicU64::new(0),
+            bytes_out: AtomicU64::new(0),
+            started: Instant::now(),
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    // Count a request as in flight until the returned guard is dropped
+    pub fn track_in_flight(&self) -> InFlight<'_> {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        InFlight(self)
+    }
+
+    pub fn observe_request(&self, method: &str, route: &str, status: &str, elapsed: Duration) {
+        let method = if method.is_empty() { "UNKNOWN" } else { method };
+        *self
+            .requests
+            .lock()
+            .unwrap()
+            .entry((method.to_string(), route.to_string(), status.to_string()))
+            .or_insert(0) += 1;
+
+        let seconds = elapsed.as_secs_f64();
+        let mut latencies = self.latencies.lock().unwrap();
+        let histogram = latencies
+            .entry((method.to_string(), route.to_string()))
+            .or_default();
+        for (bucket, bound) in histogram.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        histogram.sum += seconds;
+        histogram.count += 1;
+    }
+
+    pub fn observe_parse_error(&self, reason: &str) {
+        *self
+            .parse_errors
+            .lock()
+            .unwrap()
+            .entry(reason.to_string())
+            .or_insert(0) += 1;
+    }
+
+    pub fn observe_bytes(&self, bytes_in: u64, bytes_out: u64) {
+        self.bytes_in.fetch_add(bytes_in, Ordering::SeqCst);
+        self.bytes_out.fetch_add(bytes_out, Ordering::SeqCst);
+    }
+
+    // Time since the metrics were created, i.e. since the server started
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn in_flight(&self) -> i64 {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    // Requests handled so far, by status code
+    pub fn requests_by_status(&self) -> BTreeMap<String, u64> {
+        let mut totals = BTreeMap::new();
+        for ((_, _, status), count) in self.requests.lock().unwrap().iter() {
+            *totals.entry(status.clone()).or_insert(0) += count;
+        }
+        totals
+    }
+
+    // Render all metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "http_requests_total",
+            "counter",
+            "HTTP requests handled.",
+        );
+        for ((method, route, status), count) in self.requests.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+                escape(method),
+                escape(route),
+                escape(status),
+                count
+            );
+        }
+
+        header(
+            &mut out,
+            "http_request_duration_seconds",
+            "histogram",
+            "Time taken to handle HTTP requests.",
+        );
+        for ((method, route), histogram) in self.latencies.lock().unwrap().iter() {
+            let labels = format!("method=\"{}\",route=\"{}\"", escape(method), escape(route));
+            for (count, bound) in histogram.buckets.iter().zip(LATENCY_BUCKETS) {
+                let _ = writeln!(
+                    out,
+                    "http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bound, count
+                );
+            }
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                labels, histogram.count
+            );
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_sum{{{}}} {}",
+                labels, histogram.sum
+            );
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_count{{{}}} {}",
+                labels, histogram.count
+            );
+        }
+
+        header(
+            &mut out,
+            "http_requests_in_flight",
+            "gauge",
+            "HTTP requests currently being handled.",
+        );
+        let _ = writeln!(
+            out,
+            "http_requests_in_flight {}",
+            self.in_flight.load(Ordering::SeqCst)
+        );
+
+        header(
+            &mut out,
+            "http_received_bytes_total",
+            "counter",
+            "Bytes received from clients.",
+        );
+        let _ = writeln!(
+            out,
+            "http_received_bytes_total {}",
+            self.bytes_in.load(Ordering::SeqCst)
+        );
+
+        header(
+            &mut out,
+            "http_sent_bytes_total",
+            "counter",
+            "Bytes sent to clients.",
+        );
+        let _ = writeln!(
+            out,
+            "http_sent_bytes_total {}",
+            self.bytes_out.load(Ordering::SeqCst)
+        );
+
+        header(
+            &mut out,
+            "http_parse_errors_total",
+            "counter",
+            "Requests rejected before routing.",
+        );
+        for (reason, count) in self.parse_errors.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "http_parse_errors_total{{reason=\"{}\"}} {}",
+                escape(reason),
+                count
+            );
+        }
+        out
+    }
+}
+
+// Decrements the in-flight gauge when the request is done
+pub struct InFlight<'a>(&'a Metrics);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+// Escape a label value as the exposition format requires
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_counts_requests() {
+        let metrics = Metrics::default();
+        metrics.observe_request("GET", "static_page", "200", Duration::from_millis(3));
+        metrics.observe_request("GET", "static_page", "200", Duration::from_millis(30));
+        metrics.observe_request("", "page_not_found", "404", Duration::from_millis(1));
+        metrics.observe_parse_error("timeout");
+        metrics.observe_bytes(120, 800);
+
+        let text = metrics.render();
+        assert!(text.contains("# TYPE http_requests_total counter\n"));
+        assert!(text.contains(
+            "http_requests_total{method=\"GET\",route=\"static_page\",status=\"200\"} 2\n"
+        ));
+        assert!(text.contains(
+            "http_requests_total{method=\"UNKNOWN\",route=\"page_not_found\",status=\"404\"} 1\n"
+        ));
+        assert!(text.contains(
+            "http_request_duration_seconds_bucket{method=\"GET\",route=\"static_page\",le=\"0.005\"} 1\n"
+        ));
+        assert!(text.contains(
+            "http_request_duration_seconds_bucket{method=\"GET\",route=\"static_page\",le=\"0.05\"} 2\n"
+        ));
+        assert!(text.contains(
+            "http_request_duration_seconds_count{method=\"GET\",route=\"static_page\"} 2\n"
+        ));
+        assert!(text.contains("http_parse_errors_total{reason=\"timeout\"} 1\n"));
+        assert!(text.contains("http_received_bytes_total 120\n"));
+        assert!(text.contains("http_sent_bytes_total 800\n"));
+
+        let by_status = metrics.requests_by_status();
+        assert_eq!(by_status["200"], 2);
+        assert_eq!(by_status["404"], 1);
+    }
+
+    #[test]
+    fn test_in_flight_gauge() {
+        let metrics = Metrics::default();
+        let guard = metrics.track_in_flight();
+        assert!(metrics.render().contains("http_requests_in_flight 1\n"));
+        drop(guard);
+        assert!(metrics.render().contains("http_requests_in_flight 0\n"));
+    }
+
+    #[test]
+    fn test_escapes_label_values() {
+        assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
@@ -0,0 +1,550 @@
+//! The proxy module forwards requests under a mounted path prefix to a set
+//! of upstream servers, round-robin, streaming bodies in both directions.
+//! Upstreams that keep failing are taken out of rotation for a while
+//! (passive health checking).
+//!
+
+use std::env;
+use std::io::{self, prelude::*};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::auth::PRINCIPAL_HEADER;
+use super::connection::Connection;
+use super::cors::CorsHeaders;
+use super::limits::PendingRequest;
+use super::metrics::CLIENT_CLOSED;
+use super::server::send_error;
+use http::httprequest::Resource;
+
+// Consecutive failures after which an upstream is taken out of rotation
+const MAX_FAILS: u32 = 3;
+// How long a failed upstream stays out of rotation
+const FAIL_TIMEOUT: Duration = Duration::from_secs(10);
+// Connect, read and write timeout for upstream connections
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(30);
+// Largest upstream response head that will be relayed
+const MAX_RESPONSE_HEAD: usize = 64 * 1024;
+
+// Headers that only apply to a single connection and are never forwarded
+const HOP_BY_HOP: [&str; 8] = [
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "upgrade",
+];
+
+#[derive(Default)]
+pub struct Proxy {
+    mounts: Vec<Mount>,
+}
+
+pub struct Mount {
+    // Path prefix on this server, e.g. /api/tutors
+    prefix: String,
+    // Path prefix the matched requests are rewritten to upstream, e.g. /tutors
+    target: String,
+    upstreams: Vec<Upstream>,
+    next: AtomicUsize,
+}
+
+struct Upstream {
+    addr: String,
+    failures: AtomicU32,
+    down_until: Mutex<Option<Instant>>,
+}
+
+impl Proxy {
+    pub fn new() -> Self {
+        Proxy::default()
+    }
+
+    // Read mounts from PROXY_ROUTES, a ';' separated list of
+    // `prefix[=target]@upstream[,upstream...]` entries, for example
+    // `/api/tutors=/tutors@127.0.0.1:3001,127.0.0.1:3002`
+    pub fn from_env() -> Self {
+        let mut proxy = Proxy::new();
+        let routes = env::var("PROXY_ROUTES").unwrap_or_default();
+        for entry in routes.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            match entry.split_once('@') {
+                Some((paths, upstreams)) => {
+                    let (prefix, target) = paths.split_once('=').unwrap_or((paths, paths));
+                    let upstreams: Vec<&str> = upstreams.split(',').map(str::trim).collect();
+                    proxy = proxy.mount(prefix.trim(), target.trim(), &upstreams);
+                }
+                None => eprintln!("Ignoring malformed proxy route: {}", entry),
+            }
+        }
+        proxy
+    }
+
+    // Forward requests under `prefix` to the upstreams, replacing the prefix with `target`
+    pub fn mount(mut self, prefix: &str, target: &str, upstreams: &[&str]) -> Self {
+        self.mounts.push(Mount {
+            prefix: prefix.trim_end_matches('/').to_string(),
+            target: target.trim_end_matches('/').to_string(),
+            upstreams: upstreams
+                .iter()
+                .map(|addr| Upstream {
+                    addr: addr.to_string(),
+                    failures: AtomicU32::new(0),
+                    down_until: Mutex::new(None),
+                })
+                .collect(),
+            next: AtomicUsize::new(0),
+        });
+        self
+    }
+
+    // The mount responsible for a request path, if any
+    pub fn find(&self, path: &str) -> Option<&Mount> {
+        self.mounts.iter().find(|m| m.rewrite(path).is_some())
+    }
+}
+
+impl Mount {
+    // Map a request path onto the upstream path, if it falls under this mount
+    fn rewrite(&self, path: &str) -> Option<String> {
+        let rest = path.strip_prefix(&self.prefix)?;
+        // Only match whole path segments: /api/tutors must not match /api/tutorsX
+        if !(rest.is_empty() || rest.starts_with('/') || rest.starts_with('?')) {
+            return None;
+        }
+        let rewritten = format!("{}{}", self.target, rest);
+        if rewritten.starts_with('/') {
+            Some(rewritten)
+        } else {
+            Some(format!("/{}", rewritten))
+        }
+    }
+
+    // Pick the next healthy upstream in round-robin order. If every upstream
+    // is marked down, fall back to plain round-robin rather than refusing.
+    fn pick(&self) -> &Upstream {
+        let start = self.next.fetch_add(1, Ordering::SeqCst);
+        let count = self.upstreams.len();
+        (0..count)
+            .map(|i| &self.upstreams[(start + i) % count])
+            .find(|u| u.is_healthy())
+            .unwrap_or(&self.upstreams[start % count])
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    // Forward the request to an upstream and relay its response to the client,
+    // with `extra_headers` added, returning the status code the client got
+    pub fn forward(
+        &self,
+        mut pending: PendingRequest,
+        client: &mut Connection,
+        extra_headers: &CorsHeaders,
+    ) -> String {
+        let Resource::Path(path) = &pending.req.resource;
+        let path = match self.rewrite(path) {
+            Some(path) => path,
+            None => return reply(client, "404"),
+        };
+        let head = self.upstream_head(&pending, &path, client);
+
+        // Connection failures are retried on the next upstream, since nothing
+        // has been sent yet; once the body is streamed there is no going back
+        for _ in 0..self.upstreams.len() {
+            let upstream = self.pick();
+            let mut conn = match upstream.connect() {
+                Ok(conn) => conn,
+                Err(e) => {
+                    eprintln!("Upstream {} unavailable: {}", upstream.addr, e);
+                    upstream.record_failure();
+                    continue;
+                }
+            };
+
+            let sent = conn
+                .write_all(head.as_bytes())
+                .map_err(|_| None)
+                .and_then(|_| pending.copy_body(client, &mut conn).map_err(Some));
+            match sent {
+                Ok(()) => {}
+                // The client failed to send its body; answer if we still can
+                Err(Some(err)) => {
+                    return match err.status_code() {
+                        Some(status_code) => reply(client, status_code),
+                        None => CLIENT_CLOSED.to_string(),
+                    };
+                }
+                Err(None) => {
+                    upstream.record_failure();
+                    return reply(client, "502");
+                }
+            }
+
+            return match relay_response(&mut conn, client, extra_headers) {
+                Ok(status_code) => {
+                    upstream.record_success();
+                    status_code
+                }
+                Err(RelayError::Upstream(e)) => {
+                    eprintln!("Upstream {} failed: {}", upstream.addr, e);
+                    upstream.record_failure();
+                    reply(client, "502")
+                }
+                // The response head was already sent; nothing left to do
+                Err(RelayError::Client(status_code)) => status_code,
+            };
+        }
+        reply(client, "502")
+    }
+
+    // Build the request head sent upstream
+    fn upstream_head(&self, pending: &PendingRequest, path: &str, client: &Connection) -> String {
+        let mut head = format!("{} {} HTTP/1.1\r\n", pending.req.method.as_str(), path);
+        let mut forwarded_for = None;
+        let mut host = None;
+
+        for line in pending.raw_head.lines().skip(1) {
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            let name_lower = name.trim().to_ascii_lowercase();
+            match name_lower.as_str() {
+                // The Host header is passed through unchanged, and also reported
+                // as X-Forwarded-Host
+                "host" => {
+                    host = Some(value.trim().to_string());
+                    head.push_str(&format!("{}:{}\r\n", name, value));
+                }
+                "x-forwarded-for" => forwarded_for = Some(value.trim().to_string()),
+                "x-forwarded-proto" | "x-forwarded-host" => {}
+                // The body is sent framed by Content-Length only. Requests
+                // with a transfer coding are refused before they get here;
+                // never pass one on in case that changes.
+                "transfer-encoding" => {}
+                name if HOP_BY_HOP.contains(&name) => {}
+                // Only the principal the auth layer vouches for is sent on
+                name if name.eq_ignore_ascii_case(PRINCIPAL_HEADER) => {}
+                _ => head.push_str(&format!("{}:{}\r\n", name, value)),
+            }
+        }
+
+        // Unix domain socket clients have no address to report
+        let peer_ip = client
+            .peer_ip()
+            .map(|ip| ip.to_string())
+            .unwrap_or("unknown".to_string());
+        let forwarded_for = match forwarded_for {
+            Some(previous) => format!("{}, {}", previous, peer_ip),
+            None => peer_ip,
+        };
+        head.push_str(&format!("X-Forwarded-For: {}\r\n", forwarded_for));
+        head.push_str("X-Forwarded-Proto: http\r\n");
+        if let Some(host) = host {
+            head.push_str(&format!("X-Forwarded-Host: {}\r\n", host));
+        }
+        if let Some(principal) = pending.req.header(PRINCIPAL_HEADER) {
+            head.push_str(&format!("{}: {}\r\n", PRINCIPAL_HEADER, principal));
+        }
+        head.push_str("Connection: close\r\n\r\n");
+        head
+    }
+}
+
+impl Upstream {
+    fn is_healthy(&self) -> bool {
+        match *self.down_until.lock().unwrap() {
+            Some(until) => Instant::now() >= until,
+            None => true,
+        }
+    }
+
+    fn record_failure(&self) {
+        let failures = self.failures.fetch_add(1, Ordering::SeqCst) + 1;
+        if failures >= MAX_FAILS {
+            *self.down_until.lock().unwrap() = Some(Instant::now() + FAIL_TIMEOUT);
+        }
+    }
+
+    fn record_success(&self) {
+        self.failures.store(0, Ordering::SeqCst);
+        *self.down_until.lock().unwrap() = None;
+    }
+
+    fn connect(&self) -> io::Result<TcpStream> {
+        let addr = self
+            .addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address"))?;
+        let conn = TcpStream::connect_timeout(&addr, UPSTREAM_TIMEOUT)?;
+        conn.set_read_timeout(Some(UPSTREAM_TIMEOUT))?;
+        conn.set_write_timeout(Some(UPSTREAM_TIMEOUT))?;
+        Ok(conn)
+    }
+}
+
+// Send an error page and return its status code
+fn reply(client: &mut Connection, status_code: &str) -> String {
+    send_error(client, status_code);
+    status_code.to_string()
+}
+
+enum RelayError {
+    Upstream(io::Error),
+    // The client went away; carries the upstream status code
+    Client(String),
+}
+
+// Relay the upstream response: rewrite the head, then stream the body as-is.
+// Returns the upstream status code.
+fn relay_response(
+    upstream: &mut TcpStream,
+    client: &mut Connection,
+    extra_headers: &CorsHeaders,
+) -> Result<String, RelayError> {
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut chunk = [0; 4096];
+    let head_end = loop {
+        if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+        if buffer.len() > MAX_RESPONSE_HEAD {
+            return Err(RelayError::Upstream(io::ErrorKind::InvalidData.into()));
+        }
+        match upstream.read(&mut chunk) {
+            Ok(0) => return Err(RelayError::Upstream(io::ErrorKind::UnexpectedEof.into())),
+            Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+            Err(e) => return Err(RelayError::Upstream(e)),
+        }
+    };
+    let head = String::from_utf8_lossy(&buffer[..head_end]).to_string();
+    if !head.starts_with("HTTP/") {
+        return Err(RelayError::Upstream(io::ErrorKind::InvalidData.into()));
+    }
+
+    let mut lines = head.lines();
+    let status_line = lines.next().unwrap_or_default();
+    let status_code = status_line
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or("")
+        .to_string();
+    let mut out = format!("{}\r\n", status_line);
+    for line in lines {
+        let name = line
+            .split(':')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_ascii_lowercase();
+        let replaced = extra_headers
+            .iter()
+            .any(|(k, _)| k.eq_ignore_ascii_case(&name));
+        if !HOP_BY_HOP.contains(&name.as_str()) && !replaced {
+            out.push_str(line);
+            out.push_str("\r\n");
+        }
+    }
+    for (key, value) in extra_headers {
+        out.push_str(&format!("{}: {}\r\n", key, value));
+    }
+    out.push_str("Connection: close\r\n\r\n");
+
+    client
+        .write_all(out.as_bytes())
+        .and_then(|_| client.write_all(&buffer[head_end + 4..]))
+        .map_err(|_| RelayError::Client(status_code.clone()))?;
+    // The upstream closes the connection after the response, so copy to EOF
+    loop {
+        match upstream.read(&mut chunk) {
+            Ok(0) => return Ok(status_code),
+            Ok(n) => client
+                .write_all(&chunk[..n])
+                .map_err(|_| RelayError::Client(status_code.clone()))?,
+            // The head is out already, so a broken body can only be cut short
+            Err(_) => return Err(RelayError::Client(status_code)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Auth;
+    use crate::router::Router;
+    use crate::test_util::{free_addr, send, start_server};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    fn start_proxy(proxy: Proxy) -> String {
+        start_server(Router::new().with_proxy(proxy))
+    }
+
+    // An upstream that captures one request and answers with a fixed body
+    fn capture_upstream() -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let request = read_full_request(&mut stream);
+                let _ = stream.write_all(
+                    b"HTTP/1.1 201 Created\r\nContent-Type: text/plain\r\nConnection: keep-alive\r\n\r\ncreated",
+                );
+                tx.send(request).unwrap();
+            }
+        });
+        (addr, rx)
+    }
+
+    // Read a request head plus a Content-Length body
+    fn read_full_request(stream: &mut TcpStream) -> String {
+        let mut buffer = Vec::new();
+        let mut chunk = [0; 1024];
+        loop {
+            let text = String::from_utf8_lossy(&buffer).to_string();
+            if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                let length = head
+                    .lines()
+                    .find_map(|l| l.strip_prefix("Content-Length: "))
+                    .map(|v| v.trim().parse::<usize>().unwrap())
+                    .unwrap_or(0);
+                if body.len() >= length {
+                    return text;
+                }
+            }
+            match stream.read(&mut chunk) {
+                Ok(0) | Err(_) => return String::from_utf8_lossy(&buffer).to_string(),
+                Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+            }
+        }
+    }
+
+    #[test]
+    fn test_rewrite_only_matches_whole_segments() {
+        let proxy = Proxy::new().mount("/api/tutors/", "/tutors", &["127.0.0.1:1"]);
+        let mount = proxy.find("/api/tutors/1").unwrap();
+        assert_eq!(
+            mount.rewrite("/api/tutors/1"),
+            Some("/tutors/1".to_string())
+        );
+        assert_eq!(mount.rewrite("/api/tutors"), Some("/tutors".to_string()));
+        assert_eq!(
+            mount.rewrite("/api/tutors?page=2"),
+            Some("/tutors?page=2".to_string())
+        );
+        assert!(proxy.find("/api/tutorsX").is_none());
+        assert!(proxy.find("/api/shipping/orders").is_none());
+    }
+
+    #[test]
+    fn test_proxies_to_httpserver_upstream() {
+        let upstream = start_proxy(Proxy::new());
+        let front = start_proxy(Proxy::new().mount("/site", "/", &[&upstream]));
+
+        let response = send(&front, "GET /site/health HTTP/1.1\r\nHost: front\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("Hello welcome to health page!"));
+        assert!(response.contains("Connection: close"));
+    }
+
+    #[test]
+    fn test_forwards_headers_and_body() {
+        let (upstream, requests) = capture_upstream();
+        let front = start_proxy(Proxy::new().mount("/api/tutors", "/tutors", &[&upstream]));
+
+        let mut stream = TcpStream::connect(&front).unwrap();
+        stream
+            .write_all(b"POST /api/tutors/1 HTTP/1.1\r\nHost: example.com:3000\r\nAuthorization: Basic a:b\r\nX-Forwarded-For: 10.0.0.1\r\nContent-Length: 11\r\n\r\nhello world")
+            .unwrap();
+        let request = requests.recv_timeout(Duration::from_secs(5)).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(request.starts_with("POST /tutors/1 HTTP/1.1\r\n"));
+        assert!(request.contains("Authorization: Basic a:b\r\n"));
+        assert!(request.contains("X-Forwarded-For: 10.0.0.1, 127.0.0.1\r\n"));
+        assert!(request.contains("X-Forwarded-Proto: http\r\n"));
+        assert!(request.contains("X-Forwarded-Host: example.com:3000\r\n"));
+        assert!(request.ends_with("\r\n\r\nhello world"));
+        assert!(response.starts_with("HTTP/1.1 201 Created\r\n"));
+        assert!(!response.contains("keep-alive"));
+        assert!(response.ends_with("created"));
+    }
+
+    #[test]
+    fn test_forwards_only_the_granted_principal() {
+        let (upstream, requests) = capture_upstream();
+        let auth = Auth::new().bearer("/api", "orders", "dashboard:abc123\n");
+        let proxy = Proxy::new()
+            .mount("/api", "/", &[&upstream])
+            .mount("/open", "/", &[&upstream]);
+        let front = start_server(Router::new().with_auth(auth).with_proxy(proxy));
+
+        send(
+            &front,
+            "GET /open HTTP/1.1\r\nX-Authenticated-User : root\r\n\r\n",
+        );
+        let request = requests.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(!request.contains("root"));
+
+        send(&front, "GET /api HTTP/1.1\r\nX-Authenticated-User : root\r\nAuthorization: Bearer abc123\r\n\r\n");
+        let request = requests.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(!request.contains("root"));
+        assert_eq!(request.matches("X-Authenticated-User").count(), 1);
+        assert!(request.contains("X-Authenticated-User: dashboard\r\n"));
+    }
+
+    #[test]
+    fn test_refuses_chunked_requests() {
+        let (upstream, requests) = capture_upstream();
+        let front = start_proxy(Proxy::new().mount("/api", "/", &[&upstream]));
+
+        let response = send(
+            &front,
+            "POST /api HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n",
+        );
+        assert!(response.starts_with("HTTP/1.1 501 Not Implemented"));
+        // A smuggling attempt: the proxy and upstream could each pick a different framing
+        let response = send(&front, "POST /api HTTP/1.1\r\nContent-Length: 4\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\nGET /admin HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request"));
+        assert!(requests.recv_timeout(Duration::from_millis(200)).is_err());
+    }
+
+    #[test]
+    fn test_skips_failed_upstream() {
+        let healthy = start_proxy(Proxy::new());
+        // Nothing listens on the dead address
+        let dead = free_addr();
+        let front = start_proxy(Proxy::new().mount("/site", "/", &[&dead, &healthy]));
+
+        for _ in 0..4 {
+            let response = send(&front, "GET /site/health HTTP/1.1\r\n\r\n");
+            assert!(response.starts_with("HTTP/1.1 200 OK"));
+        }
+    }
+
+    #[test]
+    fn test_marks_upstream_down_after_failures() {
+        let proxy = Proxy::new().mount("/site", "/", &["127.0.0.1:1", "127.0.0.1:2"]);
+        let mount = proxy.find("/site").unwrap();
+        for _ in 0..MAX_FAILS {
+            mount.upstreams[0].record_failure();
+        }
+        assert!(!mount.upstreams[0].is_healthy());
+        // Round-robin now only hands out the healthy upstream
+        for _ in 0..4 {
+            assert_eq!(mount.pick().addr, "127.0.0.1:2");
+        }
+        mount.upstreams[0].record_success();
+        assert!(mount.upstreams[0].is_healthy());
+    }
+}
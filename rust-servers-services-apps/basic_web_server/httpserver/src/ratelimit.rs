@@ -0,0 +1,529 @@
+//! The ratelimit module throttles clients with token buckets, keyed by
+//! peer IP and optionally by a request header such as an API key.
+//!
+
+use std::collections::HashMap;
+use std::env;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use http::{httprequest::HttpRequest, httprequest::Resource};
+
+// How often idle buckets are swept out
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+// Most buckets kept at once. Header values are chosen by clients, so without
+// a cap they could grow the map without bound between sweeps.
+const MAX_BUCKETS: usize = 100_000;
+
+// Most header-keyed buckets one peer IP may have open under a rule, so that
+// a client rotating through made-up keys cannot fill the bucket map
+const MAX_KEYS_PER_IP: usize = 16;
+
+pub struct RateLimiter {
+    rules: Vec<Rule>,
+    buckets: Mutex<Buckets>,
+    max_buckets: usize,
+    last_sweep: Mutex<Instant>,
+}
+
+struct Rule {
+    prefix: String,
+    // Requests allowed per period, which is also the burst size
+    requests: u32,
+    period: Duration,
+    // Header identifying the client. The peer IP is limited as well, since
+    // the header value is whatever the client chooses to send.
+    key_header: Option<String>,
+}
+
+#[derive(Default)]
+struct Buckets {
+    // Buckets by (rule index, client key)
+    map: HashMap<(usize, String), Bucket>,
+    // Header-keyed buckets opened by each (rule index, peer IP key)
+    keys_per_ip: HashMap<(usize, String), usize>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    // For header-keyed buckets, the peer IP key that opened the bucket
+    opened_by: Option<String>,
+}
+
+// Outcome of a rate limit check, with the values for the RateLimit-* headers
+pub struct Decision {
+    pub allowed: bool,
+    limit: u32,
+    remaining: u32,
+    // Seconds until the bucket is full again
+    reset: u64,
+    // Seconds until the next request would be allowed
+    retry_after: u64,
+}
+
+impl Decision {
+    pub fn headers(&self) -> Vec<(&'static str, String)> {
+        let mut headers = vec![
+            ("RateLimit-Limit", self.limit.to_string()),
+            ("RateLimit-Remaining", self.remaining.to_string()),
+            ("RateLimit-Reset", self.reset.to_string()),
+        ];
+        if !self.allowed {
+            headers.push(("Retry-After", self.retry_after.to_string()));
+        }
+        headers
+    }
+}
+
+impl Rule {
+    fn rate(&self) -> f64 {
+        self.requests as f64 / self.period.as_secs_f64()
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        path == self.prefix
+            || self.prefix.is_empty()
+            || path
+                .strip_prefix(&self.prefix)
+                .is_some_and(|rest| rest.starts_with('/'))
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        RateLimiter::new()
+    }
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        RateLimiter {
+            rules: Vec::new(),
+            buckets: Mutex::new(Buckets::default()),
+            max_buckets: MAX_BUCKETS,
+            last_sweep: Mutex::new(Instant::now()),
+        }
+    }
+
+    // Read limits from RATE_LIMITS, a ';' separated list of
+    // `prefix=requests/seconds[@Header-Name]` entries, for example
+    // `/api=100/60@X-Api-Key;/=20/1`. Returns None when it is not set.
+    pub fn from_env() -> Option<Self> {
+        let config = env::var("RATE_LIMITS").ok()?;
+        let mut limiter = RateLimiter::new();
+        for entry in config.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let parsed = entry.split_once('=').and_then(|(prefix, limit)| {
+                let (limit, header) = match limit.split_once('@') {
+                    Some((limit, header)) => (limit, Some(header.trim())),
+                    None => (limit, None),
+                };
+                let (requests, seconds) = limit.split_once('/')?;
+                let requests = requests.trim().parse().ok()?;
+                let seconds = seconds.trim().parse().ok()?;
+                Some((prefix.trim(), requests, seconds, header))
+            });
+            limiter = match parsed {
+                Some((prefix, requests, seconds, header)) if requests > 0 && seconds > 0 => {
+                    let period = Duration::from_secs(seconds);
+                    match header {
+                        Some(header) => limiter.limit_by_header(prefix, requests, period, header),
+                        None => limiter.limit(prefix, requests, period),
+                    }
+                }
+                _ => {
+                    eprintln!("Ignoring malformed rate limit: {}", entry);
+                    limiter
+                }
+            };
+        }
+        if limiter.rules.is_empty() {
+            None
+        } else {
+            Some(limiter)
+        }
+    }
+
+    // Allow each peer IP `requests` requests per `period` under `prefix`
+    pub fn limit(self, prefix: &str, requests: u32, period: Duration) -> Self {
+        self.add_rule(prefix, requests, period, None)
+    }
+
+    // Like limit(), but clients sending `header` are also counted by its
+    // value, so a key is limited across addresses. Each peer IP stays
+    // limited too and may only use a few distinct keys at a time.
+    pub fn limit_by_header(
+        self,
+        prefix: &str,
+        requests: u32,
+        period: Duration,
+        header: &str,
+    ) -> Self {
+        self.add_rule(prefix, requests, period, Some(header.to_string()))
+    }
+
+    // Keep at most `max_buckets` clients. When that many are active, new
+    // clients are refused until some buckets go idle.
+    pub fn max_buckets(mut self, max_buckets: usize) -> Self {
+        self.max_buckets = max_buckets;
+        self
+    }
+
+    fn add_rule(
+        mut self,
+        prefix: &str,
+        requests: u32,
+        period: Duration,
+        key_header: Option<String>,
+    ) -> Self {
+        self.rules.push(Rule {
+            prefix: prefix.trim_end_matches('/').to_string(),
+            requests,
+            period,
+            key_header,
+        });
+        self
+    }
+
+    // Take a token for the request. Returns None when no limit applies.
+    pub fn check(&self, req: &HttpRequest, peer: Option<IpAddr>) -> Option<Decision> {
+        self.check_at(req, peer, Instant::now())
+    }
+
+    fn check_at(&self, req: &HttpRequest, peer: Option<IpAddr>, now: Instant) -> Option<Decision> {
+        let Resource::Path(path) = &req.resource;
+        let path = path.split('?').next().unwrap_or_default();
+        // The longest matching prefix wins
+        let (index, rule) = self
+            .rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| rule.matches(path))
+            .max_by_key(|(_, rule)| rule.prefix.len())?;
+
+        let ip_key = match peer {
+            Some(ip) => format!("ip:{}", ip),
+            None => "ip:unknown".to_string(),
+        };
+        let header_key = rule
+            .key_header
+            .as_deref()
+            .and_then(|h| req.header(h))
+            .map(|value| format!("key:{}", value));
+
+        self.sweep(now);
+
+        let mut buckets = self.buckets.lock().unwrap();
+        let ip_key = (index, ip_key);
+        let header_key = header_key.map(|key| (index, key));
+        // Whether the request would open a header-keyed bucket, and whether
+        // that or anything else about it is over the limits on buckets
+        let check_room = |buckets: &Buckets| {
+            let new_header_key = header_key
+                .as_ref()
+                .is_some_and(|key| !buckets.map.contains_key(key));
+            let new_buckets =
+                usize::from(!buckets.map.contains_key(&ip_key)) + usize::from(new_header_key);
+            let full = buckets.map.len() + new_buckets > self.max_buckets
+                || (new_header_key
+                    && buckets.keys_per_ip.get(&ip_key).copied().unwrap_or(0) >= MAX_KEYS_PER_IP);
+            (new_header_key, full)
+        };
+        let (mut new_header_key, mut full) = check_room(&buckets);
+        if full {
+            self.evict_idle(&mut buckets, now);
+            (new_header_key, full) = check_room(&buckets);
+            if full {
+                // Refuse rather than evict a bucket in use, which would give
+                // its client a full bucket again
+                return Some(Decision {
+                    allowed: false,
+                    limit: rule.requests,
+                    remaining: 0,
+                    reset: rule.period.as_secs(),
+                    retry_after: rule.period.as_secs().max(1),
+                });
+            }
+        }
+
+        // Refill the buckets this request is counted against. A keyed request
+        // needs a token from both its key's bucket and its peer IP's bucket.
+        let mut keys = vec![ip_key.clone()];
+        if let Some(key) = header_key {
+            if new_header_key {
+                *buckets.keys_per_ip.entry(ip_key.clone()).or_insert(0) += 1;
+            }
+            keys.push(key);
+        }
+        let rate = rule.rate();
+        let mut tokens = f64::INFINITY;
+        for key in &keys {
+            let opened_by = (key != &ip_key).then(|| ip_key.1.clone());
+            let bucket = buckets.map.entry(key.clone()).or_insert(Bucket {
+                tokens: rule.requests as f64,
+                updated: now,
+                opened_by,
+            });
+            let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * rate).min(rule.requests as f64);
+            bucket.updated = now;
+            tokens = tokens.min(bucket.tokens);
+        }
+
+        let allowed = tokens >= 1.0;
+        if allowed {
+            tokens -= 1.0;
+            for key in &keys {
+                if let Some(bucket) = buckets.map.get_mut(key) {
+                    bucket.tokens -= 1.0;
+                }
+            }
+        }
+        // Report on whichever bucket is closest to empty
+        Some(Decision {
+            allowed,
+            limit: rule.requests,
+            remaining: tokens.floor() as u32,
+            reset: ((rule.requests as f64 - tokens) / rate).ceil() as u64,
+            retry_after: ((1.0 - tokens).max(0.0) / rate).ceil().max(1.0) as u64,
+        })
+    }
+
+    // Drop buckets that have been idle long enough to refill completely,
+    // as they are no different from a new bucket
+    fn sweep(&self, now: Instant) {
+        let mut last_sweep = self.last_sweep.lock().unwrap();
+        if now.saturating_duration_since(*last_sweep) < SWEEP_INTERVAL {
+            return;
+        }
+        *last_sweep = now;
+        self.evict_idle(&mut self.buckets.lock().unwrap(), now);
+    }
+
+    fn evict_idle(&self, buckets: &mut Buckets, now: Instant) {
+        let rules = &self.rules;
+        let keys_per_ip = &mut buckets.keys_per_ip;
+        buckets.map.retain(|(index, _), bucket| {
+            let keep = now.saturating_duration_since(bucket.updated) < rules[*index].period;
+            if let Some(ip_key) = bucket.opened_by.as_ref().filter(|_| !keep) {
+                let ip_key = (*index, ip_key.clone());
+                if let Some(count) = keys_per_ip.get_mut(&ip_key) {
+                    *count -= 1;
+                    if *count == 0 {
+                        keys_per_ip.remove(&ip_key);
+                    }
+                }
+            }
+            keep
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(path: &str, headers: &str) -> HttpRequest {
+        format!("GET {} HTTP/1.1\r\n{}\r\n", path, headers).into()
+    }
+
+    fn peer(ip: &str) -> Option<IpAddr> {
+        Some(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn test_bucket_empties_and_refills() {
+        let limiter = RateLimiter::new().limit("/api", 2, Duration::from_secs(10));
+        let req = request("/api/shipping/orders", "");
+        let start = Instant::now();
+
+        let first = limiter.check_at(&req, peer("10.0.0.1"), start).unwrap();
+        assert!(first.allowed);
+        assert_eq!(first.remaining, 1);
+        assert!(
+            limiter
+                .check_at(&req, peer("10.0.0.1"), start)
+                .unwrap()
+                .allowed
+        );
+
+        let denied = limiter.check_at(&req, peer("10.0.0.1"), start).unwrap();
+        assert!(!denied.allowed);
+        assert_eq!(denied.remaining, 0);
+        assert_eq!(denied.reset, 10);
+        assert!(denied.headers().contains(&("Retry-After", "5".to_string())));
+
+        // Other clients have their own bucket
+        assert!(
+            limiter
+                .check_at(&req, peer("10.0.0.2"), start)
+                .unwrap()
+                .allowed
+        );
+        // One token comes back every five seconds
+        let later = start + Duration::from_secs(5);
+        assert!(
+            limiter
+                .check_at(&req, peer("10.0.0.1"), later)
+                .unwrap()
+                .allowed
+        );
+    }
+
+    #[test]
+    fn test_rules_by_prefix_and_header() {
+        let limiter = RateLimiter::new()
+            .limit("/", 100, Duration::from_secs(1))
+            .limit_by_header("/api", 1, Duration::from_secs(60), "X-Api-Key");
+        let start = Instant::now();
+
+        assert!(limiter
+            .check_at(&request("/apiary", ""), peer("10.0.0.1"), start)
+            .is_some_and(|d| d.limit == 100));
+
+        let alice = request("/api/orders", "X-Api-Key: alice\r\n");
+        assert!(
+            limiter
+                .check_at(&alice, peer("10.0.0.1"), start)
+                .unwrap()
+                .allowed
+        );
+        // The same key is limited from any address
+        assert!(
+            !limiter
+                .check_at(&alice, peer("10.0.0.2"), start)
+                .unwrap()
+                .allowed
+        );
+        // Requests without the key fall back to the peer IP
+        let anonymous = request("/api/orders", "");
+        assert!(
+            limiter
+                .check_at(&anonymous, peer("10.0.0.4"), start)
+                .unwrap()
+                .allowed
+        );
+        // A fresh key does not get past the limit on the peer IP
+        assert!(
+            !limiter
+                .check_at(
+                    &request("/api/orders", "X-Api-Key: bob\r\n"),
+                    peer("10.0.0.1"),
+                    start
+                )
+                .unwrap()
+                .allowed
+        );
+
+        // The query string is not part of the path
+        assert!(
+            !limiter
+                .check_at(
+                    &request("/api?x=1", "X-Api-Key: alice\r\n"),
+                    peer("10.0.0.3"),
+                    start
+                )
+                .unwrap()
+                .allowed
+        );
+
+        assert!(RateLimiter::new()
+            .limit("/api", 1, Duration::from_secs(1))
+            .check_at(&request("/", ""), peer("10.0.0.1"), start)
+            .is_none());
+    }
+
+    #[test]
+    fn test_idle_buckets_are_evicted() {
+        let limiter = RateLimiter::new().limit("/", 5, Duration::from_secs(10));
+        let start = Instant::now();
+        for i in 0..10 {
+            let ip = format!("10.0.0.{}", i);
+            limiter.check_at(&request("/", ""), peer(&ip), start);
+        }
+        assert_eq!(limiter.buckets.lock().unwrap().map.len(), 10);
+
+        let later = start + SWEEP_INTERVAL;
+        limiter.check_at(&request("/", ""), peer("10.0.1.1"), later);
+        assert_eq!(limiter.buckets.lock().unwrap().map.len(), 1);
+    }
+
+    #[test]
+    fn test_bucket_cap() {
+        let limiter = RateLimiter::new()
+            .limit_by_header("/", 5, Duration::from_secs(10), "X-Api-Key")
+            .max_buckets(6);
+        let start = Instant::now();
+        let key = |i: u32| request("/", &format!("X-Api-Key: key{}\r\n", i));
+        let ip = |i: u32| peer(&format!("10.0.0.{}", i));
+        // Each client has a bucket for its key and one for its address
+        for i in 0..3 {
+            assert!(limiter.check_at(&key(i), ip(i), start).unwrap().allowed);
+        }
+        // New clients are refused, known ones still counted
+        let refused = limiter.check_at(&key(3), ip(3), start).unwrap();
+        assert!(!refused.allowed);
+        assert!(refused
+            .headers()
+            .contains(&("Retry-After", "10".to_string())));
+        assert!(limiter.check_at(&key(0), ip(0), start).unwrap().allowed);
+        assert_eq!(limiter.buckets.lock().unwrap().map.len(), 6);
+
+        // Idle buckets make room without waiting for the sweep
+        let later = start + Duration::from_secs(10);
+        assert!(limiter.check_at(&key(3), ip(3), later).unwrap().allowed);
+        assert_eq!(limiter.buckets.lock().unwrap().map.len(), 2);
+    }
+
+    #[test]
+    fn test_rotating_keys_cannot_fill_the_map() {
+        let limiter =
+            RateLimiter::new().limit_by_header("/", 1000, Duration::from_secs(10), "X-Api-Key");
+        let start = Instant::now();
+        let key = |i: usize| request("/", &format!("X-Api-Key: key{}\r\n", i));
+        for i in 0..MAX_KEYS_PER_IP {
+            assert!(
+                limiter
+                    .check_at(&key(i), peer("10.0.0.1"), start)
+                    .unwrap()
+                    .allowed
+            );
+        }
+        // One more made-up key from the same address is refused...
+        assert!(
+            !limiter
+                .check_at(&key(MAX_KEYS_PER_IP), peer("10.0.0.1"), start)
+                .unwrap()
+                .allowed
+        );
+        // ...while its known keys and other clients carry on
+        assert!(
+            limiter
+                .check_at(&key(0), peer("10.0.0.1"), start)
+                .unwrap()
+                .allowed
+        );
+        assert!(
+            limiter
+                .check_at(&key(MAX_KEYS_PER_IP), peer("10.0.0.2"), start)
+                .unwrap()
+                .allowed
+        );
+        assert_eq!(
+            limiter.buckets.lock().unwrap().map.len(),
+            MAX_KEYS_PER_IP + 3
+        );
+
+        // Once its keys go idle the address may use new ones
+        let later = start + Duration::from_secs(10);
+        assert!(
+            limiter
+                .check_at(&key(99), peer("10.0.0.1"), later)
+                .unwrap()
+                .allowed
+        );
+        assert_eq!(limiter.buckets.lock().unwrap().keys_per_ip.len(), 1);
+    }
+}
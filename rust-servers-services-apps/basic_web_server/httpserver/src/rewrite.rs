@@ -0,0 +1,403 @@
+//! The rewrite module runs redirect and rewrite rules against each request
+//! before it is routed. Rules are loaded from a JSON file and tried in order.
+//!
+
+use regex::Regex;
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+
+use super::vhost::normalize_host;
+use http::httprequest::{HttpRequest, Resource};
+
+// A rule as written in the rules file, for example
+// `{"name": "old-blog", "match": "^/blog/(\\d+)$", "redirect": "/posts/$1", "status": 301}`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleConfig {
+    name: Option<String>,
+    #[serde(rename = "match")]
+    pattern: Option<String>,
+    redirect: Option<String>,
+    rewrite: Option<String>,
+    // "add" or "remove"
+    trailing_slash: Option<String>,
+    status: Option<u16>,
+    // Stop processing further rules after this rewrite
+    #[serde(default)]
+    last: bool,
+    // Conditions, all of which must hold for the rule to apply
+    method: Option<String>,
+    host: Option<String>,
+    header: Option<HeaderConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HeaderConfig {
+    name: String,
+    // The header only has to be present when no value pattern is given
+    value: Option<String>,
+}
+
+struct Rule {
+    name: String,
+    pattern: Regex,
+    action: Action,
+    last: bool,
+    method: Option<String>,
+    host: Option<Regex>,
+    header: Option<(String, Option<Regex>)>,
+}
+
+enum Action {
+    Redirect { to: String, status: &'static str },
+    Rewrite { to: String },
+    AddTrailingSlash { status: &'static str },
+    RemoveTrailingSlash { status: &'static str },
+}
+
+// What the rules decided for a request
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    // Route the request as it is
+    Pass,
+    // Route the request as if it asked for this path
+    Rewrite(String),
+    // Answer with a redirect instead of routing the request
+    Redirect {
+        status: &'static str,
+        location: String,
+    },
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Pass => write!(f, "no change"),
+            Outcome::Rewrite(path) => write!(f, "rewrite to {}", path),
+            Outcome::Redirect { status, location } => {
+                write!(f, "redirect {} to {}", status, location)
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Rewriter {
+    rules: Vec<Rule>,
+}
+
+impl Rewriter {
+    // Load rules from a JSON file holding an array of rules
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+        Rewriter::from_json(&contents)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let configs: Vec<RuleConfig> =
+            serde_json::from_str(json).map_err(|e| format!("invalid rules: {}", e))?;
+        let rules = configs
+            .into_iter()
+            .enumerate()
+            .map(|(i, config)| Rule::compile(i + 1, config))
+            .collect::<Result<_, _>>()?;
+        Ok(Rewriter { rules })
+    }
+
+    // Run the rules against a request
+    pub fn apply(&self, req: &HttpRequest) -> Outcome {
+        self.evaluate(req).0
+    }
+
+    // Describe which rules match a request and what they decide, without
+    // serving it
+    pub fn dry_run(&self, req: &HttpRequest) -> String {
+        let (outcome, matched) = self.evaluate(req);
+        let mut report = String::new();
+        for name in matched {
+            report.push_str(&format!("matched {}\n", name));
+        }
+        report.push_str(&format!("{}\n", outcome));
+        report
+    }
+
+    fn evaluate(&self, req: &HttpRequest) -> (Outcome, Vec<&str>) {
+        let Resource::Path(original) = &req.resource;
+        let mut path = original.clone();
+        let mut matched = Vec::new();
+
+        for rule in &self.rules {
+            if !rule.conditions_hold(req) {
+                continue;
+            }
+            let target = match rule.action.target(&rule.pattern, &path) {
+                Some(target) => target,
+                None => continue,
+            };
+            matched.push(rule.name.as_str());
+            match &rule.action {
+                Action::Rewrite { .. } => {
+                    path = target;
+                    if rule.last {
+                        break;
+                    }
+                }
+                Action::Redirect { status, .. }
+                | Action::AddTrailingSlash { status }
+                | Action::RemoveTrailingSlash { status } => {
+                    let outcome = Outcome::Redirect {
+                        status,
+                        location: target,
+                    };
+                    return (outcome, matched);
+                }
+            }
+        }
+
+        if path == *original {
+            (Outcome::Pass, matched)
+        } else {
+            (Outcome::Rewrite(path), matched)
+        }
+    }
+}
+
+impl Rule {
+    fn compile(index: usize, config: RuleConfig) -> Result<Self, String> {
+        let name = config.name.unwrap_or(format!("rule {}", index));
+        let regex = |pattern: &str| {
+            Regex::new(pattern).map_err(|e| format!("{}: invalid pattern: {}", name, e))
+        };
+        let status = |default: u16| match config.status.unwrap_or(default) {
+            301 => Ok("301"),
+            302 => Ok("302"),
+            307 => Ok("307"),
+            308 => Ok("308"),
+            other => Err(format!("{}: {} is not a redirect status", name, other)),
+        };
+
+        let action = match (config.redirect, config.rewrite, config.trailing_slash) {
+            (Some(to), None, None) => Action::Redirect {
+                to,
+                status: status(302)?,
+            },
+            (None, Some(to), None) => Action::Rewrite { to },
+            (None, None, Some(slash)) => match slash.as_str() {
+                "add" => Action::AddTrailingSlash {
+                    status: status(301)?,
+                },
+                "remove" => Action::RemoveTrailingSlash {
+                    status: status(301)?,
+                },
+                _ => return Err(format!("{}: trailing_slash must be add or remove", name)),
+            },
+            _ => {
+                return Err(format!(
+                    "{}: needs exactly one of redirect, rewrite or trailing_slash",
+                    name
+                ))
+            }
+        };
+
+        Ok(Rule {
+            pattern: regex(config.pattern.as_deref().unwrap_or("^/"))?,
+            action,
+            last: config.last,
+            method: config.method.map(|m| m.to_ascii_uppercase()),
+            host: config.host.as_deref().map(regex).transpose()?,
+            header: match config.header {
+                Some(header) => {
+                    Some((header.name, header.value.as_deref().map(regex).transpose()?))
+                }
+                None => None,
+            },
+            name,
+        })
+    }
+
+    fn conditions_hold(&self, req: &HttpRequest) -> bool {
+        if let Some(method) = &self.method {
+            if req.method.as_str() != method {
+                return false;
+            }
+        }
+        if let Some(host) = &self.host {
+            let name = req.header("Host").map(normalize_host).unwrap_or_default();
+            if !host.is_match(&name) {
+                return false;
+            }
+        }
+        if let Some((name, value)) = &self.header {
+            match (req.header(name), value) {
+                (None, _) => return false,
+                (Some(actual), Some(value)) if !value.is_match(actual) => return false,
+                _ => {}
+            }
+        }
+        true
+    }
+}
+
+impl Action {
+    // The new path or location when the rule applies to `path`
+    fn target(&self, pattern: &Regex, path: &str) -> Option<String> {
+        let captures = pattern.captures(path)?;
+        let (route, query) = match path.split_once('?') {
+            Some((route, query)) => (route, Some(query)),
+            None => (path, None),
+        };
+        let with_query = |route: &str| match query {
+            Some(query) => format!("{}?{}", route, query),
+            None => route.to_string(),
+        };
+        match self {
+            Action::Redirect { to, .. } | Action::Rewrite { to } => {
+                let mut target = String::new();
+                captures.expand(to, &mut target);
+                Some(target)
+            }
+            Action::AddTrailingSlash { .. } => {
+                // Paths that look like files are left alone
+                let last_segment = route.rsplit('/').next().unwrap_or("");
+                if route.ends_with('/') || last_segment.contains('.') {
+                    None
+                } else {
+                    Some(with_query(&format!("{}/", route)))
+                }
+            }
+            Action::RemoveTrailingSlash { .. } => {
+                let trimmed = route.trim_end_matches('/');
+                if route.len() > 1 && !trimmed.is_empty() && trimmed != route {
+                    Some(with_query(trimmed))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, path: &str, headers: &str) -> HttpRequest {
+        format!("{} {} HTTP/1.1\r\n{}\r\n", method, path, headers).into()
+    }
+
+    #[test]
+    fn test_redirect_with_captures() {
+        let rules = Rewriter::from_json(
+            r#"[{"name": "old-blog", "match": "^/blog/(\\d+)$", "redirect": "/posts/$1", "status": 301}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            rules.apply(&request("GET", "/blog/42", "")),
+            Outcome::Redirect {
+                status: "301",
+                location: "/posts/42".to_string()
+            }
+        );
+        assert_eq!(rules.apply(&request("GET", "/blog/new", "")), Outcome::Pass);
+    }
+
+    #[test]
+    fn test_rewrites_chain_until_last() {
+        let rules = Rewriter::from_json(
+            r#"[
+                {"match": "^/orders$", "rewrite": "/api/shipping/orders"},
+                {"match": "^/api/shipping/(.*)$", "rewrite": "/api/v1/shipping/$1", "last": true},
+                {"match": "^/api/v1/(.*)$", "rewrite": "/unreachable"}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            rules.apply(&request("GET", "/orders", "")),
+            Outcome::Rewrite("/api/v1/shipping/orders".to_string())
+        );
+    }
+
+    #[test]
+    fn test_conditions() {
+        let rules = Rewriter::from_json(
+            r#"[
+                {"name": "www", "host": "^www\\.example\\.com$", "match": "^(.*)$", "redirect": "https://example.com$1", "status": 308},
+                {"name": "beta", "header": {"name": "X-Beta", "value": "^on$"}, "match": "^/$", "rewrite": "/beta.html"},
+                {"name": "no-posts", "method": "post", "match": "^/health$", "redirect": "/", "status": 307}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            rules.apply(&request("GET", "/a?b=c", "Host: WWW.example.com:3000\r\n")),
+            Outcome::Redirect {
+                status: "308",
+                location: "https://example.com/a?b=c".to_string()
+            }
+        );
+        assert_eq!(
+            rules.apply(&request("GET", "/", "X-Beta: on\r\n")),
+            Outcome::Rewrite("/beta.html".to_string())
+        );
+        assert_eq!(
+            rules.apply(&request("GET", "/", "X-Beta: off\r\n")),
+            Outcome::Pass
+        );
+        assert_eq!(rules.apply(&request("GET", "/health", "")), Outcome::Pass);
+        assert!(matches!(
+            rules.apply(&request("POST", "/health", "")),
+            Outcome::Redirect { status: "307", .. }
+        ));
+    }
+
+    #[test]
+    fn test_trailing_slash() {
+        let add = Rewriter::from_json(r#"[{"trailing_slash": "add"}]"#).unwrap();
+        assert_eq!(
+            add.apply(&request("GET", "/docs?page=2", "")),
+            Outcome::Redirect {
+                status: "301",
+                location: "/docs/?page=2".to_string()
+            }
+        );
+        assert_eq!(add.apply(&request("GET", "/styles.css", "")), Outcome::Pass);
+        assert_eq!(add.apply(&request("GET", "/", "")), Outcome::Pass);
+
+        let remove =
+            Rewriter::from_json(r#"[{"trailing_slash": "remove", "status": 308}]"#).unwrap();
+        assert_eq!(
+            remove.apply(&request("GET", "/docs/", "")),
+            Outcome::Redirect {
+                status: "308",
+                location: "/docs".to_string()
+            }
+        );
+        assert_eq!(remove.apply(&request("GET", "/", "")), Outcome::Pass);
+    }
+
+    #[test]
+    fn test_dry_run_and_errors() {
+        let rules = Rewriter::from_json(
+            r#"[{"name": "home", "match": "^/$", "rewrite": "/index.html"},
+                {"name": "html", "match": "^/(\\w+)\\.html$", "redirect": "/$1"}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            rules.dry_run(&request("GET", "/", "")),
+            "matched home\nmatched html\nredirect 302 to /index\n"
+        );
+        assert_eq!(rules.dry_run(&request("GET", "/x", "")), "no change\n");
+
+        assert!(Rewriter::from_json(r#"[{"match": "("}]"#).is_err());
+        assert!(matches!(
+            Rewriter::from_json(r#"[{"match": "^/$", "redirect": "/a", "status": 200}]"#),
+            Err(e) if e.contains("not a redirect status")
+        ));
+        assert!(
+            Rewriter::from_json(r#"[{"match": "^/$", "redirect": "/a", "rewrite": "/b"}]"#)
+                .is_err()
+        );
+    }
+}
@@ -2,42 +2,678 @@
 //! determines which handler to route the request to for processing
 //!
 
-use std::io::prelude::*;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use super::handler::{Handler, PageNotFoundHandler, StaticPageHandler, WebServiceHandler};
+use super::auth::{Access, Auth, PRINCIPAL_HEADER};
+use super::connection::Connection;
+use super::cors::{Cors, CorsHeaders, Preflight};
+use super::handler::{
+    default_public_path, Handler, PageNotFoundHandler, StaticPageHandler, WebServiceHandler,
+};
+use super::limits::PendingRequest;
+use super::metrics::{Metrics, CLIENT_CLOSED};
+use super::proxy::Proxy;
+use super::ratelimit::RateLimiter;
+use super::rewrite::{Outcome, Rewriter};
+use super::server::send_error;
+use super::sse::EventBus;
+use super::template::TemplateEngine;
 use http::{httprequest, httprequest::HttpRequest, httpresponse::HttpResponse};
+use serde_json::{json, Value};
 
-pub struct Router;
+pub struct Router {
+    // Document root for static pages
+    public_path: String,
+    // File name of the 404 page inside the document root
+    not_found_page: String,
+    proxy: Proxy,
+    metrics: Arc<Metrics>,
+    cors: Option<Cors>,
+    rate_limit: Option<RateLimiter>,
+    rewrites: Rewriter,
+    auth: Auth,
+    // Handlers by path
+    routes: Vec<(String, Box<dyn Handler + Send + Sync>)>,
+    // Path of the Server-Sent Events stream, and the bus feeding it
+    events_path: String,
+    events: EventBus,
+    // Engine for page templates; the built-in one unless configured
+    templates: Option<Arc<dyn TemplateEngine>>,
+}
+
+// A response decided before the request reaches a handler
+struct Early {
+    // Route label for metrics
+    route: &'static str,
+    status: &'static str,
+    headers: CorsHeaders,
+}
+
+// A complete response, as returned by Router::reply()
+#[derive(Debug)]
+pub struct Reply {
+    pub status: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Default for Router {
+    fn default() -> Self {
+        Router {
+            public_path: default_public_path(),
+            not_found_page: "404.html".to_string(),
+            proxy: Proxy::default(),
+            metrics: Arc::default(),
+            cors: None,
+            rate_limit: None,
+            rewrites: Rewriter::default(),
+            auth: Auth::default(),
+            routes: Vec::new(),
+            events_path: "/events".to_string(),
+            events: EventBus::default(),
+            templates: None,
+        }
+    }
+}
 
 impl Router {
-    pub fn route(req: HttpRequest, stream: &mut impl Write) {
+    pub fn new() -> Self {
+        Router::default()
+    }
+
+    pub fn with_document_root(mut self, public_path: &str) -> Self {
+        self.public_path = public_path.to_string();
+        self
+    }
+
+    pub fn with_not_found_page(mut self, page: &str) -> Self {
+        self.not_found_page = page.to_string();
+        self
+    }
+
+    // Serve requests for `path`, and the paths below it, with `handler`
+    pub fn with_route<H>(mut self, path: &str, handler: H) -> Self
+    where
+        H: Handler + Send + Sync + 'static,
+    {
+        self.routes
+            .push((path.trim_end_matches('/').to_string(), Box::new(handler)));
+        self
+    }
+
+    pub fn with_proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = proxy;
+        self
+    }
+
+    pub fn with_cors(mut self, cors: Cors) -> Self {
+        self.cors = Some(cors);
+        self
+    }
+
+    pub fn with_rate_limit(mut self, limiter: RateLimiter) -> Self {
+        self.rate_limit = Some(limiter);
+        self
+    }
+
+    // Redirect or rewrite requests before they are routed
+    pub fn with_rewrites(mut self, rewrites: Rewriter) -> Self {
+        self.rewrites = rewrites;
+        self
+    }
+
+    // Require credentials for the protected areas of `auth`
+    pub fn with_auth(mut self, auth: Auth) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    // Serve the event stream of `events` on `path`
+    pub fn with_events(mut self, path: &str, events: EventBus) -> Self {
+        self.events_path = path.to_string();
+        self.events = events;
+        self
+    }
+
+    // Render page templates with another engine
+    pub fn with_templates<T: TemplateEngine + 'static>(mut self, templates: T) -> Self {
+        self.templates = Some(Arc::new(templates));
+        self
+    }
+
+    // Serve the metrics on a different path than /metrics
+    pub fn with_metrics_path(mut self, path: &str) -> Self {
+        self.metrics = Arc::new(Metrics::new(path));
+        self
+    }
+
+    // Record into (and serve) the same metrics as another router
+    pub fn with_shared_metrics(mut self, other: &Router) -> Self {
+        self.metrics = Arc::clone(&other.metrics);
+        self
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub fn route(&self, pending: PendingRequest, stream: &mut Connection) {
+        let started = Instant::now();
+        let _in_flight = self.metrics.track_in_flight();
+        let method = pending.req.method.as_str().to_string();
+
+        let (route, status_code) = self.dispatch(pending, stream);
+        self.metrics
+            .observe_request(&method, &route, &status_code, started.elapsed());
+    }
+
+    // Handle the request, returning the route label and status code for metrics
+    fn dispatch(&self, mut pending: PendingRequest, stream: &mut Connection) -> (String, String) {
+        let extra_headers = match self.screen(&mut pending.req, stream.peer_ip()) {
+            Ok(headers) => headers,
+            Err(early) => {
+                let headers: HashMap<&str, &str> = early
+                    .headers
+                    .iter()
+                    .map(|(k, v)| (*k, v.as_str()))
+                    .collect();
+                let resp = HttpResponse::new(early.status, Some(headers), Some(String::new()));
+                let _ = resp.send_response(stream);
+                return (early.route.to_string(), early.status.to_string());
+            }
+        };
+
+        // Event stream clients keep the connection until they go away
+        let httprequest::Resource::Path(s) = &pending.req.resource;
+        if pending.req.method == httprequest::Method::Get && *s == self.events_path {
+            let last_event_id = pending.req.header("Last-Event-ID");
+            let _ = self.events.serve(last_event_id, stream, &extra_headers);
+            return ("events".to_string(), "200".to_string());
+        }
+
+        // Requests under a proxy mount are streamed to the upstream as they are
+        if let Some(mount) = self.proxy.find(s) {
+            let status_code = mount.forward(pending, stream, &extra_headers);
+            return (mount.prefix().to_string(), status_code);
+        }
+
+        // Everything else is handled here, with the message body read in full
+        let req = match pending.read_body(stream) {
+            Ok(req) => req,
+            Err(err) => {
+                let status_code = err.status_code().unwrap_or(CLIENT_CLOSED);
+                if err.status_code().is_some() {
+                    send_error(stream, status_code);
+                }
+                return ("body".to_string(), status_code.to_string());
+            }
+        };
+
+        let (route, mut resp) = self.respond(&req);
+        for (key, value) in &extra_headers {
+            resp.add_header(key, value);
+        }
+        let _ = resp.send_response(stream);
+        (route.to_string(), resp.status_code().to_string())
+    }
+
+    // Apply rewrites, CORS, rate limits and auth to a request about to be
+    // routed. Returns the headers to add to its response, or the response
+    // to send instead when the request goes no further.
+    fn screen(&self, req: &mut HttpRequest, peer: Option<IpAddr>) -> Result<CorsHeaders, Early> {
+        // Redirects are answered straight away; rewrites change what is routed
+        match self.rewrites.apply(req) {
+            Outcome::Pass => {}
+            Outcome::Rewrite(path) => req.resource = httprequest::Resource::Path(path),
+            Outcome::Redirect { status, location } => {
+                return Err(Early {
+                    route: "redirect",
+                    status,
+                    headers: vec![("Location", location)],
+                });
+            }
+        }
+
+        // CORS preflights are answered here, before they reach any handler
+        let mut extra_headers = CorsHeaders::new();
+        if let Some(cors) = &self.cors {
+            let status = match cors.preflight(req) {
+                Preflight::Pass => None,
+                Preflight::Allowed(headers) => Some(("204", headers)),
+                Preflight::Rejected(headers) => Some(("403", headers)),
+            };
+            if let Some((status, headers)) = status {
+                return Err(Early {
+                    route: "cors_preflight",
+                    status,
+                    headers,
+                });
+            }
+            extra_headers = cors.response_headers(req);
+        }
+
+        // Clients over their rate limit are turned away before any work is done
+        if let Some(limiter) = &self.rate_limit {
+            if let Some(decision) = limiter.check(req, peer) {
+                extra_headers.extend(decision.headers());
+                if !decision.allowed {
+                    return Err(Early {
+                        route: "rate_limited",
+                        status: "429",
+                        headers: extra_headers,
+                    });
+                }
+            }
+        }
+
+        // Protected paths need credentials; handlers find the principal in
+        // the X-Authenticated-User header, which clients cannot set themselves
+        req.headers
+            .retain(|name, _| !name.trim().eq_ignore_ascii_case(PRINCIPAL_HEADER));
+        match self.auth.check(req) {
+            Access::Open => {}
+            Access::Granted(principal) => {
+                req.headers.insert(PRINCIPAL_HEADER.to_string(), principal);
+            }
+            Access::Denied(challenge) => {
+                extra_headers.push(("WWW-Authenticate", challenge));
+                return Err(Early {
+                    route: "unauthorized",
+                    status: "401",
+                    headers: extra_headers,
+                });
+            }
+        }
+        Ok(extra_headers)
+    }
+
+    // Handle a fully read request and hand back the response, for
+    // connections that frame responses themselves (HTTP/2). The event
+    // stream and proxy mounts need the raw connection and answer 501 here.
+    pub fn reply(&self, mut req: HttpRequest, peer: Option<IpAddr>) -> Reply {
+        let started = Instant::now();
+        let _in_flight = self.metrics.track_in_flight();
+        let method = req.method.as_str().to_string();
+
+        let (route, reply) = match self.screen(&mut req, peer) {
+            Err(early) => (
+                early.route.to_string(),
+                Reply {
+                    status: early.status.to_string(),
+                    headers: early
+                        .headers
+                        .into_iter()
+                        .map(|(k, v)| (k.to_string(), v))
+                        .collect(),
+                    body: String::new(),
+                },
+            ),
+            Ok(extra_headers) => {
+                let httprequest::Resource::Path(s) = &req.resource;
+                let streamed = (req.method == httprequest::Method::Get && *s == self.events_path)
+                    || self.proxy.find(s).is_some();
+                let (route, mut resp) = if streamed {
+                    ("not_implemented", HttpResponse::new("501", None, None))
+                } else {
+                    self.respond(&req)
+                };
+                if streamed {
+                    resp.set_body(format!("<h1>501 {}</h1>", resp.status_text()));
+                }
+                for (key, value) in &extra_headers {
+                    resp.add_header(key, value);
+                }
+                let reply = Reply {
+                    status: resp.status_code().to_string(),
+                    headers: resp
+                        .header_fields()
+                        .into_iter()
+                        .map(|(k, v)| (k.to_string(), v.to_string()))
+                        .collect(),
+                    body: resp.body().to_string(),
+                };
+                (route.to_string(), reply)
+            }
+        };
+        self.metrics
+            .observe_request(&method, &route, &reply.status, started.elapsed());
+        reply
+    }
+
+    // Pick the handler for a fully read request
+    fn respond<'a>(&'a self, req: &'a HttpRequest) -> (&'a str, HttpResponse<'a>) {
+        // Routes added with with_route() come first, the longest path winning
+        let httprequest::Resource::Path(s) = &req.resource;
+        let path = s.split('?').next().unwrap_or_default();
+        let route = self
+            .routes
+            .iter()
+            .filter(|(prefix, _)| {
+                path == prefix
+                    || prefix.is_empty()
+                    || path
+                        .strip_prefix(prefix.as_str())
+                        .is_some_and(|rest| rest.starts_with('/'))
+            })
+            .max_by_key(|(prefix, _)| prefix.len());
+        if let Some((prefix, handler)) = route {
+            let label = if prefix.is_empty() { "/" } else { prefix };
+            return (label, handler.handle(req));
+        }
+
         match req.method {
             // If GET request
             httprequest::Method::Get => match &req.resource {
                 httprequest::Resource::Path(s) => {
+                    if s == self.metrics.path() {
+                        return ("metrics", self.metrics_response());
+                    }
+
                     // Parse the URI
                     let route: Vec<&str> = s.split("/").collect();
-                    match route[1] {
+                    match route.get(1) {
                         // if the route begins with /api, invoke Web service
-                        "api" => {
-                            let resp: HttpResponse = WebServiceHandler::handle(&req);
-                            let _ = resp.send_response(stream);
+                        Some(&"api") => {
+                            let handler =
+                                WebServiceHandler::new(&self.public_path, &self.not_found_page);
+                            ("web_service", handler.handle(req))
                         }
 
                         // Else, invoke static page handler
-                        _ => {
-                            let resp: HttpResponse = StaticPageHandler::handle(&req);
-                            let _ = resp.send_response(stream);
+                        Some(_) => {
+                            let mut handler =
+                                StaticPageHandler::new(&self.public_path, &self.not_found_page)
+                                    .with_context(self.page_context(req));
+                            if let Some(templates) = &self.templates {
+                                handler = handler.with_templates(Arc::clone(templates));
+                            }
+                            ("static_page", handler.handle(req))
+                        }
+
+                        // Not a path at all, such as `*`
+                        None => {
+                            let handler =
+                                PageNotFoundHandler::new(&self.public_path, &self.not_found_page);
+                            ("page_not_found", handler.handle(req))
                         }
                     }
                 }
             },
 
+            // Order status updates are POSTed to the web service
+            httprequest::Method::Post if Self::is_api(req) => {
+                let handler = WebServiceHandler::new(&self.public_path, &self.not_found_page)
+                    .with_events(self.events.clone());
+                ("web_service", handler.handle(req))
+            }
+
             // If method is not GET request, return 404 page
             _ => {
-                let resp: HttpResponse = PageNotFoundHandler::handle(&req);
-                let _ = resp.send_response(stream);
+                let handler = PageNotFoundHandler::new(&self.public_path, &self.not_found_page);
+                ("page_not_found", handler.handle(req))
+            }
+        }
+    }
+
+    fn is_api(req: &HttpRequest) -> bool {
+        let httprequest::Resource::Path(s) = &req.resource;
+        s.split('/').nth(1) == Some("api")
+    }
+
+    // What page templates are rendered with: the request, and the server's
+    // uptime and request counts
+    fn page_context(&self, req: &HttpRequest) -> Value {
+        let httprequest::Resource::Path(path) = &req.resource;
+        let by_status = self.metrics.requests_by_status();
+        let uptime = self.metrics.uptime();
+        json!({
+            "request": {
+                "method": req.method.as_str(),
+                "path": path,
+            },
+            "server": {
+                "uptime": format_uptime(uptime),
+                "uptime_seconds": uptime.as_secs(),
+                "requests_total": by_status.values().sum::<u64>(),
+                "requests_in_flight": self.metrics.in_flight(),
+                "requests_by_status": by_status
+                    .iter()
+                    .map(|(status, count)| json!({ "status": status, "count": count }))
+                    .collect::<Vec<_>>(),
+            },
+        })
+    }
+
+    fn metrics_response<'a>(&self) -> HttpResponse<'a> {
+        let mut headers: HashMap<&str, &str> = HashMap::new();
+        headers.insert("Content-Type", "text/plain; version=0.0.4");
+        HttpResponse::new("200", Some(headers), Some(self.metrics.render()))
+    }
+}
+
+// Uptime as, e.g., `3d 4h 05m 06s`
+fn format_uptime(uptime: Duration) -> String {
+    let secs = uptime.as_secs();
+    let (days, hours, minutes) = (secs / 86_400, secs / 3600 % 24, secs / 60 % 60);
+    match (days, hours) {
+        (0, 0) => format!("{}m {:02}s", minutes, secs % 60),
+        (0, _) => format!("{}h {:02}m {:02}s", hours, minutes, secs % 60),
+        _ => format!("{}d {}h {:02}m {:02}s", days, hours, minutes, secs % 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{send, start_server};
+    use std::io::prelude::*;
+    use std::net::TcpStream;
+    use std::time::Duration;
+
+    #[test]
+    fn test_metrics_endpoint_counts_requests() {
+        let addr = start_server(Router::new().with_metrics_path("/internal/metrics"));
+        send(&addr, "GET /health HTTP/1.1\r\n\r\n");
+        send(&addr, "POST /health HTTP/1.1\r\n\r\n");
+        send(&addr, "GET / HTTP/1.1\r\nContent-Length: x\r\n\r\n");
+
+        let response = send(&addr, "GET /internal/metrics HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("Content-Type:text/plain; version=0.0.4"));
+        assert!(response.contains(
+            "http_requests_total{method=\"GET\",route=\"static_page\",status=\"200\"} 1\n"
+        ));
+        assert!(response.contains(
+            "http_requests_total{method=\"POST\",route=\"page_not_found\",status=\"404\"} 1\n"
+        ));
+        assert!(response.contains("http_parse_errors_total{reason=\"bad_request\"} 1\n"));
+        // The metrics request itself is in flight while it renders
+        assert!(response.contains("http_requests_in_flight 1\n"));
+        assert!(!response.contains("http_received_bytes_total 0\n"));
+    }
+
+    #[test]
+    fn test_cors_preflight_and_response_headers() {
+        let cors = Cors::new()
+            .allow_origins(&["https://app.example.com"])
+            .allow_methods(&["GET", "POST"])
+            .max_age(60);
+        let addr = start_server(Router::new().with_cors(cors));
+
+        let preflight = send(&addr, "OPTIONS /api/shipping/orders HTTP/1.1\r\nOrigin: https://app.example.com\r\nAccess-Control-Request-Method: POST\r\n\r\n");
+        assert!(preflight.starts_with("HTTP/1.1 204 No Content"));
+        assert!(preflight.contains("Access-Control-Allow-Origin:https://app.example.com\r\n"));
+        assert!(preflight.contains("Access-Control-Max-Age:60\r\n"));
+
+        let response = send(
+            &addr,
+            "GET /api/shipping/orders HTTP/1.1\r\nOrigin: https://app.example.com\r\n\r\n",
+        );
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("Access-Control-Allow-Origin:https://app.example.com\r\n"));
+        assert!(response.contains("Vary:Origin\r\n"));
+        assert!(response.contains("order_status"));
+
+        let rejected = send(&addr, "OPTIONS / HTTP/1.1\r\nOrigin: https://evil.com\r\nAccess-Control-Request-Method: POST\r\n\r\n");
+        assert!(rejected.starts_with("HTTP/1.1 403 Forbidden"));
+        assert!(!rejected.contains("Access-Control-Allow-Origin"));
+    }
+
+    #[test]
+    fn test_rate_limited_clients_get_429() {
+        let limiter = RateLimiter::new().limit("/api", 2, Duration::from_secs(60));
+        let addr = start_server(Router::new().with_rate_limit(limiter));
+
+        let allowed = send(&addr, "GET /api/shipping/orders HTTP/1.1\r\n\r\n");
+        assert!(allowed.starts_with("HTTP/1.1 200 OK"));
+        assert!(allowed.contains("RateLimit-Limit:2\r\n"));
+        assert!(allowed.contains("RateLimit-Remaining:1\r\n"));
+        send(&addr, "GET /api/shipping/orders HTTP/1.1\r\n\r\n");
+
+        let limited = send(&addr, "GET /api/shipping/orders HTTP/1.1\r\n\r\n");
+        assert!(limited.starts_with("HTTP/1.1 429 Too Many Requests"));
+        assert!(limited.contains("RateLimit-Remaining:0\r\n"));
+        assert!(limited.contains("Retry-After:30\r\n"));
+
+        // Paths outside the limited prefix are not counted
+        let page = send(&addr, "GET / HTTP/1.1\r\n\r\n");
+        assert!(page.starts_with("HTTP/1.1 200 OK"));
+        assert!(!page.contains("RateLimit-Limit"));
+    }
+
+    #[test]
+    fn test_event_stream_receives_published_orders() {
+        let events = EventBus::new(10).with_heartbeat(Duration::from_millis(50));
+        let addr = start_server(Router::new().with_events("/events", events));
+
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        stream.write_all(b"GET /events HTTP/1.1\r\n\r\n").unwrap();
+        let read_until = |stream: &mut TcpStream, needle: &str| {
+            let mut received = String::new();
+            let mut buf = [0; 1024];
+            while !received.contains(needle) {
+                let n = stream.read(&mut buf).unwrap();
+                assert!(n > 0, "stream closed before {:?}", needle);
+                received.push_str(&String::from_utf8_lossy(&buf[..n]));
             }
+            received
+        };
+        let head = read_until(&mut stream, ": heartbeat\n\n");
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains("Content-Type: text/event-stream\r\n"));
+
+        let order = r#"{"order_id":1,"order_date":"21 Jan 2020","order_status":"Shipped"}"#;
+        let posted = send(
+            &addr,
+            &format!(
+                "POST /api/shipping/orders HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+                order.len(),
+                order
+            ),
+        );
+        assert!(posted.starts_with("HTTP/1.1 200 OK"));
+        let pushed = read_until(&mut stream, "\n\n");
+        assert!(pushed.contains(&format!("id: 1\nevent: order_status\ndata: {}\n\n", order)));
+
+        // A reconnecting client is sent what it missed
+        let mut replay = TcpStream::connect(&addr).unwrap();
+        replay
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        replay
+            .write_all(b"GET /events HTTP/1.1\r\nLast-Event-ID: 0\r\n\r\n")
+            .unwrap();
+        assert!(read_until(&mut replay, "id: 1\n").contains("Shipped"));
+
+        let bad = send(
+            &addr,
+            "POST /api/shipping/orders HTTP/1.1\r\nContent-Length: 2\r\n\r\n{}",
+        );
+        assert!(bad.starts_with("HTTP/1.1 400 Bad Request"));
+    }
+
+    #[test]
+    fn test_rewrite_rules_run_before_routing() {
+        let rewrites = Rewriter::from_json(
+            r#"[{"match": "^/orders$", "rewrite": "/api/shipping/orders"},
+                {"match": "^/home$", "redirect": "/", "status": 301}]"#,
+        )
+        .unwrap();
+        let addr = start_server(Router::new().with_rewrites(rewrites));
+
+        let rewritten = send(&addr, "GET /orders HTTP/1.1\r\n\r\n");
+        assert!(rewritten.starts_with("HTTP/1.1 200 OK"));
+        assert!(rewritten.contains("order_status"));
+
+        let redirected = send(&addr, "GET /home HTTP/1.1\r\n\r\n");
+        assert!(redirected.starts_with("HTTP/1.1 301 Moved Permanently"));
+        assert!(redirected.contains("Location:/\r\n"));
+    }
+
+    #[test]
+    fn test_protected_paths_need_credentials() {
+        let auth = Auth::new().bearer("/api", "orders", "dashboard:abc123\n");
+        let addr = start_server(Router::new().with_auth(auth));
+
+        let denied = send(&addr, "GET /api/shipping/orders HTTP/1.1\r\n\r\n");
+        assert!(denied.starts_with("HTTP/1.1 401 Unauthorized"));
+        assert!(denied.contains("WWW-Authenticate:Bearer realm=\"orders\"\r\n"));
+
+        let allowed = send(
+            &addr,
+            "GET /api/shipping/orders HTTP/1.1\r\nAuthorization: Bearer abc123\r\n\r\n",
+        );
+        assert!(allowed.starts_with("HTTP/1.1 200 OK"));
+        assert!(send(&addr, "GET / HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 200 OK"));
+    }
+
+    #[test]
+    fn test_clients_cannot_set_the_principal() {
+        let auth = Auth::new().bearer("/api", "orders", "dashboard:abc123\n");
+        let router = Router::new().with_auth(auth);
+        let mut spoofed: HttpRequest = "GET / HTTP/1.1\r\nx-authenticated-user : root\r\n\r\n"
+            .to_string()
+            .into();
+        assert!(router.screen(&mut spoofed, None).is_ok());
+        assert_eq!(spoofed.header(PRINCIPAL_HEADER), None);
+
+        let mut granted: HttpRequest = "GET /api/shipping/orders HTTP/1.1\r\nX-Authenticated-User : root\r\nAuthorization: Bearer abc123\r\n\r\n".to_string().into();
+        assert!(router.screen(&mut granted, None).is_ok());
+        assert_eq!(granted.header(PRINCIPAL_HEADER), Some("dashboard"));
+        assert_eq!(granted.headers.len(), 2);
+    }
+
+    #[test]
+    fn test_health_page_is_rendered_from_its_template() {
+        let addr = start_server(Router::new());
+        send(&addr, "GET /nope HTTP/1.1\r\n\r\n");
+
+        let health = send(&addr, "GET /health HTTP/1.1\r\n\r\n");
+        assert!(health.starts_with("HTTP/1.1 200 OK"));
+        assert!(health.contains("<title>httpserver: /health</title>"));
+        assert!(health.contains("1 requests served"));
+        assert!(health.contains("<tr><td>404</td><td>1</td></tr>"));
+        assert!(send(&addr, "GET /health.html.tmpl HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 404"));
+    }
+
+    struct Echo;
+
+    impl TemplateEngine for Echo {
+        fn render(&self, name: &str, context: &Value) -> Result<String, String> {
+            Ok(format!("{} for {}", name, context["request"]["path"]))
         }
     }
+
+    #[test]
+    fn test_template_engine_is_pluggable() {
+        let addr = start_server(Router::new().with_templates(Echo));
+        let health = send(&addr, "GET /health HTTP/1.1\r\n\r\n");
+        assert!(health.ends_with("health.html.tmpl for \"/health\""));
+    }
 }
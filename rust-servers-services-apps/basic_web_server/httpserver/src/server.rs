@@ -1,36 +1,354 @@
-use std::io::prelude::*;
-use std::net::TcpListener;
-use std::str;
+//! The server module accepts clients on one or more listeners and serves
+//! each connection on its own thread. Servers are put together with
+//! `Server::builder()`.
+//!
 
+use std::io::{self, prelude::*};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::connection::Connection;
+use super::event_loop;
+use super::h2;
+use super::handler::Handler;
+use super::limits::{self, Limits, RequestError};
+use super::listener::{self, Listener};
 use super::router::Router;
-use http::httprequest::HttpRequest;
+use super::vhost::VirtualHosts;
+use http::httpresponse::HttpResponse;
+
+pub struct Server {
+    listeners: Vec<Listener>,
+    limits: Arc<Limits>,
+    hosts: Arc<VirtualHosts>,
+    // Number of event loop reactors; None for a thread per connection
+    reactors: Option<usize>,
+}
+
+#[derive(Default)]
+pub struct ServerBuilder {
+    // Listener specs, bound by build()
+    addrs: Vec<String>,
+    listeners: Vec<Listener>,
+    limits: Limits,
+    router: Router,
+    sites: Vec<(Vec<String>, Router)>,
+    reactors: Option<usize>,
+}
+
+impl Server {
+    pub fn builder() -> ServerBuilder {
+        ServerBuilder::default()
+    }
+
+    // Serve clients until a listener fails
+    pub fn run(self) -> io::Result<()> {
+        if let Some(reactors) = self.reactors {
+            for listener in &self.listeners {
+                println!("Running on {} ({} event loop reactors)", listener, reactors);
+            }
+            return event_loop::run(&self.listeners, reactors, &self.limits, &self.hosts);
+        }
 
-pub struct Server<'a> {
-    socket_addr: &'a str,
+        // Number of connections currently being served, across all listeners
+        let active = Arc::new(AtomicUsize::new(0));
+        let (done, failed) = mpsc::channel();
+
+        for listener in self.listeners {
+            println!("Running on {}", listener);
+            let active = Arc::clone(&active);
+            let limits = Arc::clone(&self.limits);
+            let hosts = Arc::clone(&self.hosts);
+            let done = done.clone();
+            thread::spawn(move || {
+                let result = accept_loop(&listener, &active, &limits, &hosts);
+                let _ = done.send(result);
+            });
+        }
+        drop(done);
+
+        // The first listener to stop ends the server
+        failed.recv().unwrap_or(Ok(()))
+    }
 }
 
-impl<'a> Server<'a> {
-    pub fn new(socket_addr: &'a str) -> Self {
-        Server { socket_addr }
+impl ServerBuilder {
+    // Listen on a TCP address such as `127.0.0.1:3000` or `[::1]:3000`, or on
+    // a Unix domain socket given as `unix:/path/to/socket[@mode]`
+    pub fn bind(mut self, addr: &str) -> Self {
+        self.addrs.push(addr.to_string());
+        self
+    }
+
+    // Also accept clients on an open listener, e.g. one inherited from systemd
+    pub fn listener(mut self, listener: Listener) -> Self {
+        self.listeners.push(listener);
+        self
+    }
+
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    // Use a fully configured router (proxy, CORS, metrics, ...) for
+    // requests that no virtual host claims
+    pub fn router(mut self, router: Router) -> Self {
+        self.router = router;
+        self
+    }
+
+    // Serve requests for `path`, and the paths below it, with `handler`
+    pub fn route<H>(mut self, path: &str, handler: H) -> Self
+    where
+        H: Handler + Send + Sync + 'static,
+    {
+        self.router = self.router.with_route(path, handler);
+        self
+    }
+
+    // Serve static pages from `dir`
+    pub fn static_dir(mut self, dir: &str) -> Self {
+        self.router = self.router.with_document_root(dir);
+        self
+    }
+
+    // Serve requests whose Host header matches `names` with `router`
+    pub fn virtual_host<S: AsRef<str>>(mut self, names: &[S], router: Router) -> Self {
+        let names = names.iter().map(|n| n.as_ref().to_string()).collect();
+        self.sites.push((names, router));
+        self
+    }
+
+    // Serve clients from `reactors` event loop threads, with keep-alive,
+    // instead of a thread per connection
+    pub fn event_loop(mut self, reactors: usize) -> Self {
+        self.reactors = Some(reactors.max(1));
+        self
     }
 
-    pub fn run(&self) {
-        // Start a server listening on socket address
-        let connection_listener = TcpListener::bind(self.socket_addr).unwrap();
-        println!("Running on {}", self.socket_addr);
+    // Bind the listeners
+    pub fn build(self) -> io::Result<Server> {
+        let mut listeners = self.listeners;
+        for addr in &self.addrs {
+            let listener = Listener::bind(addr)
+                .map_err(|e| io::Error::new(e.kind(), format!("cannot bind {}: {}", addr, e)))?;
+            listeners.push(listener);
+        }
+        if listeners.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no address to listen on",
+            ));
+        }
 
-        // Listen to incoming connections in a loop
-        for stream in connection_listener.incoming() {
-            let mut stream = stream.unwrap();
-            println!("Connection established");
+        let mut hosts = VirtualHosts::new(self.router);
+        for (names, router) in self.sites {
+            hosts = hosts.host(&names, router);
+        }
+        Ok(Server {
+            listeners,
+            limits: Arc::new(self.limits),
+            hosts: Arc::new(hosts),
+            reactors: self.reactors,
+        })
+    }
+}
 
-            let mut read_buffer = [0; 90];
-            stream.read(&mut read_buffer).unwrap();
+// How long to stop accepting when out of file descriptors
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(250);
 
-            // Convert HTTP request to Rust data structure
-            let req: HttpRequest = String::from_utf8(read_buffer.to_vec()).unwrap().into();
-            // Route request to appropriate handler
-            Router::route(req, &mut stream);
+// Listen to incoming connections in a loop, until accepting fails for a
+// reason other than a client giving up early or a shortage of resources
+fn accept_loop(
+    listener: &Listener,
+    active: &Arc<AtomicUsize>,
+    limits: &Arc<Limits>,
+    hosts: &Arc<VirtualHosts>,
+) -> io::Result<()> {
+    loop {
+        let mut stream = match listener.accept() {
+            Ok(stream) => stream,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::ConnectionAborted
+                        | io::ErrorKind::ConnectionReset
+                        | io::ErrorKind::Interrupted
+                ) =>
+            {
+                eprintln!("Failed to accept connection: {}", e);
+                continue;
+            }
+            Err(e) if listener::out_of_resources(&e) => {
+                eprintln!("Failed to accept connection, pausing: {}", e);
+                thread::sleep(ACCEPT_BACKOFF);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        println!("Connection established");
+
+        // Turn clients away once the connection cap is reached
+        if active.load(Ordering::SeqCst) >= limits.max_connections {
+            let _ = stream.set_write_timeout(Some(limits.write_timeout));
+            send_error(&mut stream, "503");
+            continue;
         }
+
+        let guard = ConnectionGuard::new(active);
+        let limits = Arc::clone(limits);
+        let hosts = Arc::clone(hosts);
+        // Serve each connection on its own thread
+        thread::spawn(move || {
+            let _guard = guard;
+            handle_connection(stream, &limits, &hosts);
+        });
+    }
+}
+
+fn handle_connection(mut stream: Connection, limits: &Limits, hosts: &VirtualHosts) {
+    if stream
+        .set_write_timeout(Some(limits.write_timeout))
+        .is_err()
+    {
+        return;
+    }
+
+    // Clients speaking HTTP/2 with prior knowledge open with its preface
+    if read_preface(&mut stream, limits) {
+        let written = h2::serve(&mut stream, limits, hosts);
+        hosts
+            .default_router()
+            .metrics()
+            .observe_bytes(stream.bytes_read(), stream.bytes_written() + written);
+        return;
+    }
+
+    // Convert HTTP request to Rust data structure
+    match limits::read_request_head(&mut stream, limits) {
+        // Route request to the virtual host's router, then the appropriate handler
+        Ok(pending) => hosts
+            .select(pending.req.header("Host"))
+            .route(pending, &mut stream),
+        Err(RequestError::Disconnected(e)) => println!("Connection dropped: {}", e),
+        Err(err) => {
+            hosts
+                .default_router()
+                .metrics()
+                .observe_parse_error(err.reason());
+            if let Some(status_code) = err.status_code() {
+                send_error(&mut stream, status_code);
+            }
+        }
+    }
+    hosts
+        .default_router()
+        .metrics()
+        .observe_bytes(stream.bytes_read(), stream.bytes_written());
+}
+
+// Read as much of the HTTP/2 preface as the client sends, stopping at the
+// first byte that does not match. Anything else is put back to be read as
+// an HTTP/1.1 request. The whole preface must arrive within the read timeout.
+fn read_preface(stream: &mut Connection, limits: &Limits) -> bool {
+    let deadline = Instant::now() + limits.read_timeout;
+    let mut buffer = Vec::new();
+    let mut chunk = [0; 24];
+    while buffer.len() < h2::PREFACE.len() && h2::PREFACE.starts_with(&buffer) {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() || stream.set_read_timeout(Some(remaining)).is_err() {
+            break;
+        }
+        match stream.read(&mut chunk[..h2::PREFACE.len() - buffer.len()]) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+        }
+    }
+    if buffer == h2::PREFACE {
+        return true;
+    }
+    stream.unread(&buffer);
+    false
+}
+
+// Answer with a bare error page for the given status code
+pub fn send_error(stream: &mut Connection, status_code: &str) {
+    let mut resp = HttpResponse::new(status_code, None, None);
+    let body = format!("<h1>{} {}</h1>", status_code, resp.status_text());
+    resp.set_body(body);
+    let _ = resp.send_response(stream);
+}
+
+// Keeps the count of active connections, releasing the slot when dropped
+// (even if the connection thread panics)
+struct ConnectionGuard(Arc<AtomicUsize>);
+
+impl ConnectionGuard {
+    fn new(active: &Arc<AtomicUsize>) -> Self {
+        active.fetch_add(1, Ordering::SeqCst);
+        ConnectionGuard(Arc::clone(active))
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{free_addr, send, wait_for};
+    use http::httprequest::HttpRequest;
+    use std::fs;
+
+    struct Hello;
+
+    impl Handler for Hello {
+        fn handle<'a>(&self, req: &'a HttpRequest) -> HttpResponse<'a> {
+            let http::httprequest::Resource::Path(path) = &req.resource;
+            HttpResponse::new("200", None, Some(format!("Hello from {}", path)))
+        }
+    }
+
+    #[test]
+    fn test_builder_with_routes_and_static_dir() {
+        let dir = std::env::temp_dir().join(format!("builder-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("index.html"), "<h1>Embedded</h1>").unwrap();
+
+        let addr = free_addr();
+        let server = Server::builder()
+            .bind(&addr)
+            .route("/hello", Hello)
+            .static_dir(dir.to_str().unwrap())
+            .build()
+            .unwrap();
+        thread::spawn(move || server.run());
+        wait_for(&addr);
+
+        let hello = send(&addr, "GET /hello/world HTTP/1.1\r\n\r\n");
+        assert!(hello.starts_with("HTTP/1.1 200 OK"));
+        assert!(hello.ends_with("Hello from /hello/world"));
+        assert!(send(&addr, "GET /helloworld HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 404"));
+        assert!(send(&addr, "GET / HTTP/1.1\r\n\r\n").contains("Embedded"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_build_errors_instead_of_panicking() {
+        let err = Server::builder().build().err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = taken.local_addr().unwrap().to_string();
+        let err = Server::builder().bind(&addr).build().err().unwrap();
+        assert!(err.to_string().contains(&addr));
     }
 }
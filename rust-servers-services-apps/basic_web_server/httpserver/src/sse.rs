@@ -0,0 +1,201 @@
+//! The sse module implements Server-Sent Events: handlers publish events
+//! to an event bus, and each `text/event-stream` client is sent them as
+//! they happen, over a connection that stays open.
+//!
+
+use std::collections::VecDeque;
+use std::io::{self, prelude::*};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::connection::Connection;
+use super::cors::CorsHeaders;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    pub id: u64,
+    pub event: String,
+    pub data: String,
+}
+
+impl Event {
+    // Format the event as an event-stream message. Multi-line data is
+    // sent as one `data:` field per line.
+    pub fn to_message(&self) -> String {
+        let mut message = format!("id: {}\n", self.id);
+        if !self.event.is_empty() {
+            message.push_str(&format!("event: {}\n", self.event));
+        }
+        for line in self.data.lines() {
+            message.push_str(&format!("data: {}\n", line));
+        }
+        if self.data.is_empty() {
+            message.push_str("data:\n");
+        }
+        message.push('\n');
+        message
+    }
+}
+
+// Publishes events to every subscriber. Cloning it gives another handle
+// to the same bus.
+#[derive(Clone)]
+pub struct EventBus {
+    inner: Arc<Mutex<Inner>>,
+    heartbeat: Duration,
+}
+
+struct Inner {
+    next_id: u64,
+    // The most recent events, replayed to clients that reconnect
+    buffer: VecDeque<Event>,
+    capacity: usize,
+    subscribers: Vec<Sender<Event>>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        EventBus::new(100)
+    }
+}
+
+impl EventBus {
+    // A bus that keeps the last `capacity` events for replay
+    pub fn new(capacity: usize) -> Self {
+        EventBus {
+            inner: Arc::new(Mutex::new(Inner {
+                next_id: 1,
+                buffer: VecDeque::with_capacity(capacity),
+                capacity,
+                subscribers: Vec::new(),
+            })),
+            heartbeat: Duration::from_secs(15),
+        }
+    }
+
+    // How long a stream may be idle before a heartbeat comment is sent,
+    // which keeps proxies from timing out the connection
+    pub fn with_heartbeat(mut self, heartbeat: Duration) -> Self {
+        self.heartbeat = heartbeat;
+        self
+    }
+
+    // Send an event to all subscribers, returning its id
+    pub fn publish(&self, event: &str, data: &str) -> u64 {
+        let mut inner = self.inner.lock().unwrap();
+        let event = Event {
+            id: inner.next_id,
+            event: event.to_string(),
+            data: data.to_string(),
+        };
+        inner.next_id += 1;
+
+        if inner.capacity > 0 {
+            if inner.buffer.len() == inner.capacity {
+                inner.buffer.pop_front();
+            }
+            inner.buffer.push_back(event.clone());
+        }
+        // Subscribers whose client has gone away are dropped here
+        inner
+            .subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+        event.id
+    }
+
+    // Subscribe to new events, also returning the buffered events after
+    // `last_event_id` so a reconnecting client misses nothing
+    pub fn subscribe(&self, last_event_id: Option<u64>) -> (Vec<Event>, Receiver<Event>) {
+        let mut inner = self.inner.lock().unwrap();
+        let replay = match last_event_id {
+            Some(last) => inner
+                .buffer
+                .iter()
+                .filter(|event| event.id > last)
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+        let (sender, receiver) = mpsc::channel();
+        inner.subscribers.push(sender);
+        (replay, receiver)
+    }
+
+    // Stream events to the client until it disconnects
+    pub fn serve(
+        &self,
+        last_event_id: Option<&str>,
+        stream: &mut Connection,
+        extra_headers: &CorsHeaders,
+    ) -> io::Result<()> {
+        let last_event_id = last_event_id.and_then(|id| id.trim().parse().ok());
+        let (replay, events) = self.subscribe(last_event_id);
+
+        let mut head = "HTTP/1.1 200 OK\r\n\
+            Content-Type: text/event-stream\r\n\
+            Cache-Control: no-cache\r\n\
+            Connection: keep-alive\r\n"
+            .to_string();
+        for (key, value) in extra_headers {
+            head.push_str(&format!("{}: {}\r\n", key, value));
+        }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes())?;
+
+        for event in replay {
+            stream.write_all(event.to_message().as_bytes())?;
+        }
+        stream.flush()?;
+
+        loop {
+            match events.recv_timeout(self.heartbeat) {
+                Ok(event) => stream.write_all(event.to_message().as_bytes())?,
+                Err(RecvTimeoutError::Timeout) => stream.write_all(b": heartbeat\n\n")?,
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+            stream.flush()?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_message_format() {
+        let event = Event {
+            id: 7,
+            event: "order_status".to_string(),
+            data: "line one\nline two".to_string(),
+        };
+        assert_eq!(
+            event.to_message(),
+            "id: 7\nevent: order_status\ndata: line one\ndata: line two\n\n"
+        );
+    }
+
+    #[test]
+    fn test_publish_and_replay() {
+        let bus = EventBus::new(2);
+        let (_, live) = bus.subscribe(None);
+        assert_eq!(bus.publish("order_status", "a"), 1);
+        bus.publish("order_status", "b");
+        bus.publish("order_status", "c");
+        assert_eq!(live.try_iter().count(), 3);
+
+        // Only the last two events are kept for replay
+        let (replay, _) = bus.subscribe(Some(0));
+        let ids: Vec<u64> = replay.iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![2, 3]);
+        let (replay, _) = bus.subscribe(Some(2));
+        assert_eq!(replay.len(), 1);
+        assert_eq!(replay[0].data, "c");
+
+        // Subscribers that went away are dropped on the next publish
+        drop(live);
+        bus.publish("order_status", "d");
+        assert!(bus.inner.lock().unwrap().subscribers.is_empty());
+    }
+}
@@ -0,0 +1,469 @@
+//! The template module renders pages such as `health.html.tmpl` with a
+//! context supplied by the handler. Templates support:
+//!
+//! - `{{ user.name }}`, HTML-escaped, or `{{ snippet | raw }}` as is
+//! - `{% if ready %}`, `{% if not ready %}`, `{% elif other %}`, `{% else %}`, `{% endif %}`
+//! - `{% for item in items %}` ... `{% endfor %}`, with `loop.index`,
+//!   `loop.first` and `loop.last` inside the loop
+//! - `{% include "partials/head.html.tmpl" %}`, sharing the context
+//! - `{# comments #}`
+//!
+//! Other engines can be plugged in through the `TemplateEngine` trait.
+//!
+
+use serde_json::Value;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+// File name suffix of templates in the document root
+pub const TEMPLATE_SUFFIX: &str = ".tmpl";
+
+// How deeply includes may nest, so that a template including itself fails
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+pub trait TemplateEngine: Send + Sync {
+    // Render the template `name` with `context`
+    fn render(&self, name: &str, context: &Value) -> Result<String, String>;
+}
+
+// The built-in engine, loading templates from a directory
+pub struct Templates {
+    dir: PathBuf,
+}
+
+#[derive(Debug, PartialEq)]
+enum Node {
+    Text(String),
+    Var {
+        path: Vec<String>,
+        raw: bool,
+    },
+    If {
+        negate: bool,
+        path: Vec<String>,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    For {
+        name: String,
+        path: Vec<String>,
+        body: Vec<Node>,
+    },
+    Include(String),
+}
+
+enum Token<'a> {
+    Text(&'a str),
+    Var(&'a str),
+    Tag(&'a str),
+    Comment,
+}
+
+struct Parser<'a> {
+    name: &'a str,
+    source: &'a str,
+    pos: usize,
+}
+
+// Variables visible while rendering: loop variables over the context
+struct Scope<'a> {
+    context: &'a Value,
+    locals: Vec<(String, Value)>,
+}
+
+impl Templates {
+    pub fn new(dir: &str) -> Self {
+        Templates {
+            dir: PathBuf::from(dir),
+        }
+    }
+
+    fn load(&self, name: &str) -> Result<Vec<Node>, String> {
+        // Templates cannot reach outside their directory
+        let relative = Path::new(name);
+        if relative
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_)))
+        {
+            return Err(format!("{}: invalid template name", name));
+        }
+        let source =
+            fs::read_to_string(self.dir.join(relative)).map_err(|e| format!("{}: {}", name, e))?;
+        parse(name, &source)
+    }
+
+    fn render_nodes(
+        &self,
+        nodes: &[Node],
+        scope: &mut Scope,
+        depth: usize,
+        out: &mut String,
+    ) -> Result<(), String> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => out.push_str(text),
+                Node::Var { path, raw } => {
+                    let text = scope.lookup(path).map(to_text).unwrap_or_default();
+                    if *raw {
+                        out.push_str(&text);
+                    } else {
+                        out.push_str(&escape(&text));
+                    }
+                }
+                Node::If {
+                    negate,
+                    path,
+                    then,
+                    otherwise,
+                } => {
+                    let truthy = scope.lookup(path).is_some_and(is_truthy);
+                    let branch = if truthy != *negate { then } else { otherwise };
+                    self.render_nodes(branch, scope, depth, out)?;
+                }
+                Node::For { name, path, body } => {
+                    let items: Vec<Value> = match scope.lookup(path) {
+                        Some(Value::Array(items)) => items.clone(),
+                        // Objects are walked as key/value pairs
+                        Some(Value::Object(map)) => map
+                            .iter()
+                            .map(|(k, v)| serde_json::json!({ "key": k, "value": v }))
+                            .collect(),
+                        _ => Vec::new(),
+                    };
+                    let count = items.len();
+                    for (i, item) in items.into_iter().enumerate() {
+                        let info = serde_json::json!({
+                            "index": i + 1,
+                            "first": i == 0,
+                            "last": i + 1 == count,
+                        });
+                        scope.locals.push(("loop".to_string(), info));
+                        scope.locals.push((name.clone(), item));
+                        let result = self.render_nodes(body, scope, depth, out);
+                        scope.locals.truncate(scope.locals.len() - 2);
+                        result?;
+                    }
+                }
+                Node::Include(name) => {
+                    if depth >= MAX_INCLUDE_DEPTH {
+                        return Err(format!("{}: includes nest too deeply", name));
+                    }
+                    let nodes = self.load(name)?;
+                    self.render_nodes(&nodes, scope, depth + 1, out)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TemplateEngine for Templates {
+    fn render(&self, name: &str, context: &Value) -> Result<String, String> {
+        let nodes = self.load(name)?;
+        let mut scope = Scope {
+            context,
+            locals: Vec::new(),
+        };
+        let mut out = String::new();
+        self.render_nodes(&nodes, &mut scope, 0, &mut out)?;
+        Ok(out)
+    }
+}
+
+impl Scope<'_> {
+    // Resolve a dotted path; list items are addressed by number (`items.0`)
+    fn lookup(&self, path: &[String]) -> Option<&Value> {
+        let (first, rest) = path.split_first()?;
+        let mut value = match self.locals.iter().rev().find(|(name, _)| name == first) {
+            Some((_, value)) => value,
+            None => self.context.get(first)?,
+        };
+        for key in rest {
+            value = match value {
+                Value::Array(items) => items.get(key.parse::<usize>().ok()?)?,
+                _ => value.get(key)?,
+            };
+        }
+        Some(value)
+    }
+}
+
+fn parse(name: &str, source: &str) -> Result<Vec<Node>, String> {
+    let mut parser = Parser {
+        name,
+        source,
+        pos: 0,
+    };
+    parser.nodes(&[]).map(|(nodes, _)| nodes)
+}
+
+impl<'a> Parser<'a> {
+    fn next_token(&mut self) -> Result<Option<Token<'a>>, String> {
+        let rest = &self.source[self.pos..];
+        if rest.is_empty() {
+            return Ok(None);
+        }
+        let open = ["{{", "{%", "{#"]
+            .iter()
+            .filter_map(|delim| rest.find(delim))
+            .min();
+        match open {
+            Some(0) => {
+                let close = match &rest[..2] {
+                    "{{" => "}}",
+                    "{%" => "%}",
+                    _ => "#}",
+                };
+                let end = rest
+                    .find(close)
+                    .ok_or_else(|| self.error(&format!("missing {}", close)))?;
+                let inner = rest[2..end].trim();
+                let token = match &rest[..2] {
+                    "{{" => Token::Var(inner),
+                    "{%" => Token::Tag(inner),
+                    _ => Token::Comment,
+                };
+                self.pos += end + 2;
+                Ok(Some(token))
+            }
+            Some(start) => {
+                self.pos += start;
+                Ok(Some(Token::Text(&rest[..start])))
+            }
+            None => {
+                self.pos = self.source.len();
+                Ok(Some(Token::Text(rest)))
+            }
+        }
+    }
+
+    // Parse nodes until one of the `ends` tags, returning the tag found
+    fn nodes(&mut self, ends: &[&str]) -> Result<(Vec<Node>, Option<&'a str>), String> {
+        let mut nodes = Vec::new();
+        while let Some(token) = self.next_token()? {
+            match token {
+                Token::Comment => {}
+                Token::Text(text) => nodes.push(Node::Text(text.to_string())),
+                Token::Var(expr) => {
+                    let (expr, raw) = match expr.split_once('|') {
+                        Some((expr, filter)) if filter.trim() == "raw" => (expr.trim(), true),
+                        Some((_, filter)) => {
+                            return Err(self.error(&format!("unknown filter {}", filter.trim())))
+                        }
+                        None => (expr, false),
+                    };
+                    nodes.push(Node::Var {
+                        path: self.path(expr)?,
+                        raw,
+                    });
+                }
+                Token::Tag(tag) => {
+                    let keyword = tag.split_whitespace().next().unwrap_or_default();
+                    if ends.contains(&keyword) {
+                        return Ok((nodes, Some(tag)));
+                    }
+                    let args = tag[keyword.len()..].trim();
+                    nodes.push(match keyword {
+                        "if" => self.if_node(args)?,
+                        "for" => self.for_node(args)?,
+                        "include" => Node::Include(self.quoted(args)?),
+                        _ => return Err(self.error(&format!("unexpected {{% {} %}}", tag))),
+                    });
+                }
+            }
+        }
+        match ends.first() {
+            Some(end) => Err(self.error(&format!("missing {{% {} %}}", end))),
+            None => Ok((nodes, None)),
+        }
+    }
+
+    fn if_node(&mut self, condition: &str) -> Result<Node, String> {
+        let (negate, expr) = match condition.strip_prefix("not ") {
+            Some(expr) => (true, expr.trim()),
+            None => (false, condition),
+        };
+        let path = self.path(expr)?;
+        let (then, end) = self.nodes(&["endif", "else", "elif"])?;
+        let end = end.unwrap_or_default();
+        let otherwise = if end == "else" {
+            self.nodes(&["endif"])?.0
+        } else if let Some(condition) = end.strip_prefix("elif") {
+            // The elif's own if node consumes the endif
+            vec![self.if_node(condition.trim())?]
+        } else {
+            Vec::new()
+        };
+        Ok(Node::If {
+            negate,
+            path,
+            then,
+            otherwise,
+        })
+    }
+
+    fn for_node(&mut self, args: &str) -> Result<Node, String> {
+        let parts: Vec<&str> = args.split_whitespace().collect();
+        let [name, "in", expr] = parts[..] else {
+            return Err(self.error("expected {% for item in list %}"));
+        };
+        let path = self.path(expr)?;
+        let (body, _) = self.nodes(&["endfor"])?;
+        Ok(Node::For {
+            name: name.to_string(),
+            path,
+            body,
+        })
+    }
+
+    fn path(&self, expr: &str) -> Result<Vec<String>, String> {
+        let valid = |part: &str| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        };
+        if !expr.split('.').all(valid) {
+            return Err(self.error(&format!("invalid name {:?}", expr)));
+        }
+        Ok(expr.split('.').map(str::to_string).collect())
+    }
+
+    fn quoted(&self, args: &str) -> Result<String, String> {
+        args.strip_prefix('"')
+            .and_then(|rest| rest.strip_suffix('"'))
+            .map(str::to_string)
+            .ok_or_else(|| self.error("expected a quoted template name"))
+    }
+
+    fn error(&self, message: &str) -> String {
+        let line = self.source[..self.pos].matches('\n').count() + 1;
+        format!("{}:{}: {}", self.name, line, message)
+    }
+}
+
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+    }
+}
+
+fn to_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn templates(files: &[(&str, &str)]) -> (Templates, PathBuf) {
+        let dir = std::env::temp_dir().join(format!(
+            "templates-{}-{}",
+            std::process::id(),
+            files[0].0.replace('/', "-")
+        ));
+        for (name, source) in files {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, source).unwrap();
+        }
+        (Templates::new(dir.to_str().unwrap()), dir)
+    }
+
+    #[test]
+    fn test_variables_conditionals_and_loops() {
+        let (engine, dir) = templates(&[(
+            "page.html.tmpl",
+            "{# greeting #}<h1>{{ user.name }}</h1>{{ note | raw }}\n\
+             {% if user.admin %}admin{% elif user.name %}user{% else %}guest{% endif %}\n\
+             {% if not items %}none{% endif %}\
+             {% for item in items %}{{ loop.index }}={{ item }}{% if not loop.last %},{% endif %}{% endfor %}\n\
+             {% for entry in counts %}{{ entry.key }}:{{ entry.value }} {% endfor %}{{ missing.value }}",
+        )]);
+        let context = json!({
+            "user": {"name": "<Ann & \"Bo\">", "admin": false},
+            "note": "<b>hi</b>",
+            "items": ["a", 2, true],
+            "counts": {"200": 3, "404": 1},
+        });
+        assert_eq!(
+            engine.render("page.html.tmpl", &context).unwrap(),
+            "<h1>&lt;Ann &amp; &quot;Bo&quot;&gt;</h1><b>hi</b>\nuser\n1=a,2=2,3=true\n200:3 404:1 "
+        );
+        let empty = engine.render("page.html.tmpl", &json!({})).unwrap();
+        assert!(empty.contains("guest\nnone\n"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_includes() {
+        let (engine, dir) = templates(&[
+            (
+                "index.html.tmpl",
+                "{% include \"partials/head.html.tmpl\" %}<p>body</p>",
+            ),
+            ("partials/head.html.tmpl", "<title>{{ title }}</title>"),
+            ("loop.html.tmpl", "{% include \"loop.html.tmpl\" %}"),
+            ("escape.html.tmpl", "{% include \"../secret\" %}"),
+        ]);
+        assert_eq!(
+            engine
+                .render("index.html.tmpl", &json!({"title": "Home"}))
+                .unwrap(),
+            "<title>Home</title><p>body</p>"
+        );
+        assert!(engine
+            .render("loop.html.tmpl", &json!({}))
+            .unwrap_err()
+            .contains("nest too deeply"));
+        assert!(engine
+            .render("escape.html.tmpl", &json!({}))
+            .unwrap_err()
+            .contains("invalid template name"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_syntax_errors_name_the_line() {
+        let errors = [
+            ("t", "a\n{% if x %}\nb", "t:3: missing {% endif %}"),
+            ("t", "{% endfor %}", "t:1: unexpected {% endfor %}"),
+            ("t", "{{ a b }}", "t:1: invalid name \"a b\""),
+            ("t", "{{ a | upper }}", "t:1: unknown filter upper"),
+            (
+                "t",
+                "{% for x %}{% endfor %}",
+                "t:1: expected {% for item in list %}",
+            ),
+            ("t", "{{ a", "t:1: missing }}"),
+        ];
+        for (name, source, expected) in errors {
+            assert_eq!(parse(name, source).unwrap_err(), expected);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! Helpers for tests that run a real server on a free local port.
+//!
+
+use std::io::prelude::*;
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use super::router::Router;
+use super::server::Server;
+
+// Start a full httpserver with the given router, returning its address
+pub fn start_server(router: Router) -> String {
+    let addr = free_addr();
+    let server = Server::builder()
+        .bind(&addr)
+        .router(router)
+        .build()
+        .unwrap();
+    thread::spawn(move || server.run());
+    wait_for(&addr);
+    addr
+}
+
+// An address nothing is listening on (yet)
+pub fn free_addr() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().to_string()
+}
+
+pub fn wait_for(addr: &str) {
+    for _ in 0..100 {
+        if TcpStream::connect(addr).is_ok() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    panic!("server on {} did not start", addr);
+}
+
+// Send a raw request and read the response until the server closes
+pub fn send(addr: &str, raw: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(raw.as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
@@ -0,0 +1,165 @@
+//! The vhost module implements name-based virtual hosting: the Host header
+//! of each request selects which site's router handles it.
+//!
+
+use std::env;
+
+use super::router::Router;
+
+pub struct VirtualHosts {
+    // Host names (lowercase, without port) and the router serving them
+    hosts: Vec<(Vec<String>, Router)>,
+    // Router for requests whose Host matches no virtual host
+    default: Router,
+}
+
+impl VirtualHosts {
+    pub fn new(default: Router) -> Self {
+        VirtualHosts {
+            hosts: Vec::new(),
+            default,
+        }
+    }
+
+    // Serve requests for `names` with `router`. A name may start with "*."
+    // to match any subdomain. All hosts report into the default router's
+    // metrics, so /metrics shows the whole server.
+    pub fn host<S: AsRef<str>>(mut self, names: &[S], router: Router) -> Self {
+        let names = names
+            .iter()
+            .map(|n| n.as_ref().trim().to_ascii_lowercase())
+            .collect();
+        let router = router.with_shared_metrics(&self.default);
+        self.hosts.push((names, router));
+        self
+    }
+
+    pub fn default_router(&self) -> &Router {
+        &self.default
+    }
+
+    // The router for a request's Host header
+    pub fn select(&self, host: Option<&str>) -> &Router {
+        let name = match host {
+            Some(host) => normalize_host(host),
+            None => return &self.default,
+        };
+        self.hosts
+            .iter()
+            .find(|(names, _)| names.iter().any(|n| host_matches(n, &name)))
+            .map(|(_, router)| router)
+            .unwrap_or(&self.default)
+    }
+}
+
+// Read sites from VIRTUAL_HOSTS, a ';' separated list of
+// `name[,name...]=document_root[|not_found_page]` entries, for example
+// `example.com,www.example.com=/srv/example|missing.html`
+pub fn sites_from_env() -> Vec<(Vec<String>, Router)> {
+    let mut sites = Vec::new();
+    let config = env::var("VIRTUAL_HOSTS").unwrap_or_default();
+    for entry in config.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        match entry.split_once('=') {
+            Some((names, site)) => {
+                let (root, not_found_page) = site.split_once('|').unwrap_or((site, "404.html"));
+                let names = names.split(',').map(|n| n.trim().to_string()).collect();
+                let router = Router::new()
+                    .with_document_root(root.trim())
+                    .with_not_found_page(not_found_page.trim());
+                sites.push((names, router));
+            }
+            None => eprintln!("Ignoring malformed virtual host: {}", entry),
+        }
+    }
+    sites
+}
+
+// Lowercase the host and strip the port and any trailing dot:
+// "Example.com.:3000" becomes "example.com", "[::1]:3000" becomes "[::1]"
+pub fn normalize_host(host: &str) -> String {
+    let host = host.trim();
+    let without_port = if host.starts_with('[') {
+        // IPv6 literal: the port, if any, follows the closing bracket
+        match host.find(']') {
+            Some(end) => &host[..=end],
+            None => host,
+        }
+    } else {
+        host.split(':').next().unwrap_or(host)
+    };
+    without_port.trim_end_matches('.').to_ascii_lowercase()
+}
+
+fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+        None => pattern == host,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::Server;
+    use crate::test_util::{free_addr, send, wait_for};
+    use std::fs;
+    use std::thread;
+
+    #[test]
+    fn test_normalize_host() {
+        assert_eq!(normalize_host("Example.COM:3000"), "example.com");
+        assert_eq!(normalize_host(" example.com. "), "example.com");
+        assert_eq!(normalize_host("[::1]:8080"), "[::1]");
+        assert_eq!(normalize_host("[::1]"), "[::1]");
+    }
+
+    #[test]
+    fn test_host_matches() {
+        assert!(host_matches("example.com", "example.com"));
+        assert!(host_matches("*.example.com", "blog.example.com"));
+        assert!(!host_matches("*.example.com", "example.com"));
+        assert!(!host_matches("*.example.com", "badexample.com"));
+    }
+
+    #[test]
+    fn test_each_host_has_its_own_site() {
+        let root = std::env::temp_dir().join(format!("vhost-test-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("index.html"), "<h1>Blog home</h1>").unwrap();
+        fs::write(root.join("missing.html"), "<h1>No such post</h1>").unwrap();
+
+        let blog = Router::new()
+            .with_document_root(root.to_str().unwrap())
+            .with_not_found_page("missing.html");
+        let addr = free_addr();
+        let server = Server::builder()
+            .bind(&addr)
+            .virtual_host(&["blog.example.com"], blog)
+            .build()
+            .unwrap();
+        thread::spawn(move || server.run());
+        wait_for(&addr);
+
+        let blog_home = send(
+            &addr,
+            "GET / HTTP/1.1\r\nHost: Blog.Example.com:3000\r\n\r\n",
+        );
+        assert!(blog_home.contains("Blog home"));
+        let blog_missing = send(
+            &addr,
+            "GET /nope HTTP/1.1\r\nHost: blog.example.com\r\n\r\n",
+        );
+        assert!(blog_missing.starts_with("HTTP/1.1 404"));
+        assert!(blog_missing.contains("No such post"));
+
+        // Unknown hosts and requests without Host fall back to the default site
+        let default_home = send(&addr, "GET / HTTP/1.1\r\nHost: other.com\r\n\r\n");
+        assert!(default_home.contains("welcome to home page"));
+        let default_missing = send(&addr, "GET /nope HTTP/1.1\r\n\r\n");
+        assert!(default_missing.contains("Sorry the requested page does not exist"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
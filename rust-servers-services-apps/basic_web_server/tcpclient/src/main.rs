@@ -1,18 +1,43 @@
 use std::{
-    io::{Read, Write},
-    net::TcpStream,
-    str,
+    env,
+    io::{self, BufRead, BufReader, Write},
+    net::{Shutdown, TcpStream},
+    process, thread,
 };
 
+// An interactive client for the tcpserver chat: lines typed on the terminal
+// are sent to the server, and everything the server sends is printed.
 fn main() {
-    let mut stream = TcpStream::connect("localhost:3000").unwrap();
+    let addr = env::args().nth(1).unwrap_or("localhost:3000".to_string());
+    let mut stream = TcpStream::connect(&addr).unwrap_or_else(|e| {
+        eprintln!("Failed to connect to {}: {}", addr, e);
+        process::exit(1);
+    });
 
-    stream.write("hello world".as_bytes()).unwrap();
-    // Read the bytes received from server.
-    let mut buffer = [0; 5];
-    stream.read(&mut buffer).unwrap();
-    println!(
-        "Got response from server:{:?}",
-        str::from_utf8(&buffer).unwrap()
-    )
+    // Print what the server sends, and leave once it hangs up
+    let incoming = BufReader::new(stream.try_clone().unwrap());
+    thread::spawn(move || {
+        for line in incoming.lines() {
+            match line {
+                Ok(line) => println!("{}", line),
+                Err(_) => break,
+            }
+        }
+        println!("Disconnected from server");
+        process::exit(0);
+    });
+
+    // Send every line typed; end of input (Ctrl-D) quits
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else { break };
+        if writeln!(stream, "{}", line).is_err() {
+            break;
+        }
+    }
+    let _ = writeln!(stream, "/quit");
+    let _ = stream.shutdown(Shutdown::Write);
+    // Wait for the server to close the connection, which exits the process
+    loop {
+        thread::park();
+    }
 }
@@ -0,0 +1,494 @@
+use crate::command::Command;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::mpsc::{Receiver, SyncSender, TrySendError};
+
+// Every client joins this room when it connects
+pub const LOBBY: &str = "#lobby";
+
+const MAX_NICK_CHARS: usize = 16;
+const MAX_ROOM_CHARS: usize = 32;
+
+pub type ClientId = u64;
+
+// What the client threads tell the broker
+pub enum Event {
+    // A client connected; lines for it are sent to `outbox`, which is
+    // bounded: a client that falls that far behind is disconnected
+    Connected {
+        id: ClientId,
+        outbox: SyncSender<String>,
+    },
+    Line {
+        id: ClientId,
+        line: String,
+    },
+    // A line could not be read; the client is told why
+    Refused {
+        id: ClientId,
+        reason: String,
+    },
+    Disconnected {
+        id: ClientId,
+    },
+}
+
+struct Client {
+    nick: String,
+    outbox: SyncSender<String>,
+    rooms: BTreeSet<String>,
+    // The room plain text is said in, the one joined last
+    current: Option<String>,
+}
+
+// Owns all chat state: who is connected, under which nickname and in which
+// rooms. Only the broker thread touches it, so no locking is needed.
+#[derive(Default)]
+pub struct Broker {
+    clients: HashMap<ClientId, Client>,
+    rooms: BTreeMap<String, BTreeSet<ClientId>>,
+    // Clients whose outbox was full, to disconnect once the event is handled
+    lagging: BTreeSet<ClientId>,
+}
+
+impl Broker {
+    // Handle events until every sender is gone
+    pub fn run(events: Receiver<Event>) {
+        let mut broker = Broker::default();
+        for event in events {
+            broker.handle(event);
+        }
+    }
+
+    pub fn handle(&mut self, event: Event) {
+        match event {
+            Event::Connected { id, outbox } => self.connect(id, outbox),
+            Event::Line { id, line } => {
+                if self.clients.contains_key(&id) {
+                    match Command::try_from(line.as_str()) {
+                        Ok(command) => self.command(id, command),
+                        Err(e) => self.send(id, &format!("! {}", e)),
+                    }
+                }
+            }
+            Event::Refused { id, reason } => self.send(id, &format!("! {}", reason)),
+            Event::Disconnected { id } => self.disconnect(id),
+        }
+        // Telling others about a disconnect can fill more outboxes
+        while let Some(id) = self.lagging.pop_first() {
+            self.disconnect(id);
+        }
+    }
+
+    fn connect(&mut self, id: ClientId, outbox: SyncSender<String>) {
+        let mut nick = format!("guest{}", id);
+        while self.nick_taken(&nick) {
+            nick.push('_');
+        }
+        self.clients.insert(
+            id,
+            Client {
+                nick: nick.clone(),
+                outbox,
+                rooms: BTreeSet::new(),
+                current: None,
+            },
+        );
+        self.send(
+            id,
+            &format!(
+                "* Welcome, {}! Pick a nickname with /nick NAME, /help lists the commands",
+                nick
+            ),
+        );
+        self.join(id, LOBBY.to_string());
+    }
+
+    fn command(&mut self, id: ClientId, command: Command) {
+        match command {
+            Command::Nick(nick) => self.nick(id, nick),
+            Command::Join(room) => self.join(id, room),
+            Command::Part(room) => self.part(id, room),
+            Command::Msg(nick, text) => self.msg(id, &nick, &text),
+            Command::Who(room) => self.who(id, room),
+            Command::Help => {
+                for line in [
+                    "* /nick NAME       change your nickname",
+                    "* /join ROOM       join a room and talk in it",
+                    "* /part [ROOM]     leave a room, the current one by default",
+                    "* /msg NICK TEXT   send a private message",
+                    "* /who [ROOM]      list the people in a room",
+                    "* /quit            disconnect",
+                    "* Anything else is said in the current room",
+                ] {
+                    self.send(id, line);
+                }
+            }
+            Command::Quit => {
+                self.send(id, "* Bye");
+                self.disconnect(id);
+            }
+            Command::Say(text) => self.say(id, &text),
+        }
+    }
+
+    fn nick(&mut self, id: ClientId, nick: String) {
+        let valid = nick.chars().count() <= MAX_NICK_CHARS
+            && nick
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            let msg = format!(
+                "! Nicknames are up to {} letters, digits, '-' or '_'",
+                MAX_NICK_CHARS
+            );
+            return self.send(id, &msg);
+        }
+        if self.nick_of(id) == Some(nick.as_str()) {
+            return;
+        }
+        if self.nick_taken(&nick) && !self.nick_of(id).unwrap_or("").eq_ignore_ascii_case(&nick) {
+            return self.send(id, &format!("! {} is already taken", nick));
+        }
+
+        let Some(client) = self.clients.get_mut(&id) else {
+            return;
+        };
+        let old = std::mem::replace(&mut client.nick, nick.clone());
+        let msg = format!("* {} is now known as {}", old, nick);
+        for other in self.neighbours(id) {
+            self.send(other, &msg);
+        }
+    }
+
+    fn join(&mut self, id: ClientId, room: String) {
+        let name = &room[1..];
+        if name.is_empty()
+            || name.chars().count() > MAX_ROOM_CHARS
+            || !name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        {
+            let msg = format!(
+                "! Room names are up to {} letters, digits, '-' or '_'",
+                MAX_ROOM_CHARS
+            );
+            return self.send(id, &msg);
+        }
+        let Some(client) = self.clients.get_mut(&id) else {
+            return;
+        };
+        client.current = Some(room.clone());
+        if !client.rooms.insert(room.clone()) {
+            return self.send(id, &format!("* Now talking in {}", room));
+        }
+
+        let msg = format!("* {} joined {}", client.nick, room);
+        self.rooms.entry(room.clone()).or_default().insert(id);
+        self.broadcast(&room, &msg);
+        self.who(id, Some(room));
+    }
+
+    fn part(&mut self, id: ClientId, room: Option<String>) {
+        let Some(client) = self.clients.get(&id) else {
+            return;
+        };
+        let Some(room) = room.or(client.current.clone()) else {
+            return self.send(id, "! You are not in a room");
+        };
+        if !client.rooms.contains(&room) {
+            return self.send(id, &format!("! You are not in {}", room));
+        }
+
+        let msg = format!("* {} left {}", client.nick, room);
+        self.broadcast(&room, &msg);
+        self.leave(id, &room);
+
+        let Some(client) = self.clients.get_mut(&id) else {
+            return;
+        };
+        if client.current.as_ref() == Some(&room) {
+            client.current = client.rooms.iter().next_back().cloned();
+            if let Some(current) = client.current.clone() {
+                self.send(id, &format!("* Now talking in {}", current));
+            }
+        }
+    }
+
+    fn msg(&mut self, id: ClientId, nick: &str, text: &str) {
+        let target = self
+            .clients
+            .iter()
+            .find(|(_, client)| client.nick.eq_ignore_ascii_case(nick))
+            .map(|(target, client)| (*target, client.nick.clone()));
+        let Some((target, to)) = target else {
+            return self.send(id, &format!("! No such nickname: {}", nick));
+        };
+        let from = self.nick_of(id).unwrap_or_default().to_string();
+        self.send(target, &format!("[pm from {}] {}", from, text));
+        if target != id {
+            self.send(id, &format!("[pm to {}] {}", to, text));
+        }
+    }
+
+    fn who(&mut self, id: ClientId, room: Option<String>) {
+        let room = room.or_else(|| self.clients.get(&id)?.current.clone());
+        let (title, members): (String, Vec<ClientId>) = match room {
+            Some(room) => match self.rooms.get(&room) {
+                Some(members) => (format!("In {}", room), members.iter().copied().collect()),
+                None => return self.send(id, &format!("! Nobody is in {}", room)),
+            },
+            None => ("Online".to_string(), self.clients.keys().copied().collect()),
+        };
+        let mut nicks: Vec<&str> = members.iter().filter_map(|m| self.nick_of(*m)).collect();
+        nicks.sort_unstable();
+        let msg = format!("* {}: {}", title, nicks.join(", "));
+        self.send(id, &msg);
+    }
+
+    fn say(&mut self, id: ClientId, text: &str) {
+        if text.trim().is_empty() {
+            return;
+        }
+        let Some(client) = self.clients.get(&id) else {
+            return;
+        };
+        match client.current.clone() {
+            Some(room) => {
+                let msg = format!("[{}] {}: {}", room, client.nick, text);
+                self.broadcast(&room, &msg);
+            }
+            None => self.send(id, "! You are not in a room, /join one first"),
+        }
+    }
+
+    // Forget a client, telling everyone who shared a room with it. Dropping
+    // its outbox ends the client's writer thread, which closes the socket.
+    fn disconnect(&mut self, id: ClientId) {
+        let Some(nick) = self.nick_of(id).map(str::to_string) else {
+            return;
+        };
+        let neighbours = self.neighbours(id);
+        let rooms = self.clients[&id].rooms.clone();
+        for room in rooms {
+            self.leave(id, &room);
+        }
+        self.clients.remove(&id);
+        let msg = format!("* {} has quit", nick);
+        for other in neighbours.into_iter().filter(|other| *other != id) {
+            self.send(other, &msg);
+        }
+    }
+
+    fn leave(&mut self, id: ClientId, room: &str) {
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.rooms.remove(room);
+        }
+        if let Some(members) = self.rooms.get_mut(room) {
+            members.remove(&id);
+            if members.is_empty() {
+                self.rooms.remove(room);
+            }
+        }
+    }
+
+    // The client and everyone sharing a room with it
+    fn neighbours(&self, id: ClientId) -> BTreeSet<ClientId> {
+        let mut ids = BTreeSet::from([id]);
+        if let Some(client) = self.clients.get(&id) {
+            for room in &client.rooms {
+                ids.extend(self.rooms.get(room).into_iter().flatten());
+            }
+        }
+        ids
+    }
+
+    fn nick_of(&self, id: ClientId) -> Option<&str> {
+        self.clients.get(&id).map(|client| client.nick.as_str())
+    }
+
+    fn nick_taken(&self, nick: &str) -> bool {
+        self.clients
+            .values()
+            .any(|client| client.nick.eq_ignore_ascii_case(nick))
+    }
+
+    fn broadcast(&mut self, room: &str, msg: &str) {
+        let members: Vec<ClientId> = self
+            .rooms
+            .get(room)
+            .into_iter()
+            .flatten()
+            .copied()
+            .collect();
+        for id in members {
+            self.send(id, msg);
+        }
+    }
+
+    // Never wait for a client: one that has not read its backlog is marked
+    // for disconnecting. A client whose writer has gone away is about to
+    // disconnect anyway.
+    fn send(&mut self, id: ClientId, msg: &str) {
+        if let Some(client) = self.clients.get(&id) {
+            if let Err(TrySendError::Full(_)) = client.outbox.try_send(msg.to_string()) {
+                self.lagging.insert(id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::{self, Receiver};
+
+    fn connect(broker: &mut Broker, id: ClientId) -> Receiver<String> {
+        let (outbox, inbox) = mpsc::sync_channel(64);
+        broker.handle(Event::Connected { id, outbox });
+        inbox
+    }
+
+    fn say(broker: &mut Broker, id: ClientId, line: &str) {
+        broker.handle(Event::Line {
+            id,
+            line: line.to_string(),
+        });
+    }
+
+    fn received(inbox: &Receiver<String>) -> Vec<String> {
+        inbox.try_iter().collect()
+    }
+
+    #[test]
+    fn test_rooms_and_private_messages() {
+        let mut broker = Broker::default();
+        let ana = connect(&mut broker, 1);
+        let bo = connect(&mut broker, 2);
+        say(&mut broker, 1, "/nick ana");
+        say(&mut broker, 2, "/nick bo");
+        assert!(received(&ana).contains(&"* guest2 joined #lobby".to_string()));
+        assert_eq!(
+            received(&bo),
+            vec![
+                "* Welcome, guest2! Pick a nickname with /nick NAME, /help lists the commands",
+                "* guest2 joined #lobby",
+                "* In #lobby: guest1, guest2",
+                "* guest1 is now known as ana",
+                "* guest2 is now known as bo",
+            ]
+        );
+
+        say(&mut broker, 1, "hello");
+        assert_eq!(received(&bo), vec!["[#lobby] ana: hello"]);
+
+        // Plain text goes to the room joined last
+        say(&mut broker, 1, "/join rust");
+        say(&mut broker, 1, "anyone?");
+        assert_eq!(
+            received(&ana),
+            vec![
+                "[#lobby] ana: hello",
+                "* ana joined #rust",
+                "* In #rust: ana",
+                "[#rust] ana: anyone?",
+            ]
+        );
+        assert!(received(&bo).is_empty());
+
+        say(&mut broker, 2, "/who #rust");
+        assert_eq!(received(&bo), vec!["* In #rust: ana"]);
+        say(&mut broker, 2, "/msg ANA psst");
+        assert_eq!(received(&ana), vec!["[pm from bo] psst"]);
+        assert_eq!(received(&bo), vec!["[pm to ana] psst"]);
+
+        say(&mut broker, 1, "/part");
+        assert_eq!(
+            received(&ana),
+            vec!["* ana left #rust", "* Now talking in #lobby"]
+        );
+        say(&mut broker, 2, "/who #rust");
+        assert_eq!(received(&bo), vec!["! Nobody is in #rust"]);
+    }
+
+    #[test]
+    fn test_nicknames_errors_and_disconnects() {
+        let mut broker = Broker::default();
+        let ana = connect(&mut broker, 1);
+        let bo = connect(&mut broker, 2);
+        say(&mut broker, 1, "/nick ana");
+        received(&ana);
+        received(&bo);
+
+        say(&mut broker, 2, "/nick ANA");
+        say(&mut broker, 2, "/nick two words");
+        say(&mut broker, 2, "/msg nobody hi");
+        say(&mut broker, 2, "/part #rust");
+        say(&mut broker, 2, "/dance");
+        assert_eq!(
+            received(&bo),
+            vec![
+                "! ANA is already taken",
+                "! Nicknames are up to 16 letters, digits, '-' or '_'",
+                "! No such nickname: nobody",
+                "! You are not in #rust",
+                "! Unknown command /dance, try /help",
+            ]
+        );
+
+        // Leaving the last room leaves nowhere to talk
+        say(&mut broker, 2, "/part");
+        say(&mut broker, 2, "hello?");
+        assert_eq!(
+            received(&bo),
+            vec![
+                "* guest2 left #lobby",
+                "! You are not in a room, /join one first"
+            ]
+        );
+
+        // Whoever shared a room hears about a disconnect, and the client's
+        // outbox is dropped
+        say(&mut broker, 2, "/join #lobby");
+        received(&ana);
+        broker.handle(Event::Disconnected { id: 2 });
+        assert_eq!(received(&ana), vec!["* guest2 has quit"]);
+        received(&bo);
+        assert!(matches!(
+            bo.try_recv(),
+            Err(mpsc::TryRecvError::Disconnected)
+        ));
+        say(&mut broker, 1, "/who");
+        assert_eq!(received(&ana), vec!["* In #lobby: ana"]);
+
+        // Nicknames of departed clients are free again
+        say(&mut broker, 1, "/quit");
+        assert_eq!(received(&ana), vec!["* Bye"]);
+        let cy = connect(&mut broker, 3);
+        say(&mut broker, 3, "/nick ana");
+        say(&mut broker, 3, "/who");
+        assert_eq!(received(&cy).last().unwrap(), "* In #lobby: ana");
+    }
+
+    #[test]
+    fn test_clients_that_do_not_read_are_disconnected() {
+        let mut broker = Broker::default();
+        let ana = connect(&mut broker, 1);
+        let (outbox, slow) = mpsc::sync_channel(4);
+        broker.handle(Event::Connected { id: 2, outbox });
+        received(&ana);
+
+        // The slow client never reads, so its outbox fills up and it is
+        // dropped rather than buffered for
+        for n in 0..10 {
+            say(&mut broker, 1, &format!("line {}", n));
+        }
+        assert!(received(&ana).contains(&"* guest2 has quit".to_string()));
+        assert_eq!(received(&slow).len(), 4);
+        assert!(matches!(
+            slow.try_recv(),
+            Err(mpsc::TryRecvError::Disconnected)
+        ));
+        say(&mut broker, 1, "/who");
+        assert_eq!(received(&ana), vec!["* In #lobby: guest1"]);
+    }
+}
@@ -0,0 +1,108 @@
+// A line sent by a client: either a /command or text for the current room
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Nick(String),
+    Join(String),
+    Part(Option<String>),
+    Msg(String, String),
+    Who(Option<String>),
+    Help,
+    Quit,
+    Say(String),
+}
+
+impl TryFrom<&str> for Command {
+    type Error = String;
+
+    fn try_from(line: &str) -> Result<Self, Self::Error> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        // Text that does not start with a slash is said in the current room;
+        // "//" escapes a leading slash
+        let Some(rest) = line.strip_prefix('/') else {
+            return Ok(Command::Say(line.to_string()));
+        };
+        if rest.starts_with('/') {
+            return Ok(Command::Say(rest.to_string()));
+        }
+
+        let (name, args) = rest.split_once(' ').unwrap_or((rest, ""));
+        let args = args.trim();
+        let arg = (!args.is_empty()).then(|| args.to_string());
+        match name.to_ascii_lowercase().as_str() {
+            "nick" => arg
+                .map(Command::Nick)
+                .ok_or("Usage: /nick NAME".to_string()),
+            "join" => arg
+                .map(|room| Command::Join(room_name(&room)))
+                .ok_or("Usage: /join ROOM".to_string()),
+            "part" => Ok(Command::Part(arg.map(|room| room_name(&room)))),
+            "msg" => match args.split_once(' ') {
+                Some((nick, text)) if !text.trim().is_empty() => {
+                    Ok(Command::Msg(nick.to_string(), text.trim().to_string()))
+                }
+                _ => Err("Usage: /msg NICK TEXT".to_string()),
+            },
+            "who" => Ok(Command::Who(arg.map(|room| room_name(&room)))),
+            "help" => Ok(Command::Help),
+            "quit" => Ok(Command::Quit),
+            _ => Err(format!("Unknown command /{}, try /help", name)),
+        }
+    }
+}
+
+// Room names always start with '#', so "/join rust" and "/join #rust" agree
+pub fn room_name(room: &str) -> String {
+    if room.starts_with('#') {
+        room.to_string()
+    } else {
+        format!("#{}", room)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(
+            Command::try_from("/nick ana\r\n"),
+            Ok(Command::Nick("ana".into()))
+        );
+        assert_eq!(
+            Command::try_from("/join rust"),
+            Ok(Command::Join("#rust".into()))
+        );
+        assert_eq!(
+            Command::try_from("/JOIN #rust"),
+            Ok(Command::Join("#rust".into()))
+        );
+        assert_eq!(Command::try_from("/part"), Ok(Command::Part(None)));
+        assert_eq!(
+            Command::try_from("/msg bo  hi there "),
+            Ok(Command::Msg("bo".into(), "hi there".into()))
+        );
+        assert_eq!(
+            Command::try_from("/who #rust"),
+            Ok(Command::Who(Some("#rust".into())))
+        );
+        assert_eq!(Command::try_from("/quit"), Ok(Command::Quit));
+        assert_eq!(
+            Command::try_from("hello /all"),
+            Ok(Command::Say("hello /all".into()))
+        );
+        assert_eq!(
+            Command::try_from("//shrug"),
+            Ok(Command::Say("/shrug".into()))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Command::try_from("/nick").is_err());
+        assert!(Command::try_from("/msg bo").is_err());
+        assert!(Command::try_from("/dance")
+            .unwrap_err()
+            .contains("Unknown command /dance"));
+    }
+}
@@ -1,22 +1,170 @@
+mod broker;
+mod command;
+
+use broker::{Broker, ClientId, Event};
 use std::{
-    io::{Read, Write},
-    net::TcpListener,
+    env,
+    io::{BufRead, BufReader, Read, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::mpsc::{self, Sender},
+    thread,
+    time::Duration,
 };
 
+// Longest line a client may send, in bytes
+const MAX_LINE: usize = 4096;
+// Lines waiting to be written to a client before it is disconnected
+const OUTBOX_LINES: usize = 256;
+// How long a write to a client may block
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
 fn main() {
-    // Initialize a socket server to bind to IP address 127.0.0.1 (localhost) and port 3000
-    let connection_listener = TcpListener::bind("127.0.0.1:3000").unwrap();
+    // Initialize a socket server to bind to the address given on the command
+    // line, 127.0.0.1 (localhost) port 3000 by default
+    let addr = env::args().nth(1).unwrap_or("127.0.0.1:3000".to_string());
+    let connection_listener = TcpListener::bind(&addr).unwrap();
 
-    println!("Running on port 3000");
+    println!("Running on {}", addr);
+    serve(connection_listener);
+}
+
+// Accept clients forever, serving each one from its own thread. A single
+// broker thread keeps the chat state and relays messages between them.
+fn serve(connection_listener: TcpListener) {
+    let (events, inbox) = mpsc::channel();
+    thread::spawn(move || Broker::run(inbox));
 
     // The socket server waits (listens) for incoming connections.
-    for stream in connection_listener.incoming() {
-        let mut stream = stream.unwrap();
+    for (id, stream) in (1..).zip(connection_listener.incoming()) {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("Failed to accept connection: {}", e);
+                continue;
+            }
+        };
         println!("Connection established");
+        let events = events.clone();
+        thread::spawn(move || {
+            if let Err(e) = handle_client(stream, id, &events) {
+                eprintln!("Connection {} failed: {}", id, e);
+            }
+            let _ = events.send(Event::Disconnected { id });
+        });
+    }
+}
+
+// Pass the client's lines to the broker until it hangs up. What the broker
+// sends back is written out by a second thread, so that a client which does
+// not read can never stall the broker or the other clients. Such a client
+// is disconnected once its outbox is full or a write times out.
+fn handle_client(stream: TcpStream, id: ClientId, events: &Sender<Event>) -> std::io::Result<()> {
+    let (outbox, lines) = mpsc::sync_channel::<String>(OUTBOX_LINES);
+    let mut writer = stream.try_clone()?;
+    writer.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    thread::spawn(move || {
+        for line in lines {
+            if writer.write_all(format!("{}\n", line).as_bytes()).is_err() {
+                break;
+            }
+        }
+        // The broker forgot this client, or it stopped reading: hang up,
+        // which also ends the read loop below
+        let _ = writer.shutdown(Shutdown::Both);
+    });
+    let _ = events.send(Event::Connected { id, outbox });
+
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let n = (&mut reader)
+            .take(MAX_LINE as u64)
+            .read_until(b'\n', &mut line)?;
+        if n == 0 {
+            return Ok(());
+        }
+        if !line.ends_with(b"\n") && n == MAX_LINE {
+            // Refuse the line and skip whatever remains of it
+            let _ = events.send(Event::Refused {
+                id,
+                reason: format!("Lines are limited to {} bytes", MAX_LINE),
+            });
+            while !line.ends_with(b"\n") {
+                line.clear();
+                if (&mut reader)
+                    .take(MAX_LINE as u64)
+                    .read_until(b'\n', &mut line)?
+                    == 0
+                {
+                    return Ok(());
+                }
+            }
+            continue;
+        }
+        let line = String::from_utf8_lossy(&line).into_owned();
+        if events.send(Event::Line { id, line }).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn connect(addr: &str) -> (TcpStream, BufReader<TcpStream>) {
+        let stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let reader = BufReader::new(stream.try_clone().unwrap());
+        (stream, reader)
+    }
+
+    // Read lines until one starts with `prefix`, returning it
+    fn expect(reader: &mut BufReader<TcpStream>, prefix: &str) -> String {
+        loop {
+            let mut line = String::new();
+            assert!(
+                reader.read_line(&mut line).unwrap() > 0,
+                "closed before {}",
+                prefix
+            );
+            if line.starts_with(prefix) {
+                return line.trim_end().to_string();
+            }
+        }
+    }
+
+    #[test]
+    fn test_chat_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || serve(listener));
+
+        let (mut ana, mut ana_in) = connect(&addr);
+        ana.write_all(b"/nick ana\n").unwrap();
+        expect(&mut ana_in, "* guest1 is now known as ana");
+        let (mut bo, mut bo_in) = connect(&addr);
+        bo.write_all(b"/nick bo\r\nhi ana\n").unwrap();
+        assert_eq!(expect(&mut ana_in, "["), "[#lobby] bo: hi ana");
+
+        // An overlong line is refused without dropping the connection
+        let long = vec![b'x'; MAX_LINE * 2];
+        bo.write_all(&long).unwrap();
+        bo.write_all(b"\n/msg ana still here\n").unwrap();
+        expect(&mut bo_in, "! Lines are limited to 4096 bytes");
+        assert_eq!(expect(&mut ana_in, "["), "[pm from bo] still here");
 
-        let mut buffer = [0; 1024];
-        stream.read(&mut buffer).unwrap();
-        // Echo back whatever is received to the client on the same connection.
-        stream.write(&mut buffer).unwrap();
+        // Hanging up is noticed, and /quit closes the connection
+        drop(bo);
+        drop(bo_in);
+        expect(&mut ana_in, "* bo has quit");
+        ana.write_all(b"/quit\n").unwrap();
+        expect(&mut ana_in, "* Bye");
+        let mut rest = String::new();
+        assert_eq!(ana_in.read_line(&mut rest).unwrap(), 0);
     }
 }
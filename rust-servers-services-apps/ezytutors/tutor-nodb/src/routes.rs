@@ -61,7 +61,7 @@ pub async fn get_courses_for_tutor(
         .filter(|course| course.tutor_id == tutor_id)
         .collect::<Vec<Course>>();
 
-    if filtered_courses.len() > 0 {
+    if !filtered_courses.is_empty() {
         HttpResponse::Ok().json(filtered_courses)
     } else {
         HttpResponse::Ok().json("No courses found for tutor".to_string())
@@ -16,21 +16,50 @@ pub struct Tutor {
     pub tutor_profile: String,
 }
 
+// A page of the tutor listing, as returned by tutor-web-service
+#[derive(Deserialize, Debug)]
+pub struct TutorPage {
+    pub items: Vec<Tutor>,
+    pub paging: Paging,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Paging {
+    // Where the next page starts; absent on the last page
+    pub next_cursor: Option<String>,
+}
+
 async fn handle_get_tutors(tmpl: web::Data<tera::Tera>) -> Result<HttpResponse, Error> {
     let client = Client::new(); // Client::default();
 
-    // Create request builder and send request
-    let response = client
-        .get("http://localhost:3000/tutors/")
-        .send() // <- Send request
-        .await
-        .unwrap()
-        .body()
-        .await
-        .unwrap();
-
-    let str_list = std::str::from_utf8(&response.as_ref()).unwrap();
-    let tutor_list: Vec<Tutor> = serde_json::from_str(str_list).unwrap();
+    // The listing is paged, so follow the cursors until the last page
+    let mut tutor_list: Vec<Tutor> = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let mut query = vec![("limit", "100".to_string())];
+        if let Some(cursor) = cursor {
+            query.push(("cursor", cursor));
+        }
+        // Create request builder and send request
+        let response = client
+            .get("http://localhost:3000/tutors/")
+            .query(&query)
+            .unwrap()
+            .send() // <- Send request
+            .await
+            .unwrap()
+            .body()
+            .await
+            .unwrap();
+
+        let str_list = std::str::from_utf8(response.as_ref()).unwrap();
+        let page = serde_json::from_str::<TutorPage>(str_list).unwrap();
+        tutor_list.extend(page.items);
+        match page.paging.next_cursor {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
     let mut ctx = tera::Context::new();
 
     ctx.insert("tutors", &tutor_list);
@@ -48,7 +48,9 @@ pub async fn handle_register(
             let new_tutor = json!({
                 "tutor_name": &params.name,
                 "tutor_pic_url": &params.imageurl,
-                "tutor_profile": &params.profile
+                "tutor_profile": &params.profile,
+                "username": &params.username,
+                "password": &params.password
             });
             let awc_client = awc::Client::default();
             let res = awc_client
@@ -58,7 +60,7 @@ pub async fn handle_register(
                 .unwrap()
                 .body()
                 .await?;
-            let tutor_response: TutorResponse = serde_json::from_str(&std::str::from_utf8(&res)?)?;
+            let tutor_response: TutorResponse = serde_json::from_str(std::str::from_utf8(&res)?)?;
             s = format!("Congratulations. You have been successfully registered with EzyTutor and your tutor id is: {}. To start using EzyTutor, please login with your credentials.",tutor_response.tutor_id);
             // Hash the password
             let salt = b"somerandomsalt";
@@ -111,7 +113,7 @@ pub async fn handle_signin(
     let user = get_user_record(&app_state.db, username.to_string()).await;
     if let Ok(user) = user {
         let does_password_match = argon2::verify_encoded(
-            &user.user_password.trim(),
+            user.user_password.trim(),
             params.password.clone().as_bytes(),
         )
         .unwrap();
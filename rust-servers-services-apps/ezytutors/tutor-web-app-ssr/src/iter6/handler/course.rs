@@ -1,13 +1,24 @@
 use crate::model::{NewCourse, NewCourseResponse, UpdateCourse, UpdateCourseResponse};
 use crate::state::AppState;
-use actix_web::{web, Error, HttpResponse, Result};
+use actix_web::http::header;
+use actix_web::{web, Error, HttpRequest, HttpResponse, Result};
 use serde_json::json;
 
+// Changes to courses are made with the caller's own token for the tutor
+// web service
+fn forward_token(req: &HttpRequest, request: awc::ClientRequest) -> awc::ClientRequest {
+    match req.headers().get(header::AUTHORIZATION) {
+        Some(token) => request.insert_header((header::AUTHORIZATION, token.clone())),
+        None => request,
+    }
+}
+
 pub async fn handle_insert_course(
     _tmpl: web::Data<tera::Tera>,
     _app_state: web::Data<AppState>,
     /*web::Path(tutor_id)*/ path: web::Path<i32>,
     params: web::Json<NewCourse>,
+    req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     let tutor_id = path.into_inner();
     let new_course = json!({
@@ -24,15 +35,14 @@ pub async fn handle_insert_course(
     });
 
     let awc_client = awc::Client::default();
-    let res = awc_client
-        .post("http://localhost:3000/courses/")
+    let res = forward_token(&req, awc_client.post("http://localhost:3000/courses/"))
         .send_json(&new_course)
         .await
         .unwrap()
         .body()
         .await?;
     println!("Finished call: {:?}", res);
-    let course_response: NewCourseResponse = serde_json::from_str(&std::str::from_utf8(&res)?)?;
+    let course_response: NewCourseResponse = serde_json::from_str(std::str::from_utf8(&res)?)?;
     Ok(HttpResponse::Ok().json(course_response))
 }
 
@@ -41,6 +51,7 @@ pub async fn handle_update_course(
     _app_state: web::Data<AppState>,
     /*web::Path((tutor_id, course_id))*/ path: web::Path<(i32, i32)>,
     params: web::Json<UpdateCourse>,
+    req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     let (tutor_id, course_id) = path.into_inner();
     let update_course = json!({
@@ -57,14 +68,13 @@ pub async fn handle_update_course(
 
     let awc_client = awc::Client::default();
     let update_url = format!("http://localhost:3000/courses/{}/{}", tutor_id, course_id);
-    let res = awc_client
-        .put(update_url)
+    let res = forward_token(&req, awc_client.put(update_url))
         .send_json(&update_course)
         .await
         .unwrap()
         .body()
         .await?;
-    let course_response: UpdateCourseResponse = serde_json::from_str(&std::str::from_utf8(&res)?)?;
+    let course_response: UpdateCourseResponse = serde_json::from_str(std::str::from_utf8(&res)?)?;
 
     Ok(HttpResponse::Ok().json(course_response))
 }
@@ -74,10 +84,14 @@ pub async fn handle_delete_course(
     _tmpl: web::Data<tera::Tera>,
     _app_state: web::Data<AppState>,
     /*web::Path((tutor_id, course_id))*/ path: web::Path<(i32, i32)>,
+    req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     let (tutor_id, course_id) = path.into_inner();
     let awc_client = awc::Client::default();
     let delete_url = format!("http://localhost:3000/courses/{}/{}", tutor_id, course_id);
-    let _res = awc_client.delete(delete_url).send().await.unwrap();
+    let _res = forward_token(&req, awc_client.delete(delete_url))
+        .send()
+        .await
+        .unwrap();
     Ok(HttpResponse::Ok().body("Course deleted"))
 }
@@ -1,24 +1,38 @@
 use actix_web::{web, App, HttpServer};
 use dotenv::dotenv;
 use sqlx::postgres::PgPool;
+#[cfg(feature = "sqlite")]
+use sqlx::sqlite::SqlitePool;
 use std::env;
 use std::io;
-use std::sync::Mutex;
+use std::process;
 
+#[path = "../iter5/auth.rs"]
+mod auth;
 #[path = "../iter5/dbaccess/mod.rs"]
 mod dbaccess;
 #[path = "../iter5/errors.rs"]
 mod errors;
 #[path = "../iter5/handlers/mod.rs"]
 mod handlers;
+#[path = "../iter5/migrate.rs"]
+mod migrate;
 #[path = "../iter5/models/mod.rs"]
 mod models;
+#[path = "../iter5/openapi.rs"]
+mod openapi;
+#[path = "../iter5/repository/mod.rs"]
+mod repository;
 #[path = "../iter5/routes.rs"]
 mod routes;
 #[path = "../iter5/state.rs"]
 mod state;
 
+use auth::JwtKeys;
 use errors::EzyTutorError;
+#[cfg(feature = "sqlite")]
+use repository::sqlite::{self, SqliteRepository};
+use repository::{memory::MemoryRepository, postgres::PgRepository};
 use routes::*;
 use state::AppState;
 
@@ -26,29 +40,89 @@ use state::AppState;
 async fn main() -> io::Result<()> {
     dotenv().ok();
 
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
-    let db_pool = PgPool::connect(&database_url).await.unwrap();
+    // `iter5 migrate ...` manages the schema and exits
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("migrate") {
+        let result = match connect().await {
+            Db::Postgres(pool) => migrate::command(&pool, &args[1..]).await,
+            #[cfg(feature = "sqlite")]
+            Db::Sqlite(pool) => migrate::command(&pool, &args[1..]).await,
+        };
+        if let Err(err) = result {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+        return Ok(());
+    }
 
-    // Construct App State
-    let shared_data = web::Data::new(AppState {
-        health_check_response: "I'm good. You've already asked me ".to_string(),
-        visit_count: Mutex::new(0),
-        db: db_pool,
+    // Construct App State. With STORAGE=memory the service runs on the
+    // sample data without a database, and forgets all changes on exit.
+    // Otherwise pending schema migrations are applied before serving.
+    let health_check_response = "I'm good. You've already asked me ";
+    let shared_data = web::Data::new(match env::var("STORAGE").as_deref() {
+        Ok("memory") => AppState::new(health_check_response, MemoryRepository::with_sample_data()),
+        _ => match connect().await {
+            Db::Postgres(pool) => {
+                migrate::SCHEMA
+                    .run(&pool)
+                    .await
+                    .expect("Failed to apply schema migrations");
+                AppState::new(health_check_response, PgRepository::new(pool))
+            }
+            #[cfg(feature = "sqlite")]
+            Db::Sqlite(pool) => {
+                migrate::SQLITE_SCHEMA
+                    .run(&pool)
+                    .await
+                    .expect("Failed to apply schema migrations");
+                AppState::new(health_check_response, SqliteRepository::new(pool))
+            }
+        },
     });
+    let jwt_keys = web::Data::new(JwtKeys::from_env());
 
     //Construct app and configure routes
     let app = move || {
         App::new()
             .app_data(shared_data.clone())
+            .app_data(jwt_keys.clone())
             .app_data(web::JsonConfig::default().error_handler(|_err, _req| {
                 EzyTutorError::InvalidInput("Please provide valid Json input".to_string()).into()
             }))
+            .app_data(web::QueryConfig::default().error_handler(|err, _req| {
+                EzyTutorError::InvalidInput(format!("Invalid query parameters: {}", err)).into()
+            }))
             .configure(general_routes)
+            .configure(auth_routes)
             .configure(course_routes)
             .configure(tutor_routes)
+            .configure(category_routes)
+            .configure(tag_routes)
+            .configure(student_routes)
+            .configure(enrollment_routes)
+            .configure(docs_routes)
     };
 
     //Start HTTP server
     let host_port = env::var("HOST_PORT").expect("HOST:PORT address is not set in .env file");
     HttpServer::new(app).bind(&host_port)?.run().await
 }
+
+// The database in DATABASE_URL: SQLite for "sqlite:" URLs, when built with
+// the sqlite feature, and Postgres otherwise
+enum Db {
+    Postgres(PgPool),
+    #[cfg(feature = "sqlite")]
+    Sqlite(SqlitePool),
+}
+
+async fn connect() -> Db {
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
+    if database_url.starts_with("sqlite:") {
+        #[cfg(feature = "sqlite")]
+        return Db::Sqlite(sqlite::connect(&database_url).await.unwrap());
+        #[cfg(not(feature = "sqlite"))]
+        panic!("DATABASE_URL is a SQLite database, build with --features sqlite to use it");
+    }
+    Db::Postgres(PgPool::connect(&database_url).await.unwrap())
+}
@@ -4,5 +4,7 @@ use std::sync::Mutex;
 pub struct AppState {
     pub health_check_response: String,
     pub visit_count: Mutex<u32>,
+    // Connected here, but only queried by the handlers from iter3 on
+    #[allow(dead_code)]
     pub db: PgPool,
 }
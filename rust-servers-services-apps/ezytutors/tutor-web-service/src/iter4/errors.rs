@@ -53,7 +53,11 @@ impl error::ResponseError for EzyTutorError {
 
 impl fmt::Display for EzyTutorError {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        write!(f, "{}", self)
+        match self {
+            EzyTutorError::DBError(msg)
+            | EzyTutorError::ActixError(msg)
+            | EzyTutorError::NotFound(msg) => write!(f, "{}", msg),
+        }
     }
 }
 
@@ -104,7 +104,12 @@ mod tests {
             course_id: 3,
             tutor_id: 1,
             course_name: "Third course".into(),
-            posted_time: Some(NaiveDate::from_ymd(2020, 9, 17).and_hms(14, 01, 11)),
+            posted_time: Some(
+                NaiveDate::from_ymd_opt(2020, 9, 17)
+                    .unwrap()
+                    .and_hms_opt(14, 1, 11)
+                    .unwrap(),
+            ),
         };
 
         let course_param = web::Json(new_course_msg);
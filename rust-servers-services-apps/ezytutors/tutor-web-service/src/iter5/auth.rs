@@ -0,0 +1,278 @@
+use crate::errors::EzyTutorError;
+use crate::models::auth::User;
+use actix_web::{dev::Payload, http::header, web, FromRequest, HttpRequest};
+use chrono::Utc;
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::future::{ready, Ready};
+
+// How long an access token stays valid, in seconds
+pub const TOKEN_TTL: i64 = 60 * 60;
+
+// The example value of JWT_SECRET, which anyone could sign tokens with
+const PLACEHOLDER_SECRET: &str = "change-me-in-production";
+
+// What an access token says about its holder
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Claims {
+    pub sub: String,
+    pub tutor_id: Option<i32>,
+    #[serde(default)]
+    pub student_id: Option<i32>,
+    #[serde(default)]
+    pub admin: bool,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+// The keys access tokens are signed and checked with. Tokens signed with
+// any other algorithm are refused.
+pub struct JwtKeys {
+    algorithm: Algorithm,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+}
+
+impl JwtKeys {
+    pub fn hs256(secret: &[u8]) -> Self {
+        JwtKeys {
+            algorithm: Algorithm::HS256,
+            encoding: EncodingKey::from_secret(secret),
+            decoding: DecodingKey::from_secret(secret),
+        }
+    }
+
+    pub fn rs256(private_pem: &[u8], public_pem: &[u8]) -> jsonwebtoken::errors::Result<Self> {
+        Ok(JwtKeys {
+            algorithm: Algorithm::RS256,
+            encoding: EncodingKey::from_rsa_pem(private_pem)?,
+            decoding: DecodingKey::from_rsa_pem(public_pem)?,
+        })
+    }
+
+    // RS256 if JWT_PRIVATE_KEY and JWT_PUBLIC_KEY name PEM files, otherwise
+    // HS256 with JWT_SECRET
+    pub fn from_env() -> Self {
+        match (env::var("JWT_PRIVATE_KEY"), env::var("JWT_PUBLIC_KEY")) {
+            (Ok(private_key), Ok(public_key)) => {
+                let private_pem = fs::read(&private_key).expect("Cannot read JWT_PRIVATE_KEY");
+                let public_pem = fs::read(&public_key).expect("Cannot read JWT_PUBLIC_KEY");
+                JwtKeys::rs256(&private_pem, &public_pem).expect("Invalid RSA key for JWT")
+            }
+            _ => {
+                let secret = env::var("JWT_SECRET")
+                    .ok()
+                    .filter(|secret| usable_secret(secret))
+                    .expect("Set JWT_SECRET to a random secret, or JWT_PRIVATE_KEY and JWT_PUBLIC_KEY, in .env file");
+                JwtKeys::hs256(secret.as_bytes())
+            }
+        }
+    }
+
+    pub fn issue(&self, user: &User) -> Result<String, EzyTutorError> {
+        let now = Utc::now().timestamp();
+        let claims = Claims {
+            sub: user.username.clone(),
+            tutor_id: user.tutor_id,
+            student_id: user.student_id,
+            admin: user.is_admin,
+            iat: now,
+            exp: now + TOKEN_TTL,
+        };
+        jsonwebtoken::encode(&Header::new(self.algorithm), &claims, &self.encoding)
+            .map_err(|err| EzyTutorError::ActixError(err.to_string()))
+    }
+
+    pub fn verify(&self, token: &str) -> Result<Claims, EzyTutorError> {
+        jsonwebtoken::decode::<Claims>(token, &self.decoding, &Validation::new(self.algorithm))
+            .map(|data| data.claims)
+            .map_err(|err| EzyTutorError::Unauthorized(format!("Invalid token: {}", err)))
+    }
+}
+
+fn usable_secret(secret: &str) -> bool {
+    !secret.trim().is_empty() && secret != PLACEHOLDER_SECRET
+}
+
+// The caller of a handler, from the bearer token in the Authorization header.
+// Requests without a valid token are refused with 401.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthUser {
+    pub username: String,
+    pub tutor_id: Option<i32>,
+    pub student_id: Option<i32>,
+    pub admin: bool,
+}
+
+impl AuthUser {
+    // Tutors may only change their own profile and courses; admins may
+    // change any
+    pub fn check_owner(&self, tutor_id: i32) -> Result<(), EzyTutorError> {
+        if self.admin || self.tutor_id == Some(tutor_id) {
+            Ok(())
+        } else {
+            Err(EzyTutorError::Forbidden(format!(
+                "{} may not change tutor {}",
+                self.username, tutor_id
+            )))
+        }
+    }
+
+    // Students may only act for themselves, such as enroll or see their
+    // courses; admins may for any student
+    pub fn check_student(&self, student_id: i32) -> Result<(), EzyTutorError> {
+        if self.admin || self.student_id == Some(student_id) {
+            Ok(())
+        } else {
+            Err(EzyTutorError::Forbidden(format!(
+                "{} may not act for student {}",
+                self.username, student_id
+            )))
+        }
+    }
+
+    // Only admins may change what all tutors share, such as the categories
+    pub fn check_admin(&self) -> Result<(), EzyTutorError> {
+        if self.admin {
+            Ok(())
+        } else {
+            Err(EzyTutorError::Forbidden(format!(
+                "{} is not an admin",
+                self.username
+            )))
+        }
+    }
+
+    fn from_request(req: &HttpRequest) -> Result<Self, EzyTutorError> {
+        let keys = req
+            .app_data::<web::Data<JwtKeys>>()
+            .ok_or_else(|| EzyTutorError::ActixError("JWT keys are not configured".into()))?;
+        let token = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| EzyTutorError::Unauthorized("Please provide a bearer token".into()))?;
+        let claims = keys.verify(token.trim())?;
+        Ok(AuthUser {
+            username: claims.sub,
+            tutor_id: claims.tutor_id,
+            student_id: claims.student_id,
+            admin: claims.admin,
+        })
+    }
+}
+
+impl FromRequest for AuthUser {
+    type Error = EzyTutorError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(AuthUser::from_request(req))
+    }
+}
+
+// Hash a password with argon2 and a random salt, in the encoded form that
+// holds the salt and parameters along with the hash
+pub fn hash_password(password: &str) -> Result<String, EzyTutorError> {
+    let mut salt = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut salt);
+    argon2::hash_encoded(password.as_bytes(), &salt, &argon2::Config::default())
+        .map_err(|err| EzyTutorError::ActixError(err.to_string()))
+}
+
+pub fn verify_password(hash: &str, password: &str) -> bool {
+    argon2::verify_encoded(hash, password.as_bytes()).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RSA_PRIVATE: &[u8] = include_bytes!("testdata/jwt-rs256-private.pem");
+    const RSA_PUBLIC: &[u8] = include_bytes!("testdata/jwt-rs256-public.pem");
+
+    fn tutor() -> User {
+        User {
+            username: "merlene".into(),
+            tutor_id: Some(1),
+            student_id: None,
+            user_password: hash_password("secret").unwrap(),
+            is_admin: false,
+        }
+    }
+
+    #[test]
+    fn token_round_trip_test() {
+        let hs256 = JwtKeys::hs256(b"test secret");
+        let rs256 = JwtKeys::rs256(RSA_PRIVATE, RSA_PUBLIC).unwrap();
+        for keys in [&hs256, &rs256] {
+            let claims = keys.verify(&keys.issue(&tutor()).unwrap()).unwrap();
+            assert_eq!(claims.sub, "merlene");
+            assert_eq!(claims.tutor_id, Some(1));
+            assert!(!claims.admin);
+        }
+
+        // Tokens signed with another key or algorithm are refused
+        let token = hs256.issue(&tutor()).unwrap();
+        assert!(rs256.verify(&token).is_err());
+        assert!(JwtKeys::hs256(b"other secret").verify(&token).is_err());
+        assert!(hs256.verify(&format!("{}x", token)).is_err());
+
+        // So are expired ones
+        let expired = Claims {
+            sub: "merlene".into(),
+            tutor_id: Some(1),
+            student_id: None,
+            admin: false,
+            iat: 0,
+            exp: 60,
+        };
+        let token = jsonwebtoken::encode(&Header::default(), &expired, &hs256.encoding).unwrap();
+        assert!(hs256.verify(&token).is_err());
+
+        // The service will not start with no secret or the example one
+        assert!(usable_secret("test secret"));
+        assert!(!usable_secret(" "));
+        assert!(!usable_secret(PLACEHOLDER_SECRET));
+    }
+
+    #[test]
+    fn password_and_owner_test() {
+        let user = tutor();
+        assert!(verify_password(&user.user_password, "secret"));
+        assert!(!verify_password(&user.user_password, "Secret"));
+        assert!(!verify_password("not a hash", "secret"));
+        assert_ne!(user.user_password, hash_password("secret").unwrap());
+
+        let caller = AuthUser {
+            username: "merlene".into(),
+            tutor_id: Some(1),
+            student_id: None,
+            admin: false,
+        };
+        assert!(caller.check_owner(1).is_ok());
+        assert!(caller.check_owner(2).is_err());
+        assert!(caller.check_admin().is_err());
+        assert!(caller.check_student(1).is_err());
+        let student = AuthUser {
+            tutor_id: None,
+            student_id: Some(1),
+            ..caller.clone()
+        };
+        assert!(student.check_student(1).is_ok());
+        assert!(student.check_student(2).is_err());
+        assert!(student.check_owner(1).is_err());
+        let admin = AuthUser {
+            tutor_id: None,
+            admin: true,
+            ..caller
+        };
+        assert!(admin.check_owner(2).is_ok());
+        assert!(admin.check_admin().is_ok());
+        assert!(admin.check_student(2).is_ok());
+    }
+}
@@ -0,0 +1,14 @@
+use crate::errors::EzyTutorError;
+use crate::models::auth::User;
+use sqlx::postgres::PgPool;
+
+pub async fn get_user_db(pool: &PgPool, username: &str) -> Result<Option<User>, EzyTutorError> {
+    let user = sqlx::query_as!(
+        User,
+        "SELECT username, tutor_id, student_id, user_password, is_admin FROM ezy_user_c6 where username = $1",
+        username
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(user)
+}
@@ -0,0 +1,179 @@
+use crate::dbaccess::course::{get_course_details_db, push_course_filters};
+use crate::dbaccess::paging::push_page;
+use crate::errors::EzyTutorError;
+use crate::models::category::{Category, NewCategory, UpdateCategory};
+use crate::models::course::{Course, CourseQuery};
+use crate::models::paging::Page;
+use sqlx::postgres::PgPool;
+use sqlx::{Database, Encode, QueryBuilder, Type};
+
+// Parents come before the categories below them
+pub async fn get_all_categories_db(pool: &PgPool) -> Result<Vec<Category>, EzyTutorError> {
+    let categories = sqlx::query_as(
+        "SELECT category_id, category_name, parent_id FROM ezy_category_c6 ORDER BY category_id",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(categories)
+}
+
+pub async fn get_category_db(pool: &PgPool, category_id: i32) -> Result<Category, EzyTutorError> {
+    sqlx::query_as(
+        "SELECT category_id, category_name, parent_id FROM ezy_category_c6 WHERE category_id = $1",
+    )
+    .bind(category_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| EzyTutorError::NotFound("Category id not found".into()))
+}
+
+pub async fn post_new_category_db(
+    pool: &PgPool,
+    new_category: NewCategory,
+) -> Result<Category, EzyTutorError> {
+    sqlx::query_as(
+        "INSERT INTO ezy_category_c6 (category_name, parent_id) VALUES ($1, $2) \
+        RETURNING category_id, category_name, parent_id",
+    )
+    .bind(new_category.category_name)
+    .bind(new_category.parent_id)
+    .fetch_one(pool)
+    .await
+    .map_err(
+        |err| match err.as_database_error().and_then(|err| err.code()) {
+            // foreign_key_violation
+            Some(code) if code == "23503" => {
+                EzyTutorError::NotFound("Parent category id not found".into())
+            }
+            _ => err.into(),
+        },
+    )
+}
+
+pub async fn update_category_db(
+    pool: &PgPool,
+    category_id: i32,
+    update_category: UpdateCategory,
+) -> Result<Category, EzyTutorError> {
+    sqlx::query_as(
+        "UPDATE ezy_category_c6 SET category_name = $1 WHERE category_id = $2 \
+        RETURNING category_id, category_name, parent_id",
+    )
+    .bind(update_category.category_name)
+    .bind(category_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| EzyTutorError::NotFound("Category id not found".into()))
+}
+
+// The categories below it are deleted by the database
+pub async fn delete_category_db(pool: &PgPool, category_id: i32) -> Result<String, EzyTutorError> {
+    let deleted = sqlx::query("DELETE FROM ezy_category_c6 WHERE category_id = $1")
+        .bind(category_id)
+        .execute(pool)
+        .await?;
+    Ok(format!("Deleted {} record", deleted.rows_affected()))
+}
+
+pub async fn get_courses_in_category_db(
+    pool: &PgPool,
+    category_id: i32,
+    query: &CourseQuery,
+) -> Result<Page<Course>, EzyTutorError> {
+    let page = query.page_request()?;
+    let filters = query.filters();
+    filters.check()?;
+    get_category_db(pool, category_id).await?;
+
+    let mut count = QueryBuilder::new("SELECT count(*) FROM ezy_course_c6 WHERE true");
+    push_course_filters(&mut count, None, &filters);
+    push_in_category(&mut count, category_id);
+    let (total,): (i64,) = count.build_query_as().fetch_one(pool).await?;
+
+    let mut select = QueryBuilder::new("SELECT * FROM ezy_course_c6 WHERE true");
+    push_course_filters(&mut select, None, &filters);
+    push_in_category(&mut select, category_id);
+    push_page(&mut select, "course_id", &page);
+    let course_rows: Vec<Course> = select.build_query_as().fetch_all(pool).await?;
+
+    Ok(page.into_page(course_rows, total))
+}
+
+// Keep to the courses in a category or any category below it. The SQL is
+// the same for Postgres and SQLite.
+pub fn push_in_category<'args, DB>(sql: &mut QueryBuilder<'args, DB>, category_id: i32)
+where
+    DB: Database,
+    i32: Encode<'args, DB> + Type<DB>,
+{
+    sql.push(
+        " AND course_id IN (SELECT course_id FROM ezy_course_category_c6 WHERE category_id IN (\
+        WITH RECURSIVE tree(category_id) AS (\
+        SELECT category_id FROM ezy_category_c6 WHERE category_id = ",
+    )
+    .push_bind(category_id)
+    .push(
+        " UNION SELECT below.category_id FROM ezy_category_c6 below \
+        JOIN tree ON below.parent_id = tree.category_id) \
+        SELECT category_id FROM tree))",
+    );
+}
+
+pub async fn get_course_categories_db(
+    pool: &PgPool,
+    tutor_id: i32,
+    course_id: i32,
+) -> Result<Vec<Category>, EzyTutorError> {
+    get_course_details_db(pool, tutor_id, course_id).await?;
+    let categories = sqlx::query_as(
+        "SELECT category_id, category_name, parent_id FROM ezy_category_c6 \
+        JOIN ezy_course_category_c6 USING (category_id) WHERE course_id = $1 \
+        ORDER BY category_id",
+    )
+    .bind(course_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(categories)
+}
+
+pub async fn add_course_category_db(
+    pool: &PgPool,
+    tutor_id: i32,
+    course_id: i32,
+    category_id: i32,
+) -> Result<(), EzyTutorError> {
+    get_course_details_db(pool, tutor_id, course_id).await?;
+    sqlx::query(
+        "INSERT INTO ezy_course_category_c6 (course_id, category_id) VALUES ($1, $2) \
+        ON CONFLICT DO NOTHING",
+    )
+    .bind(course_id)
+    .bind(category_id)
+    .execute(pool)
+    .await
+    .map_err(
+        |err| match err.as_database_error().and_then(|err| err.code()) {
+            // foreign_key_violation
+            Some(code) if code == "23503" => {
+                EzyTutorError::NotFound("Category id not found".into())
+            }
+            _ => err.into(),
+        },
+    )?;
+    Ok(())
+}
+
+pub async fn remove_course_category_db(
+    pool: &PgPool,
+    tutor_id: i32,
+    course_id: i32,
+    category_id: i32,
+) -> Result<(), EzyTutorError> {
+    get_course_details_db(pool, tutor_id, course_id).await?;
+    sqlx::query("DELETE FROM ezy_course_category_c6 WHERE course_id = $1 AND category_id = $2")
+        .bind(course_id)
+        .bind(category_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
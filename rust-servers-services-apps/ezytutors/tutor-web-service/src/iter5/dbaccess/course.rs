@@ -1,22 +1,128 @@
+use crate::dbaccess::paging::push_page;
 use crate::errors::EzyTutorError;
 use crate::models::course::*;
-use sqlx::postgres::PgPool;
+use crate::models::paging::{page_bounds, Page, Paging};
+use sqlx::postgres::{PgPool, Postgres};
+use sqlx::{Database, Encode, QueryBuilder, Type};
+
+// The text searched in a course, as indexed by the course_search migration.
+// Both must stay the same for the index to be used.
+const SEARCH_DOCUMENT: &str =
+    "(setweight(to_tsvector('english', coalesce(course_name, '')), 'A') || \
+    setweight(to_tsvector('english', coalesce(course_description, '')), 'B') || \
+    setweight(to_tsvector('english', coalesce(course_structure, '')), 'C'))";
+
+// The text search snippets are cut from: the course text, escaped for HTML
+// so that only the <mark> tags added by ts_headline are markup
+const SNIPPET_TEXT: &str = "replace(replace(replace(replace(replace(\
+    concat_ws(' ', course_name, course_description, course_structure), \
+    '&', '&amp;'), '<', '&lt;'), '>', '&gt;'), '\"', '&quot;'), '''', '&#39;')";
+
+// How search snippets are cut and marked up, see ts_headline
+const SNIPPET_OPTIONS: &str =
+    "StartSel=<mark>, StopSel=</mark>, MaxWords=25, MinWords=10, MaxFragments=2, FragmentDelimiter=\" ... \"";
 
 pub async fn get_courses_for_tutor_db(
     pool: &PgPool,
     tutor_id: i32,
-) -> Result<Vec<Course>, EzyTutorError> {
-    // Prepare SQL statement
+    query: &CourseQuery,
+) -> Result<Page<Course>, EzyTutorError> {
+    let page = query.page_request()?;
+    let filters = query.filters();
+    filters.check()?;
 
-    let course_rows: Vec<Course> = sqlx::query_as!(
-        Course,
-        "SELECT * FROM ezy_course_c6 where tutor_id = $1 order by course_id desc",
-        tutor_id
-    )
-    .fetch_all(pool)
-    .await?;
+    // Count the matching courses, then fetch the requested page of them
+    let mut count = QueryBuilder::new("SELECT count(*) FROM ezy_course_c6 WHERE true");
+    push_course_filters(&mut count, Some(tutor_id), &filters);
+    let (total,): (i64,) = count.build_query_as().fetch_one(pool).await?;
+
+    let mut select = QueryBuilder::new("SELECT * FROM ezy_course_c6 WHERE true");
+    push_course_filters(&mut select, Some(tutor_id), &filters);
+    push_page(&mut select, "course_id", &page);
+    let course_rows: Vec<Course> = select.build_query_as().fetch_all(pool).await?;
+
+    Ok(page.into_page(course_rows, total))
+}
+
+// Search the courses of all tutors, best matches first. Pages are fetched
+// by offset only, as ranks make for poor cursors.
+pub async fn search_courses_db(
+    pool: &PgPool,
+    query: &CourseSearchQuery,
+) -> Result<Page<CourseSearchResult>, EzyTutorError> {
+    let terms = query.terms()?;
+    let (limit, offset) = page_bounds(query.limit, query.offset)?;
+    let filters = query.filters();
+    filters.check()?;
 
-    Ok(course_rows)
+    let mut count = QueryBuilder::new("SELECT count(*)");
+    push_search_matches(&mut count, terms, &filters);
+    let (total,): (i64,) = count.build_query_as().fetch_one(pool).await?;
+
+    let mut select = QueryBuilder::new(format!(
+        "SELECT ezy_course_c6.*, ts_rank({}, terms) AS rank, ts_headline('english', {}, terms, ",
+        SEARCH_DOCUMENT, SNIPPET_TEXT
+    ));
+    select.push_bind(SNIPPET_OPTIONS).push(") AS snippet");
+    push_search_matches(&mut select, terms, &filters);
+    select
+        .push(" ORDER BY rank DESC, course_id DESC LIMIT ")
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind(offset);
+    let results: Vec<CourseSearchResult> = select.build_query_as().fetch_all(pool).await?;
+
+    Ok(Page {
+        items: results,
+        paging: Paging {
+            limit,
+            offset: Some(offset),
+            total,
+            next_cursor: None,
+        },
+    })
+}
+
+// The FROM and WHERE clauses selecting the courses that match a search
+fn push_search_matches(sql: &mut QueryBuilder<Postgres>, terms: &str, filters: &CourseFilters) {
+    sql.push(" FROM ezy_course_c6, websearch_to_tsquery('english', ")
+        .push_bind(terms.to_string())
+        .push(format!(") terms WHERE {} @@ terms", SEARCH_DOCUMENT));
+    push_course_filters(sql, None, filters);
+}
+
+// Append the filters to a query that already has a WHERE clause. Text
+// filters ignore case; the price range includes both ends. The SQL is the
+// same for Postgres and SQLite.
+pub fn push_course_filters<'args, DB>(
+    sql: &mut QueryBuilder<'args, DB>,
+    tutor_id: Option<i32>,
+    filters: &CourseFilters,
+) where
+    DB: Database,
+    i32: Encode<'args, DB> + Type<DB>,
+    String: Encode<'args, DB> + Type<DB>,
+{
+    if let Some(tutor_id) = tutor_id {
+        sql.push(" AND tutor_id = ").push_bind(tutor_id);
+    }
+    for (column, value) in [
+        ("course_level", filters.course_level),
+        ("course_language", filters.course_language),
+        ("course_format", filters.course_format),
+    ] {
+        if let Some(value) = value {
+            sql.push(format!(" AND lower({}) = lower(", column))
+                .push_bind(value.to_string())
+                .push(")");
+        }
+    }
+    if let Some(min) = filters.min_price {
+        sql.push(" AND course_price >= ").push_bind(min);
+    }
+    if let Some(max) = filters.max_price {
+        sql.push(" AND course_price <= ").push_bind(max);
+    }
 }
 
 //Return result
@@ -47,11 +153,18 @@ pub async fn post_new_course_db(
     pool: &PgPool,
     new_course: CreateCourse,
 ) -> Result<Course, EzyTutorError> {
-    let course_row= sqlx::query_as!(Course,"insert into ezy_course_c6 (tutor_id, course_name, course_description,course_duration, course_level, course_format, course_language, course_structure, course_price) values ($1,$2,$3,$4,$5,$6,$7,$8,$9) returning tutor_id, course_id,course_name, course_description, course_duration, course_level, course_format, course_language, course_structure, course_price, posted_time", 
+    let course_row= sqlx::query_as!(Course,"insert into ezy_course_c6 (tutor_id, course_name, course_description,course_duration, course_level, course_format, course_language, course_structure, course_price) values ($1,$2,$3,$4,$5,$6,$7,$8,$9) returning tutor_id, course_id,course_name, course_description, course_duration, course_level, course_format, course_language, course_structure, course_price, posted_time, course_capacity", 
     new_course.tutor_id, new_course.course_name, new_course.course_description,
     new_course.course_duration, new_course.course_level, new_course.course_format, new_course.course_language, new_course.course_structure, new_course.course_price)
     .fetch_one(pool)
-    .await?;
+    .await
+    .map_err(
+        |err| match err.as_database_error().and_then(|err| err.code()) {
+            // foreign_key_violation
+            Some(code) if code == "23503" => EzyTutorError::NotFound("Tutor id not found".into()),
+            _ => err.into(),
+        },
+    )?;
 
     Ok(course_row)
 }
@@ -143,7 +256,7 @@ pub async fn update_course_details_db(
         course_structure = $4, course_duration = $5, course_price = $6, course_language = $7, 
         course_level = $8 where tutor_id = $9 and course_id = $10 returning tutor_id, course_id, 
         course_name, course_description, course_duration, course_level, course_format, 
-        course_language, course_structure, course_price, posted_time ", name, description, format, 
+        course_language, course_structure, course_price, posted_time, course_capacity ", name, description, format, 
         structure, duration, price, language,level, tutor_id, course_id
     )
         .fetch_one(pool)
@@ -0,0 +1,257 @@
+use crate::dbaccess::course::get_course_details_db;
+use crate::errors::EzyTutorError;
+use crate::models::course::Course;
+use crate::models::enrollment::{Enrollment, EnrollmentQuery, EnrollmentStatus, RosterEntry};
+use sqlx::postgres::{PgPool, Postgres};
+use sqlx::{Database, Encode, QueryBuilder, Transaction, Type};
+
+pub const ENROLLMENT_COLUMNS: &str = "enrollment_id, course_id, student_id, status, enrolled_time";
+
+// Lock the course until the transaction ends, so that its seats are
+// counted and handed out by one transaction at a time. Returns the
+// capacity of the course.
+async fn lock_course(
+    tx: &mut Transaction<'_, Postgres>,
+    tutor_id: i32,
+    course_id: i32,
+) -> Result<Option<i32>, EzyTutorError> {
+    let capacity: Option<(Option<i32>,)> = sqlx::query_as(
+        "SELECT course_capacity FROM ezy_course_c6 WHERE tutor_id = $1 AND course_id = $2 \
+        FOR UPDATE",
+    )
+    .bind(tutor_id)
+    .bind(course_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    capacity
+        .map(|(capacity,)| capacity)
+        .ok_or_else(|| EzyTutorError::NotFound("Course id not found".into()))
+}
+
+// The seats left in a course, or None if it has no capacity. Courses whose
+// capacity was lowered below their active students have none left.
+async fn free_seats(
+    tx: &mut Transaction<'_, Postgres>,
+    course_id: i32,
+    capacity: Option<i32>,
+) -> Result<Option<i64>, EzyTutorError> {
+    let Some(capacity) = capacity else {
+        return Ok(None);
+    };
+    let (active,): (i64,) = sqlx::query_as(
+        "SELECT count(*) FROM ezy_enrollment_c6 WHERE course_id = $1 AND status = 'active'",
+    )
+    .bind(course_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    Ok(Some((capacity as i64 - active).max(0)))
+}
+
+// Give the free seats of a course to the students first on its waitlist
+async fn promote_waitlist(
+    tx: &mut Transaction<'_, Postgres>,
+    course_id: i32,
+    capacity: Option<i32>,
+) -> Result<(), EzyTutorError> {
+    let seats = free_seats(tx, course_id, capacity).await?;
+    promote_sql(course_id, seats)
+        .build()
+        .execute(&mut *tx)
+        .await?;
+    Ok(())
+}
+
+pub async fn enroll_db(
+    pool: &PgPool,
+    tutor_id: i32,
+    course_id: i32,
+    student_id: i32,
+) -> Result<Enrollment, EzyTutorError> {
+    let mut tx = pool.begin().await?;
+    let capacity = lock_course(&mut tx, tutor_id, course_id).await?;
+    let status = match free_seats(&mut tx, course_id, capacity).await? {
+        Some(0) => EnrollmentStatus::Pending,
+        _ => EnrollmentStatus::Active,
+    };
+    let enrollment = sqlx::query_as(&format!(
+        "INSERT INTO ezy_enrollment_c6 (course_id, student_id, status) VALUES ($1, $2, $3) \
+        RETURNING {}",
+        ENROLLMENT_COLUMNS
+    ))
+    .bind(course_id)
+    .bind(student_id)
+    .bind(status)
+    .fetch_one(&mut tx)
+    .await
+    .map_err(
+        |err| match err.as_database_error().and_then(|err| err.code()) {
+            // foreign_key_violation, the course being locked
+            Some(code) if code == "23503" => EzyTutorError::NotFound("Student id not found".into()),
+            // unique_violation
+            Some(code) if code == "23505" => EzyTutorError::InvalidInput(format!(
+                "Student {} is already enrolled in course {}",
+                student_id, course_id
+            )),
+            _ => err.into(),
+        },
+    )?;
+    tx.commit().await?;
+    Ok(enrollment)
+}
+
+pub async fn get_enrollment_db(
+    pool: &PgPool,
+    tutor_id: i32,
+    course_id: i32,
+    enrollment_id: i32,
+) -> Result<Enrollment, EzyTutorError> {
+    get_course_details_db(pool, tutor_id, course_id).await?;
+    sqlx::query_as(&format!(
+        "SELECT {} FROM ezy_enrollment_c6 WHERE course_id = $1 AND enrollment_id = $2",
+        ENROLLMENT_COLUMNS
+    ))
+    .bind(course_id)
+    .bind(enrollment_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| EzyTutorError::NotFound("Enrollment id not found".into()))
+}
+
+pub async fn get_course_roster_db(
+    pool: &PgPool,
+    tutor_id: i32,
+    course_id: i32,
+    query: &EnrollmentQuery,
+) -> Result<Vec<RosterEntry>, EzyTutorError> {
+    get_course_details_db(pool, tutor_id, course_id).await?;
+    let roster = roster_sql(course_id, query)
+        .build_query_as()
+        .fetch_all(pool)
+        .await?;
+    Ok(roster)
+}
+
+pub async fn update_enrollment_status_db(
+    pool: &PgPool,
+    tutor_id: i32,
+    course_id: i32,
+    enrollment_id: i32,
+    status: EnrollmentStatus,
+) -> Result<Enrollment, EzyTutorError> {
+    let mut tx = pool.begin().await?;
+    let capacity = lock_course(&mut tx, tutor_id, course_id).await?;
+    let current: Enrollment = sqlx::query_as(&format!(
+        "SELECT {} FROM ezy_enrollment_c6 WHERE course_id = $1 AND enrollment_id = $2",
+        ENROLLMENT_COLUMNS
+    ))
+    .bind(course_id)
+    .bind(enrollment_id)
+    .fetch_optional(&mut tx)
+    .await?
+    .ok_or_else(|| EzyTutorError::NotFound("Enrollment id not found".into()))?;
+    current.status.check_change(status)?;
+    let enrollment = sqlx::query_as(&format!(
+        "UPDATE ezy_enrollment_c6 SET status = $1 WHERE enrollment_id = $2 RETURNING {}",
+        ENROLLMENT_COLUMNS
+    ))
+    .bind(status)
+    .bind(enrollment_id)
+    .fetch_one(&mut tx)
+    .await?;
+    if current.status == EnrollmentStatus::Active {
+        promote_waitlist(&mut tx, course_id, capacity).await?;
+    }
+    tx.commit().await?;
+    Ok(enrollment)
+}
+
+pub async fn set_course_capacity_db(
+    pool: &PgPool,
+    tutor_id: i32,
+    course_id: i32,
+    capacity: Option<i32>,
+) -> Result<Course, EzyTutorError> {
+    let mut tx = pool.begin().await?;
+    lock_course(&mut tx, tutor_id, course_id).await?;
+    let course = sqlx::query_as(
+        "UPDATE ezy_course_c6 SET course_capacity = $1 WHERE course_id = $2 RETURNING *",
+    )
+    .bind(capacity)
+    .bind(course_id)
+    .fetch_one(&mut tx)
+    .await?;
+    promote_waitlist(&mut tx, course_id, capacity).await?;
+    tx.commit().await?;
+    Ok(course)
+}
+
+// The queries below are the same for Postgres and SQLite
+
+// Make the first `seats` pending students of a course active, or all of
+// them if there is no limit
+pub fn promote_sql<'args, DB>(course_id: i32, seats: Option<i64>) -> QueryBuilder<'args, DB>
+where
+    DB: Database,
+    i32: Encode<'args, DB> + Type<DB>,
+    i64: Encode<'args, DB> + Type<DB>,
+{
+    let mut sql = QueryBuilder::new(
+        "UPDATE ezy_enrollment_c6 SET status = 'active' WHERE enrollment_id IN (\
+        SELECT enrollment_id FROM ezy_enrollment_c6 WHERE status = 'pending' AND course_id = ",
+    );
+    sql.push_bind(course_id)
+        .push(" ORDER BY enrollment_id LIMIT ")
+        .push_bind(seats.unwrap_or(i64::MAX))
+        .push(")");
+    sql
+}
+
+// The roster of a course, in the order the students enrolled, which is
+// also the order of its waitlist
+pub fn roster_sql<'args, DB>(course_id: i32, query: &EnrollmentQuery) -> QueryBuilder<'args, DB>
+where
+    DB: Database,
+    i32: Encode<'args, DB> + Type<DB>,
+    EnrollmentStatus: Encode<'args, DB> + Type<DB>,
+{
+    let mut sql = QueryBuilder::new(format!(
+        "SELECT {}, student_name FROM ezy_enrollment_c6 JOIN ezy_student_c6 USING (student_id) \
+        WHERE course_id = ",
+        ENROLLMENT_COLUMNS
+    ));
+    sql.push_bind(course_id);
+    push_status(&mut sql, query);
+    sql.push(" ORDER BY enrollment_id");
+    sql
+}
+
+// The enrollments of a student, the latest first
+pub fn student_courses_sql<'args, DB>(
+    student_id: i32,
+    query: &EnrollmentQuery,
+) -> QueryBuilder<'args, DB>
+where
+    DB: Database,
+    i32: Encode<'args, DB> + Type<DB>,
+    EnrollmentStatus: Encode<'args, DB> + Type<DB>,
+{
+    let mut sql = QueryBuilder::new(format!(
+        "SELECT {}, tutor_id, course_name FROM ezy_enrollment_c6 \
+        JOIN ezy_course_c6 USING (course_id) WHERE student_id = ",
+        ENROLLMENT_COLUMNS
+    ));
+    sql.push_bind(student_id);
+    push_status(&mut sql, query);
+    sql.push(" ORDER BY enrollment_id DESC");
+    sql
+}
+
+fn push_status<'args, DB>(sql: &mut QueryBuilder<'args, DB>, query: &EnrollmentQuery)
+where
+    DB: Database,
+    EnrollmentStatus: Encode<'args, DB> + Type<DB>,
+{
+    if let Some(status) = query.status {
+        sql.push(" AND status = ").push_bind(status);
+    }
+}
@@ -0,0 +1,85 @@
+use crate::models::paging::{PageRequest, SortValue};
+use chrono::NaiveDateTime;
+use sqlx::{Database, Encode, QueryBuilder, Type};
+
+// Append the keyset condition, order and limit for a page to a query that
+// already has a WHERE clause. Rows are ordered on the sort column, nulls
+// last, then on `id_column`, so every row has a unique position to resume
+// from. One row more than the limit is fetched, to tell if there is a next page.
+// The SQL is the same for Postgres and SQLite.
+pub fn push_page<'args, DB>(
+    query: &mut QueryBuilder<'args, DB>,
+    id_column: &str,
+    page: &PageRequest,
+) where
+    DB: Database,
+    i32: Encode<'args, DB> + Type<DB>,
+    i64: Encode<'args, DB> + Type<DB>,
+    String: Encode<'args, DB> + Type<DB>,
+    NaiveDateTime: Encode<'args, DB> + Type<DB>,
+{
+    let column = page.sort.column;
+    let (after, direction) = if page.sort.descending {
+        ("<", "DESC")
+    } else {
+        (">", "ASC")
+    };
+
+    if let Some(cursor) = &page.cursor {
+        match &cursor.value {
+            _ if column == id_column => {
+                query
+                    .push(format!(" AND {} {} ", id_column, after))
+                    .push_bind(cursor.id);
+            }
+            SortValue::Null => {
+                query
+                    .push(format!(
+                        " AND {} IS NULL AND {} {} ",
+                        column, id_column, after
+                    ))
+                    .push_bind(cursor.id);
+            }
+            value => {
+                // Past the cursor's value, level with it and past its id,
+                // or null, as nulls come last
+                query.push(format!(" AND ({} IS NULL OR {} {} ", column, column, after));
+                push_value(query, value);
+                query.push(format!(" OR ({} = ", column));
+                push_value(query, value);
+                query
+                    .push(format!(" AND {} {} ", id_column, after))
+                    .push_bind(cursor.id)
+                    .push("))");
+            }
+        }
+    }
+
+    if column == id_column {
+        query.push(format!(" ORDER BY {} {}", id_column, direction));
+    } else {
+        query.push(format!(
+            " ORDER BY {} {} NULLS LAST, {} {}",
+            column, direction, id_column, direction
+        ));
+    }
+    query.push(" LIMIT ").push_bind(page.limit + 1);
+    if page.cursor.is_none() {
+        query.push(" OFFSET ").push_bind(page.offset);
+    }
+}
+
+fn push_value<'args, DB>(query: &mut QueryBuilder<'args, DB>, value: &SortValue)
+where
+    DB: Database,
+    i32: Encode<'args, DB> + Type<DB>,
+    String: Encode<'args, DB> + Type<DB>,
+    NaiveDateTime: Encode<'args, DB> + Type<DB>,
+{
+    match value {
+        SortValue::Null => query.push("NULL"),
+        SortValue::Int(value) => query.push_bind(*value),
+        SortValue::Text(value) => query.push_bind(value.clone()),
+        SortValue::Time(value) => query.push_bind(*value),
+    };
+}
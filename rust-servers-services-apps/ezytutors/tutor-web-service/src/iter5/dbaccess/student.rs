@@ -0,0 +1,63 @@
+use crate::dbaccess::enrollment::student_courses_sql;
+use crate::errors::EzyTutorError;
+use crate::models::auth::NewUser;
+use crate::models::enrollment::{EnrollmentQuery, StudentCourse};
+use crate::models::student::{NewStudent, Student};
+use sqlx::postgres::PgPool;
+
+pub async fn get_student_db(pool: &PgPool, student_id: i32) -> Result<Student, EzyTutorError> {
+    sqlx::query_as("SELECT student_id, student_name FROM ezy_student_c6 WHERE student_id = $1")
+        .bind(student_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| EzyTutorError::NotFound("Student id not found".into()))
+}
+
+// Create a student and the login that owns it. Neither is created if the
+// username is taken.
+pub async fn post_new_student_db(
+    pool: &PgPool,
+    new_student: NewStudent,
+    login: NewUser,
+) -> Result<Student, EzyTutorError> {
+    let mut tx = pool.begin().await?;
+    let student: Student = sqlx::query_as(
+        "INSERT INTO ezy_student_c6 (student_name) VALUES ($1) \
+        RETURNING student_id, student_name",
+    )
+    .bind(new_student.student_name)
+    .fetch_one(&mut tx)
+    .await?;
+    sqlx::query(
+        "INSERT INTO ezy_user_c6 (username, student_id, user_password) VALUES ($1, $2, $3)",
+    )
+    .bind(&login.username)
+    .bind(student.student_id)
+    .bind(&login.user_password)
+    .execute(&mut tx)
+    .await
+    .map_err(
+        |err| match err.as_database_error().and_then(|err| err.code()) {
+            // unique_violation
+            Some(code) if code == "23505" => {
+                EzyTutorError::InvalidInput(format!("Username {} is taken", login.username))
+            }
+            _ => err.into(),
+        },
+    )?;
+    tx.commit().await?;
+    Ok(student)
+}
+
+pub async fn get_student_courses_db(
+    pool: &PgPool,
+    student_id: i32,
+    query: &EnrollmentQuery,
+) -> Result<Vec<StudentCourse>, EzyTutorError> {
+    get_student_db(pool, student_id).await?;
+    let courses = student_courses_sql(student_id, query)
+        .build_query_as()
+        .fetch_all(pool)
+        .await?;
+    Ok(courses)
+}
@@ -0,0 +1,169 @@
+use crate::dbaccess::course::{get_course_details_db, push_course_filters};
+use crate::dbaccess::paging::push_page;
+use crate::errors::EzyTutorError;
+use crate::models::course::{Course, CourseQuery};
+use crate::models::paging::Page;
+use crate::models::tag::{NewTag, Tag, TagCount, UpdateTag};
+use sqlx::postgres::PgPool;
+use sqlx::{Database, Encode, QueryBuilder, Type};
+
+pub async fn get_all_tags_db(pool: &PgPool) -> Result<Vec<Tag>, EzyTutorError> {
+    let tags = sqlx::query_as("SELECT tag_id, tag_name FROM ezy_tag_c6 ORDER BY tag_name")
+        .fetch_all(pool)
+        .await?;
+    Ok(tags)
+}
+
+pub async fn get_tag_db(pool: &PgPool, tag_id: i32) -> Result<Tag, EzyTutorError> {
+    sqlx::query_as("SELECT tag_id, tag_name FROM ezy_tag_c6 WHERE tag_id = $1")
+        .bind(tag_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| EzyTutorError::NotFound("Tag id not found".into()))
+}
+
+// Most used tags first. Tags no course has are counted too.
+pub async fn get_tag_counts_db(pool: &PgPool) -> Result<Vec<TagCount>, EzyTutorError> {
+    let counts = sqlx::query_as(
+        "SELECT tag_id, tag_name, count(course_id) AS course_count FROM ezy_tag_c6 \
+        LEFT JOIN ezy_course_tag_c6 USING (tag_id) GROUP BY tag_id, tag_name \
+        ORDER BY course_count DESC, tag_name",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(counts)
+}
+
+fn tag_taken(err: sqlx::Error, tag_name: &str) -> EzyTutorError {
+    match err.as_database_error().and_then(|err| err.code()) {
+        // unique_violation
+        Some(code) if code == "23505" => {
+            EzyTutorError::InvalidInput(format!("Tag {} already exists", tag_name))
+        }
+        _ => err.into(),
+    }
+}
+
+pub async fn post_new_tag_db(pool: &PgPool, new_tag: NewTag) -> Result<Tag, EzyTutorError> {
+    sqlx::query_as("INSERT INTO ezy_tag_c6 (tag_name) VALUES ($1) RETURNING tag_id, tag_name")
+        .bind(&new_tag.tag_name)
+        .fetch_one(pool)
+        .await
+        .map_err(|err| tag_taken(err, &new_tag.tag_name))
+}
+
+pub async fn update_tag_db(
+    pool: &PgPool,
+    tag_id: i32,
+    update_tag: UpdateTag,
+) -> Result<Tag, EzyTutorError> {
+    sqlx::query_as(
+        "UPDATE ezy_tag_c6 SET tag_name = $1 WHERE tag_id = $2 RETURNING tag_id, tag_name",
+    )
+    .bind(&update_tag.tag_name)
+    .bind(tag_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|err| tag_taken(err, &update_tag.tag_name))?
+    .ok_or_else(|| EzyTutorError::NotFound("Tag id not found".into()))
+}
+
+pub async fn delete_tag_db(pool: &PgPool, tag_id: i32) -> Result<String, EzyTutorError> {
+    let deleted = sqlx::query("DELETE FROM ezy_tag_c6 WHERE tag_id = $1")
+        .bind(tag_id)
+        .execute(pool)
+        .await?;
+    Ok(format!("Deleted {} record", deleted.rows_affected()))
+}
+
+pub async fn get_courses_with_tag_db(
+    pool: &PgPool,
+    tag_id: i32,
+    query: &CourseQuery,
+) -> Result<Page<Course>, EzyTutorError> {
+    let page = query.page_request()?;
+    let filters = query.filters();
+    filters.check()?;
+    get_tag_db(pool, tag_id).await?;
+
+    let mut count = QueryBuilder::new("SELECT count(*) FROM ezy_course_c6 WHERE true");
+    push_course_filters(&mut count, None, &filters);
+    push_with_tag(&mut count, tag_id);
+    let (total,): (i64,) = count.build_query_as().fetch_one(pool).await?;
+
+    let mut select = QueryBuilder::new("SELECT * FROM ezy_course_c6 WHERE true");
+    push_course_filters(&mut select, None, &filters);
+    push_with_tag(&mut select, tag_id);
+    push_page(&mut select, "course_id", &page);
+    let course_rows: Vec<Course> = select.build_query_as().fetch_all(pool).await?;
+
+    Ok(page.into_page(course_rows, total))
+}
+
+// Keep to the courses that have a tag. The SQL is the same for Postgres and
+// SQLite.
+pub fn push_with_tag<'args, DB>(sql: &mut QueryBuilder<'args, DB>, tag_id: i32)
+where
+    DB: Database,
+    i32: Encode<'args, DB> + Type<DB>,
+{
+    sql.push(" AND course_id IN (SELECT course_id FROM ezy_course_tag_c6 WHERE tag_id = ")
+        .push_bind(tag_id)
+        .push(")");
+}
+
+pub async fn get_course_tags_db(
+    pool: &PgPool,
+    tutor_id: i32,
+    course_id: i32,
+) -> Result<Vec<Tag>, EzyTutorError> {
+    get_course_details_db(pool, tutor_id, course_id).await?;
+    let tags = sqlx::query_as(
+        "SELECT tag_id, tag_name FROM ezy_tag_c6 JOIN ezy_course_tag_c6 USING (tag_id) \
+        WHERE course_id = $1 ORDER BY tag_name",
+    )
+    .bind(course_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(tags)
+}
+
+pub async fn add_course_tag_db(
+    pool: &PgPool,
+    tutor_id: i32,
+    course_id: i32,
+    tag_id: i32,
+) -> Result<(), EzyTutorError> {
+    get_course_details_db(pool, tutor_id, course_id).await?;
+    sqlx::query(
+        "INSERT INTO ezy_course_tag_c6 (course_id, tag_id) VALUES ($1, $2) \
+        ON CONFLICT DO NOTHING",
+    )
+    .bind(course_id)
+    .bind(tag_id)
+    .execute(pool)
+    .await
+    .map_err(
+        |err| match err.as_database_error().and_then(|err| err.code()) {
+            // foreign_key_violation
+            Some(code) if code == "23503" => EzyTutorError::NotFound("Tag id not found".into()),
+            _ => err.into(),
+        },
+    )?;
+    Ok(())
+}
+
+pub async fn remove_course_tag_db(
+    pool: &PgPool,
+    tutor_id: i32,
+    course_id: i32,
+    tag_id: i32,
+) -> Result<(), EzyTutorError> {
+    get_course_details_db(pool, tutor_id, course_id).await?;
+    sqlx::query("DELETE FROM ezy_course_tag_c6 WHERE course_id = $1 AND tag_id = $2")
+        .bind(course_id)
+        .bind(tag_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
@@ -1,28 +1,29 @@
+use crate::dbaccess::paging::push_page;
 use crate::errors::EzyTutorError;
-use crate::models::tutor::{NewTutor, Tutor, UpdateTutor};
+use crate::models::auth::NewUser;
+use crate::models::paging::Page;
+use crate::models::tutor::{NewTutor, Tutor, TutorQuery, UpdateTutor};
 use sqlx::postgres::PgPool;
+use sqlx::QueryBuilder;
 
-pub async fn get_all_tutors_db(pool: &PgPool) -> Result<Vec<Tutor>, EzyTutorError> {
-    // Prepare SQL statement
-    let tutor_rows =
-        sqlx::query!("SELECT tutor_id, tutor_name, tutor_pic_url, tutor_profile FROM ezy_tutor_c6")
-            .fetch_all(pool)
-            .await?;
-    // Extract result
+// An empty page is a valid answer, even when there are no tutors at all
+pub async fn get_all_tutors_db(
+    pool: &PgPool,
+    query: &TutorQuery,
+) -> Result<Page<Tutor>, EzyTutorError> {
+    let page = query.page_request()?;
 
-    let tutors: Vec<Tutor> = tutor_rows
-        .iter()
-        .map(|tutor_row| Tutor {
-            tutor_id: tutor_row.tutor_id,
-            tutor_name: tutor_row.tutor_name.clone(),
-            tutor_pic_url: tutor_row.tutor_pic_url.clone(),
-            tutor_profile: tutor_row.tutor_profile.clone(),
-        })
-        .collect();
-    match tutors.len() {
-        0 => Err(EzyTutorError::NotFound("No tutors found".into())),
-        _ => Ok(tutors),
-    }
+    let (total,): (i64,) = sqlx::query_as("SELECT count(*) FROM ezy_tutor_c6")
+        .fetch_one(pool)
+        .await?;
+
+    let mut select = QueryBuilder::new(
+        "SELECT tutor_id, tutor_name, tutor_pic_url, tutor_profile FROM ezy_tutor_c6 WHERE true",
+    );
+    push_page(&mut select, "tutor_id", &page);
+    let tutor_rows: Vec<Tutor> = select.build_query_as().fetch_all(pool).await?;
+
+    Ok(page.into_page(tutor_rows, total))
 }
 
 //Return result
@@ -98,10 +99,37 @@ pub async fn update_tutor_details_db(
     Ok(tutor_updated_row)
 }
 
-pub async fn post_new_tutor_db(pool: &PgPool, new_tutor: NewTutor) -> Result<Tutor, EzyTutorError> {
+// Create a tutor, and the login that owns it if one is given. Neither is
+// created if the username is taken.
+pub async fn post_new_tutor_db(
+    pool: &PgPool,
+    new_tutor: NewTutor,
+    login: Option<NewUser>,
+) -> Result<Tutor, EzyTutorError> {
+    let mut tx = pool.begin().await?;
     let tutor_row = sqlx::query!("insert into ezy_tutor_c6 (tutor_name, tutor_pic_url, tutor_profile) values ($1,$2,$3) returning tutor_id, tutor_name, tutor_pic_url, tutor_profile", new_tutor.tutor_name, new_tutor.tutor_pic_url, new_tutor.tutor_profile)
-    .fetch_one(pool)
+    .fetch_one(&mut tx)
     .await?;
+    if let Some(login) = login {
+        sqlx::query!(
+            "insert into ezy_user_c6 (username, tutor_id, user_password) values ($1, $2, $3)",
+            login.username,
+            tutor_row.tutor_id,
+            login.user_password
+        )
+        .execute(&mut tx)
+        .await
+        .map_err(
+            |err| match err.as_database_error().and_then(|err| err.code()) {
+                // unique_violation
+                Some(code) if code == "23505" => {
+                    EzyTutorError::InvalidInput(format!("Username {} is taken", login.username))
+                }
+                _ => err.into(),
+            },
+        )?;
+    }
+    tx.commit().await?;
     //Retrieve result
     Ok(Tutor {
         tutor_id: tutor_row.tutor_id,
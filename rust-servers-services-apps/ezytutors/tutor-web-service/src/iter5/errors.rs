@@ -1,7 +1,10 @@
-use actix_web::{error, http::StatusCode, HttpResponse, Result};
+use actix_web::{error, http::header, http::StatusCode, HttpResponse, Result};
 use serde::Serialize;
 use sqlx::error::Error as SQLxError;
+use std::collections::BTreeMap;
 use std::fmt;
+use utoipa::ToSchema;
+use validator::{ValidationError, ValidationErrors};
 
 #[derive(Debug, Serialize)]
 pub enum EzyTutorError {
@@ -9,10 +12,16 @@ pub enum EzyTutorError {
     ActixError(String),
     NotFound(String),
     InvalidInput(String),
+    Unauthorized(String),
+    Forbidden(String),
+    // What is wrong with each field of a request body
+    InvalidFields(BTreeMap<String, Vec<String>>),
 }
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct MyErrorResponse {
     error_message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    field_errors: Option<BTreeMap<String, Vec<String>>>,
 }
 impl std::error::Error for EzyTutorError {}
 
@@ -35,6 +44,18 @@ impl EzyTutorError {
                 println!("Not found error occurred: {:?}", msg);
                 msg.into()
             }
+            EzyTutorError::Unauthorized(msg) => {
+                println!("Unauthenticated request: {:?}", msg);
+                msg.into()
+            }
+            EzyTutorError::Forbidden(msg) => {
+                println!("Forbidden request: {:?}", msg);
+                msg.into()
+            }
+            EzyTutorError::InvalidFields(fields) => {
+                println!("Invalid fields received: {:?}", fields);
+                "Please correct the fields in error".into()
+            }
         }
     }
 }
@@ -47,18 +68,40 @@ impl error::ResponseError for EzyTutorError {
             }
             EzyTutorError::InvalidInput(_msg) => StatusCode::BAD_REQUEST,
             EzyTutorError::NotFound(_msg) => StatusCode::NOT_FOUND,
+            EzyTutorError::Unauthorized(_msg) => StatusCode::UNAUTHORIZED,
+            EzyTutorError::Forbidden(_msg) => StatusCode::FORBIDDEN,
+            EzyTutorError::InvalidFields(_fields) => StatusCode::UNPROCESSABLE_ENTITY,
         }
     }
     fn error_response(&self) -> HttpResponse {
-        HttpResponse::build(self.status_code()).json(MyErrorResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if let EzyTutorError::Unauthorized(_msg) = self {
+            response.insert_header((header::WWW_AUTHENTICATE, "Bearer"));
+        }
+        response.json(MyErrorResponse {
             error_message: self.error_response(),
+            field_errors: match self {
+                EzyTutorError::InvalidFields(fields) => Some(fields.clone()),
+                _ => None,
+            },
         })
     }
 }
 
 impl fmt::Display for EzyTutorError {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        write!(f, "{}", self)
+        match self {
+            EzyTutorError::DBError(msg)
+            | EzyTutorError::ActixError(msg)
+            | EzyTutorError::NotFound(msg)
+            | EzyTutorError::InvalidInput(msg)
+            | EzyTutorError::Unauthorized(msg)
+            | EzyTutorError::Forbidden(msg) => write!(f, "{}", msg),
+            EzyTutorError::InvalidFields(fields) => {
+                let names: Vec<&str> = fields.keys().map(String::as_str).collect();
+                write!(f, "Invalid fields: {}", names.join(", "))
+            }
+        }
     }
 }
 
@@ -73,3 +116,46 @@ impl From<SQLxError> for EzyTutorError {
         EzyTutorError::DBError(err.to_string())
     }
 }
+
+impl From<ValidationErrors> for EzyTutorError {
+    fn from(errors: ValidationErrors) -> Self {
+        let fields = errors
+            .field_errors()
+            .into_iter()
+            .map(|(field, errors)| (field.to_string(), errors.iter().map(describe).collect()))
+            .collect();
+        EzyTutorError::InvalidFields(fields)
+    }
+}
+
+// A message for a failed check, from its code and parameters unless the
+// check gave one
+fn describe(error: &ValidationError) -> String {
+    if let Some(message) = &error.message {
+        return message.to_string();
+    }
+    let number = |name: &str| error.params.get(name).and_then(|value| value.as_f64());
+    match error.code.as_ref() {
+        "length" => {
+            let length = error
+                .params
+                .get("value")
+                .and_then(|value| value.as_str())
+                .map_or(0, |value| value.chars().count()) as f64;
+            match (number("min"), number("max")) {
+                (Some(min), _) if length < min && min == 1.0 => "must not be empty".into(),
+                (Some(min), _) if length < min => format!("must be at least {} characters", min),
+                (_, Some(max)) => format!("must be at most {} characters", max),
+                _ => "has the wrong length".into(),
+            }
+        }
+        "range" => match (number("min"), number("max")) {
+            (Some(0.0), _) => "must not be negative".into(),
+            (Some(min), None) => format!("must be at least {}", min),
+            (None, Some(max)) => format!("must be at most {}", max),
+            _ => "is out of range".into(),
+        },
+        "url" => "must be a valid URL".into(),
+        code => format!("failed the {} check", code),
+    }
+}
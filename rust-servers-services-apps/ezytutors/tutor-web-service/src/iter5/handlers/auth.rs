@@ -0,0 +1,134 @@
+use crate::auth::{verify_password, JwtKeys, TOKEN_TTL};
+use crate::errors::{EzyTutorError, MyErrorResponse};
+use crate::models::auth::{TokenRequest, TokenResponse};
+use crate::state::AppState;
+use actix_web::{web, HttpResponse};
+
+/*
+curl -X POST localhost:3000/auth/token -H "Content-Type: application/json" -d '{"username":"jessica", "password":"correct horse"}'
+*/
+#[utoipa::path(
+    post,
+    path = "/auth/token",
+    tag = "auth",
+    request_body = TokenRequest,
+    responses(
+        (status = 200, description = "A bearer token for the user", body = TokenResponse),
+        (status = 400, description = "Invalid input", body = MyErrorResponse),
+        (status = 401, description = "Invalid username or password", body = MyErrorResponse),
+    )
+)]
+pub async fn issue_token(
+    app_state: web::Data<AppState>,
+    keys: web::Data<JwtKeys>,
+    credentials: web::Json<TokenRequest>,
+) -> Result<HttpResponse, EzyTutorError> {
+    let user = app_state
+        .tutors
+        .get_user(&credentials.username)
+        .await?
+        .filter(|user| verify_password(&user.user_password, &credentials.password))
+        .ok_or_else(|| EzyTutorError::Unauthorized("Invalid username or password".into()))?;
+    Ok(HttpResponse::Ok().json(TokenResponse {
+        access_token: keys.issue(&user)?,
+        token_type: "Bearer".into(),
+        expires_in: TOKEN_TTL,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::auth::JwtKeys;
+    use crate::models::auth::TokenResponse;
+    use crate::repository::memory::MemoryRepository;
+    use crate::routes::{auth_routes, tutor_routes};
+    use crate::state::AppState;
+    use actix_web::http::{header, StatusCode};
+    use actix_web::{test, web, App};
+    use serde_json::{json, Value};
+
+    // Sign up, log in, and change only your own profile
+    #[actix_rt::test]
+    async fn token_and_ownership_test() {
+        let app_state: web::Data<AppState> =
+            web::Data::new(AppState::new("", MemoryRepository::with_sample_data()));
+        let app = test::init_service(
+            App::new()
+                .app_data(app_state)
+                .app_data(web::Data::new(JwtKeys::hs256(b"test secret")))
+                .configure(auth_routes)
+                .configure(tutor_routes),
+        )
+        .await;
+
+        let new_tutor = json!({
+            "tutor_name": "Jessica",
+            "tutor_pic_url": "http://tutor1.com/tutor1.pic",
+            "tutor_profile": "Experienced professional",
+            "username": "jessica",
+            "password": "correct horse",
+        });
+        let req = test::TestRequest::post()
+            .uri("/tutors/")
+            .set_json(&new_tutor)
+            .to_request();
+        let tutor: Value = test::call_and_read_body_json(&app, req).await;
+        let tutor_id = tutor["tutor_id"].as_i64().unwrap();
+        let req = test::TestRequest::post()
+            .uri("/tutors/")
+            .set_json(&new_tutor)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::post()
+            .uri("/auth/token")
+            .set_json(json!({"username": "jessica", "password": "wrong horse"}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let req = test::TestRequest::post()
+            .uri("/auth/token")
+            .set_json(json!({"username": "jessica", "password": "correct horse"}))
+            .to_request();
+        let token: TokenResponse = test::call_and_read_body_json(&app, req).await;
+        let bearer = format!("Bearer {}", token.access_token);
+
+        let update = json!({"tutor_profile": "Teaches finance"});
+        for (uri, authorization, status) in [
+            (
+                format!("/tutors/{}", tutor_id),
+                None,
+                StatusCode::UNAUTHORIZED,
+            ),
+            (
+                format!("/tutors/{}", tutor_id),
+                Some("Bearer not-a-token"),
+                StatusCode::UNAUTHORIZED,
+            ),
+            (
+                format!("/tutors/{}", tutor_id),
+                Some(&*bearer),
+                StatusCode::OK,
+            ),
+            (
+                "/tutors/1".to_string(),
+                Some(&*bearer),
+                StatusCode::FORBIDDEN,
+            ),
+        ] {
+            let mut req = test::TestRequest::put().uri(&uri).set_json(&update);
+            if let Some(authorization) = authorization {
+                req = req.insert_header((header::AUTHORIZATION, authorization));
+            }
+            let resp = test::call_service(&app, req.to_request()).await;
+            assert_eq!(
+                resp.status(),
+                status,
+                "PUT {} with {:?}",
+                uri,
+                authorization
+            );
+        }
+    }
+}
@@ -0,0 +1,418 @@
+use crate::auth::AuthUser;
+use crate::errors::{EzyTutorError, MyErrorResponse};
+use crate::handlers::paging::paged_response;
+use crate::models::category::{Category, NewCategory, UpdateCategory};
+use crate::models::course::{Course, CourseQuery};
+use crate::models::paging::Page;
+use crate::state::AppState;
+use actix_web::{web, HttpRequest, HttpResponse};
+use validator::Validate;
+
+// curl localhost:3000/categories
+#[utoipa::path(
+    get,
+    path = "/categories",
+    tag = "categories",
+    responses(
+        (status = 200, description = "All categories, each after its parent", body = Vec<Category>),
+    )
+)]
+pub async fn get_all_categories(
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, EzyTutorError> {
+    app_state
+        .categories
+        .get_all_categories()
+        .await
+        .map(|categories| HttpResponse::Ok().json(categories))
+}
+
+#[utoipa::path(
+    get,
+    path = "/categories/{category_id}",
+    tag = "categories",
+    params(("category_id" = i32, Path, description = "Id of the category")),
+    responses(
+        (status = 200, description = "The category", body = Category),
+        (status = 404, description = "Category not found", body = MyErrorResponse),
+    )
+)]
+pub async fn get_category(
+    app_state: web::Data<AppState>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, EzyTutorError> {
+    app_state
+        .categories
+        .get_category(path.into_inner())
+        .await
+        .map(|category| HttpResponse::Ok().json(category))
+}
+
+/*
+curl -X POST localhost:3000/categories -H "Authorization: Bearer $TOKEN" -H "Content-Type: application/json" -d '{"category_name":"Corporate finance", "parent_id":2}'
+*/
+// The category tree is shared by all tutors, so only admins may change it
+#[utoipa::path(
+    post,
+    path = "/categories",
+    tag = "categories",
+    request_body = NewCategory,
+    responses(
+        (status = 200, description = "The new category", body = Category),
+        (status = 400, description = "Invalid input", body = MyErrorResponse),
+        (status = 401, description = "Missing or invalid bearer token", body = MyErrorResponse),
+        (status = 403, description = "The token is not an admin's", body = MyErrorResponse),
+        (status = 404, description = "Parent category not found", body = MyErrorResponse),
+        (status = 422, description = "Fields of the request body out of bounds", body = MyErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn post_new_category(
+    new_category: web::Json<NewCategory>,
+    app_state: web::Data<AppState>,
+    user: AuthUser,
+) -> Result<HttpResponse, EzyTutorError> {
+    user.check_admin()?;
+    new_category.validate()?;
+    app_state
+        .categories
+        .post_new_category(new_category.into_inner())
+        .await
+        .map(|category| HttpResponse::Ok().json(category))
+}
+
+/*
+curl -X PUT localhost:3000/categories/6 -H "Authorization: Bearer $TOKEN" -H "Content-Type: application/json" -d '{"category_name":"Accounting"}'
+*/
+#[utoipa::path(
+    put,
+    path = "/categories/{category_id}",
+    tag = "categories",
+    params(("category_id" = i32, Path, description = "Id of the category")),
+    request_body = UpdateCategory,
+    responses(
+        (status = 200, description = "The renamed category", body = Category),
+        (status = 400, description = "Invalid input", body = MyErrorResponse),
+        (status = 401, description = "Missing or invalid bearer token", body = MyErrorResponse),
+        (status = 403, description = "The token is not an admin's", body = MyErrorResponse),
+        (status = 404, description = "Category not found", body = MyErrorResponse),
+        (status = 422, description = "Fields of the request body out of bounds", body = MyErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn update_category(
+    app_state: web::Data<AppState>,
+    path: web::Path<i32>,
+    update_category: web::Json<UpdateCategory>,
+    user: AuthUser,
+) -> Result<HttpResponse, EzyTutorError> {
+    user.check_admin()?;
+    update_category.validate()?;
+    app_state
+        .categories
+        .update_category(path.into_inner(), update_category.into_inner())
+        .await
+        .map(|category| HttpResponse::Ok().json(category))
+}
+
+// curl -X DELETE localhost:3000/categories/6 -H "Authorization: Bearer $TOKEN"
+#[utoipa::path(
+    delete,
+    path = "/categories/{category_id}",
+    tag = "categories",
+    params(("category_id" = i32, Path, description = "Id of the category")),
+    responses(
+        (status = 200, description = "How many categories were deleted, not counting those below it", body = String),
+        (status = 401, description = "Missing or invalid bearer token", body = MyErrorResponse),
+        (status = 403, description = "The token is not an admin's", body = MyErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn delete_category(
+    app_state: web::Data<AppState>,
+    path: web::Path<i32>,
+    user: AuthUser,
+) -> Result<HttpResponse, EzyTutorError> {
+    user.check_admin()?;
+    app_state
+        .categories
+        .delete_category(path.into_inner())
+        .await
+        .map(|resp| HttpResponse::Ok().json(resp))
+}
+
+/*
+curl "localhost:3000/categories/3/courses?sort=course_name&course_format=ebook"
+*/
+#[utoipa::path(
+    get,
+    path = "/categories/{category_id}/courses",
+    tag = "categories",
+    params(("category_id" = i32, Path, description = "Id of the category"), CourseQuery),
+    responses(
+        (status = 200, description = "A page of the courses in the category or any category below it, with a Link header to the other pages", body = Page<Course>),
+        (status = 400, description = "Invalid input", body = MyErrorResponse),
+        (status = 404, description = "Category not found", body = MyErrorResponse),
+    )
+)]
+pub async fn get_courses_in_category(
+    app_state: web::Data<AppState>,
+    path: web::Path<i32>,
+    query: web::Query<CourseQuery>,
+    req: HttpRequest,
+) -> Result<HttpResponse, EzyTutorError> {
+    app_state
+        .categories
+        .get_courses_in_category(path.into_inner(), &query)
+        .await
+        .map(|courses| paged_response(&req, &*query, courses))
+}
+
+// curl localhost:3000/courses/1/1/categories
+#[utoipa::path(
+    get,
+    path = "/courses/{tutor_id}/{course_id}/categories",
+    tag = "categories",
+    params(
+        ("tutor_id" = i32, Path, description = "Id of the tutor"),
+        ("course_id" = i32, Path, description = "Id of the course"),
+    ),
+    responses(
+        (status = 200, description = "The categories the course was put in", body = Vec<Category>),
+        (status = 404, description = "Course not found", body = MyErrorResponse),
+    )
+)]
+pub async fn get_course_categories(
+    app_state: web::Data<AppState>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse, EzyTutorError> {
+    let (tutor_id, course_id) = path.into_inner();
+    app_state
+        .categories
+        .get_course_categories(tutor_id, course_id)
+        .await
+        .map(|categories| HttpResponse::Ok().json(categories))
+}
+
+// curl -X PUT localhost:3000/courses/1/1/categories/1 -H "Authorization: Bearer $TOKEN"
+#[utoipa::path(
+    put,
+    path = "/courses/{tutor_id}/{course_id}/categories/{category_id}",
+    tag = "categories",
+    params(
+        ("tutor_id" = i32, Path, description = "Id of the tutor"),
+        ("course_id" = i32, Path, description = "Id of the course"),
+        ("category_id" = i32, Path, description = "Id of the category"),
+    ),
+    responses(
+        (status = 200, description = "The categories the course is now in", body = Vec<Category>),
+        (status = 401, description = "Missing or invalid bearer token", body = MyErrorResponse),
+        (status = 403, description = "The token is not for this tutor", body = MyErrorResponse),
+        (status = 404, description = "Course or category not found", body = MyErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn add_course_category(
+    app_state: web::Data<AppState>,
+    path: web::Path<(i32, i32, i32)>,
+    user: AuthUser,
+) -> Result<HttpResponse, EzyTutorError> {
+    let (tutor_id, course_id, category_id) = path.into_inner();
+    user.check_owner(tutor_id)?;
+    app_state
+        .categories
+        .add_course_category(tutor_id, course_id, category_id)
+        .await?;
+    get_course_categories(app_state, web::Path::from((tutor_id, course_id))).await
+}
+
+// curl -X DELETE localhost:3000/courses/1/1/categories/1 -H "Authorization: Bearer $TOKEN"
+#[utoipa::path(
+    delete,
+    path = "/courses/{tutor_id}/{course_id}/categories/{category_id}",
+    tag = "categories",
+    params(
+        ("tutor_id" = i32, Path, description = "Id of the tutor"),
+        ("course_id" = i32, Path, description = "Id of the course"),
+        ("category_id" = i32, Path, description = "Id of the category"),
+    ),
+    responses(
+        (status = 200, description = "The categories the course is still in", body = Vec<Category>),
+        (status = 401, description = "Missing or invalid bearer token", body = MyErrorResponse),
+        (status = 403, description = "The token is not for this tutor", body = MyErrorResponse),
+        (status = 404, description = "Course not found", body = MyErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn remove_course_category(
+    app_state: web::Data<AppState>,
+    path: web::Path<(i32, i32, i32)>,
+    user: AuthUser,
+) -> Result<HttpResponse, EzyTutorError> {
+    let (tutor_id, course_id, category_id) = path.into_inner();
+    user.check_owner(tutor_id)?;
+    app_state
+        .categories
+        .remove_course_category(tutor_id, course_id, category_id)
+        .await?;
+    get_course_categories(app_state, web::Path::from((tutor_id, course_id))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::memory::MemoryRepository;
+    use actix_web::body::to_bytes;
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use actix_web::ResponseError;
+    use serde_json::Value;
+
+    fn admin() -> AuthUser {
+        AuthUser {
+            username: "admin".into(),
+            tutor_id: None,
+            student_id: None,
+            admin: true,
+        }
+    }
+
+    fn tutor(tutor_id: i32) -> AuthUser {
+        AuthUser {
+            username: format!("tutor{}", tutor_id),
+            tutor_id: Some(tutor_id),
+            student_id: None,
+            admin: false,
+        }
+    }
+
+    async fn body(resp: HttpResponse) -> Value {
+        serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap()
+    }
+
+    // A course in a category is listed in the categories above it too
+    #[actix_rt::test]
+    async fn courses_in_category_test() {
+        let app_state: web::Data<AppState> =
+            web::Data::new(AppState::new("", MemoryRepository::with_sample_data()));
+        let req = TestRequest::default()
+            .uri("/categories/3/courses")
+            .to_http_request();
+        let resp = get_courses_in_category(
+            app_state.clone(),
+            web::Path::from(3),
+            web::Query(CourseQuery::default()),
+            req,
+        )
+        .await
+        .unwrap();
+        let page = body(resp).await;
+        assert_eq!(page["paging"]["total"], 2);
+        let ids: Vec<_> = page["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|course| course["course_id"].as_i64().unwrap())
+            .collect();
+        assert_eq!(ids, [5, 2]);
+
+        let req = TestRequest::default().to_http_request();
+        let err = get_courses_in_category(
+            app_state,
+            web::Path::from(99),
+            web::Query(CourseQuery::default()),
+            req,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+    }
+
+    // Only admins may change the tree; deleting a category deletes the
+    // categories below it
+    #[actix_rt::test]
+    async fn change_categories_test() {
+        let app_state: web::Data<AppState> =
+            web::Data::new(AppState::new("", MemoryRepository::with_sample_data()));
+        let new_category = NewCategory {
+            category_name: "Corporate finance".into(),
+            parent_id: Some(2),
+        };
+        let err = post_new_category(web::Json(new_category.clone()), app_state.clone(), tutor(1))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+        let resp = post_new_category(web::Json(new_category.clone()), app_state.clone(), admin())
+            .await
+            .unwrap();
+        let category = body(resp).await;
+        assert_eq!(category["parent_id"], 2);
+        let category_id = category["category_id"].as_i64().unwrap() as i32;
+
+        let orphan = NewCategory {
+            parent_id: Some(99),
+            ..new_category
+        };
+        let err = post_new_category(web::Json(orphan), app_state.clone(), admin())
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+        let unnamed = UpdateCategory {
+            category_name: "".into(),
+        };
+        let err = update_category(
+            app_state.clone(),
+            web::Path::from(category_id),
+            web::Json(unnamed),
+            admin(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        delete_category(app_state.clone(), web::Path::from(1), admin())
+            .await
+            .unwrap();
+        for category_id in [1, 2, category_id] {
+            let err = get_category(app_state.clone(), web::Path::from(category_id))
+                .await
+                .unwrap_err();
+            assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+        }
+        let resp = get_course_categories(app_state, web::Path::from((1, 1)))
+            .await
+            .unwrap();
+        assert_eq!(body(resp).await, serde_json::json!([]));
+    }
+
+    // Only the tutor of a course may put it in categories
+    #[actix_rt::test]
+    async fn add_course_category_test() {
+        let app_state: web::Data<AppState> =
+            web::Data::new(AppState::new("", MemoryRepository::with_sample_data()));
+        let err = add_course_category(app_state.clone(), web::Path::from((1, 1, 5)), tutor(2))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+        let resp = add_course_category(app_state.clone(), web::Path::from((1, 1, 5)), tutor(1))
+            .await
+            .unwrap();
+        let names: Vec<_> = body(resp)
+            .await
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|category| category["category_name"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(names, ["Finance", "Machine learning"]);
+        let err = add_course_category(app_state.clone(), web::Path::from((1, 2, 5)), tutor(1))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+
+        let resp = remove_course_category(app_state, web::Path::from((1, 1, 2)), tutor(1))
+            .await
+            .unwrap();
+        assert_eq!(body(resp).await.as_array().unwrap().len(), 1);
+    }
+}
@@ -1,62 +1,179 @@
-use crate::dbaccess::course::*;
-use crate::errors::EzyTutorError;
-use crate::models::course::{CreateCourse, UpdateCourse};
+use crate::auth::AuthUser;
+use crate::errors::{EzyTutorError, MyErrorResponse};
+use crate::handlers::paging::paged_response;
+use crate::models::course::{
+    Course, CourseQuery, CourseSearchQuery, CourseSearchResult, CreateCourse, UpdateCourse,
+};
+use crate::models::paging::Page;
 use crate::state::AppState;
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
+use validator::Validate;
 
+/*
+curl "localhost:3000/courses/1?limit=10&sort=-posted_time&course_format=ebook&max_price=100"
+*/
+#[utoipa::path(
+    get,
+    path = "/courses/{tutor_id}",
+    tag = "courses",
+    params(("tutor_id" = i32, Path, description = "Id of the tutor"), CourseQuery),
+    responses(
+        (status = 200, description = "A page of the tutor's courses, with a Link header to the other pages", body = Page<Course>),
+        (status = 400, description = "Invalid input", body = MyErrorResponse),
+    )
+)]
 pub async fn get_courses_for_tutor(
     app_state: web::Data<AppState>,
     /*web::Path(tutor_id)*/ path: web::Path<i32>,
+    query: web::Query<CourseQuery>,
+    req: HttpRequest,
 ) -> Result<HttpResponse, EzyTutorError> {
     let tutor_id = path.into_inner();
-    get_courses_for_tutor_db(&app_state.db, tutor_id)
+    app_state
+        .courses
+        .get_courses_for_tutor(tutor_id, &query)
+        .await
+        .map(|courses| paged_response(&req, &*query, courses))
+}
+
+/*
+curl "localhost:3000/courses/search?q=machine+learning+-beginner&course_language=english"
+*/
+#[utoipa::path(
+    get,
+    path = "/courses/search",
+    tag = "courses",
+    params(CourseSearchQuery),
+    responses(
+        (status = 200, description = "A page of the matching courses, best matches first", body = Page<CourseSearchResult>),
+        (status = 400, description = "Invalid input", body = MyErrorResponse),
+    )
+)]
+pub async fn search_courses(
+    app_state: web::Data<AppState>,
+    query: web::Query<CourseSearchQuery>,
+    req: HttpRequest,
+) -> Result<HttpResponse, EzyTutorError> {
+    app_state
+        .courses
+        .search_courses(&query)
         .await
-        .map(|courses| HttpResponse::Ok().json(courses))
+        .map(|results| paged_response(&req, &*query, results))
 }
 
+#[utoipa::path(
+    get,
+    path = "/courses/{tutor_id}/{course_id}",
+    tag = "courses",
+    params(("tutor_id" = i32, Path, description = "Id of the tutor"), ("course_id" = i32, Path, description = "Id of the course")),
+    responses(
+        (status = 200, description = "The course", body = Course),
+        (status = 404, description = "Course not found", body = MyErrorResponse),
+    )
+)]
 pub async fn get_course_details(
     app_state: web::Data<AppState>,
     /*web::Path((tutor_id, course_id))*/ path: web::Path<(i32, i32)>,
 ) -> Result<HttpResponse, EzyTutorError> {
     let (tutor_id, course_id) = path.into_inner();
-    get_course_details_db(&app_state.db, tutor_id, course_id)
+    app_state
+        .courses
+        .get_course_details(tutor_id, course_id)
         .await
         .map(|course| HttpResponse::Ok().json(course))
 }
 /*
-curl -X PUT localhost:3000/courses/1/5 -H "Content-Type: application/json"  -d '{"course_name":"Valid course 3", "course_duration":"Its a long, long course"}'
+curl -X PUT localhost:3000/courses/1/5 -H "Authorization: Bearer $TOKEN" -H "Content-Type: application/json"  -d '{"course_name":"Valid course 3", "course_duration":"Its a long, long course"}'
 */
+#[utoipa::path(
+    put,
+    path = "/courses/{tutor_id}/{course_id}",
+    tag = "courses",
+    params(("tutor_id" = i32, Path, description = "Id of the tutor"), ("course_id" = i32, Path, description = "Id of the course")),
+    request_body = UpdateCourse,
+    responses(
+        (status = 200, description = "The updated course", body = Course),
+        (status = 400, description = "Invalid input", body = MyErrorResponse),
+        (status = 401, description = "Missing or invalid bearer token", body = MyErrorResponse),
+        (status = 403, description = "The token is not for this tutor", body = MyErrorResponse),
+        (status = 404, description = "Course not found", body = MyErrorResponse),
+        (status = 422, description = "Fields of the request body out of bounds", body = MyErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn update_course_details(
     app_state: web::Data<AppState>,
     update_course: web::Json<UpdateCourse>,
     /*web::Path((tutor_id, course_id))*/ path: web::Path<(i32, i32)>,
+    user: AuthUser,
 ) -> Result<HttpResponse, EzyTutorError> {
     let (tutor_id, course_id) = path.into_inner();
-    update_course_details_db(&app_state.db, tutor_id, course_id, update_course.into())
+    user.check_owner(tutor_id)?;
+    update_course.validate()?;
+    app_state
+        .courses
+        .update_course_details(tutor_id, course_id, update_course.into())
         .await
         .map(|course| HttpResponse::Ok().json(course))
 }
 
 /* curl -X POST localhost:3000/courses/ \
+-H "Authorization: Bearer $TOKEN" \
 -H "Content-Type: application/json" \
  -d '{"tutor_id":1, "course_name":"Course 1"}'
 */
+#[utoipa::path(
+    post,
+    path = "/courses",
+    tag = "courses",
+    request_body = CreateCourse,
+    responses(
+        (status = 200, description = "The new course", body = Course),
+        (status = 400, description = "Invalid input", body = MyErrorResponse),
+        (status = 401, description = "Missing or invalid bearer token", body = MyErrorResponse),
+        (status = 403, description = "The token is not for this tutor", body = MyErrorResponse),
+        (status = 404, description = "Tutor not found", body = MyErrorResponse),
+        (status = 422, description = "Fields of the request body out of bounds", body = MyErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn post_new_course(
     new_course: web::Json<CreateCourse>,
     app_state: web::Data<AppState>,
+    user: AuthUser,
 ) -> Result<HttpResponse, EzyTutorError> {
-    post_new_course_db(&app_state.db, new_course.into())
+    user.check_owner(new_course.tutor_id)?;
+    new_course.validate()?;
+    app_state
+        .courses
+        .post_new_course(new_course.into())
         .await
         .map(|course| HttpResponse::Ok().json(course))
 }
-// curl -X DELETE http://localhost:3000/courses/1/6
+// curl -X DELETE http://localhost:3000/courses/1/6 -H "Authorization: Bearer $TOKEN"
 
+#[utoipa::path(
+    delete,
+    path = "/courses/{tutor_id}/{course_id}",
+    tag = "courses",
+    params(("tutor_id" = i32, Path, description = "Id of the tutor"), ("course_id" = i32, Path, description = "Id of the course")),
+    responses(
+        (status = 200, description = "How many courses were deleted", body = String),
+        (status = 401, description = "Missing or invalid bearer token", body = MyErrorResponse),
+        (status = 403, description = "The token is not for this tutor", body = MyErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn delete_course(
     app_state: web::Data<AppState>,
     /*web::Path((tutor_id, course_id))*/ path: web::Path<(i32, i32)>,
+    user: AuthUser,
 ) -> Result<HttpResponse, EzyTutorError> {
     let (tutor_id, course_id) = path.into_inner();
-    delete_course_db(&app_state.db, tutor_id, course_id)
+    user.check_owner(tutor_id)?;
+    app_state
+        .courses
+        .delete_course(tutor_id, course_id)
         .await
         .map(|resp| HttpResponse::Ok().json(resp))
 }
@@ -64,39 +181,229 @@ pub async fn delete_course(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::repository::memory::MemoryRepository;
+    use crate::repository::CourseRepository;
+    use crate::routes::course_routes;
+    use actix_web::body::to_bytes;
     use actix_web::http::StatusCode;
-    use actix_web::ResponseError;
-    use dotenv::dotenv;
-    use sqlx::postgres::PgPool;
-    use std::env;
-    use std::sync::Mutex;
+    use actix_web::test::{self, TestRequest};
+    use actix_web::{App, ResponseError};
+    use serde_json::Value;
+
+    fn tutor(tutor_id: i32) -> AuthUser {
+        AuthUser {
+            username: format!("tutor{}", tutor_id),
+            tutor_id: Some(tutor_id),
+            student_id: None,
+            admin: false,
+        }
+    }
 
     // Get list of all courses for a tutor
     #[actix_rt::test]
     async fn get_all_courses_success() {
-        dotenv().ok();
-        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
-        let pool: PgPool = PgPool::connect(&database_url).await.unwrap();
-        let app_state: web::Data<AppState> = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            visit_count: Mutex::new(0),
-            db: pool,
-        });
+        let app_state: web::Data<AppState> =
+            web::Data::new(AppState::new("", MemoryRepository::with_sample_data()));
         let tutor_id: web::Path<i32> = web::Path::from(1);
-        let resp = get_courses_for_tutor(app_state, tutor_id).await.unwrap();
+        let query = web::Query(CourseQuery::default());
+        let req = TestRequest::default().to_http_request();
+        let resp = get_courses_for_tutor(app_state, tutor_id, query, req)
+            .await
+            .unwrap();
         assert_eq!(resp.status(), StatusCode::OK);
     }
+    // Page through the ebooks of a tutor, by cursor and by offset
+    #[actix_rt::test]
+    async fn get_courses_paging_success_test() {
+        let app_state: web::Data<AppState> =
+            web::Data::new(AppState::new("", MemoryRepository::with_sample_data()));
+        let ebooks = CourseQuery {
+            limit: Some(1),
+            sort: Some("-course_id".into()),
+            course_format: Some("EBOOK".into()),
+            ..Default::default()
+        };
+        let req = TestRequest::default().uri("/courses/1").to_http_request();
+        let resp = get_courses_for_tutor(
+            app_state.clone(),
+            web::Path::from(1),
+            web::Query(ebooks.clone()),
+            req,
+        )
+        .await
+        .unwrap();
+        let link = resp
+            .headers()
+            .get("Link")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(link.contains(
+            r#"</courses/1?limit=1&sort=-course_id&course_format=EBOOK&offset=1>; rel="next""#
+        ));
+        let page: Value =
+            serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap();
+        assert_eq!(page["items"][0]["course_id"], 4);
+        assert_eq!(page["paging"]["total"], 2);
+        assert_eq!(page["paging"]["next_cursor"], "4:4");
+
+        // The last page has no next page
+        let req = TestRequest::default().uri("/courses/1").to_http_request();
+        let after_first = CourseQuery {
+            cursor: Some("4:4".into()),
+            ..ebooks.clone()
+        };
+        let resp = get_courses_for_tutor(
+            app_state.clone(),
+            web::Path::from(1),
+            web::Query(after_first),
+            req,
+        )
+        .await
+        .unwrap();
+        let link = resp
+            .headers()
+            .get("Link")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(!link.contains(r#"rel="next""#));
+        let page: Value =
+            serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap();
+        assert_eq!(page["items"][0]["course_id"], 3);
+        assert!(page["paging"]["next_cursor"].is_null());
+
+        let req = TestRequest::default().uri("/courses/1").to_http_request();
+        let second = CourseQuery {
+            offset: Some(1),
+            ..ebooks
+        };
+        let resp = get_courses_for_tutor(app_state, web::Path::from(1), web::Query(second), req)
+            .await
+            .unwrap();
+        let link = resp
+            .headers()
+            .get("Link")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(link.contains(r#"&offset=0>; rel="prev""#));
+        let page: Value =
+            serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap();
+        assert_eq!(page["items"][0]["course_id"], 3);
+        assert_eq!(page["paging"]["offset"], 1);
+    }
+    // Filters and sort columns are checked
+    #[actix_rt::test]
+    async fn get_courses_query_failure_test() {
+        let app_state: web::Data<AppState> =
+            web::Data::new(AppState::new("", MemoryRepository::with_sample_data()));
+        let no_price = CourseQuery {
+            min_price: Some(1),
+            ..Default::default()
+        };
+        let req = TestRequest::default().to_http_request();
+        let resp = get_courses_for_tutor(
+            app_state.clone(),
+            web::Path::from(1),
+            web::Query(no_price),
+            req,
+        )
+        .await
+        .unwrap();
+        let page: Value =
+            serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap();
+        assert_eq!(page["paging"]["total"], 0);
+
+        for query in [
+            CourseQuery {
+                sort: Some("course_description".into()),
+                ..Default::default()
+            },
+            CourseQuery {
+                min_price: Some(10),
+                max_price: Some(5),
+                ..Default::default()
+            },
+            CourseQuery {
+                limit: Some(0),
+                ..Default::default()
+            },
+        ] {
+            let req = TestRequest::default().to_http_request();
+            let resp = get_courses_for_tutor(
+                app_state.clone(),
+                web::Path::from(1),
+                web::Query(query),
+                req,
+            )
+            .await;
+            match resp {
+                Ok(_) => panic!("Invalid query accepted"),
+                Err(err) => assert_eq!(err.status_code(), StatusCode::BAD_REQUEST),
+            }
+        }
+    }
+    // Search all courses through the routes, so that /courses/search is
+    // not mistaken for a tutor's course list
+    #[actix_rt::test]
+    async fn search_courses_success_test() {
+        let repository = MemoryRepository::with_sample_data();
+        for (name, description) in [
+            (
+                "Physics for all",
+                Some("Ends with a look at quantum effects"),
+            ),
+            ("Quantum computing primer", None),
+        ] {
+            let new_course = CreateCourse {
+                tutor_id: 3,
+                course_name: name.into(),
+                course_description: description.map(String::from),
+                course_format: Some("podcast".into()),
+                course_level: None,
+                course_price: None,
+                course_duration: None,
+                course_language: None,
+                course_structure: None,
+            };
+            repository.post_new_course(new_course).await.unwrap();
+        }
+        let app_state: web::Data<AppState> = web::Data::new(AppState::new("", repository));
+        let app = test::init_service(App::new().app_data(app_state).configure(course_routes)).await;
+
+        // A match in the name ranks above one in the description
+        let req = test::TestRequest::get()
+            .uri("/courses/search?q=quantum&course_format=Podcast")
+            .to_request();
+        let page: Value = test::call_and_read_body_json(&app, req).await;
+        let items = page["items"].as_array().unwrap();
+        assert!(items.len() >= 2);
+        assert_eq!(items[0]["course_name"], "Quantum computing primer");
+        assert_eq!(items[0]["snippet"], "<mark>Quantum</mark> computing primer");
+        assert_eq!(items.last().unwrap()["course_name"], "Physics for all");
+        assert!(items[0]["rank"].as_f64() > items.last().unwrap()["rank"].as_f64());
+
+        let req = test::TestRequest::get()
+            .uri("/courses/search?q=quantum+-primer&course_format=podcast&limit=1")
+            .to_request();
+        let page: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(page["items"][0]["course_name"], "Physics for all");
+
+        let req = test::TestRequest::get()
+            .uri("/courses/search?q=+")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
     // Get course details for valid course id.
     #[actix_rt::test]
     async fn get_course_detail_success_test() {
-        dotenv().ok();
-        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
-        let pool: PgPool = PgPool::connect(&database_url).await.unwrap();
-        let app_state: web::Data<AppState> = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            visit_count: Mutex::new(0),
-            db: pool,
-        });
+        let app_state: web::Data<AppState> =
+            web::Data::new(AppState::new("", MemoryRepository::with_sample_data()));
         let parameters: web::Path<(i32, i32)> = web::Path::from((1, 1));
         let resp = get_course_details(app_state, parameters).await.unwrap();
         assert_eq!(resp.status(), StatusCode::OK);
@@ -105,14 +412,8 @@ mod tests {
     // Get course details with invalid course id.
     #[actix_rt::test]
     async fn get_course_detail_failure_test() {
-        dotenv().ok();
-        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
-        let pool: PgPool = PgPool::connect(&database_url).await.unwrap();
-        let app_state: web::Data<AppState> = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            visit_count: Mutex::new(0),
-            db: pool,
-        });
+        let app_state: web::Data<AppState> =
+            web::Data::new(AppState::new("", MemoryRepository::with_sample_data()));
         let parameters: web::Path<(i32, i32)> = web::Path::from((1, 21));
         let resp = get_course_details(app_state, parameters).await;
         match resp {
@@ -124,14 +425,8 @@ mod tests {
     //#[ignore]
     #[actix_rt::test]
     async fn post_course_success() {
-        dotenv().ok();
-        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
-        let pool: PgPool = PgPool::connect(&database_url).await.unwrap();
-        let app_state: web::Data<AppState> = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            visit_count: Mutex::new(0),
-            db: pool,
-        });
+        let app_state: web::Data<AppState> =
+            web::Data::new(AppState::new("", MemoryRepository::with_sample_data()));
         let new_course_msg = CreateCourse {
             tutor_id: 1,
             course_name: "Third course".into(),
@@ -144,20 +439,48 @@ mod tests {
             course_structure: None,
         };
         let course_param = web::Json(new_course_msg);
-        let resp = post_new_course(course_param, app_state).await.unwrap();
+        let resp = post_new_course(course_param, app_state, tutor(1))
+            .await
+            .unwrap();
         assert_eq!(resp.status(), StatusCode::OK);
     }
+    // Invalid fields are all reported, before anything is written
+    #[actix_rt::test]
+    async fn post_course_invalid_test() {
+        let app_state: web::Data<AppState> =
+            web::Data::new(AppState::new("", MemoryRepository::with_sample_data()));
+        let new_course_msg = CreateCourse {
+            tutor_id: 1,
+            course_name: "x".repeat(141),
+            course_description: None,
+            course_format: Some("Video".into()),
+            course_level: Some("Expert".into()),
+            course_price: Some(-5),
+            course_duration: None,
+            course_language: Some("English".into()),
+            course_structure: None,
+        };
+        let err = post_new_course(web::Json(new_course_msg), app_state, tutor(1))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: Value =
+            serde_json::from_slice(&to_bytes(err.error_response().into_body()).await.unwrap())
+                .unwrap();
+        assert_eq!(
+            body["field_errors"],
+            serde_json::json!({
+                "course_name": ["must be at most 140 characters"],
+                "course_level": ["must be one of: Beginner, Intermediate, Advanced"],
+                "course_price": ["must not be negative"],
+            })
+        );
+    }
     // Update course successfully
     #[actix_rt::test]
     async fn update_course_success() {
-        dotenv().ok();
-        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
-        let pool: PgPool = PgPool::connect(&database_url).await.unwrap();
-        let app_state: web::Data<AppState> = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            visit_count: Mutex::new(0),
-            db: pool,
-        });
+        let app_state: web::Data<AppState> =
+            web::Data::new(AppState::new("", MemoryRepository::with_sample_data()));
         let update_course_msg = UpdateCourse {
             course_name: Some("Course name changed".into()),
             course_description: Some("This is yet another test course".into()),
@@ -170,40 +493,70 @@ mod tests {
         };
         let parameters: web::Path<(i32, i32)> = web::Path::from((1, 3));
         let update_param = web::Json(update_course_msg);
-        let resp = update_course_details(app_state, update_param, parameters)
+        let resp = update_course_details(app_state, update_param, parameters, tutor(1))
             .await
             .unwrap();
         assert_eq!(resp.status(), StatusCode::OK);
     }
+    // Only the tutor of a course, or an admin, may change it
+    #[actix_rt::test]
+    async fn update_course_forbidden_test() {
+        let app_state: web::Data<AppState> =
+            web::Data::new(AppState::new("", MemoryRepository::with_sample_data()));
+        let update_course_msg = UpdateCourse {
+            course_name: None,
+            course_description: None,
+            course_format: None,
+            course_level: None,
+            course_price: Some(10),
+            course_duration: None,
+            course_language: None,
+            course_structure: None,
+        };
+        let resp = update_course_details(
+            app_state.clone(),
+            web::Json(update_course_msg.clone()),
+            web::Path::from((1, 4)),
+            tutor(2),
+        )
+        .await;
+        match resp {
+            Ok(_) => panic!("Tutor 2 changed a course of tutor 1"),
+            Err(err) => assert_eq!(err.status_code(), StatusCode::FORBIDDEN),
+        }
+        let admin = AuthUser {
+            admin: true,
+            ..tutor(2)
+        };
+        let resp = update_course_details(
+            app_state,
+            web::Json(update_course_msg),
+            web::Path::from((1, 4)),
+            admin,
+        )
+        .await
+        .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
     // Delete course successfully
     //#[ignore]
     #[actix_rt::test]
     async fn delete_test_success() {
-        dotenv().ok();
-        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
-        let pool: PgPool = PgPool::connect(&database_url).await.unwrap();
-        let app_state: web::Data<AppState> = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            visit_count: Mutex::new(0),
-            db: pool,
-        });
+        let app_state: web::Data<AppState> =
+            web::Data::new(AppState::new("", MemoryRepository::with_sample_data()));
         let parameters: web::Path<(i32, i32)> = web::Path::from((3, 5));
-        let resp = delete_course(app_state, parameters).await.unwrap();
+        let resp = delete_course(app_state, parameters, tutor(3))
+            .await
+            .unwrap();
         assert_eq!(resp.status(), StatusCode::OK);
     }
     // Delete test failure
     #[actix_rt::test]
     async fn delete_test_failure() {
-        dotenv().ok();
-        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
-        let pool: PgPool = PgPool::connect(&database_url).await.unwrap();
-        let app_state: web::Data<AppState> = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            visit_count: Mutex::new(0),
-            db: pool,
-        });
+        let app_state: web::Data<AppState> =
+            web::Data::new(AppState::new("", MemoryRepository::with_sample_data()));
         let parameters: web::Path<(i32, i32)> = web::Path::from((1, 21));
-        let resp = delete_course(app_state, parameters).await;
+        let resp = delete_course(app_state, parameters, tutor(1)).await;
         match resp {
             Ok(_) => println!("Something wrong"),
             Err(err) => assert_eq!(err.status_code(), StatusCode::NOT_FOUND),
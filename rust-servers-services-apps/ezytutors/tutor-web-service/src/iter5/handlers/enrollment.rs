@@ -0,0 +1,304 @@
+use crate::auth::AuthUser;
+use crate::errors::{EzyTutorError, MyErrorResponse};
+use crate::models::course::Course;
+use crate::models::enrollment::{
+    CourseCapacity, Enrollment, EnrollmentQuery, EnrollmentStatus, NewEnrollment, RosterEntry,
+    UpdateEnrollment,
+};
+use crate::state::AppState;
+use actix_web::{web, HttpResponse};
+use validator::Validate;
+
+/*
+curl -X POST localhost:3000/courses/1/1/enrollments -H "Authorization: Bearer $TOKEN" -H "Content-Type: application/json" -d '{"student_id":4}'
+*/
+// Students enroll themselves; admins may enroll anyone
+#[utoipa::path(
+    post,
+    path = "/courses/{tutor_id}/{course_id}/enrollments",
+    tag = "enrollments",
+    params(
+        ("tutor_id" = i32, Path, description = "Id of the tutor"),
+        ("course_id" = i32, Path, description = "Id of the course"),
+    ),
+    request_body = NewEnrollment,
+    responses(
+        (status = 200, description = "The enrollment: active if the course had a free seat, pending on its waitlist if not", body = Enrollment),
+        (status = 400, description = "Invalid input, or the student is already enrolled", body = MyErrorResponse),
+        (status = 401, description = "Missing or invalid bearer token", body = MyErrorResponse),
+        (status = 403, description = "The token is not for this student", body = MyErrorResponse),
+        (status = 404, description = "Course or student not found", body = MyErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn enroll(
+    app_state: web::Data<AppState>,
+    path: web::Path<(i32, i32)>,
+    new_enrollment: web::Json<NewEnrollment>,
+    user: AuthUser,
+) -> Result<HttpResponse, EzyTutorError> {
+    let (tutor_id, course_id) = path.into_inner();
+    user.check_student(new_enrollment.student_id)?;
+    app_state
+        .enrollments
+        .enroll(tutor_id, course_id, new_enrollment.student_id)
+        .await
+        .map(|enrollment| HttpResponse::Ok().json(enrollment))
+}
+
+// curl "localhost:3000/courses/1/1/enrollments?status=pending" -H "Authorization: Bearer $TOKEN"
+#[utoipa::path(
+    get,
+    path = "/courses/{tutor_id}/{course_id}/enrollments",
+    tag = "enrollments",
+    params(
+        ("tutor_id" = i32, Path, description = "Id of the tutor"),
+        ("course_id" = i32, Path, description = "Id of the course"),
+        EnrollmentQuery,
+    ),
+    responses(
+        (status = 200, description = "The roster of the course, in the order the students enrolled, which is also the order of the waitlist", body = Vec<RosterEntry>),
+        (status = 400, description = "Invalid input", body = MyErrorResponse),
+        (status = 401, description = "Missing or invalid bearer token", body = MyErrorResponse),
+        (status = 403, description = "The token is not for this tutor", body = MyErrorResponse),
+        (status = 404, description = "Course not found", body = MyErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_course_roster(
+    app_state: web::Data<AppState>,
+    path: web::Path<(i32, i32)>,
+    query: web::Query<EnrollmentQuery>,
+    user: AuthUser,
+) -> Result<HttpResponse, EzyTutorError> {
+    let (tutor_id, course_id) = path.into_inner();
+    user.check_owner(tutor_id)?;
+    app_state
+        .enrollments
+        .get_course_roster(tutor_id, course_id, &query)
+        .await
+        .map(|roster| HttpResponse::Ok().json(roster))
+}
+
+/*
+curl -X PUT localhost:3000/courses/1/1/enrollments/1 -H "Authorization: Bearer $TOKEN" -H "Content-Type: application/json" -d '{"status":"completed"}'
+*/
+// The tutor completes enrollments. The tutor or the student may cancel one.
+#[utoipa::path(
+    put,
+    path = "/courses/{tutor_id}/{course_id}/enrollments/{enrollment_id}",
+    tag = "enrollments",
+    params(
+        ("tutor_id" = i32, Path, description = "Id of the tutor"),
+        ("course_id" = i32, Path, description = "Id of the course"),
+        ("enrollment_id" = i32, Path, description = "Id of the enrollment"),
+    ),
+    request_body = UpdateEnrollment,
+    responses(
+        (status = 200, description = "The updated enrollment. The seat of an active one goes to the first student on the waitlist.", body = Enrollment),
+        (status = 400, description = "Invalid input, or the enrollment cannot be changed to the status", body = MyErrorResponse),
+        (status = 401, description = "Missing or invalid bearer token", body = MyErrorResponse),
+        (status = 403, description = "The token is neither for this tutor nor for the enrolled student", body = MyErrorResponse),
+        (status = 404, description = "Course or enrollment not found", body = MyErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn update_enrollment(
+    app_state: web::Data<AppState>,
+    path: web::Path<(i32, i32, i32)>,
+    update_enrollment: web::Json<UpdateEnrollment>,
+    user: AuthUser,
+) -> Result<HttpResponse, EzyTutorError> {
+    let (tutor_id, course_id, enrollment_id) = path.into_inner();
+    let status = update_enrollment.status;
+    if let Err(err) = user.check_owner(tutor_id) {
+        if status != EnrollmentStatus::Cancelled {
+            return Err(err);
+        }
+        let enrollment = app_state
+            .enrollments
+            .get_enrollment(tutor_id, course_id, enrollment_id)
+            .await?;
+        user.check_student(enrollment.student_id)?;
+    }
+    app_state
+        .enrollments
+        .update_enrollment_status(tutor_id, course_id, enrollment_id, status)
+        .await
+        .map(|enrollment| HttpResponse::Ok().json(enrollment))
+}
+
+/*
+curl -X PUT localhost:3000/courses/1/1/capacity -H "Authorization: Bearer $TOKEN" -H "Content-Type: application/json" -d '{"course_capacity":3}'
+*/
+#[utoipa::path(
+    put,
+    path = "/courses/{tutor_id}/{course_id}/capacity",
+    tag = "enrollments",
+    params(
+        ("tutor_id" = i32, Path, description = "Id of the tutor"),
+        ("course_id" = i32, Path, description = "Id of the course"),
+    ),
+    request_body = CourseCapacity,
+    responses(
+        (status = 200, description = "The course. Seats added go to the students first on the waitlist; lowering the capacity takes no seat away.", body = Course),
+        (status = 400, description = "Invalid input", body = MyErrorResponse),
+        (status = 401, description = "Missing or invalid bearer token", body = MyErrorResponse),
+        (status = 403, description = "The token is not for this tutor", body = MyErrorResponse),
+        (status = 404, description = "Course not found", body = MyErrorResponse),
+        (status = 422, description = "Fields of the request body out of bounds", body = MyErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn set_course_capacity(
+    app_state: web::Data<AppState>,
+    path: web::Path<(i32, i32)>,
+    capacity: web::Json<CourseCapacity>,
+    user: AuthUser,
+) -> Result<HttpResponse, EzyTutorError> {
+    let (tutor_id, course_id) = path.into_inner();
+    user.check_owner(tutor_id)?;
+    capacity.validate()?;
+    app_state
+        .enrollments
+        .set_course_capacity(tutor_id, course_id, capacity.course_capacity)
+        .await
+        .map(|course| HttpResponse::Ok().json(course))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::memory::MemoryRepository;
+    use actix_web::body::to_bytes;
+    use actix_web::http::StatusCode;
+    use actix_web::ResponseError;
+    use serde_json::Value;
+
+    fn tutor(tutor_id: i32) -> AuthUser {
+        AuthUser {
+            username: format!("tutor{}", tutor_id),
+            tutor_id: Some(tutor_id),
+            student_id: None,
+            admin: false,
+        }
+    }
+
+    fn student(student_id: i32) -> AuthUser {
+        AuthUser {
+            username: format!("student{}", student_id),
+            tutor_id: None,
+            student_id: Some(student_id),
+            admin: false,
+        }
+    }
+
+    async fn body(resp: HttpResponse) -> Value {
+        serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap()
+    }
+
+    async fn statuses(app_state: &web::Data<AppState>) -> Vec<Value> {
+        let query = web::Query(EnrollmentQuery::default());
+        let resp = get_course_roster(app_state.clone(), web::Path::from((1, 1)), query, tutor(1))
+            .await
+            .unwrap();
+        let roster = body(resp).await;
+        roster
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["status"].clone())
+            .collect()
+    }
+
+    fn status(status: EnrollmentStatus) -> web::Json<UpdateEnrollment> {
+        web::Json(UpdateEnrollment { status })
+    }
+
+    // A student cancelling a seat gives it to the first student waiting
+    #[actix_rt::test]
+    async fn cancel_enrollment_test() {
+        let app_state: web::Data<AppState> =
+            web::Data::new(AppState::new("", MemoryRepository::with_sample_data()));
+        assert_eq!(statuses(&app_state).await, ["active", "active", "pending"]);
+        let query = web::Query(EnrollmentQuery::default());
+        let err = get_course_roster(app_state.clone(), web::Path::from((1, 1)), query, tutor(2))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+
+        let path = || web::Path::from((1, 1, 1));
+        let cancelled = || status(EnrollmentStatus::Cancelled);
+        let err = update_enrollment(app_state.clone(), path(), cancelled(), student(2))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+        let err = update_enrollment(
+            app_state.clone(),
+            path(),
+            status(EnrollmentStatus::Completed),
+            student(1),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+        update_enrollment(app_state.clone(), path(), cancelled(), student(1))
+            .await
+            .unwrap();
+        assert_eq!(
+            statuses(&app_state).await,
+            ["cancelled", "active", "active"]
+        );
+
+        let err = update_enrollment(app_state.clone(), path(), cancelled(), tutor(1))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+    }
+
+    // Only the tutor sets the capacity, and raising it seats the waitlist
+    #[actix_rt::test]
+    async fn set_course_capacity_test() {
+        let app_state: web::Data<AppState> =
+            web::Data::new(AppState::new("", MemoryRepository::with_sample_data()));
+        let capacity = |course_capacity| web::Json(CourseCapacity { course_capacity });
+        let err = set_course_capacity(
+            app_state.clone(),
+            web::Path::from((1, 1)),
+            capacity(Some(3)),
+            tutor(2),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+        let err = set_course_capacity(
+            app_state.clone(),
+            web::Path::from((1, 1)),
+            capacity(Some(0)),
+            tutor(1),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let resp = set_course_capacity(
+            app_state.clone(),
+            web::Path::from((1, 1)),
+            capacity(Some(3)),
+            tutor(1),
+        )
+        .await
+        .unwrap();
+        assert_eq!(body(resp).await["course_capacity"], 3);
+        assert_eq!(statuses(&app_state).await, ["active", "active", "active"]);
+
+        let resp = enroll(
+            app_state.clone(),
+            web::Path::from((1, 1)),
+            web::Json(NewEnrollment { student_id: 2 }),
+            student(2),
+        )
+        .await;
+        assert_eq!(resp.unwrap_err().status_code(), StatusCode::BAD_REQUEST);
+    }
+}
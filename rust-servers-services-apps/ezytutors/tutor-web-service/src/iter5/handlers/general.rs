@@ -2,6 +2,14 @@ use crate::errors::EzyTutorError;
 use crate::state::AppState;
 use actix_web::{web, HttpResponse};
 
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "general",
+    responses(
+        (status = 200, description = "How many times the service was asked", body = String),
+    )
+)]
 pub async fn health_check_handler(
     app_state: web::Data<AppState>,
 ) -> Result<HttpResponse, EzyTutorError> {
@@ -1,3 +1,9 @@
+pub mod auth;
+pub mod category;
 pub mod course;
+pub mod enrollment;
 pub mod general;
+pub mod paging;
+pub mod student;
+pub mod tag;
 pub mod tutor;
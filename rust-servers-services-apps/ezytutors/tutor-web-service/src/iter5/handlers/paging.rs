@@ -0,0 +1,69 @@
+use crate::models::paging::Page;
+use actix_web::{http::header, HttpRequest, HttpResponse};
+use serde::Serialize;
+
+// Answer with a page of a listing and a Link header pointing to the first,
+// previous and next pages. The links repeat the request's query, so they
+// keep its filters, sort order and limit.
+pub fn paged_response<T: Serialize, Q: Serialize>(
+    req: &HttpRequest,
+    query: &Q,
+    page: Page<T>,
+) -> HttpResponse {
+    let paging = &page.paging;
+    let mut links = vec![page_link(req.path(), query, None, "first")];
+    match paging.offset {
+        // A page fetched by offset links to its neighbours by offset
+        Some(offset) => {
+            if offset > 0 {
+                let prev = (offset - paging.limit).max(0);
+                links.push(page_link(
+                    req.path(),
+                    query,
+                    Some(("offset", prev.to_string())),
+                    "prev",
+                ));
+            }
+            if offset + paging.limit < paging.total {
+                let next = offset + paging.limit;
+                links.push(page_link(
+                    req.path(),
+                    query,
+                    Some(("offset", next.to_string())),
+                    "next",
+                ));
+            }
+        }
+        None => {
+            if let Some(cursor) = &paging.next_cursor {
+                links.push(page_link(
+                    req.path(),
+                    query,
+                    Some(("cursor", cursor.clone())),
+                    "next",
+                ));
+            }
+        }
+    }
+    HttpResponse::Ok()
+        .insert_header((header::LINK, links.join(", ")))
+        .json(page)
+}
+
+fn page_link<Q: Serialize>(
+    path: &str,
+    query: &Q,
+    start: Option<(&str, String)>,
+    rel: &str,
+) -> String {
+    let query = serde_urlencoded::to_string(query).unwrap_or_default();
+    let mut params: Vec<(String, String)> = serde_urlencoded::from_str(&query).unwrap_or_default();
+    params.retain(|(name, _)| name != "offset" && name != "cursor");
+    if let Some((name, value)) = start {
+        params.push((name.to_string(), value));
+    }
+    match serde_urlencoded::to_string(&params) {
+        Ok(query) if !query.is_empty() => format!("<{}?{}>; rel=\"{}\"", path, query, rel),
+        _ => format!("<{}>; rel=\"{}\"", path, rel),
+    }
+}
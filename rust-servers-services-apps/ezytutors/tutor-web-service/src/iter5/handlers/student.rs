@@ -0,0 +1,224 @@
+use crate::auth::{hash_password, AuthUser};
+use crate::errors::{EzyTutorError, MyErrorResponse};
+use crate::models::auth::NewUser;
+use crate::models::enrollment::{EnrollmentQuery, StudentCourse};
+use crate::models::student::{NewStudent, Student};
+use crate::state::AppState;
+use actix_web::{web, HttpResponse};
+use validator::Validate;
+
+/*
+curl -X POST localhost:3000/students -H "Content-Type: application/json" -d '{"student_name":"Priya", "username":"priya", "password":"correct horse"}'
+*/
+// Anyone may sign up as a student, with the login that owns the profile
+#[utoipa::path(
+    post,
+    path = "/students",
+    tag = "students",
+    request_body = NewStudent,
+    responses(
+        (status = 200, description = "The new student", body = Student),
+        (status = 400, description = "Invalid input, or the username is taken", body = MyErrorResponse),
+        (status = 422, description = "Fields of the request body out of bounds", body = MyErrorResponse),
+    )
+)]
+pub async fn post_new_student(
+    new_student: web::Json<NewStudent>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, EzyTutorError> {
+    new_student.validate()?;
+    let login = NewUser {
+        username: new_student.username.clone(),
+        user_password: hash_password(&new_student.password)?,
+    };
+    app_state
+        .students
+        .post_new_student(new_student.into_inner(), login)
+        .await
+        .map(|student| HttpResponse::Ok().json(student))
+}
+
+// curl localhost:3000/students/1 -H "Authorization: Bearer $TOKEN"
+#[utoipa::path(
+    get,
+    path = "/students/{student_id}",
+    tag = "students",
+    params(("student_id" = i32, Path, description = "Id of the student")),
+    responses(
+        (status = 200, description = "The student", body = Student),
+        (status = 401, description = "Missing or invalid bearer token", body = MyErrorResponse),
+        (status = 403, description = "The token is not for this student", body = MyErrorResponse),
+        (status = 404, description = "Student not found", body = MyErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_student(
+    app_state: web::Data<AppState>,
+    path: web::Path<i32>,
+    user: AuthUser,
+) -> Result<HttpResponse, EzyTutorError> {
+    let student_id = path.into_inner();
+    user.check_student(student_id)?;
+    app_state
+        .students
+        .get_student(student_id)
+        .await
+        .map(|student| HttpResponse::Ok().json(student))
+}
+
+// curl "localhost:3000/students/1/courses?status=active" -H "Authorization: Bearer $TOKEN"
+#[utoipa::path(
+    get,
+    path = "/students/{student_id}/courses",
+    tag = "students",
+    params(("student_id" = i32, Path, description = "Id of the student"), EnrollmentQuery),
+    responses(
+        (status = 200, description = "The student's enrollments and their courses, the latest first", body = Vec<StudentCourse>),
+        (status = 400, description = "Invalid input", body = MyErrorResponse),
+        (status = 401, description = "Missing or invalid bearer token", body = MyErrorResponse),
+        (status = 403, description = "The token is not for this student", body = MyErrorResponse),
+        (status = 404, description = "Student not found", body = MyErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_student_courses(
+    app_state: web::Data<AppState>,
+    path: web::Path<i32>,
+    query: web::Query<EnrollmentQuery>,
+    user: AuthUser,
+) -> Result<HttpResponse, EzyTutorError> {
+    let student_id = path.into_inner();
+    user.check_student(student_id)?;
+    app_state
+        .students
+        .get_student_courses(student_id, &query)
+        .await
+        .map(|courses| HttpResponse::Ok().json(courses))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::auth::JwtKeys;
+    use crate::models::auth::TokenResponse;
+    use crate::repository::memory::MemoryRepository;
+    use crate::routes::{auth_routes, enrollment_routes, student_routes};
+    use crate::state::AppState;
+    use actix_web::http::{header, StatusCode};
+    use actix_web::{test, web, App};
+    use serde_json::{json, Value};
+
+    // Sign up as a student, join a waitlist, and see only your own courses
+    #[actix_rt::test]
+    async fn student_sign_up_test() {
+        let app_state: web::Data<AppState> =
+            web::Data::new(AppState::new("", MemoryRepository::with_sample_data()));
+        let app = test::init_service(
+            App::new()
+                .app_data(app_state)
+                .app_data(web::Data::new(JwtKeys::hs256(b"test secret")))
+                .configure(auth_routes)
+                .configure(student_routes)
+                .configure(enrollment_routes),
+        )
+        .await;
+
+        let new_student = json!({
+            "student_name": "Priya",
+            "username": "priya",
+            "password": "correct horse",
+        });
+        let req = test::TestRequest::post()
+            .uri("/students")
+            .set_json(&new_student)
+            .to_request();
+        let student: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(student, json!({"student_id": 4, "student_name": "Priya"}));
+        let req = test::TestRequest::post()
+            .uri("/students")
+            .set_json(&new_student)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::post()
+            .uri("/auth/token")
+            .set_json(json!({"username": "priya", "password": "correct horse"}))
+            .to_request();
+        let token: TokenResponse = test::call_and_read_body_json(&app, req).await;
+        let bearer = format!("Bearer {}", token.access_token);
+
+        // The first course is full, so Priya waits for a seat
+        let req = test::TestRequest::post()
+            .uri("/courses/1/1/enrollments")
+            .insert_header((header::AUTHORIZATION, bearer.as_str()))
+            .set_json(json!({"student_id": 4}))
+            .to_request();
+        let enrollment: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(enrollment["status"], "pending");
+        let req = test::TestRequest::get()
+            .uri("/students/4/courses")
+            .insert_header((header::AUTHORIZATION, bearer.as_str()))
+            .to_request();
+        let courses: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(courses[0]["course_name"], "First course");
+        assert_eq!(courses[0]["enrollment_id"], enrollment["enrollment_id"]);
+
+        let cancel = format!("/courses/1/1/enrollments/{}", enrollment["enrollment_id"]);
+        for (method, uri, body, status) in [
+            (
+                "POST",
+                "/courses/1/1/enrollments",
+                json!({"student_id": 1}),
+                StatusCode::FORBIDDEN,
+            ),
+            ("GET", "/students/4", json!(null), StatusCode::OK),
+            ("GET", "/students/1", json!(null), StatusCode::FORBIDDEN),
+            (
+                "GET",
+                "/students/1/courses",
+                json!(null),
+                StatusCode::FORBIDDEN,
+            ),
+            (
+                "GET",
+                "/courses/1/1/enrollments",
+                json!(null),
+                StatusCode::FORBIDDEN,
+            ),
+            (
+                "PUT",
+                "/courses/1/1/enrollments/1",
+                json!({"status": "cancelled"}),
+                StatusCode::FORBIDDEN,
+            ),
+            (
+                "PUT",
+                cancel.as_str(),
+                json!({"status": "completed"}),
+                StatusCode::FORBIDDEN,
+            ),
+            (
+                "PUT",
+                cancel.as_str(),
+                json!({"status": "cancelled"}),
+                StatusCode::OK,
+            ),
+        ] {
+            let req = match method {
+                "POST" => test::TestRequest::post().set_json(&body),
+                "PUT" => test::TestRequest::put().set_json(&body),
+                _ => test::TestRequest::get(),
+            };
+            let req = req
+                .uri(uri)
+                .insert_header((header::AUTHORIZATION, bearer.as_str()))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), status, "{} {}", method, uri);
+        }
+
+        let req = test::TestRequest::get().uri("/students/4").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+}
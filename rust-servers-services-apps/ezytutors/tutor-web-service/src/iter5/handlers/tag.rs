@@ -0,0 +1,384 @@
+use crate::auth::AuthUser;
+use crate::errors::{EzyTutorError, MyErrorResponse};
+use crate::handlers::paging::paged_response;
+use crate::models::course::{Course, CourseQuery};
+use crate::models::paging::Page;
+use crate::models::tag::{normalize_tag_name, NewTag, Tag, TagCount, UpdateTag};
+use crate::state::AppState;
+use actix_web::{web, HttpRequest, HttpResponse};
+use validator::Validate;
+
+// curl localhost:3000/tags
+#[utoipa::path(
+    get,
+    path = "/tags",
+    tag = "tags",
+    responses(
+        (status = 200, description = "All tags, by name", body = Vec<Tag>),
+    )
+)]
+pub async fn get_all_tags(app_state: web::Data<AppState>) -> Result<HttpResponse, EzyTutorError> {
+    app_state
+        .tags
+        .get_all_tags()
+        .await
+        .map(|tags| HttpResponse::Ok().json(tags))
+}
+
+// curl localhost:3000/tags/counts
+#[utoipa::path(
+    get,
+    path = "/tags/counts",
+    tag = "tags",
+    responses(
+        (status = 200, description = "How many courses have each tag, most used tags first, for a tag cloud", body = Vec<TagCount>),
+    )
+)]
+pub async fn get_tag_counts(app_state: web::Data<AppState>) -> Result<HttpResponse, EzyTutorError> {
+    app_state
+        .tags
+        .get_tag_counts()
+        .await
+        .map(|counts| HttpResponse::Ok().json(counts))
+}
+
+#[utoipa::path(
+    get,
+    path = "/tags/{tag_id}",
+    tag = "tags",
+    params(("tag_id" = i32, Path, description = "Id of the tag")),
+    responses(
+        (status = 200, description = "The tag", body = Tag),
+        (status = 404, description = "Tag not found", body = MyErrorResponse),
+    )
+)]
+pub async fn get_tag(
+    app_state: web::Data<AppState>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, EzyTutorError> {
+    app_state
+        .tags
+        .get_tag(path.into_inner())
+        .await
+        .map(|tag| HttpResponse::Ok().json(tag))
+}
+
+/*
+curl -X POST localhost:3000/tags -H "Authorization: Bearer $TOKEN" -H "Content-Type: application/json" -d '{"tag_name":"Spreadsheets"}'
+*/
+// Any tutor may add a tag; only admins may rename or delete one, as other
+// tutors' courses may have it
+#[utoipa::path(
+    post,
+    path = "/tags",
+    tag = "tags",
+    request_body = NewTag,
+    responses(
+        (status = 200, description = "The new tag, its name trimmed and in lower case", body = Tag),
+        (status = 400, description = "Invalid input, or the tag already exists", body = MyErrorResponse),
+        (status = 401, description = "Missing or invalid bearer token", body = MyErrorResponse),
+        (status = 422, description = "Fields of the request body out of bounds", body = MyErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn post_new_tag(
+    new_tag: web::Json<NewTag>,
+    app_state: web::Data<AppState>,
+    _user: AuthUser,
+) -> Result<HttpResponse, EzyTutorError> {
+    let new_tag = NewTag {
+        tag_name: normalize_tag_name(&new_tag.tag_name),
+    };
+    new_tag.validate()?;
+    app_state
+        .tags
+        .post_new_tag(new_tag)
+        .await
+        .map(|tag| HttpResponse::Ok().json(tag))
+}
+
+/*
+curl -X PUT localhost:3000/tags/4 -H "Authorization: Bearer $TOKEN" -H "Content-Type: application/json" -d '{"tag_name":"excel"}'
+*/
+#[utoipa::path(
+    put,
+    path = "/tags/{tag_id}",
+    tag = "tags",
+    params(("tag_id" = i32, Path, description = "Id of the tag")),
+    request_body = UpdateTag,
+    responses(
+        (status = 200, description = "The renamed tag", body = Tag),
+        (status = 400, description = "Invalid input, or the tag already exists", body = MyErrorResponse),
+        (status = 401, description = "Missing or invalid bearer token", body = MyErrorResponse),
+        (status = 403, description = "The token is not an admin's", body = MyErrorResponse),
+        (status = 404, description = "Tag not found", body = MyErrorResponse),
+        (status = 422, description = "Fields of the request body out of bounds", body = MyErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn update_tag(
+    app_state: web::Data<AppState>,
+    path: web::Path<i32>,
+    update_tag: web::Json<UpdateTag>,
+    user: AuthUser,
+) -> Result<HttpResponse, EzyTutorError> {
+    user.check_admin()?;
+    let update_tag = UpdateTag {
+        tag_name: normalize_tag_name(&update_tag.tag_name),
+    };
+    update_tag.validate()?;
+    app_state
+        .tags
+        .update_tag(path.into_inner(), update_tag)
+        .await
+        .map(|tag| HttpResponse::Ok().json(tag))
+}
+
+// curl -X DELETE localhost:3000/tags/4 -H "Authorization: Bearer $TOKEN"
+#[utoipa::path(
+    delete,
+    path = "/tags/{tag_id}",
+    tag = "tags",
+    params(("tag_id" = i32, Path, description = "Id of the tag")),
+    responses(
+        (status = 200, description = "How many tags were deleted", body = String),
+        (status = 401, description = "Missing or invalid bearer token", body = MyErrorResponse),
+        (status = 403, description = "The token is not an admin's", body = MyErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn delete_tag(
+    app_state: web::Data<AppState>,
+    path: web::Path<i32>,
+    user: AuthUser,
+) -> Result<HttpResponse, EzyTutorError> {
+    user.check_admin()?;
+    app_state
+        .tags
+        .delete_tag(path.into_inner())
+        .await
+        .map(|resp| HttpResponse::Ok().json(resp))
+}
+
+// curl "localhost:3000/tags/2/courses?limit=2"
+#[utoipa::path(
+    get,
+    path = "/tags/{tag_id}/courses",
+    tag = "tags",
+    params(("tag_id" = i32, Path, description = "Id of the tag"), CourseQuery),
+    responses(
+        (status = 200, description = "A page of the courses with the tag, with a Link header to the other pages", body = Page<Course>),
+        (status = 400, description = "Invalid input", body = MyErrorResponse),
+        (status = 404, description = "Tag not found", body = MyErrorResponse),
+    )
+)]
+pub async fn get_courses_with_tag(
+    app_state: web::Data<AppState>,
+    path: web::Path<i32>,
+    query: web::Query<CourseQuery>,
+    req: HttpRequest,
+) -> Result<HttpResponse, EzyTutorError> {
+    app_state
+        .tags
+        .get_courses_with_tag(path.into_inner(), &query)
+        .await
+        .map(|courses| paged_response(&req, &*query, courses))
+}
+
+// curl localhost:3000/courses/1/4/tags
+#[utoipa::path(
+    get,
+    path = "/courses/{tutor_id}/{course_id}/tags",
+    tag = "tags",
+    params(
+        ("tutor_id" = i32, Path, description = "Id of the tutor"),
+        ("course_id" = i32, Path, description = "Id of the course"),
+    ),
+    responses(
+        (status = 200, description = "The tags of the course, by name", body = Vec<Tag>),
+        (status = 404, description = "Course not found", body = MyErrorResponse),
+    )
+)]
+pub async fn get_course_tags(
+    app_state: web::Data<AppState>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse, EzyTutorError> {
+    let (tutor_id, course_id) = path.into_inner();
+    app_state
+        .tags
+        .get_course_tags(tutor_id, course_id)
+        .await
+        .map(|tags| HttpResponse::Ok().json(tags))
+}
+
+// curl -X PUT localhost:3000/courses/1/4/tags/1 -H "Authorization: Bearer $TOKEN"
+#[utoipa::path(
+    put,
+    path = "/courses/{tutor_id}/{course_id}/tags/{tag_id}",
+    tag = "tags",
+    params(
+        ("tutor_id" = i32, Path, description = "Id of the tutor"),
+        ("course_id" = i32, Path, description = "Id of the course"),
+        ("tag_id" = i32, Path, description = "Id of the tag"),
+    ),
+    responses(
+        (status = 200, description = "The tags the course now has", body = Vec<Tag>),
+        (status = 401, description = "Missing or invalid bearer token", body = MyErrorResponse),
+        (status = 403, description = "The token is not for this tutor", body = MyErrorResponse),
+        (status = 404, description = "Course or tag not found", body = MyErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn add_course_tag(
+    app_state: web::Data<AppState>,
+    path: web::Path<(i32, i32, i32)>,
+    user: AuthUser,
+) -> Result<HttpResponse, EzyTutorError> {
+    let (tutor_id, course_id, tag_id) = path.into_inner();
+    user.check_owner(tutor_id)?;
+    app_state
+        .tags
+        .add_course_tag(tutor_id, course_id, tag_id)
+        .await?;
+    get_course_tags(app_state, web::Path::from((tutor_id, course_id))).await
+}
+
+// curl -X DELETE localhost:3000/courses/1/4/tags/1 -H "Authorization: Bearer $TOKEN"
+#[utoipa::path(
+    delete,
+    path = "/courses/{tutor_id}/{course_id}/tags/{tag_id}",
+    tag = "tags",
+    params(
+        ("tutor_id" = i32, Path, description = "Id of the tutor"),
+        ("course_id" = i32, Path, description = "Id of the course"),
+        ("tag_id" = i32, Path, description = "Id of the tag"),
+    ),
+    responses(
+        (status = 200, description = "The tags the course still has", body = Vec<Tag>),
+        (status = 401, description = "Missing or invalid bearer token", body = MyErrorResponse),
+        (status = 403, description = "The token is not for this tutor", body = MyErrorResponse),
+        (status = 404, description = "Course not found", body = MyErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn remove_course_tag(
+    app_state: web::Data<AppState>,
+    path: web::Path<(i32, i32, i32)>,
+    user: AuthUser,
+) -> Result<HttpResponse, EzyTutorError> {
+    let (tutor_id, course_id, tag_id) = path.into_inner();
+    user.check_owner(tutor_id)?;
+    app_state
+        .tags
+        .remove_course_tag(tutor_id, course_id, tag_id)
+        .await?;
+    get_course_tags(app_state, web::Path::from((tutor_id, course_id))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::memory::MemoryRepository;
+    use actix_web::body::to_bytes;
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use actix_web::ResponseError;
+    use serde_json::{json, Value};
+
+    fn tutor(tutor_id: i32) -> AuthUser {
+        AuthUser {
+            username: format!("tutor{}", tutor_id),
+            tutor_id: Some(tutor_id),
+            student_id: None,
+            admin: false,
+        }
+    }
+
+    async fn body(resp: HttpResponse) -> Value {
+        serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap()
+    }
+
+    // Tag names are normalized, so the same tag cannot be added twice
+    #[actix_rt::test]
+    async fn post_new_tag_test() {
+        let app_state: web::Data<AppState> =
+            web::Data::new(AppState::new("", MemoryRepository::with_sample_data()));
+        let new_tag = |tag_name: &str| {
+            web::Json(NewTag {
+                tag_name: tag_name.into(),
+            })
+        };
+        let resp = post_new_tag(new_tag(" Spreadsheets "), app_state.clone(), tutor(1))
+            .await
+            .unwrap();
+        assert_eq!(body(resp).await["tag_name"], "spreadsheets");
+        let err = post_new_tag(new_tag("SPREADSHEETS"), app_state.clone(), tutor(2))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+        let err = post_new_tag(new_tag("   "), app_state.clone(), tutor(2))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let rename = web::Json(UpdateTag {
+            tag_name: "excel".into(),
+        });
+        let err = update_tag(app_state, web::Path::from(4), rename, tutor(1))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+    }
+
+    // Counts follow tags being added to and taken off courses
+    #[actix_rt::test]
+    async fn tag_counts_test() {
+        let app_state: web::Data<AppState> =
+            web::Data::new(AppState::new("", MemoryRepository::with_sample_data()));
+        let resp = get_tag_counts(app_state.clone()).await.unwrap();
+        assert_eq!(
+            body(resp).await,
+            json!([
+                {"tag_id": 2, "tag_name": "self-paced", "course_count": 4},
+                {"tag_id": 1, "tag_name": "beginner", "course_count": 1},
+                {"tag_id": 3, "tag_name": "certification", "course_count": 1},
+            ])
+        );
+
+        let err = add_course_tag(app_state.clone(), web::Path::from((1, 3, 3)), tutor(2))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+        let resp = add_course_tag(app_state.clone(), web::Path::from((1, 3, 3)), tutor(1))
+            .await
+            .unwrap();
+        assert_eq!(
+            body(resp).await,
+            json!([
+                {"tag_id": 3, "tag_name": "certification"},
+                {"tag_id": 2, "tag_name": "self-paced"},
+            ])
+        );
+        remove_course_tag(app_state.clone(), web::Path::from((1, 1, 1)), tutor(1))
+            .await
+            .unwrap();
+        let resp = get_tag_counts(app_state.clone()).await.unwrap();
+        let counts = body(resp).await;
+        assert_eq!(counts[1]["tag_name"], "certification");
+        assert_eq!(counts[1]["course_count"], 2);
+        assert_eq!(counts[2]["course_count"], 0);
+
+        let req = TestRequest::default()
+            .uri("/tags/3/courses")
+            .to_http_request();
+        let resp = get_courses_with_tag(
+            app_state,
+            web::Path::from(3),
+            web::Query(CourseQuery::default()),
+            req,
+        )
+        .await
+        .unwrap();
+        assert_eq!(body(resp).await["paging"]["total"], 2);
+    }
+}
@@ -1,63 +1,172 @@
-use crate::dbaccess::tutor::*;
-use crate::errors::EzyTutorError;
-use crate::models::tutor::{NewTutor, UpdateTutor};
+use crate::auth::{hash_password, AuthUser};
+use crate::errors::{EzyTutorError, MyErrorResponse};
+use crate::handlers::paging::paged_response;
+use crate::models::auth::NewUser;
+use crate::models::paging::Page;
+use crate::models::tutor::{NewTutor, Tutor, TutorQuery, UpdateTutor};
 use crate::state::AppState;
 
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
+use validator::Validate;
 
-pub async fn get_all_tutors(app_state: web::Data<AppState>) -> Result<HttpResponse, EzyTutorError> {
-    get_all_tutors_db(&app_state.db)
+/*
+curl "localhost:3000/tutors/?limit=2&sort=tutor_name"
+*/
+#[utoipa::path(
+    get,
+    path = "/tutors/",
+    tag = "tutors",
+    params(TutorQuery),
+    responses(
+        (status = 200, description = "A page of tutors, with a Link header to the other pages", body = Page<Tutor>),
+        (status = 400, description = "Invalid input", body = MyErrorResponse),
+    )
+)]
+pub async fn get_all_tutors(
+    app_state: web::Data<AppState>,
+    query: web::Query<TutorQuery>,
+    req: HttpRequest,
+) -> Result<HttpResponse, EzyTutorError> {
+    app_state
+        .tutors
+        .get_all_tutors(&query)
         .await
-        .map(|tutors| HttpResponse::Ok().json(tutors))
+        .map(|tutors| paged_response(&req, &*query, tutors))
 }
 
+#[utoipa::path(
+    get,
+    path = "/tutors/{tutor_id}",
+    tag = "tutors",
+    params(("tutor_id" = i32, Path, description = "Id of the tutor")),
+    responses(
+        (status = 200, description = "The tutor", body = Tutor),
+        (status = 404, description = "Tutor not found", body = MyErrorResponse),
+    )
+)]
 pub async fn get_tutor_details(
     app_state: web::Data<AppState>,
     /*web::Path(tutor_id)*/ path: web::Path<i32>,
 ) -> Result<HttpResponse, EzyTutorError> {
     let tutor_id = path.into_inner();
-    get_tutor_details_db(&app_state.db, tutor_id)
+    app_state
+        .tutors
+        .get_tutor_details(tutor_id)
         .await
         .map(|tutor| HttpResponse::Ok().json(tutor))
 }
 
 /*
-curl -X POST localhost:3000/tutors/ -H "Content-Type: application/json"  -d '{ "tutor_name":"Jessica", "tutor_pic_url":"http://tutor1.com/tutor1.pic", "tutor_profile":"Experienced professional"}'
+curl -X POST localhost:3000/tutors/ -H "Content-Type: application/json"  -d '{ "tutor_name":"Jessica", "tutor_pic_url":"http://tutor1.com/tutor1.pic", "tutor_profile":"Experienced professional", "username":"jessica", "password":"correct horse"}'
 */
+// Anyone may sign up as a tutor with a username and password for the new
+// profile. Admins may also create profiles without a login.
+#[utoipa::path(
+    post,
+    path = "/tutors/",
+    tag = "tutors",
+    request_body = NewTutor,
+    responses(
+        (status = 200, description = "The new tutor", body = Tutor),
+        (status = 400, description = "Invalid input", body = MyErrorResponse),
+        (status = 401, description = "No login given, and the bearer token is not an admin's", body = MyErrorResponse),
+        (status = 422, description = "Fields of the request body out of bounds", body = MyErrorResponse),
+    ),
+    security((), ("bearer" = []))
+)]
 pub async fn post_new_tutor(
     new_tutor: web::Json<NewTutor>,
     app_state: web::Data<AppState>,
+    user: Option<AuthUser>,
 ) -> Result<HttpResponse, EzyTutorError> {
-    post_new_tutor_db(&app_state.db, NewTutor::from(new_tutor))
+    let new_tutor = NewTutor::from(new_tutor);
+    new_tutor.validate()?;
+    let login = match (&new_tutor.username, &new_tutor.password) {
+        (Some(username), Some(password)) => Some(NewUser {
+            username: username.clone(),
+            user_password: hash_password(password)?,
+        }),
+        (None, None) if user.is_some_and(|user| user.admin) => None,
+        (None, None) => {
+            return Err(EzyTutorError::Unauthorized(
+                "Please provide a username and password for the new tutor".into(),
+            ))
+        }
+        _ => {
+            return Err(EzyTutorError::InvalidInput(
+                "Please provide both username and password".into(),
+            ))
+        }
+    };
+    app_state
+        .tutors
+        .post_new_tutor(new_tutor, login)
         .await
         .map(|tutor| HttpResponse::Ok().json(tutor))
 }
 
 // Update tutor details
 /*
-curl -X PUT localhost:3000/tutors/4 -H "Content-Type: application/json"  -d '{"tutor_name":"James", "tutor_pic_url":"http://james.com/pic","tutor_profile":"Expert in thermodynamics"}'
+curl -X PUT localhost:3000/tutors/4 -H "Authorization: Bearer $TOKEN" -H "Content-Type: application/json"  -d '{"tutor_name":"James", "tutor_pic_url":"http://james.com/pic","tutor_profile":"Expert in thermodynamics"}'
 */
+#[utoipa::path(
+    put,
+    path = "/tutors/{tutor_id}",
+    tag = "tutors",
+    params(("tutor_id" = i32, Path, description = "Id of the tutor")),
+    request_body = UpdateTutor,
+    responses(
+        (status = 200, description = "The updated tutor", body = Tutor),
+        (status = 400, description = "Invalid input", body = MyErrorResponse),
+        (status = 401, description = "Missing or invalid bearer token", body = MyErrorResponse),
+        (status = 403, description = "The token is not for this tutor", body = MyErrorResponse),
+        (status = 404, description = "Tutor not found", body = MyErrorResponse),
+        (status = 422, description = "Fields of the request body out of bounds", body = MyErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn update_tutor_details(
     app_state: web::Data<AppState>,
     /*web::Path(tutor_id)*/ path: web::Path<i32>,
     update_tutor: web::Json<UpdateTutor>,
+    user: AuthUser,
 ) -> Result<HttpResponse, EzyTutorError> {
     let tutor_id = path.into_inner();
-    update_tutor_details_db(&app_state.db, tutor_id, UpdateTutor::from(update_tutor))
+    user.check_owner(tutor_id)?;
+    update_tutor.validate()?;
+    app_state
+        .tutors
+        .update_tutor_details(tutor_id, UpdateTutor::from(update_tutor))
         .await
         .map(|tutor| HttpResponse::Ok().json(tutor))
 }
 
 /*
-curl -X DELETE http://localhost0/tutors/4
+curl -X DELETE http://localhost0/tutors/4 -H "Authorization: Bearer $TOKEN"
 */
+#[utoipa::path(
+    delete,
+    path = "/tutors/{tutor_id}",
+    tag = "tutors",
+    params(("tutor_id" = i32, Path, description = "Id of the tutor")),
+    responses(
+        (status = 200, description = "How many tutors were deleted", body = String),
+        (status = 401, description = "Missing or invalid bearer token", body = MyErrorResponse),
+        (status = 403, description = "The token is not for this tutor", body = MyErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn delete_tutor(
     app_state: web::Data<AppState>,
     /*web::Path(tutor_id)*/ path: web::Path<i32>,
+    user: AuthUser,
 ) -> Result<HttpResponse, EzyTutorError> {
     let tutor_id = path.into_inner();
+    user.check_owner(tutor_id)?;
     println!("{:#?}",tutor_id);
-    delete_tutor_db(&app_state.db, tutor_id)
+    app_state
+        .tutors
+        .delete_tutor(tutor_id)
         .await
         .map(|tutor| HttpResponse::Ok().json(tutor))
 }
@@ -65,74 +174,111 @@ pub async fn delete_tutor(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::repository::memory::MemoryRepository;
+    use actix_web::body::to_bytes;
     use actix_web::http::StatusCode;
-    use dotenv::dotenv;
-    use sqlx::postgres::PgPool;
-    use std::env;
-    use std::sync::Mutex;
+    use actix_web::test::TestRequest;
+    use actix_web::ResponseError;
+    use serde_json::Value;
 
     #[actix_rt::test]
     async fn get_all_tutors_success_test() {
-        dotenv().ok();
-        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
-        let pool: PgPool = PgPool::connect(&database_url).await.unwrap();
-        let app_state: web::Data<AppState> = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            visit_count: Mutex::new(0),
-            db: pool,
-        });
-        let resp = get_all_tutors(app_state).await.unwrap();
+        let app_state: web::Data<AppState> =
+            web::Data::new(AppState::new("", MemoryRepository::with_sample_data()));
+        let query = web::Query(TutorQuery::default());
+        let req = TestRequest::default().to_http_request();
+        let resp = get_all_tutors(app_state, query, req).await.unwrap();
         assert_eq!(resp.status(), StatusCode::OK);
     }
 
     #[actix_rt::test]
-    async fn get_tutor_detail_success_test() {
-        dotenv().ok();
-        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
-        let pool: PgPool = PgPool::connect(&database_url).await.unwrap();
-        let app_state: web::Data<AppState> = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            visit_count: Mutex::new(0),
-            db: pool,
+    async fn get_tutors_sorted_page_test() {
+        let app_state: web::Data<AppState> =
+            web::Data::new(AppState::new("", MemoryRepository::with_sample_data()));
+        let query = web::Query(TutorQuery {
+            limit: Some(1),
+            sort: Some("tutor_name".into()),
+            ..Default::default()
         });
+        let req = TestRequest::default().uri("/tutors/").to_http_request();
+        let resp = get_all_tutors(app_state, query, req).await.unwrap();
+        let link = resp
+            .headers()
+            .get("Link")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(link.contains(r#"</tutors/?limit=1&sort=tutor_name&offset=1>; rel="next""#));
+        let page: Value =
+            serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap();
+        assert_eq!(page["items"][0]["tutor_name"], "Bob");
+        assert_eq!(page["paging"]["next_cursor"], "3:Bob");
+    }
+
+    #[actix_rt::test]
+    async fn get_tutor_detail_success_test() {
+        let app_state: web::Data<AppState> =
+            web::Data::new(AppState::new("", MemoryRepository::with_sample_data()));
         let parameters: web::Path<i32> = web::Path::from(3);
         let resp = get_tutor_details(app_state, parameters).await.unwrap();
         assert_eq!(resp.status(), StatusCode::OK);
     }
-    ///
+
     //#[ignore]
     #[actix_rt::test]
     async fn post_tutor_success_test() {
-        dotenv().ok();
-        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
-        let pool: PgPool = PgPool::connect(&database_url).await.unwrap();
-        let app_state: web::Data<AppState> = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            visit_count: Mutex::new(0),
-            db: pool,
-        });
+        let app_state: web::Data<AppState> =
+            web::Data::new(AppState::new("", MemoryRepository::with_sample_data()));
         let new_tutor_msg = NewTutor {
             tutor_name: "Third tutor".into(),
             tutor_pic_url: "http://tutor.s3.com/ssdfds".into(),
             tutor_profile: "Experienced tutor in Statistics".into(),
+            username: Some("third".into()),
+            password: Some("third password".into()),
         };
         let tutor_param = web::Json(new_tutor_msg);
-        let resp = post_new_tutor(tutor_param, app_state).await.unwrap();
+        let resp = post_new_tutor(tutor_param, app_state, None).await.unwrap();
         assert_eq!(resp.status(), StatusCode::OK);
     }
+    #[actix_rt::test]
+    async fn post_tutor_invalid_test() {
+        let app_state: web::Data<AppState> =
+            web::Data::new(AppState::new("", MemoryRepository::with_sample_data()));
+        let new_tutor_msg = NewTutor {
+            tutor_name: "".into(),
+            tutor_pic_url: "tutor.s3.com/ssdfds".into(),
+            tutor_profile: "Experienced tutor in Statistics".into(),
+            username: Some("fourth".into()),
+            password: Some("short".into()),
+        };
+        let err = post_new_tutor(web::Json(new_tutor_msg), app_state, None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+        match err {
+            EzyTutorError::InvalidFields(fields) => {
+                assert_eq!(fields["tutor_name"], ["must not be empty"]);
+                assert_eq!(fields["tutor_pic_url"], ["must be a valid URL"]);
+                assert_eq!(fields["password"], ["must be at least 8 characters"]);
+                assert_eq!(fields.len(), 3);
+            }
+            _ => panic!("Unexpected error {:?}", err),
+        }
+    }
     // Delete tutor
     #[actix_rt::test]
     async fn delete_tutor_success_test() {
-        dotenv().ok();
-        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
-        let pool: PgPool = PgPool::connect(&database_url).await.unwrap();
-        let app_state: web::Data<AppState> = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            visit_count: Mutex::new(0),
-            db: pool,
-        });
+        let app_state: web::Data<AppState> =
+            web::Data::new(AppState::new("", MemoryRepository::with_sample_data()));
         let parameters: web::Path<i32> = web::Path::from(2);
-        let resp = delete_tutor(app_state, parameters).await.unwrap();
+        let frank = AuthUser {
+            username: "frank".into(),
+            tutor_id: Some(2),
+            student_id: None,
+            admin: false,
+        };
+        let resp = delete_tutor(app_state, parameters, frank).await.unwrap();
         assert_eq!(resp.status(), StatusCode::OK);
     }
 }
@@ -0,0 +1,175 @@
+use sqlx::migrate::{Migrate, MigrateError, Migration, Migrator};
+use sqlx::postgres::Postgres;
+#[cfg(feature = "sqlite")]
+use sqlx::sqlite::Sqlite;
+use sqlx::{Database, Pool};
+
+// Both sets share the _sqlx_migrations table, so each one has to ignore the
+// versions applied by the other
+pub static SCHEMA: Migrator = Migrator {
+    ignore_missing: true,
+    ..sqlx::migrate!("./src/iter5/migrations/schema")
+};
+
+// Sample data for development; never applied at startup
+pub static SEEDS: Migrator = Migrator {
+    ignore_missing: true,
+    ..sqlx::migrate!("./src/iter5/migrations/seeds")
+};
+
+// The same tables for SQLite, without the full-text search index
+#[cfg(feature = "sqlite")]
+pub static SQLITE_SCHEMA: Migrator = Migrator {
+    ignore_missing: true,
+    ..sqlx::migrate!("./src/iter5/migrations/sqlite/schema")
+};
+
+#[cfg(feature = "sqlite")]
+pub static SQLITE_SEEDS: Migrator = Migrator {
+    ignore_missing: true,
+    ..sqlx::migrate!("./src/iter5/migrations/sqlite/seeds")
+};
+
+// The schema and seed migrations for a kind of database
+pub trait Migrations: Database {
+    fn schema() -> &'static Migrator;
+    fn seeds() -> &'static Migrator;
+}
+
+impl Migrations for Postgres {
+    fn schema() -> &'static Migrator {
+        &SCHEMA
+    }
+
+    fn seeds() -> &'static Migrator {
+        &SEEDS
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl Migrations for Sqlite {
+    fn schema() -> &'static Migrator {
+        &SQLITE_SCHEMA
+    }
+
+    fn seeds() -> &'static Migrator {
+        &SQLITE_SEEDS
+    }
+}
+
+const USAGE: &str = "Usage: iter5 migrate <command>
+
+    up              apply pending schema migrations
+    down [VERSION]  revert the last schema migration, or all after VERSION
+                    (the seed data is removed first)
+    status          list migrations and whether they are applied
+    seed            load the sample data for development
+    unseed          remove the sample data";
+
+// Run `iter5 migrate ...`, with the arguments after "migrate"
+pub async fn command<DB>(pool: &Pool<DB>, args: &[String]) -> Result<(), String>
+where
+    DB: Migrations,
+    DB::Connection: Migrate,
+{
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match args.as_slice() {
+        ["up"] => DB::schema().run(pool).await,
+        ["down"] => revert_last(pool).await,
+        ["down", version] => match version.parse() {
+            Ok(target) => revert_schema(pool, target).await,
+            Err(_) => return Err(format!("Invalid version {}\n\n{}", version, USAGE)),
+        },
+        ["status"] => Ok(()),
+        ["seed"] => DB::seeds().run(pool).await,
+        ["unseed"] => DB::seeds().undo(pool, 0).await,
+        _ => return Err(USAGE.to_string()),
+    };
+    result.map_err(|err| err.to_string())?;
+    status(pool).await.map_err(|err| err.to_string())
+}
+
+async fn revert_last<DB>(pool: &Pool<DB>) -> Result<(), MigrateError>
+where
+    DB: Migrations,
+    DB::Connection: Migrate,
+{
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let applied = conn.list_applied_migrations().await?;
+    let mut versions = ups(DB::schema())
+        .map(|migration| migration.version)
+        .filter(|version| applied.iter().any(|m| m.version == *version))
+        .rev();
+    match versions.next() {
+        Some(_last) => revert_schema(pool, versions.next().unwrap_or(0)).await,
+        None => Ok(()),
+    }
+}
+
+async fn revert_schema<DB>(pool: &Pool<DB>, target: i64) -> Result<(), MigrateError>
+where
+    DB: Migrations,
+    DB::Connection: Migrate,
+{
+    DB::seeds().undo(pool, 0).await?;
+    DB::schema().undo(pool, target).await
+}
+
+async fn status<DB>(pool: &Pool<DB>) -> Result<(), MigrateError>
+where
+    DB: Migrations,
+    DB::Connection: Migrate,
+{
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let applied = conn.list_applied_migrations().await?;
+    for (title, migrator) in [("Schema", DB::schema()), ("Seed data", DB::seeds())] {
+        println!("{}:", title);
+        for migration in ups(migrator) {
+            let state = match applied.iter().find(|m| m.version == migration.version) {
+                None => "pending",
+                Some(m) if m.checksum != migration.checksum => "changed",
+                Some(_) => "applied",
+            };
+            println!(
+                "  {} {:8} {}",
+                migration.version, state, migration.description
+            );
+        }
+    }
+    Ok(())
+}
+
+fn ups(migrator: &Migrator) -> impl DoubleEndedIterator<Item = &Migration> {
+    migrator
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every migration can be reverted, and the two sets never share a version
+    #[test]
+    fn migrations_test() {
+        check_migrations(&SCHEMA, &SEEDS);
+        #[cfg(feature = "sqlite")]
+        check_migrations(&SQLITE_SCHEMA, &SQLITE_SEEDS);
+    }
+
+    fn check_migrations(schema: &Migrator, seeds: &Migrator) {
+        for migrator in [schema, seeds] {
+            for up in ups(migrator) {
+                assert!(migrator
+                    .iter()
+                    .any(|down| down.version == up.version
+                        && down.migration_type.is_down_migration()));
+            }
+        }
+        for seed in ups(seeds) {
+            assert!(ups(schema).all(|schema| schema.version < seed.version));
+        }
+    }
+}
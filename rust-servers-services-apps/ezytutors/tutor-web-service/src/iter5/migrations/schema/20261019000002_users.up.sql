@@ -0,0 +1,14 @@
+/* Logins for the API. tutor_id is the profile the user owns, if any.
+   Admins can change any profile or course; there is no API to make one:
+   update ezy_user_c6 set is_admin = true where username = '...'; */
+create table if not exists ezy_user_c6
+(
+    username varchar(50) primary key,
+    tutor_id INT,
+    user_password varchar(200) not null,
+    is_admin boolean not null default false,
+    CONSTRAINT fk_user_tutor
+    FOREIGN KEY(tutor_id)
+        REFERENCES ezy_tutor_c6(tutor_id)
+        ON DELETE cascade
+);
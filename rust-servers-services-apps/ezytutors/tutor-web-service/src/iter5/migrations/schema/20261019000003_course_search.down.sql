@@ -0,0 +1 @@
+drop index if exists ezy_course_c6_search_idx;
@@ -0,0 +1,9 @@
+/* Full-text search over courses. The document searched: the course name
+   weighs most, then the description, then the structure. Search queries
+   must use this exact expression for Postgres to use the index (see
+   SEARCH_DOCUMENT in dbaccess/course.rs). */
+create index if not exists ezy_course_c6_search_idx on ezy_course_c6 using gin ((
+    setweight(to_tsvector('english', coalesce(course_name, '')), 'A') ||
+    setweight(to_tsvector('english', coalesce(course_description, '')), 'B') ||
+    setweight(to_tsvector('english', coalesce(course_structure, '')), 'C')
+));
@@ -0,0 +1,4 @@
+drop table if exists ezy_course_tag_c6;
+drop table if exists ezy_tag_c6;
+drop table if exists ezy_course_category_c6;
+drop table if exists ezy_category_c6;
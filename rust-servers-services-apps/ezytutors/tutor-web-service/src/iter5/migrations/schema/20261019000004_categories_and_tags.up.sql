@@ -0,0 +1,53 @@
+/* Categories of courses, in a tree: parent_id is the category above, or
+   null at the top. Deleting a category deletes the ones below it; the
+   courses in them are only taken out. */
+create table if not exists ezy_category_c6
+(
+    category_id serial primary key,
+    category_name varchar(100) not null,
+    parent_id INT,
+    CONSTRAINT fk_category_parent
+    FOREIGN KEY(parent_id)
+        REFERENCES ezy_category_c6(category_id)
+        ON DELETE cascade
+);
+create index if not exists ezy_category_c6_parent_idx on ezy_category_c6 (parent_id);
+
+create table if not exists ezy_course_category_c6
+(
+    course_id INT not null,
+    category_id INT not null,
+    primary key (course_id, category_id),
+    CONSTRAINT fk_course_category_course
+    FOREIGN KEY(course_id)
+        REFERENCES ezy_course_c6(course_id)
+        ON DELETE cascade,
+    CONSTRAINT fk_course_category_category
+    FOREIGN KEY(category_id)
+        REFERENCES ezy_category_c6(category_id)
+        ON DELETE cascade
+);
+create index if not exists ezy_course_category_c6_category_idx on ezy_course_category_c6 (category_id);
+
+/* Free-form tags. Names are kept trimmed and in lower case. */
+create table if not exists ezy_tag_c6
+(
+    tag_id serial primary key,
+    tag_name varchar(50) not null unique
+);
+
+create table if not exists ezy_course_tag_c6
+(
+    course_id INT not null,
+    tag_id INT not null,
+    primary key (course_id, tag_id),
+    CONSTRAINT fk_course_tag_course
+    FOREIGN KEY(course_id)
+        REFERENCES ezy_course_c6(course_id)
+        ON DELETE cascade,
+    CONSTRAINT fk_course_tag_tag
+    FOREIGN KEY(tag_id)
+        REFERENCES ezy_tag_c6(tag_id)
+        ON DELETE cascade
+);
+create index if not exists ezy_course_tag_c6_tag_idx on ezy_course_tag_c6 (tag_id);
@@ -0,0 +1,4 @@
+drop table if exists ezy_enrollment_c6;
+alter table ezy_course_c6 drop column if exists course_capacity;
+alter table ezy_user_c6 drop column if exists student_id;
+drop table if exists ezy_student_c6;
@@ -0,0 +1,41 @@
+/* Students, and the courses they enroll in. A login owns a tutor profile,
+   a student profile, or neither. */
+create table if not exists ezy_student_c6
+(
+    student_id serial primary key,
+    student_name varchar(200) not null
+);
+
+alter table ezy_user_c6 add column if not exists student_id INT
+    CONSTRAINT fk_user_student
+        REFERENCES ezy_student_c6(student_id)
+        ON DELETE cascade;
+
+/* How many students may be active in a course at once; null for no limit */
+alter table ezy_course_c6 add column if not exists course_capacity INT
+    CONSTRAINT course_capacity_positive CHECK (course_capacity > 0);
+
+/* Students who enroll in a full course are pending: on the waitlist, in
+   enrollment_id order, until a seat frees up and they are made active. A
+   student is pending or active in a course at most once. */
+create table if not exists ezy_enrollment_c6
+(
+    enrollment_id serial primary key,
+    course_id INT not null,
+    student_id INT not null,
+    status varchar(20) not null,
+    enrolled_time TIMESTAMP default now(),
+    CONSTRAINT enrollment_status
+        CHECK (status in ('pending', 'active', 'completed', 'cancelled')),
+    CONSTRAINT fk_enrollment_course
+    FOREIGN KEY(course_id)
+        REFERENCES ezy_course_c6(course_id)
+        ON DELETE cascade,
+    CONSTRAINT fk_enrollment_student
+    FOREIGN KEY(student_id)
+        REFERENCES ezy_student_c6(student_id)
+        ON DELETE cascade
+);
+create unique index if not exists ezy_enrollment_c6_current_idx
+    on ezy_enrollment_c6 (course_id, student_id) where status in ('pending', 'active');
+create index if not exists ezy_enrollment_c6_student_idx on ezy_enrollment_c6 (student_id);
@@ -0,0 +1,2 @@
+/* Removes the sample tutors, their courses and logins with them */
+delete from ezy_tutor_c6 where tutor_id in (1, 2, 3);
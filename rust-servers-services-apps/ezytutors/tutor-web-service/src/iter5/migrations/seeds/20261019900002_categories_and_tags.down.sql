@@ -0,0 +1,3 @@
+/* Removes the sample categories and tags, and which courses are in them */
+delete from ezy_category_c6 where category_id in (1, 2, 3, 4, 5);
+delete from ezy_tag_c6 where tag_id in (1, 2, 3);
@@ -0,0 +1,13 @@
+/* Sample students, enrolled in the sample courses. The first course has
+   room for two students, so the third is on its waitlist. */
+insert into ezy_student_c6 (student_id, student_name)
+values (1, 'Alice'), (2, 'Ravi'), (3, 'Mei');
+
+update ezy_course_c6 set course_capacity = 2 where course_id = 1;
+
+insert into ezy_enrollment_c6 (enrollment_id, course_id, student_id, status, enrolled_time)
+values (1, 1, 1, 'active', '2021-05-03 09:00:00'), (2, 1, 2, 'active', '2021-05-03 09:30:00'),
+(3, 1, 3, 'pending', '2021-05-04 10:00:00'), (4, 2, 1, 'completed', '2021-05-01 08:00:00');
+
+SELECT setval('ezy_student_c6_student_id_seq', (SELECT MAX(student_id) FROM ezy_student_c6));
+SELECT setval('ezy_enrollment_c6_enrollment_id_seq', (SELECT MAX(enrollment_id) FROM ezy_enrollment_c6));
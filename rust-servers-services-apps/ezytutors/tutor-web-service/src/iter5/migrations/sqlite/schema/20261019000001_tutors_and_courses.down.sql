@@ -0,0 +1,2 @@
+drop table if exists ezy_course_c6;
+drop table if exists ezy_tutor_c6;
@@ -0,0 +1 @@
+drop table if exists ezy_user_c6;
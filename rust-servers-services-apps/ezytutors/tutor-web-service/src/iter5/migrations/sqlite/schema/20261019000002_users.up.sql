@@ -0,0 +1,13 @@
+/* Logins for the API, as in schema/20261019000002_users. To make an admin:
+   update ezy_user_c6 set is_admin = true where username = '...'; */
+create table if not exists ezy_user_c6
+(
+    username varchar(50) primary key,
+    tutor_id INT,
+    user_password varchar(200) not null,
+    is_admin boolean not null default false,
+    CONSTRAINT fk_user_tutor
+    FOREIGN KEY(tutor_id)
+        REFERENCES ezy_tutor_c6(tutor_id)
+        ON DELETE cascade
+);
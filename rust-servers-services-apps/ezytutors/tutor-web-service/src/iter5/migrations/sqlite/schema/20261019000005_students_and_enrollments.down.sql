@@ -0,0 +1,22 @@
+drop table if exists ezy_enrollment_c6;
+alter table ezy_course_c6 drop column course_capacity;
+
+/* SQLite cannot drop a column with a foreign key, so the logins are copied
+   into a table without it */
+create table ezy_user_c6_old
+(
+    username varchar(50) primary key,
+    tutor_id INT,
+    user_password varchar(200) not null,
+    is_admin boolean not null default false,
+    CONSTRAINT fk_user_tutor
+    FOREIGN KEY(tutor_id)
+        REFERENCES ezy_tutor_c6(tutor_id)
+        ON DELETE cascade
+);
+insert into ezy_user_c6_old (username, tutor_id, user_password, is_admin)
+select username, tutor_id, user_password, is_admin from ezy_user_c6;
+drop table ezy_user_c6;
+alter table ezy_user_c6_old rename to ezy_user_c6;
+
+drop table if exists ezy_student_c6;
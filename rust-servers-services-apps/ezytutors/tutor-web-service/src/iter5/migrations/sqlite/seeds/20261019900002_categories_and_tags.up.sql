@@ -0,0 +1,13 @@
+/* Sample categories and tags of the sample courses */
+insert into ezy_category_c6 (category_id, category_name, parent_id)
+values (1, 'Business', null), (2, 'Finance', 1), (3, 'Engineering', null),
+(4, 'Nuclear engineering', 3), (5, 'Machine learning', 3);
+
+insert into ezy_course_category_c6 (course_id, category_id)
+values (1, 2), (2, 4), (3, 2), (4, 1), (5, 5);
+
+insert into ezy_tag_c6 (tag_id, tag_name)
+values (1, 'beginner'), (2, 'self-paced'), (3, 'certification');
+
+insert into ezy_course_tag_c6 (course_id, tag_id)
+values (1, 1), (2, 2), (3, 2), (4, 2), (5, 2), (4, 3);
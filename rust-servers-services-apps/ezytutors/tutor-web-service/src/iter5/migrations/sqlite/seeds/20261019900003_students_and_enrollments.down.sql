@@ -0,0 +1,3 @@
+/* Removes the sample students and their enrollments */
+delete from ezy_student_c6 where student_id in (1, 2, 3);
+update ezy_course_c6 set course_capacity = null where course_id = 1;
@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// A login, as stored in ezy_user_c6. The password is an encoded argon2 hash.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct User {
+    pub username: String,
+    pub tutor_id: Option<i32>,
+    pub student_id: Option<i32>,
+    pub user_password: String,
+    pub is_admin: bool,
+}
+
+// A login to create along with a tutor or student profile
+#[derive(Debug, Clone)]
+pub struct NewUser {
+    pub username: String,
+    pub user_password: String,
+}
+
+#[derive(Deserialize, Debug, Clone, ToSchema)]
+pub struct TokenRequest {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+}
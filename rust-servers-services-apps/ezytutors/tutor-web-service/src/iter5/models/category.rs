@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+// A category of courses. Categories form a tree; those without a parent are
+// at the top, and a course in a category is listed in every category above
+// it too.
+#[derive(Serialize, Debug, Clone, PartialEq, sqlx::FromRow, ToSchema)]
+pub struct Category {
+    pub category_id: i32,
+    pub category_name: String,
+    pub parent_id: Option<i32>,
+}
+
+// Lengths follow the columns of ezy_category_c6
+#[derive(Deserialize, Debug, Clone, Validate, ToSchema)]
+pub struct NewCategory {
+    #[validate(length(min = 1, max = 100))]
+    pub category_name: String,
+    // The category to create it under, or none for a top level category
+    pub parent_id: Option<i32>,
+}
+
+// Categories can be renamed, but not moved to another parent
+#[derive(Deserialize, Debug, Clone, Validate, ToSchema)]
+pub struct UpdateCategory {
+    #[validate(length(min = 1, max = 100))]
+    pub category_name: String,
+}
@@ -1,11 +1,30 @@
+use crate::errors::EzyTutorError;
+use crate::models::paging::{ColumnKind, Keyed, PageRequest, SortValue};
 use actix_web::web;
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::{Validate, ValidationError};
+
+// The values course_level and course_format may take, in any case
+pub const COURSE_LEVELS: &[&str] = &["Beginner", "Intermediate", "Advanced"];
+pub const COURSE_FORMATS: &[&str] = &["ebook", "video", "podcast", "live", "in-person"];
+
+// The columns course listings can be sorted on
+pub const COURSE_SORT_COLUMNS: &[(&str, ColumnKind)] = &[
+    ("course_id", ColumnKind::Int),
+    ("course_name", ColumnKind::Text),
+    ("course_price", ColumnKind::Int),
+    ("course_level", ColumnKind::Text),
+    ("course_language", ColumnKind::Text),
+    ("course_format", ColumnKind::Text),
+    ("posted_time", ColumnKind::Time),
+];
 
 // The query_as! macro comes in handy to map the columns in the database record to
 // the Course data struct. This mapping is done automatically by sqlx if the
 // sqlx::FromRow trait is implemented for the Course struct.
-#[derive(Serialize, Debug, Clone, sqlx::FromRow)]
+#[derive(Serialize, Debug, Clone, sqlx::FromRow, ToSchema)]
 pub struct Course {
     pub course_id: i32,
     pub tutor_id: i32,
@@ -18,33 +37,73 @@ pub struct Course {
     pub course_language: Option<String>,
     pub course_level: Option<String>,
     pub posted_time: Option<NaiveDateTime>,
+    // How many students may be active at once, or None for no limit. Set
+    // with PUT /courses/{tutor_id}/{course_id}/capacity.
+    pub course_capacity: Option<i32>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+// Lengths follow the columns of ezy_course_c6
+#[derive(Deserialize, Debug, Clone, Validate, ToSchema)]
 pub struct CreateCourse {
     pub tutor_id: i32,
+    #[validate(length(min = 1, max = 140))]
     pub course_name: String,
+    #[validate(length(max = 2000))]
     pub course_description: Option<String>,
+    #[validate(length(max = 30), custom(function = "validate_format"))]
     pub course_format: Option<String>,
+    #[validate(length(max = 200))]
     pub course_structure: Option<String>,
+    #[validate(length(max = 30))]
     pub course_duration: Option<String>,
+    #[validate(range(min = 0))]
     pub course_price: Option<i32>,
+    #[validate(length(max = 30))]
     pub course_language: Option<String>,
+    #[validate(length(max = 30), custom(function = "validate_level"))]
     pub course_level: Option<String>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, Validate, ToSchema)]
 pub struct UpdateCourse {
+    #[validate(length(min = 1, max = 140))]
     pub course_name: Option<String>,
+    #[validate(length(max = 2000))]
     pub course_description: Option<String>,
+    #[validate(length(max = 30), custom(function = "validate_format"))]
     pub course_format: Option<String>,
+    #[validate(length(max = 200))]
     pub course_structure: Option<String>,
+    #[validate(length(max = 30))]
     pub course_duration: Option<String>,
+    #[validate(range(min = 0))]
     pub course_price: Option<i32>,
+    #[validate(length(max = 30))]
     pub course_language: Option<String>,
+    #[validate(length(max = 30), custom(function = "validate_level"))]
     pub course_level: Option<String>,
 }
 
+fn validate_level(level: &str) -> Result<(), ValidationError> {
+    one_of(level, COURSE_LEVELS)
+}
+
+fn validate_format(format: &str) -> Result<(), ValidationError> {
+    one_of(format, COURSE_FORMATS)
+}
+
+fn one_of(value: &str, allowed: &[&str]) -> Result<(), ValidationError> {
+    if allowed
+        .iter()
+        .any(|allowed| allowed.eq_ignore_ascii_case(value))
+    {
+        Ok(())
+    } else {
+        Err(ValidationError::new("one_of")
+            .with_message(format!("must be one of: {}", allowed.join(", ")).into()))
+    }
+}
+
 impl From<web::Json<CreateCourse>> for CreateCourse {
     fn from(new_course: web::Json<CreateCourse>) -> Self {
         CreateCourse {
@@ -75,3 +134,150 @@ impl From<web::Json<UpdateCourse>> for UpdateCourse {
         }
     }
 }
+
+impl Keyed for Course {
+    fn id(&self) -> i32 {
+        self.course_id
+    }
+
+    fn sort_value(&self, column: &str) -> SortValue {
+        let text = |value: &Option<String>| value.clone().map_or(SortValue::Null, SortValue::Text);
+        match column {
+            "course_id" => SortValue::Int(self.course_id),
+            "course_name" => SortValue::Text(self.course_name.clone()),
+            "course_price" => self.course_price.map_or(SortValue::Null, SortValue::Int),
+            "course_level" => text(&self.course_level),
+            "course_language" => text(&self.course_language),
+            "course_format" => text(&self.course_format),
+            "posted_time" => self.posted_time.map_or(SortValue::Null, SortValue::Time),
+            _ => SortValue::Null,
+        }
+    }
+}
+
+// Query parameters of a course listing: paging, sorting ("column" or
+// "-column" for descending) and filters. Serialized again to build the
+// links to other pages.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CourseQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub course_level: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub course_language: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub course_format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_price: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_price: Option<i32>,
+}
+
+impl CourseQuery {
+    // The page asked for, newest courses first by default
+    pub fn page_request(&self) -> Result<PageRequest, EzyTutorError> {
+        PageRequest::new(
+            self.limit,
+            self.offset,
+            self.cursor.as_deref(),
+            self.sort.as_deref(),
+            COURSE_SORT_COLUMNS,
+            "-course_id",
+        )
+    }
+
+    pub fn filters(&self) -> CourseFilters<'_> {
+        CourseFilters {
+            course_level: self.course_level.as_deref(),
+            course_language: self.course_language.as_deref(),
+            course_format: self.course_format.as_deref(),
+            min_price: self.min_price,
+            max_price: self.max_price,
+        }
+    }
+}
+
+// Query parameters of a course search: the search terms, in web search
+// syntax ("quoted phrases", -excluded words, or), paging by offset and the
+// same filters as the listings
+#[derive(Deserialize, Serialize, Debug, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CourseSearchQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub q: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub course_level: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub course_language: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub course_format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_price: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_price: Option<i32>,
+}
+
+impl CourseSearchQuery {
+    pub fn terms(&self) -> Result<&str, EzyTutorError> {
+        self.q
+            .as_deref()
+            .map(str::trim)
+            .filter(|terms| !terms.is_empty())
+            .ok_or_else(|| EzyTutorError::InvalidInput("Please provide search terms in q".into()))
+    }
+
+    pub fn filters(&self) -> CourseFilters<'_> {
+        CourseFilters {
+            course_level: self.course_level.as_deref(),
+            course_language: self.course_language.as_deref(),
+            course_format: self.course_format.as_deref(),
+            min_price: self.min_price,
+            max_price: self.max_price,
+        }
+    }
+}
+
+// The filters course listings and searches have in common
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CourseFilters<'a> {
+    pub course_level: Option<&'a str>,
+    pub course_language: Option<&'a str>,
+    pub course_format: Option<&'a str>,
+    pub min_price: Option<i32>,
+    pub max_price: Option<i32>,
+}
+
+impl CourseFilters<'_> {
+    pub fn check(&self) -> Result<(), EzyTutorError> {
+        match (self.min_price, self.max_price) {
+            (Some(min), Some(max)) if min > max => Err(EzyTutorError::InvalidInput(
+                "min_price must not be more than max_price".into(),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+// A course matching a search, with how well it matches and an excerpt of
+// its text where the matching words are wrapped in <mark> tags. The rest of
+// the excerpt is escaped for HTML, so the <mark> tags are its only markup.
+#[derive(Serialize, Debug, Clone, sqlx::FromRow, ToSchema)]
+pub struct CourseSearchResult {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub course: Course,
+    pub rank: f32,
+    pub snippet: Option<String>,
+}
@@ -0,0 +1,97 @@
+use crate::errors::EzyTutorError;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+// Where an enrollment stands. Pending students are on the waitlist of a
+// full course; only active ones take up a seat.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "varchar", rename_all = "lowercase")]
+pub enum EnrollmentStatus {
+    Pending,
+    Active,
+    Completed,
+    Cancelled,
+}
+
+impl EnrollmentStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EnrollmentStatus::Pending => "pending",
+            EnrollmentStatus::Active => "active",
+            EnrollmentStatus::Completed => "completed",
+            EnrollmentStatus::Cancelled => "cancelled",
+        }
+    }
+
+    // Only active enrollments can be completed, and only pending or active
+    // ones cancelled. Students become active when they get a seat, never
+    // by request.
+    pub fn check_change(self, status: EnrollmentStatus) -> Result<(), EzyTutorError> {
+        match (self, status) {
+            (EnrollmentStatus::Active, EnrollmentStatus::Completed)
+            | (EnrollmentStatus::Pending | EnrollmentStatus::Active, EnrollmentStatus::Cancelled) => {
+                Ok(())
+            }
+            _ => Err(EzyTutorError::InvalidInput(format!(
+                "A {} enrollment cannot be made {}",
+                self.as_str(),
+                status.as_str()
+            ))),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, sqlx::FromRow, ToSchema)]
+pub struct Enrollment {
+    pub enrollment_id: i32,
+    pub course_id: i32,
+    pub student_id: i32,
+    pub status: EnrollmentStatus,
+    pub enrolled_time: Option<NaiveDateTime>,
+}
+
+// An enrollment on a course's roster, with the student's name
+#[derive(Serialize, Debug, Clone, sqlx::FromRow, ToSchema)]
+pub struct RosterEntry {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub enrollment: Enrollment,
+    pub student_name: String,
+}
+
+// An enrollment of a student, with the course it is in
+#[derive(Serialize, Debug, Clone, sqlx::FromRow, ToSchema)]
+pub struct StudentCourse {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub enrollment: Enrollment,
+    pub tutor_id: i32,
+    pub course_name: String,
+}
+
+#[derive(Deserialize, Debug, Clone, ToSchema)]
+pub struct NewEnrollment {
+    pub student_id: i32,
+}
+
+#[derive(Deserialize, Debug, Clone, ToSchema)]
+pub struct UpdateEnrollment {
+    pub status: EnrollmentStatus,
+}
+
+// How many students may be active in a course at once; null for no limit
+#[derive(Deserialize, Debug, Clone, Validate, ToSchema)]
+pub struct CourseCapacity {
+    #[validate(range(min = 1))]
+    pub course_capacity: Option<i32>,
+}
+
+// Query parameters of rosters and of the courses of a student
+#[derive(Deserialize, Debug, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EnrollmentQuery {
+    pub status: Option<EnrollmentStatus>,
+}
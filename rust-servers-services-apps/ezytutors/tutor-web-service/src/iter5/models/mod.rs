@@ -1,2 +1,8 @@
+pub mod auth;
+pub mod category;
 pub mod course;
+pub mod enrollment;
+pub mod paging;
+pub mod student;
+pub mod tag;
 pub mod tutor;
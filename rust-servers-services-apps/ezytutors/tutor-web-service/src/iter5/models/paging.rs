@@ -0,0 +1,247 @@
+use crate::errors::EzyTutorError;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+pub const DEFAULT_LIMIT: i64 = 20;
+pub const MAX_LIMIT: i64 = 100;
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
+
+// The SQL type of a column a listing can be sorted on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnKind {
+    Int,
+    Text,
+    Time,
+}
+
+// A row's value in the column a listing is sorted on
+#[derive(Debug, Clone, PartialEq)]
+pub enum SortValue {
+    Null,
+    Int(i32),
+    Text(String),
+    Time(NaiveDateTime),
+}
+
+// Rows that can be listed a page at a time
+pub trait Keyed {
+    fn id(&self) -> i32;
+    fn sort_value(&self, column: &str) -> SortValue;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sort {
+    pub column: &'static str,
+    pub kind: ColumnKind,
+    pub descending: bool,
+}
+
+impl Sort {
+    // Parse "column" (ascending) or "-column" (descending), accepting only
+    // the columns in `allowed`
+    pub fn parse(
+        sort: &str,
+        allowed: &[(&'static str, ColumnKind)],
+    ) -> Result<Self, EzyTutorError> {
+        let (name, descending) = match sort.strip_prefix('-') {
+            Some(name) => (name, true),
+            None => (sort, false),
+        };
+        allowed
+            .iter()
+            .find(|(column, _)| *column == name)
+            .map(|&(column, kind)| Sort {
+                column,
+                kind,
+                descending,
+            })
+            .ok_or_else(|| {
+                let columns: Vec<&str> = allowed.iter().map(|(column, _)| *column).collect();
+                EzyTutorError::InvalidInput(format!(
+                    "Cannot sort by {}, use one of: {}",
+                    name,
+                    columns.join(", ")
+                ))
+            })
+    }
+}
+
+// The last row of the previous page, which the next page starts after.
+// Written as "id:value", or just "id" when the sort value is null.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cursor {
+    pub id: i32,
+    pub value: SortValue,
+}
+
+impl Cursor {
+    pub fn parse(cursor: &str, kind: ColumnKind) -> Result<Self, EzyTutorError> {
+        let invalid = || EzyTutorError::InvalidInput("Invalid cursor".into());
+        let (id, value) = match cursor.split_once(':') {
+            Some((id, value)) => (id, Some(value)),
+            None => (cursor, None),
+        };
+        let id = id.parse().map_err(|_| invalid())?;
+        let value = match (value, kind) {
+            (None, _) => SortValue::Null,
+            (Some(value), ColumnKind::Int) => SortValue::Int(value.parse().map_err(|_| invalid())?),
+            (Some(value), ColumnKind::Text) => SortValue::Text(value.to_string()),
+            (Some(value), ColumnKind::Time) => SortValue::Time(
+                NaiveDateTime::parse_from_str(value, TIME_FORMAT).map_err(|_| invalid())?,
+            ),
+        };
+        Ok(Cursor { id, value })
+    }
+
+    pub fn encode(&self) -> String {
+        match &self.value {
+            SortValue::Null => self.id.to_string(),
+            SortValue::Int(value) => format!("{}:{}", self.id, value),
+            SortValue::Text(value) => format!("{}:{}", self.id, value),
+            SortValue::Time(value) => format!("{}:{}", self.id, value.format(TIME_FORMAT)),
+        }
+    }
+}
+
+// Which page of a listing to fetch: `limit` rows, either skipping `offset`
+// rows or following the row in `cursor`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageRequest {
+    pub limit: i64,
+    pub offset: i64,
+    pub cursor: Option<Cursor>,
+    pub sort: Sort,
+}
+
+impl PageRequest {
+    pub fn new(
+        limit: Option<i64>,
+        offset: Option<i64>,
+        cursor: Option<&str>,
+        sort: Option<&str>,
+        allowed: &[(&'static str, ColumnKind)],
+        default_sort: &str,
+    ) -> Result<Self, EzyTutorError> {
+        if offset.is_some() && cursor.is_some() {
+            return Err(EzyTutorError::InvalidInput(
+                "Use either offset or cursor, not both".into(),
+            ));
+        }
+        let (limit, offset) = page_bounds(limit, offset)?;
+        let sort = Sort::parse(sort.unwrap_or(default_sort), allowed)?;
+        let cursor = cursor
+            .map(|cursor| Cursor::parse(cursor, sort.kind))
+            .transpose()?;
+        Ok(PageRequest {
+            limit,
+            offset,
+            cursor,
+            sort,
+        })
+    }
+
+    // Turn the rows fetched for this page (one more than the limit, if
+    // there are more) into the page returned to the client
+    pub fn into_page<T: Keyed>(self, mut rows: Vec<T>, total: i64) -> Page<T> {
+        let more = rows.len() as i64 > self.limit;
+        rows.truncate(self.limit as usize);
+        let next_cursor = rows.last().filter(|_| more).map(|row| {
+            Cursor {
+                id: row.id(),
+                value: row.sort_value(self.sort.column),
+            }
+            .encode()
+        });
+        Page {
+            items: rows,
+            paging: Paging {
+                limit: self.limit,
+                offset: self.cursor.is_none().then_some(self.offset),
+                total,
+                next_cursor,
+            },
+        }
+    }
+}
+
+// Check the limit and offset asked for, filling in the defaults
+pub fn page_bounds(limit: Option<i64>, offset: Option<i64>) -> Result<(i64, i64), EzyTutorError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(EzyTutorError::InvalidInput(format!(
+            "limit must be between 1 and {}",
+            MAX_LIMIT
+        )));
+    }
+    let offset = offset.unwrap_or(0);
+    if offset < 0 {
+        return Err(EzyTutorError::InvalidInput(
+            "offset must not be negative".into(),
+        ));
+    }
+    Ok((limit, offset))
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub paging: Paging,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct Paging {
+    pub limit: i64,
+    // Absent when the page was fetched with a cursor
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<i64>,
+    // Rows matching the filters, on all pages
+    pub total: i64,
+    // Where the next page starts, if there is one and the listing can be
+    // paged by cursor
+    pub next_cursor: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLUMNS: &[(&str, ColumnKind)] = &[
+        ("course_id", ColumnKind::Int),
+        ("posted_time", ColumnKind::Time),
+    ];
+
+    #[test]
+    fn page_request_validation_test() {
+        let page = PageRequest::new(
+            None,
+            None,
+            Some("4:2021-04-12 05:45:00"),
+            Some("-posted_time"),
+            COLUMNS,
+            "course_id",
+        )
+        .unwrap();
+        assert_eq!(page.limit, DEFAULT_LIMIT);
+        assert!(page.sort.descending);
+        let cursor = page.cursor.unwrap();
+        assert_eq!(cursor.encode(), "4:2021-04-12 05:45:00");
+        assert_eq!(
+            Cursor::parse("7", ColumnKind::Time).unwrap().value,
+            SortValue::Null
+        );
+
+        for (limit, offset, cursor, sort) in [
+            (Some(0), None, None, None),
+            (Some(MAX_LIMIT + 1), None, None, None),
+            (None, Some(-1), None, None),
+            (None, Some(20), Some("4"), None),
+            (None, None, Some("x:1"), None),
+            (None, None, Some("4:yesterday"), Some("posted_time")),
+            (None, None, None, Some("course_description")),
+        ] {
+            assert!(PageRequest::new(limit, offset, cursor, sort, COLUMNS, "course_id").is_err());
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Serialize, Debug, Clone, PartialEq, sqlx::FromRow, ToSchema)]
+pub struct Student {
+    pub student_id: i32,
+    pub student_name: String,
+}
+
+// Lengths follow the columns of ezy_student_c6 and ezy_user_c6. Unlike
+// tutors, students always sign up with the login that owns the profile.
+#[derive(Deserialize, Debug, Clone, Validate, ToSchema)]
+pub struct NewStudent {
+    #[validate(length(min = 1, max = 200))]
+    pub student_name: String,
+    #[validate(length(min = 1, max = 50))]
+    pub username: String,
+    #[validate(length(min = 8))]
+    pub password: String,
+}
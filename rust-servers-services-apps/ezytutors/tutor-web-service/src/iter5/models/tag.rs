@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Serialize, Debug, Clone, PartialEq, sqlx::FromRow, ToSchema)]
+pub struct Tag {
+    pub tag_id: i32,
+    pub tag_name: String,
+}
+
+// How many courses have a tag, to size it by in a tag cloud
+#[derive(Serialize, Debug, Clone, PartialEq, sqlx::FromRow, ToSchema)]
+pub struct TagCount {
+    pub tag_id: i32,
+    pub tag_name: String,
+    pub course_count: i64,
+}
+
+// Lengths follow the columns of ezy_tag_c6, once the name is normalized
+#[derive(Deserialize, Debug, Clone, Validate, ToSchema)]
+pub struct NewTag {
+    #[validate(length(min = 1, max = 50))]
+    pub tag_name: String,
+}
+
+#[derive(Deserialize, Debug, Clone, Validate, ToSchema)]
+pub struct UpdateTag {
+    #[validate(length(min = 1, max = 50))]
+    pub tag_name: String,
+}
+
+// Tags are kept trimmed and in lower case, so that "Rust" and "rust " are
+// the same tag
+pub fn normalize_tag_name(tag_name: &str) -> String {
+    tag_name.trim().to_lowercase()
+}
@@ -1,7 +1,17 @@
+use crate::errors::EzyTutorError;
+use crate::models::paging::{ColumnKind, Keyed, PageRequest, SortValue};
 use actix_web::web;
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
 
-#[derive(Serialize, Debug, Clone)]
+// The columns tutor listings can be sorted on
+pub const TUTOR_SORT_COLUMNS: &[(&str, ColumnKind)] = &[
+    ("tutor_id", ColumnKind::Int),
+    ("tutor_name", ColumnKind::Text),
+];
+
+#[derive(Serialize, Debug, Clone, sqlx::FromRow, ToSchema)]
 pub struct Tutor {
     pub tutor_id: i32,
     pub tutor_name: String,
@@ -9,17 +19,29 @@ pub struct Tutor {
     pub tutor_profile: String,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+// Lengths follow the columns of ezy_tutor_c6 and ezy_user_c6
+#[derive(Deserialize, Debug, Clone, Validate, ToSchema)]
 pub struct NewTutor {
+    #[validate(length(min = 1, max = 200))]
     pub tutor_name: String,
+    #[validate(length(max = 200), url)]
     pub tutor_pic_url: String,
+    #[validate(length(max = 2000))]
     pub tutor_profile: String,
+    // The login that will own the profile. Only admins may leave it out.
+    #[validate(length(min = 1, max = 50))]
+    pub username: Option<String>,
+    #[validate(length(min = 8))]
+    pub password: Option<String>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, Validate, ToSchema)]
 pub struct UpdateTutor {
+    #[validate(length(min = 1, max = 200))]
     pub tutor_name: Option<String>,
+    #[validate(length(max = 200), url)]
     pub tutor_pic_url: Option<String>,
+    #[validate(length(max = 2000))]
     pub tutor_profile: Option<String>,
 }
 
@@ -29,6 +51,8 @@ impl From<web::Json<NewTutor>> for NewTutor {
             tutor_name: new_tutor.tutor_name.clone(),
             tutor_pic_url: new_tutor.tutor_pic_url.clone(),
             tutor_profile: new_tutor.tutor_profile.clone(),
+            username: new_tutor.username.clone(),
+            password: new_tutor.password.clone(),
         }
     }
 }
@@ -42,3 +66,44 @@ impl From<web::Json<UpdateTutor>> for UpdateTutor {
         }
     }
 }
+
+impl Keyed for Tutor {
+    fn id(&self) -> i32 {
+        self.tutor_id
+    }
+
+    fn sort_value(&self, column: &str) -> SortValue {
+        match column {
+            "tutor_id" => SortValue::Int(self.tutor_id),
+            "tutor_name" => SortValue::Text(self.tutor_name.clone()),
+            _ => SortValue::Null,
+        }
+    }
+}
+
+// Query parameters of the tutor listing, see CourseQuery
+#[derive(Deserialize, Serialize, Debug, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TutorQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>,
+}
+
+impl TutorQuery {
+    pub fn page_request(&self) -> Result<PageRequest, EzyTutorError> {
+        PageRequest::new(
+            self.limit,
+            self.offset,
+            self.cursor.as_deref(),
+            self.sort.as_deref(),
+            TUTOR_SORT_COLUMNS,
+            "tutor_id",
+        )
+    }
+}
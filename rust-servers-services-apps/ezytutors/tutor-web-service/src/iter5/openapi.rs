@@ -0,0 +1,160 @@
+use crate::handlers::{auth, category, course, enrollment, general, student, tag, tutor};
+use actix_web::HttpResponse;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+// The OpenAPI document of the API, built from the #[utoipa::path]
+// attributes of the handlers and the schemas of the models they take and
+// return
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "EzyTutors",
+        description = "Tutors, the courses they offer, the categories and tags of courses, and the students enrolled in them. Changes need a bearer token from /auth/token."
+    ),
+    paths(
+        general::health_check_handler,
+        auth::issue_token,
+        course::post_new_course,
+        course::search_courses,
+        course::get_courses_for_tutor,
+        course::get_course_details,
+        course::update_course_details,
+        course::delete_course,
+        tutor::post_new_tutor,
+        tutor::get_all_tutors,
+        tutor::get_tutor_details,
+        tutor::update_tutor_details,
+        tutor::delete_tutor,
+        category::post_new_category,
+        category::get_all_categories,
+        category::get_category,
+        category::update_category,
+        category::delete_category,
+        category::get_courses_in_category,
+        category::get_course_categories,
+        category::add_course_category,
+        category::remove_course_category,
+        tag::post_new_tag,
+        tag::get_all_tags,
+        tag::get_tag_counts,
+        tag::get_tag,
+        tag::update_tag,
+        tag::delete_tag,
+        tag::get_courses_with_tag,
+        tag::get_course_tags,
+        tag::add_course_tag,
+        tag::remove_course_tag,
+        student::post_new_student,
+        student::get_student,
+        student::get_student_courses,
+        enrollment::enroll,
+        enrollment::get_course_roster,
+        enrollment::update_enrollment,
+        enrollment::set_course_capacity,
+    ),
+    modifiers(&BearerAuth)
+)]
+pub struct ApiDoc;
+
+// The "bearer" security scheme the protected routes refer to
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+    }
+}
+
+// curl localhost:3000/openapi.json
+pub async fn openapi_json() -> HttpResponse {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::{
+        docs_routes, AUTH_ROUTES, CATEGORY_ROUTES, COURSE_ROUTES, ENROLLMENT_ROUTES,
+        GENERAL_ROUTES, STUDENT_ROUTES, TAG_ROUTES, TUTOR_ROUTES,
+    };
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App};
+    use serde_json::Value;
+
+    // Fails when a route is added without documenting it, or a documented
+    // operation has no route
+    #[actix_rt::test]
+    async fn every_route_documented_test() {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let mut routes = 0;
+        let all_routes = [
+            GENERAL_ROUTES,
+            AUTH_ROUTES,
+            COURSE_ROUTES,
+            TUTOR_ROUTES,
+            CATEGORY_ROUTES,
+            TAG_ROUTES,
+            STUDENT_ROUTES,
+            ENROLLMENT_ROUTES,
+        ];
+        for route in all_routes.iter().flat_map(|routes| routes.iter()) {
+            let method = route.method.as_str().to_lowercase();
+            assert!(
+                spec["paths"][route.path][&method].is_object(),
+                "{} {} is missing from the OpenAPI document",
+                route.method,
+                route.path
+            );
+            routes += 1;
+        }
+        let operations: usize = spec["paths"]
+            .as_object()
+            .unwrap()
+            .values()
+            .map(|item| item.as_object().unwrap().len())
+            .sum();
+        assert_eq!(operations, routes);
+
+        for schema in [
+            "Course",
+            "CreateCourse",
+            "UpdateCourse",
+            "Tutor",
+            "NewTutor",
+            "UpdateTutor",
+            "Enrollment",
+            "EnrollmentStatus",
+            "MyErrorResponse",
+        ] {
+            assert!(
+                spec["components"]["schemas"][schema].is_object(),
+                "{} is missing from the OpenAPI document",
+                schema
+            );
+        }
+    }
+
+    #[actix_rt::test]
+    async fn docs_routes_test() {
+        let app = test::init_service(App::new().configure(docs_routes)).await;
+
+        let req = test::TestRequest::get().uri("/openapi.json").to_request();
+        let spec: Value = test::call_and_read_body_json(&app, req).await;
+        assert!(spec["openapi"].as_str().unwrap().starts_with("3."));
+        assert!(spec["components"]["securitySchemes"]["bearer"].is_object());
+
+        let req = test::TestRequest::get().uri("/docs/").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+}
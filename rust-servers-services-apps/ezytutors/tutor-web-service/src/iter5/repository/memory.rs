@@ -0,0 +1,1102 @@
+use crate::errors::EzyTutorError;
+use crate::models::auth::{NewUser, User};
+use crate::models::category::{Category, NewCategory, UpdateCategory};
+use crate::models::course::{
+    Course, CourseFilters, CourseQuery, CourseSearchQuery, CourseSearchResult, CreateCourse,
+    UpdateCourse,
+};
+use crate::models::enrollment::{
+    Enrollment, EnrollmentQuery, EnrollmentStatus, RosterEntry, StudentCourse,
+};
+use crate::models::paging::{Keyed, Page, PageRequest, SortValue};
+use crate::models::student::{NewStudent, Student};
+use crate::models::tag::{NewTag, Tag, TagCount, UpdateTag};
+use crate::models::tutor::{NewTutor, Tutor, TutorQuery, UpdateTutor};
+use crate::repository::search::Search;
+use crate::repository::{
+    CategoryRepository, CourseRepository, EnrollmentRepository, StudentRepository, TagRepository,
+    TutorRepository,
+};
+use async_trait::async_trait;
+use chrono::{Local, NaiveDateTime};
+use std::cmp::Ordering;
+use std::sync::Mutex;
+
+// Courses, tutors, students, logins and the rest kept in memory, the way
+// tutor-nodb keeps its courses. Nothing survives a restart.
+#[derive(Default)]
+pub struct MemoryRepository {
+    store: Mutex<Store>,
+}
+
+#[derive(Default)]
+struct Store {
+    tutors: Vec<Tutor>,
+    courses: Vec<Course>,
+    users: Vec<User>,
+    categories: Vec<Category>,
+    tags: Vec<Tag>,
+    students: Vec<Student>,
+    // In enrollment_id order, which is also the order of the waitlists
+    enrollments: Vec<Enrollment>,
+    // (course_id, category_id) and (course_id, tag_id) pairs, as in the
+    // join tables
+    course_categories: Vec<(i32, i32)>,
+    course_tags: Vec<(i32, i32)>,
+    // Ids are never reused, as with serial columns
+    last_tutor_id: i32,
+    last_course_id: i32,
+    last_category_id: i32,
+    last_tag_id: i32,
+    last_student_id: i32,
+    last_enrollment_id: i32,
+}
+
+impl Store {
+    // Fails the way the databases do when the course is missing
+    fn check_course(&self, tutor_id: i32, course_id: i32) -> Result<(), EzyTutorError> {
+        if self
+            .courses
+            .iter()
+            .any(|course| course.tutor_id == tutor_id && course.course_id == course_id)
+        {
+            Ok(())
+        } else {
+            Err(EzyTutorError::NotFound("Course id not found".into()))
+        }
+    }
+
+    // The capacity of a course, failing as check_course does when it is
+    // missing
+    fn course_capacity(&self, tutor_id: i32, course_id: i32) -> Result<Option<i32>, EzyTutorError> {
+        self.courses
+            .iter()
+            .find(|course| course.tutor_id == tutor_id && course.course_id == course_id)
+            .map(|course| course.course_capacity)
+            .ok_or_else(|| EzyTutorError::NotFound("Course id not found".into()))
+    }
+
+    // The seats left in a course, or None if it has no capacity
+    fn free_seats(&self, course_id: i32, capacity: Option<i32>) -> Option<i64> {
+        let active = self
+            .enrollments
+            .iter()
+            .filter(|enrollment| {
+                enrollment.course_id == course_id && enrollment.status == EnrollmentStatus::Active
+            })
+            .count() as i64;
+        capacity.map(|capacity| (capacity as i64 - active).max(0))
+    }
+
+    // Give the free seats of a course to the students first on its waitlist
+    fn promote_waitlist(&mut self, course_id: i32, capacity: Option<i32>) {
+        let seats = self.free_seats(course_id, capacity).unwrap_or(i64::MAX);
+        self.enrollments
+            .iter_mut()
+            .filter(|enrollment| {
+                enrollment.course_id == course_id && enrollment.status == EnrollmentStatus::Pending
+            })
+            .take(seats as usize)
+            .for_each(|enrollment| enrollment.status = EnrollmentStatus::Active);
+    }
+
+    fn find_enrollment(
+        &mut self,
+        course_id: i32,
+        enrollment_id: i32,
+    ) -> Result<&mut Enrollment, EzyTutorError> {
+        self.enrollments
+            .iter_mut()
+            .find(|enrollment| {
+                enrollment.course_id == course_id && enrollment.enrollment_id == enrollment_id
+            })
+            .ok_or_else(|| EzyTutorError::NotFound("Enrollment id not found".into()))
+    }
+
+    // The ids of a category and of all the categories below it
+    fn category_tree(&self, category_id: i32) -> Vec<i32> {
+        let mut tree = vec![category_id];
+        let mut next = 0;
+        while next < tree.len() {
+            let parent = tree[next];
+            tree.extend(
+                self.categories
+                    .iter()
+                    .filter(|category| category.parent_id == Some(parent))
+                    .map(|category| category.category_id),
+            );
+            next += 1;
+        }
+        tree
+    }
+
+    // The courses with an id in `course_ids` that match the query's filters
+    fn courses_among(
+        &self,
+        course_ids: &[i32],
+        query: &CourseQuery,
+    ) -> Result<Page<Course>, EzyTutorError> {
+        let page = query.page_request()?;
+        let filters = query.filters();
+        filters.check()?;
+        let courses = self
+            .courses
+            .iter()
+            .filter(|course| {
+                course_ids.contains(&course.course_id) && matches_filters(course, &filters)
+            })
+            .cloned()
+            .collect();
+        Ok(page_of(courses, page))
+    }
+
+    // Forget which categories and tags the courses that are gone were in,
+    // and the enrollments in them
+    fn drop_course_links(&mut self) {
+        let courses = &self.courses;
+        let exists = |course_id: i32| courses.iter().any(|course| course.course_id == course_id);
+        self.course_categories
+            .retain(|&(course_id, _)| exists(course_id));
+        self.course_tags.retain(|&(course_id, _)| exists(course_id));
+        self.enrollments
+            .retain(|enrollment| exists(enrollment.course_id));
+    }
+}
+
+impl MemoryRepository {
+    // The same tutors, courses and students as the seed data migrations
+    pub fn with_sample_data() -> Self {
+        let time = |time: &str| NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S").ok();
+        let tutor = |tutor_id, tutor_name: &str, pic: &str, tutor_profile: &str| Tutor {
+            tutor_id,
+            tutor_name: tutor_name.into(),
+            tutor_pic_url: format!("http://s3.amazon.aws.com/{}", pic),
+            tutor_profile: tutor_profile.into(),
+        };
+        let course = |course_id,
+                      tutor_id,
+                      course_name: &str,
+                      format: Option<&str>,
+                      level: Option<&str>,
+                      posted| Course {
+            course_id,
+            tutor_id,
+            course_name: course_name.into(),
+            course_description: None,
+            course_format: format.map(String::from),
+            course_structure: None,
+            course_duration: None,
+            course_price: None,
+            course_language: None,
+            course_level: level.map(String::from),
+            posted_time: time(posted),
+            course_capacity: None,
+        };
+        let category = |category_id, category_name: &str, parent_id| Category {
+            category_id,
+            category_name: category_name.into(),
+            parent_id,
+        };
+        let tag = |tag_id, tag_name: &str| Tag {
+            tag_id,
+            tag_name: tag_name.into(),
+        };
+        let student = |student_id, student_name: &str| Student {
+            student_id,
+            student_name: student_name.into(),
+        };
+        let enrollment = |enrollment_id, course_id, student_id, status, enrolled| Enrollment {
+            enrollment_id,
+            course_id,
+            student_id,
+            status,
+            enrolled_time: time(enrolled),
+        };
+        let mut store = Store {
+            tutors: vec![
+                tutor(
+                    1,
+                    "Merlene",
+                    "pic1",
+                    "Merlene is an experienced finance professional",
+                ),
+                tutor(2, "Frank", "pic2", "Frank is an expert nuclear engineer"),
+                tutor(
+                    3,
+                    "Bob",
+                    "pic3",
+                    "Bob has spent many years teaching ML to students and professionals alike",
+                ),
+            ],
+            courses: vec![
+                course(
+                    1,
+                    1,
+                    "First course",
+                    None,
+                    Some("Beginner"),
+                    "2021-04-12 05:40:00",
+                ),
+                course(
+                    2,
+                    2,
+                    "Second course",
+                    Some("ebook"),
+                    None,
+                    "2021-04-12 05:45:00",
+                ),
+                course(
+                    3,
+                    1,
+                    "Second course from author 1",
+                    Some("ebook"),
+                    None,
+                    "2021-04-12 05:45:00",
+                ),
+                course(
+                    4,
+                    1,
+                    "Third course from author 1",
+                    Some("ebook"),
+                    None,
+                    "2021-04-12 05:45:00",
+                ),
+                course(
+                    5,
+                    3,
+                    "First course from author 3",
+                    Some("ebook"),
+                    None,
+                    "2021-04-12 05:45:00",
+                ),
+            ],
+            users: vec![],
+            categories: vec![
+                category(1, "Business", None),
+                category(2, "Finance", Some(1)),
+                category(3, "Engineering", None),
+                category(4, "Nuclear engineering", Some(3)),
+                category(5, "Machine learning", Some(3)),
+            ],
+            tags: vec![
+                tag(1, "beginner"),
+                tag(2, "self-paced"),
+                tag(3, "certification"),
+            ],
+            course_categories: vec![(1, 2), (2, 4), (3, 2), (4, 1), (5, 5)],
+            course_tags: vec![(1, 1), (2, 2), (3, 2), (4, 2), (5, 2), (4, 3)],
+            students: vec![student(1, "Alice"), student(2, "Ravi"), student(3, "Mei")],
+            enrollments: vec![
+                enrollment(1, 1, 1, EnrollmentStatus::Active, "2021-05-03 09:00:00"),
+                enrollment(2, 1, 2, EnrollmentStatus::Active, "2021-05-03 09:30:00"),
+                enrollment(3, 1, 3, EnrollmentStatus::Pending, "2021-05-04 10:00:00"),
+                enrollment(4, 2, 1, EnrollmentStatus::Completed, "2021-05-01 08:00:00"),
+            ],
+            last_tutor_id: 3,
+            last_course_id: 5,
+            last_category_id: 5,
+            last_tag_id: 3,
+            last_student_id: 3,
+            last_enrollment_id: 4,
+        };
+        // The first course is full, with one student on its waitlist
+        store.courses[0].course_capacity = Some(2);
+        MemoryRepository {
+            store: Mutex::new(store),
+        }
+    }
+}
+
+#[async_trait]
+impl CourseRepository for MemoryRepository {
+    async fn get_courses_for_tutor(
+        &self,
+        tutor_id: i32,
+        query: &CourseQuery,
+    ) -> Result<Page<Course>, EzyTutorError> {
+        let page = query.page_request()?;
+        let filters = query.filters();
+        filters.check()?;
+        let store = self.store.lock().unwrap();
+        let courses = store
+            .courses
+            .iter()
+            .filter(|course| course.tutor_id == tutor_id && matches_filters(course, &filters))
+            .cloned()
+            .collect();
+        Ok(page_of(courses, page))
+    }
+
+    async fn search_courses(
+        &self,
+        query: &CourseSearchQuery,
+    ) -> Result<Page<CourseSearchResult>, EzyTutorError> {
+        let search = Search::new(query)?;
+        let filters = query.filters();
+        let store = self.store.lock().unwrap();
+        Ok(search.page(
+            store
+                .courses
+                .iter()
+                .filter(|course| matches_filters(course, &filters)),
+        ))
+    }
+
+    async fn get_course_details(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+    ) -> Result<Course, EzyTutorError> {
+        let store = self.store.lock().unwrap();
+        store
+            .courses
+            .iter()
+            .find(|course| course.tutor_id == tutor_id && course.course_id == course_id)
+            .cloned()
+            .ok_or_else(|| EzyTutorError::NotFound("Course id not found".into()))
+    }
+
+    async fn post_new_course(&self, new_course: CreateCourse) -> Result<Course, EzyTutorError> {
+        let mut store = self.store.lock().unwrap();
+        if !store
+            .tutors
+            .iter()
+            .any(|tutor| tutor.tutor_id == new_course.tutor_id)
+        {
+            return Err(EzyTutorError::NotFound("Tutor id not found".into()));
+        }
+        store.last_course_id += 1;
+        let course = Course {
+            course_id: store.last_course_id,
+            tutor_id: new_course.tutor_id,
+            course_name: new_course.course_name,
+            course_description: new_course.course_description,
+            course_format: new_course.course_format,
+            course_structure: new_course.course_structure,
+            course_duration: new_course.course_duration,
+            course_price: new_course.course_price,
+            course_language: new_course.course_language,
+            course_level: new_course.course_level,
+            posted_time: Some(Local::now().naive_local()),
+            course_capacity: None,
+        };
+        store.courses.push(course.clone());
+        Ok(course)
+    }
+
+    async fn update_course_details(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        update_course: UpdateCourse,
+    ) -> Result<Course, EzyTutorError> {
+        let mut store = self.store.lock().unwrap();
+        let course = store
+            .courses
+            .iter_mut()
+            .find(|course| course.tutor_id == tutor_id && course.course_id == course_id)
+            .ok_or_else(|| EzyTutorError::NotFound("Course id not found".into()))?;
+        if let Some(name) = update_course.course_name {
+            course.course_name = name;
+        }
+        for (field, value) in [
+            (
+                &mut course.course_description,
+                update_course.course_description,
+            ),
+            (&mut course.course_format, update_course.course_format),
+            (&mut course.course_structure, update_course.course_structure),
+            (&mut course.course_duration, update_course.course_duration),
+            (&mut course.course_language, update_course.course_language),
+            (&mut course.course_level, update_course.course_level),
+        ] {
+            if value.is_some() {
+                *field = value;
+            }
+        }
+        if update_course.course_price.is_some() {
+            course.course_price = update_course.course_price;
+        }
+        Ok(course.clone())
+    }
+
+    async fn delete_course(&self, tutor_id: i32, course_id: i32) -> Result<String, EzyTutorError> {
+        let mut store = self.store.lock().unwrap();
+        let before = store.courses.len();
+        store
+            .courses
+            .retain(|course| !(course.tutor_id == tutor_id && course.course_id == course_id));
+        store.drop_course_links();
+        Ok(format!("Deleted {} record", before - store.courses.len()))
+    }
+}
+
+#[async_trait]
+impl TutorRepository for MemoryRepository {
+    async fn get_all_tutors(&self, query: &TutorQuery) -> Result<Page<Tutor>, EzyTutorError> {
+        let page = query.page_request()?;
+        let store = self.store.lock().unwrap();
+        Ok(page_of(store.tutors.clone(), page))
+    }
+
+    async fn get_tutor_details(&self, tutor_id: i32) -> Result<Tutor, EzyTutorError> {
+        let store = self.store.lock().unwrap();
+        store
+            .tutors
+            .iter()
+            .find(|tutor| tutor.tutor_id == tutor_id)
+            .cloned()
+            .ok_or_else(|| EzyTutorError::NotFound("Tutor id not found".into()))
+    }
+
+    async fn post_new_tutor(
+        &self,
+        new_tutor: NewTutor,
+        login: Option<NewUser>,
+    ) -> Result<Tutor, EzyTutorError> {
+        let mut store = self.store.lock().unwrap();
+        if let Some(login) = &login {
+            if store
+                .users
+                .iter()
+                .any(|user| user.username == login.username)
+            {
+                return Err(EzyTutorError::InvalidInput(format!(
+                    "Username {} is taken",
+                    login.username
+                )));
+            }
+        }
+        store.last_tutor_id += 1;
+        let tutor = Tutor {
+            tutor_id: store.last_tutor_id,
+            tutor_name: new_tutor.tutor_name,
+            tutor_pic_url: new_tutor.tutor_pic_url,
+            tutor_profile: new_tutor.tutor_profile,
+        };
+        store.tutors.push(tutor.clone());
+        if let Some(login) = login {
+            store.users.push(User {
+                username: login.username,
+                tutor_id: Some(tutor.tutor_id),
+                student_id: None,
+                user_password: login.user_password,
+                is_admin: false,
+            });
+        }
+        Ok(tutor)
+    }
+
+    async fn update_tutor_details(
+        &self,
+        tutor_id: i32,
+        update_tutor: UpdateTutor,
+    ) -> Result<Tutor, EzyTutorError> {
+        let mut store = self.store.lock().unwrap();
+        let tutor = store
+            .tutors
+            .iter_mut()
+            .find(|tutor| tutor.tutor_id == tutor_id)
+            .ok_or_else(|| EzyTutorError::NotFound("Tutor id not found".into()))?;
+        for (field, value) in [
+            (&mut tutor.tutor_name, update_tutor.tutor_name),
+            (&mut tutor.tutor_pic_url, update_tutor.tutor_pic_url),
+            (&mut tutor.tutor_profile, update_tutor.tutor_profile),
+        ] {
+            if let Some(value) = value {
+                *field = value;
+            }
+        }
+        Ok(tutor.clone())
+    }
+
+    async fn delete_tutor(&self, tutor_id: i32) -> Result<String, EzyTutorError> {
+        let mut store = self.store.lock().unwrap();
+        let before = store.tutors.len();
+        store.tutors.retain(|tutor| tutor.tutor_id != tutor_id);
+        store.courses.retain(|course| course.tutor_id != tutor_id);
+        store.drop_course_links();
+        store.users.retain(|user| user.tutor_id != Some(tutor_id));
+        Ok(format!("Deleted {} record", before - store.tutors.len()))
+    }
+
+    async fn get_user(&self, username: &str) -> Result<Option<User>, EzyTutorError> {
+        let store = self.store.lock().unwrap();
+        Ok(store
+            .users
+            .iter()
+            .find(|user| user.username == username)
+            .cloned())
+    }
+}
+
+#[async_trait]
+impl CategoryRepository for MemoryRepository {
+    async fn get_all_categories(&self) -> Result<Vec<Category>, EzyTutorError> {
+        let store = self.store.lock().unwrap();
+        Ok(store.categories.clone())
+    }
+
+    async fn get_category(&self, category_id: i32) -> Result<Category, EzyTutorError> {
+        let store = self.store.lock().unwrap();
+        store
+            .categories
+            .iter()
+            .find(|category| category.category_id == category_id)
+            .cloned()
+            .ok_or_else(|| EzyTutorError::NotFound("Category id not found".into()))
+    }
+
+    async fn post_new_category(
+        &self,
+        new_category: NewCategory,
+    ) -> Result<Category, EzyTutorError> {
+        let mut store = self.store.lock().unwrap();
+        if let Some(parent_id) = new_category.parent_id {
+            if !store
+                .categories
+                .iter()
+                .any(|category| category.category_id == parent_id)
+            {
+                return Err(EzyTutorError::NotFound(
+                    "Parent category id not found".into(),
+                ));
+            }
+        }
+        store.last_category_id += 1;
+        let category = Category {
+            category_id: store.last_category_id,
+            category_name: new_category.category_name,
+            parent_id: new_category.parent_id,
+        };
+        store.categories.push(category.clone());
+        Ok(category)
+    }
+
+    async fn update_category(
+        &self,
+        category_id: i32,
+        update_category: UpdateCategory,
+    ) -> Result<Category, EzyTutorError> {
+        let mut store = self.store.lock().unwrap();
+        let category = store
+            .categories
+            .iter_mut()
+            .find(|category| category.category_id == category_id)
+            .ok_or_else(|| EzyTutorError::NotFound("Category id not found".into()))?;
+        category.category_name = update_category.category_name;
+        Ok(category.clone())
+    }
+
+    async fn delete_category(&self, category_id: i32) -> Result<String, EzyTutorError> {
+        let mut store = self.store.lock().unwrap();
+        let before = store.categories.len();
+        let tree = store.category_tree(category_id);
+        store
+            .categories
+            .retain(|category| !tree.contains(&category.category_id));
+        store
+            .course_categories
+            .retain(|(_, category_id)| !tree.contains(category_id));
+        // As in SQL, only the category asked for counts as deleted
+        let deleted = (before - store.categories.len()).min(1);
+        Ok(format!("Deleted {} record", deleted))
+    }
+
+    async fn get_courses_in_category(
+        &self,
+        category_id: i32,
+        query: &CourseQuery,
+    ) -> Result<Page<Course>, EzyTutorError> {
+        self.get_category(category_id).await?;
+        let store = self.store.lock().unwrap();
+        let tree = store.category_tree(category_id);
+        let course_ids: Vec<i32> = store
+            .course_categories
+            .iter()
+            .filter(|(_, category_id)| tree.contains(category_id))
+            .map(|&(course_id, _)| course_id)
+            .collect();
+        store.courses_among(&course_ids, query)
+    }
+
+    async fn get_course_categories(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+    ) -> Result<Vec<Category>, EzyTutorError> {
+        let store = self.store.lock().unwrap();
+        store.check_course(tutor_id, course_id)?;
+        Ok(store
+            .categories
+            .iter()
+            .filter(|category| {
+                store
+                    .course_categories
+                    .contains(&(course_id, category.category_id))
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn add_course_category(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        category_id: i32,
+    ) -> Result<(), EzyTutorError> {
+        let mut store = self.store.lock().unwrap();
+        store.check_course(tutor_id, course_id)?;
+        if !store
+            .categories
+            .iter()
+            .any(|category| category.category_id == category_id)
+        {
+            return Err(EzyTutorError::NotFound("Category id not found".into()));
+        }
+        if !store.course_categories.contains(&(course_id, category_id)) {
+            store.course_categories.push((course_id, category_id));
+        }
+        Ok(())
+    }
+
+    async fn remove_course_category(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        category_id: i32,
+    ) -> Result<(), EzyTutorError> {
+        let mut store = self.store.lock().unwrap();
+        store.check_course(tutor_id, course_id)?;
+        store
+            .course_categories
+            .retain(|&link| link != (course_id, category_id));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TagRepository for MemoryRepository {
+    async fn get_all_tags(&self) -> Result<Vec<Tag>, EzyTutorError> {
+        let store = self.store.lock().unwrap();
+        let mut tags = store.tags.clone();
+        tags.sort_by(|a, b| a.tag_name.cmp(&b.tag_name));
+        Ok(tags)
+    }
+
+    async fn get_tag(&self, tag_id: i32) -> Result<Tag, EzyTutorError> {
+        let store = self.store.lock().unwrap();
+        store
+            .tags
+            .iter()
+            .find(|tag| tag.tag_id == tag_id)
+            .cloned()
+            .ok_or_else(|| EzyTutorError::NotFound("Tag id not found".into()))
+    }
+
+    async fn get_tag_counts(&self) -> Result<Vec<TagCount>, EzyTutorError> {
+        let store = self.store.lock().unwrap();
+        let mut counts: Vec<TagCount> = store
+            .tags
+            .iter()
+            .map(|tag| TagCount {
+                tag_id: tag.tag_id,
+                tag_name: tag.tag_name.clone(),
+                course_count: store
+                    .course_tags
+                    .iter()
+                    .filter(|(_, tag_id)| *tag_id == tag.tag_id)
+                    .count() as i64,
+            })
+            .collect();
+        counts.sort_by(|a, b| {
+            b.course_count
+                .cmp(&a.course_count)
+                .then_with(|| a.tag_name.cmp(&b.tag_name))
+        });
+        Ok(counts)
+    }
+
+    async fn post_new_tag(&self, new_tag: NewTag) -> Result<Tag, EzyTutorError> {
+        let mut store = self.store.lock().unwrap();
+        if store
+            .tags
+            .iter()
+            .any(|tag| tag.tag_name == new_tag.tag_name)
+        {
+            return Err(EzyTutorError::InvalidInput(format!(
+                "Tag {} already exists",
+                new_tag.tag_name
+            )));
+        }
+        store.last_tag_id += 1;
+        let tag = Tag {
+            tag_id: store.last_tag_id,
+            tag_name: new_tag.tag_name,
+        };
+        store.tags.push(tag.clone());
+        Ok(tag)
+    }
+
+    async fn update_tag(&self, tag_id: i32, update_tag: UpdateTag) -> Result<Tag, EzyTutorError> {
+        let mut store = self.store.lock().unwrap();
+        if store
+            .tags
+            .iter()
+            .any(|tag| tag.tag_id != tag_id && tag.tag_name == update_tag.tag_name)
+        {
+            return Err(EzyTutorError::InvalidInput(format!(
+                "Tag {} already exists",
+                update_tag.tag_name
+            )));
+        }
+        let tag = store
+            .tags
+            .iter_mut()
+            .find(|tag| tag.tag_id == tag_id)
+            .ok_or_else(|| EzyTutorError::NotFound("Tag id not found".into()))?;
+        tag.tag_name = update_tag.tag_name;
+        Ok(tag.clone())
+    }
+
+    async fn delete_tag(&self, tag_id: i32) -> Result<String, EzyTutorError> {
+        let mut store = self.store.lock().unwrap();
+        let before = store.tags.len();
+        store.tags.retain(|tag| tag.tag_id != tag_id);
+        store.course_tags.retain(|&(_, id)| id != tag_id);
+        Ok(format!("Deleted {} record", before - store.tags.len()))
+    }
+
+    async fn get_courses_with_tag(
+        &self,
+        tag_id: i32,
+        query: &CourseQuery,
+    ) -> Result<Page<Course>, EzyTutorError> {
+        self.get_tag(tag_id).await?;
+        let store = self.store.lock().unwrap();
+        let course_ids: Vec<i32> = store
+            .course_tags
+            .iter()
+            .filter(|&&(_, id)| id == tag_id)
+            .map(|&(course_id, _)| course_id)
+            .collect();
+        store.courses_among(&course_ids, query)
+    }
+
+    async fn get_course_tags(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+    ) -> Result<Vec<Tag>, EzyTutorError> {
+        let store = self.store.lock().unwrap();
+        store.check_course(tutor_id, course_id)?;
+        let mut tags: Vec<Tag> = store
+            .tags
+            .iter()
+            .filter(|tag| store.course_tags.contains(&(course_id, tag.tag_id)))
+            .cloned()
+            .collect();
+        tags.sort_by(|a, b| a.tag_name.cmp(&b.tag_name));
+        Ok(tags)
+    }
+
+    async fn add_course_tag(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        tag_id: i32,
+    ) -> Result<(), EzyTutorError> {
+        let mut store = self.store.lock().unwrap();
+        store.check_course(tutor_id, course_id)?;
+        if !store.tags.iter().any(|tag| tag.tag_id == tag_id) {
+            return Err(EzyTutorError::NotFound("Tag id not found".into()));
+        }
+        if !store.course_tags.contains(&(course_id, tag_id)) {
+            store.course_tags.push((course_id, tag_id));
+        }
+        Ok(())
+    }
+
+    async fn remove_course_tag(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        tag_id: i32,
+    ) -> Result<(), EzyTutorError> {
+        let mut store = self.store.lock().unwrap();
+        store.check_course(tutor_id, course_id)?;
+        store
+            .course_tags
+            .retain(|&link| link != (course_id, tag_id));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl StudentRepository for MemoryRepository {
+    async fn get_student(&self, student_id: i32) -> Result<Student, EzyTutorError> {
+        let store = self.store.lock().unwrap();
+        store
+            .students
+            .iter()
+            .find(|student| student.student_id == student_id)
+            .cloned()
+            .ok_or_else(|| EzyTutorError::NotFound("Student id not found".into()))
+    }
+
+    async fn post_new_student(
+        &self,
+        new_student: NewStudent,
+        login: NewUser,
+    ) -> Result<Student, EzyTutorError> {
+        let mut store = self.store.lock().unwrap();
+        if store
+            .users
+            .iter()
+            .any(|user| user.username == login.username)
+        {
+            return Err(EzyTutorError::InvalidInput(format!(
+                "Username {} is taken",
+                login.username
+            )));
+        }
+        store.last_student_id += 1;
+        let student = Student {
+            student_id: store.last_student_id,
+            student_name: new_student.student_name,
+        };
+        store.students.push(student.clone());
+        store.users.push(User {
+            username: login.username,
+            tutor_id: None,
+            student_id: Some(student.student_id),
+            user_password: login.user_password,
+            is_admin: false,
+        });
+        Ok(student)
+    }
+
+    async fn get_student_courses(
+        &self,
+        student_id: i32,
+        query: &EnrollmentQuery,
+    ) -> Result<Vec<StudentCourse>, EzyTutorError> {
+        self.get_student(student_id).await?;
+        let store = self.store.lock().unwrap();
+        Ok(store
+            .enrollments
+            .iter()
+            .rev()
+            .filter(|enrollment| {
+                enrollment.student_id == student_id
+                    && query
+                        .status
+                        .is_none_or(|status| enrollment.status == status)
+            })
+            .filter_map(|enrollment| {
+                let course = store
+                    .courses
+                    .iter()
+                    .find(|course| course.course_id == enrollment.course_id)?;
+                Some(StudentCourse {
+                    enrollment: enrollment.clone(),
+                    tutor_id: course.tutor_id,
+                    course_name: course.course_name.clone(),
+                })
+            })
+            .collect())
+    }
+}
+
+// The store is locked for the whole of each change, which makes it as
+// atomic as the transactions of the databases
+#[async_trait]
+impl EnrollmentRepository for MemoryRepository {
+    async fn enroll(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        student_id: i32,
+    ) -> Result<Enrollment, EzyTutorError> {
+        let mut store = self.store.lock().unwrap();
+        let capacity = store.course_capacity(tutor_id, course_id)?;
+        if !store
+            .students
+            .iter()
+            .any(|student| student.student_id == student_id)
+        {
+            return Err(EzyTutorError::NotFound("Student id not found".into()));
+        }
+        if store.enrollments.iter().any(|enrollment| {
+            enrollment.course_id == course_id
+                && enrollment.student_id == student_id
+                && matches!(
+                    enrollment.status,
+                    EnrollmentStatus::Pending | EnrollmentStatus::Active
+                )
+        }) {
+            return Err(EzyTutorError::InvalidInput(format!(
+                "Student {} is already enrolled in course {}",
+                student_id, course_id
+            )));
+        }
+        let status = match store.free_seats(course_id, capacity) {
+            Some(0) => EnrollmentStatus::Pending,
+            _ => EnrollmentStatus::Active,
+        };
+        store.last_enrollment_id += 1;
+        let enrollment = Enrollment {
+            enrollment_id: store.last_enrollment_id,
+            course_id,
+            student_id,
+            status,
+            enrolled_time: Some(Local::now().naive_local()),
+        };
+        store.enrollments.push(enrollment.clone());
+        Ok(enrollment)
+    }
+
+    async fn get_enrollment(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        enrollment_id: i32,
+    ) -> Result<Enrollment, EzyTutorError> {
+        let mut store = self.store.lock().unwrap();
+        store.check_course(tutor_id, course_id)?;
+        store
+            .find_enrollment(course_id, enrollment_id)
+            .map(|enrollment| enrollment.clone())
+    }
+
+    async fn get_course_roster(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        query: &EnrollmentQuery,
+    ) -> Result<Vec<RosterEntry>, EzyTutorError> {
+        let store = self.store.lock().unwrap();
+        store.check_course(tutor_id, course_id)?;
+        Ok(store
+            .enrollments
+            .iter()
+            .filter(|enrollment| {
+                enrollment.course_id == course_id
+                    && query
+                        .status
+                        .is_none_or(|status| enrollment.status == status)
+            })
+            .filter_map(|enrollment| {
+                let student = store
+                    .students
+                    .iter()
+                    .find(|student| student.student_id == enrollment.student_id)?;
+                Some(RosterEntry {
+                    enrollment: enrollment.clone(),
+                    student_name: student.student_name.clone(),
+                })
+            })
+            .collect())
+    }
+
+    async fn update_enrollment_status(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        enrollment_id: i32,
+        status: EnrollmentStatus,
+    ) -> Result<Enrollment, EzyTutorError> {
+        let mut store = self.store.lock().unwrap();
+        let capacity = store.course_capacity(tutor_id, course_id)?;
+        let enrollment = store.find_enrollment(course_id, enrollment_id)?;
+        let current = enrollment.status;
+        current.check_change(status)?;
+        enrollment.status = status;
+        let enrollment = enrollment.clone();
+        if current == EnrollmentStatus::Active {
+            store.promote_waitlist(course_id, capacity);
+        }
+        Ok(enrollment)
+    }
+
+    async fn set_course_capacity(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        capacity: Option<i32>,
+    ) -> Result<Course, EzyTutorError> {
+        let mut store = self.store.lock().unwrap();
+        let course = store
+            .courses
+            .iter_mut()
+            .find(|course| course.tutor_id == tutor_id && course.course_id == course_id)
+            .ok_or_else(|| EzyTutorError::NotFound("Course id not found".into()))?;
+        course.course_capacity = capacity;
+        let course = course.clone();
+        store.promote_waitlist(course_id, capacity);
+        Ok(course)
+    }
+}
+
+// The page of `rows` asked for, in the order push_page gives them in SQL:
+// on the sort column with nulls last, then on the id
+fn page_of<T: Keyed>(mut rows: Vec<T>, page: PageRequest) -> Page<T> {
+    let total = rows.len() as i64;
+    let key = |row: &T| (row.sort_value(page.sort.column), row.id());
+    let descending = page.sort.descending;
+    rows.sort_by(|a, b| compare(&key(a), &key(b), descending));
+    let start = match &page.cursor {
+        Some(cursor) => {
+            let cursor = (cursor.value.clone(), cursor.id);
+            rows.iter()
+                .position(|row| compare(&key(row), &cursor, descending) == Ordering::Greater)
+                .unwrap_or(rows.len())
+        }
+        None => rows.len().min(page.offset as usize),
+    };
+    let rows = rows
+        .into_iter()
+        .skip(start)
+        .take(page.limit as usize + 1)
+        .collect();
+    page.into_page(rows, total)
+}
+
+fn compare(a: &(SortValue, i32), b: &(SortValue, i32), descending: bool) -> Ordering {
+    let values = match (&a.0, &b.0) {
+        (SortValue::Null, SortValue::Null) => Ordering::Equal,
+        // Nulls come last either way
+        (SortValue::Null, _) => return Ordering::Greater,
+        (_, SortValue::Null) => return Ordering::Less,
+        (SortValue::Int(a), SortValue::Int(b)) => a.cmp(b),
+        (SortValue::Text(a), SortValue::Text(b)) => a.cmp(b),
+        (SortValue::Time(a), SortValue::Time(b)) => a.cmp(b),
+        _ => Ordering::Equal,
+    };
+    let ordering = values.then(a.1.cmp(&b.1));
+    if descending {
+        ordering.reverse()
+    } else {
+        ordering
+    }
+}
+
+// The filters as push_course_filters applies them: text ignoring case,
+// and courses without a price left out of any price range
+fn matches_filters(course: &Course, filters: &CourseFilters) -> bool {
+    let same = |value: &Option<String>, filter: Option<&str>| match filter {
+        Some(filter) => value
+            .as_deref()
+            .is_some_and(|value| value.to_lowercase() == filter.to_lowercase()),
+        None => true,
+    };
+    same(&course.course_level, filters.course_level)
+        && same(&course.course_language, filters.course_language)
+        && same(&course.course_format, filters.course_format)
+        && filters
+            .min_price
+            .is_none_or(|min| course.course_price.is_some_and(|price| price >= min))
+        && filters
+            .max_price
+            .is_none_or(|max| course.course_price.is_some_and(|price| price <= max))
+}
@@ -0,0 +1,898 @@
+use crate::errors::EzyTutorError;
+use crate::models::auth::{NewUser, User};
+use crate::models::category::{Category, NewCategory, UpdateCategory};
+use crate::models::course::{
+    Course, CourseQuery, CourseSearchQuery, CourseSearchResult, CreateCourse, UpdateCourse,
+};
+use crate::models::enrollment::{
+    Enrollment, EnrollmentQuery, EnrollmentStatus, RosterEntry, StudentCourse,
+};
+use crate::models::paging::Page;
+use crate::models::student::{NewStudent, Student};
+use crate::models::tag::{NewTag, Tag, TagCount, UpdateTag};
+use crate::models::tutor::{NewTutor, Tutor, TutorQuery, UpdateTutor};
+use async_trait::async_trait;
+
+pub mod memory;
+pub mod postgres;
+mod search;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+// Where the handlers keep courses. Listings and searches check their query
+// parameters and answer with the same errors in every implementation.
+#[async_trait]
+pub trait CourseRepository: Send + Sync {
+    async fn get_courses_for_tutor(
+        &self,
+        tutor_id: i32,
+        query: &CourseQuery,
+    ) -> Result<Page<Course>, EzyTutorError>;
+    async fn search_courses(
+        &self,
+        query: &CourseSearchQuery,
+    ) -> Result<Page<CourseSearchResult>, EzyTutorError>;
+    async fn get_course_details(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+    ) -> Result<Course, EzyTutorError>;
+    async fn post_new_course(&self, new_course: CreateCourse) -> Result<Course, EzyTutorError>;
+    async fn update_course_details(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        update_course: UpdateCourse,
+    ) -> Result<Course, EzyTutorError>;
+    async fn delete_course(&self, tutor_id: i32, course_id: i32) -> Result<String, EzyTutorError>;
+}
+
+// Where the handlers keep tutors, and the logins that own them
+#[async_trait]
+pub trait TutorRepository: Send + Sync {
+    async fn get_all_tutors(&self, query: &TutorQuery) -> Result<Page<Tutor>, EzyTutorError>;
+    async fn get_tutor_details(&self, tutor_id: i32) -> Result<Tutor, EzyTutorError>;
+    // Neither the tutor nor the login is created if the username is taken
+    async fn post_new_tutor(
+        &self,
+        new_tutor: NewTutor,
+        login: Option<NewUser>,
+    ) -> Result<Tutor, EzyTutorError>;
+    async fn update_tutor_details(
+        &self,
+        tutor_id: i32,
+        update_tutor: UpdateTutor,
+    ) -> Result<Tutor, EzyTutorError>;
+    // Deletes the tutor's courses and login too
+    async fn delete_tutor(&self, tutor_id: i32) -> Result<String, EzyTutorError>;
+    async fn get_user(&self, username: &str) -> Result<Option<User>, EzyTutorError>;
+}
+
+// Where the handlers keep the category tree, and which courses are in which
+// categories. Courses are named by tutor and course id, and must exist.
+#[async_trait]
+pub trait CategoryRepository: Send + Sync {
+    async fn get_all_categories(&self) -> Result<Vec<Category>, EzyTutorError>;
+    async fn get_category(&self, category_id: i32) -> Result<Category, EzyTutorError>;
+    async fn post_new_category(&self, new_category: NewCategory)
+        -> Result<Category, EzyTutorError>;
+    async fn update_category(
+        &self,
+        category_id: i32,
+        update_category: UpdateCategory,
+    ) -> Result<Category, EzyTutorError>;
+    // Deletes the categories below it too, but none of their courses
+    async fn delete_category(&self, category_id: i32) -> Result<String, EzyTutorError>;
+    // The courses in the category or any category below it
+    async fn get_courses_in_category(
+        &self,
+        category_id: i32,
+        query: &CourseQuery,
+    ) -> Result<Page<Course>, EzyTutorError>;
+    async fn get_course_categories(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+    ) -> Result<Vec<Category>, EzyTutorError>;
+    // Adding a course to a category it is already in changes nothing
+    async fn add_course_category(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        category_id: i32,
+    ) -> Result<(), EzyTutorError>;
+    async fn remove_course_category(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        category_id: i32,
+    ) -> Result<(), EzyTutorError>;
+}
+
+// Where the handlers keep tags, and which courses have them. Tag names are
+// unique, and normalized by the handlers.
+#[async_trait]
+pub trait TagRepository: Send + Sync {
+    async fn get_all_tags(&self) -> Result<Vec<Tag>, EzyTutorError>;
+    async fn get_tag(&self, tag_id: i32) -> Result<Tag, EzyTutorError>;
+    // Most used tags first, then by name
+    async fn get_tag_counts(&self) -> Result<Vec<TagCount>, EzyTutorError>;
+    async fn post_new_tag(&self, new_tag: NewTag) -> Result<Tag, EzyTutorError>;
+    async fn update_tag(&self, tag_id: i32, update_tag: UpdateTag) -> Result<Tag, EzyTutorError>;
+    async fn delete_tag(&self, tag_id: i32) -> Result<String, EzyTutorError>;
+    async fn get_courses_with_tag(
+        &self,
+        tag_id: i32,
+        query: &CourseQuery,
+    ) -> Result<Page<Course>, EzyTutorError>;
+    async fn get_course_tags(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+    ) -> Result<Vec<Tag>, EzyTutorError>;
+    // Tagging a course twice changes nothing
+    async fn add_course_tag(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        tag_id: i32,
+    ) -> Result<(), EzyTutorError>;
+    async fn remove_course_tag(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        tag_id: i32,
+    ) -> Result<(), EzyTutorError>;
+}
+
+// Where the handlers keep students, and the logins that own them
+#[async_trait]
+pub trait StudentRepository: Send + Sync {
+    async fn get_student(&self, student_id: i32) -> Result<Student, EzyTutorError>;
+    // Neither the student nor the login is created if the username is taken
+    async fn post_new_student(
+        &self,
+        new_student: NewStudent,
+        login: NewUser,
+    ) -> Result<Student, EzyTutorError>;
+    // The enrollments of the student in every course, the latest first
+    async fn get_student_courses(
+        &self,
+        student_id: i32,
+        query: &EnrollmentQuery,
+    ) -> Result<Vec<StudentCourse>, EzyTutorError>;
+}
+
+// Where the handlers keep enrollments. The seats of a course are counted
+// and handed out in the same transaction as the change that takes or frees
+// them, so that a course never has more active students than its capacity.
+#[async_trait]
+pub trait EnrollmentRepository: Send + Sync {
+    // Active if the course has a free seat, pending on its waitlist if not
+    async fn enroll(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        student_id: i32,
+    ) -> Result<Enrollment, EzyTutorError>;
+    async fn get_enrollment(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        enrollment_id: i32,
+    ) -> Result<Enrollment, EzyTutorError>;
+    // In the order the students enrolled, which is the waitlist order
+    async fn get_course_roster(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        query: &EnrollmentQuery,
+    ) -> Result<Vec<RosterEntry>, EzyTutorError>;
+    // Completing or cancelling an active enrollment gives its seat to the
+    // first student on the waitlist
+    async fn update_enrollment_status(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        enrollment_id: i32,
+        status: EnrollmentStatus,
+    ) -> Result<Enrollment, EzyTutorError>;
+    // Seats added go to the waitlist; lowering the capacity takes no seat
+    // away, but keeps new students pending until enough are freed
+    async fn set_course_capacity(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        capacity: Option<i32>,
+    ) -> Result<Course, EzyTutorError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::memory::MemoryRepository;
+    use super::postgres::PgRepository;
+    use super::*;
+    use dotenv::dotenv;
+    use sqlx::postgres::PgPool;
+    use std::env;
+
+    fn new_course(tutor_id: i32, course_name: String, course_price: Option<i32>) -> CreateCourse {
+        CreateCourse {
+            tutor_id,
+            course_name,
+            course_description: None,
+            course_format: Some("video".into()),
+            course_structure: None,
+            course_duration: None,
+            course_price,
+            course_language: Some("English".into()),
+            course_level: None,
+        }
+    }
+
+    // The behaviour the handlers rely on, from any repository. `tag` keeps
+    // the rows of one run apart from those of earlier runs.
+    async fn check_repository<R: CourseRepository + TutorRepository>(repository: &R, tag: u32) {
+        let new_tutor = NewTutor {
+            tutor_name: format!("Tutor {}", tag),
+            tutor_pic_url: "http://tutor.s3.com/contract".into(),
+            tutor_profile: "Teaches whatever the tests need".into(),
+            username: None,
+            password: None,
+        };
+        let login = NewUser {
+            username: format!("contract{}", tag),
+            user_password: "not really a hash".into(),
+        };
+        let tutor = repository
+            .post_new_tutor(new_tutor.clone(), Some(login.clone()))
+            .await
+            .unwrap();
+        let tutor_id = tutor.tutor_id;
+        match repository.post_new_tutor(new_tutor, Some(login)).await {
+            Err(EzyTutorError::InvalidInput(msg)) => assert!(msg.contains("is taken")),
+            other => panic!("Username taken twice: {:?}", other),
+        }
+        let user = repository
+            .get_user(&format!("contract{}", tag))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.tutor_id, Some(tutor_id));
+        assert!(!user.is_admin);
+
+        let mut ids = vec![];
+        for (name, price) in [
+            (format!("Course zq{} one", tag), Some(30)),
+            ("Course two".to_string(), None),
+            ("Course three".to_string(), Some(10)),
+        ] {
+            let course = repository
+                .post_new_course(new_course(tutor_id, name, price))
+                .await
+                .unwrap();
+            assert!(course.posted_time.is_some());
+            ids.push(course.course_id);
+        }
+
+        assert!(matches!(
+            repository
+                .post_new_course(new_course(tutor_id + 1000, "Nobody's".into(), None))
+                .await,
+            Err(EzyTutorError::NotFound(_))
+        ));
+
+        // Courses without a price come last, whichever way they are sorted
+        let by_price = CourseQuery {
+            limit: Some(2),
+            sort: Some("course_price".into()),
+            ..Default::default()
+        };
+        let page = repository
+            .get_courses_for_tutor(tutor_id, &by_price)
+            .await
+            .unwrap();
+        let prices: Vec<_> = page.items.iter().map(|c| c.course_price).collect();
+        assert_eq!(prices, [Some(10), Some(30)]);
+        assert_eq!(page.paging.total, 3);
+        let after = CourseQuery {
+            cursor: page.paging.next_cursor,
+            ..by_price
+        };
+        let page = repository
+            .get_courses_for_tutor(tutor_id, &after)
+            .await
+            .unwrap();
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].course_id, ids[1]);
+        assert!(page.paging.next_cursor.is_none());
+        let descending = CourseQuery {
+            sort: Some("-course_price".into()),
+            ..Default::default()
+        };
+        let page = repository
+            .get_courses_for_tutor(tutor_id, &descending)
+            .await
+            .unwrap();
+        let ordered: Vec<_> = page.items.iter().map(|c| c.course_id).collect();
+        assert_eq!(ordered, [ids[0], ids[2], ids[1]]);
+
+        let filtered = CourseQuery {
+            min_price: Some(20),
+            course_format: Some("VIDEO".into()),
+            ..Default::default()
+        };
+        let page = repository
+            .get_courses_for_tutor(tutor_id, &filtered)
+            .await
+            .unwrap();
+        assert_eq!(page.paging.total, 1);
+        assert_eq!(page.items[0].course_id, ids[0]);
+
+        let search = CourseSearchQuery {
+            q: Some(format!("zq{}", tag)),
+            ..Default::default()
+        };
+        let page = repository.search_courses(&search).await.unwrap();
+        assert_eq!(page.paging.total, 1);
+        assert_eq!(page.items[0].course.course_id, ids[0]);
+        let snippet = page.items[0].snippet.as_deref().unwrap();
+        assert!(snippet.starts_with(&format!("Course <mark>zq{}</mark>", tag)));
+
+        let update = UpdateCourse {
+            course_name: None,
+            course_description: Some("Now with a description".into()),
+            course_format: None,
+            course_structure: None,
+            course_duration: None,
+            course_price: Some(15),
+            course_language: None,
+            course_level: None,
+        };
+        let course = repository
+            .update_course_details(tutor_id, ids[2], update.clone())
+            .await
+            .unwrap();
+        assert_eq!(course.course_name, "Course three");
+        assert_eq!(course.course_price, Some(15));
+        assert_eq!(course.course_format.as_deref(), Some("video"));
+        assert!(matches!(
+            repository
+                .update_course_details(tutor_id + 1, ids[2], update)
+                .await,
+            Err(EzyTutorError::NotFound(_))
+        ));
+
+        // Snippets are escaped for HTML, apart from the <mark> tags
+        let update = UpdateCourse {
+            course_name: None,
+            course_description: Some(format!(
+                "zx{} <script>alert(\"hi\")</script> & more",
+                tag
+            )),
+            course_format: None,
+            course_structure: None,
+            course_duration: None,
+            course_price: None,
+            course_language: None,
+            course_level: None,
+        };
+        repository
+            .update_course_details(tutor_id, ids[1], update)
+            .await
+            .unwrap();
+        let search = CourseSearchQuery {
+            q: Some(format!("zx{}", tag)),
+            ..Default::default()
+        };
+        let page = repository.search_courses(&search).await.unwrap();
+        let snippet = page.items[0].snippet.as_deref().unwrap();
+        assert!(!snippet.contains("<script>"), "{}", snippet);
+        assert!(
+            snippet.contains("&lt;script&gt;alert(&quot;hi&quot;)"),
+            "{}",
+            snippet
+        );
+        assert!(snippet.contains(&format!("<mark>zx{}</mark>", tag)), "{}", snippet);
+        assert!(snippet.contains("&amp; more"), "{}", snippet);
+
+        repository.delete_course(tutor_id, ids[2]).await.unwrap();
+        assert!(matches!(
+            repository.get_course_details(tutor_id, ids[2]).await,
+            Err(EzyTutorError::NotFound(_))
+        ));
+
+        // Deleting the tutor deletes the rest of the courses and the login
+        repository.delete_tutor(tutor_id).await.unwrap();
+        assert!(matches!(
+            repository.get_tutor_details(tutor_id).await,
+            Err(EzyTutorError::NotFound(_))
+        ));
+        assert!(matches!(
+            repository.get_course_details(tutor_id, ids[0]).await,
+            Err(EzyTutorError::NotFound(_))
+        ));
+        assert!(repository
+            .get_user(&format!("contract{}", tag))
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    // Categories and tags, from any repository. As above, `tag` keeps the
+    // rows of one run apart.
+    async fn check_categories_and_tags<R>(repository: &R, tag: u32)
+    where
+        R: CourseRepository + TutorRepository + CategoryRepository + TagRepository,
+    {
+        let new_tutor = NewTutor {
+            tutor_name: format!("Tutor {}", tag),
+            tutor_pic_url: "http://tutor.s3.com/contract".into(),
+            tutor_profile: "Teaches whatever the tests need".into(),
+            username: None,
+            password: None,
+        };
+        let tutor_id = repository
+            .post_new_tutor(new_tutor, None)
+            .await
+            .unwrap()
+            .tutor_id;
+        let mut ids = vec![];
+        for (name, price) in [("Course one", Some(30)), ("Course two", None)] {
+            let course = repository
+                .post_new_course(new_course(tutor_id, name.into(), price))
+                .await
+                .unwrap();
+            ids.push(course.course_id);
+        }
+
+        // top > middle > bottom
+        let mut parent_id = None;
+        let mut categories = vec![];
+        for level in ["top", "middle", "bottom"] {
+            let category = repository
+                .post_new_category(NewCategory {
+                    category_name: format!("{} {}", level, tag),
+                    parent_id,
+                })
+                .await
+                .unwrap();
+            assert_eq!(category.parent_id, parent_id);
+            parent_id = Some(category.category_id);
+            categories.push(category.category_id);
+        }
+        let orphan = NewCategory {
+            category_name: "orphan".into(),
+            parent_id: Some(categories[2] + 1000),
+        };
+        assert!(matches!(
+            repository.post_new_category(orphan).await,
+            Err(EzyTutorError::NotFound(_))
+        ));
+        let all = repository.get_all_categories().await.unwrap();
+        assert!(all
+            .iter()
+            .any(|category| category.category_id == categories[1]));
+
+        repository
+            .add_course_category(tutor_id, ids[0], categories[2])
+            .await
+            .unwrap();
+        repository
+            .add_course_category(tutor_id, ids[0], categories[2])
+            .await
+            .unwrap();
+        repository
+            .add_course_category(tutor_id, ids[1], categories[0])
+            .await
+            .unwrap();
+        assert!(matches!(
+            repository
+                .add_course_category(tutor_id + 1, ids[0], categories[0])
+                .await,
+            Err(EzyTutorError::NotFound(_))
+        ));
+        assert!(matches!(
+            repository
+                .add_course_category(tutor_id, ids[0], categories[2] + 1000)
+                .await,
+            Err(EzyTutorError::NotFound(_))
+        ));
+        let in_course = repository
+            .get_course_categories(tutor_id, ids[0])
+            .await
+            .unwrap();
+        assert_eq!(in_course.len(), 1);
+        assert_eq!(in_course[0].category_name, format!("bottom {}", tag));
+
+        // A course is in the categories above its own
+        let by_id = CourseQuery {
+            sort: Some("course_id".into()),
+            ..Default::default()
+        };
+        let page = repository
+            .get_courses_in_category(categories[0], &by_id)
+            .await
+            .unwrap();
+        let listed: Vec<_> = page.items.iter().map(|c| c.course_id).collect();
+        assert_eq!(listed, ids);
+        assert_eq!(page.paging.total, 2);
+        let page = repository
+            .get_courses_in_category(categories[1], &by_id)
+            .await
+            .unwrap();
+        assert_eq!(page.paging.total, 1);
+        assert_eq!(page.items[0].course_id, ids[0]);
+        let priced = CourseQuery {
+            max_price: Some(50),
+            ..Default::default()
+        };
+        let page = repository
+            .get_courses_in_category(categories[0], &priced)
+            .await
+            .unwrap();
+        assert_eq!(page.paging.total, 1);
+        assert!(matches!(
+            repository
+                .get_courses_in_category(categories[2] + 1000, &by_id)
+                .await,
+            Err(EzyTutorError::NotFound(_))
+        ));
+
+        let renamed = repository
+            .update_category(
+                categories[1],
+                UpdateCategory {
+                    category_name: format!("renamed {}", tag),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(renamed.parent_id, Some(categories[0]));
+
+        // Tags
+        let new_tag = NewTag {
+            tag_name: format!("zq{}", tag),
+        };
+        let tag_id = repository
+            .post_new_tag(new_tag.clone())
+            .await
+            .unwrap()
+            .tag_id;
+        match repository.post_new_tag(new_tag).await {
+            Err(EzyTutorError::InvalidInput(msg)) => assert!(msg.contains("already exists")),
+            other => panic!("Tag added twice: {:?}", other),
+        }
+        for course_id in &ids {
+            repository
+                .add_course_tag(tutor_id, *course_id, tag_id)
+                .await
+                .unwrap();
+        }
+        repository
+            .add_course_tag(tutor_id, ids[0], tag_id)
+            .await
+            .unwrap();
+        let count = |counts: Vec<TagCount>| {
+            counts
+                .into_iter()
+                .find(|count| count.tag_id == tag_id)
+                .unwrap()
+                .course_count
+        };
+        assert_eq!(count(repository.get_tag_counts().await.unwrap()), 2);
+        let tags = repository.get_course_tags(tutor_id, ids[1]).await.unwrap();
+        assert_eq!(tags[0].tag_name, format!("zq{}", tag));
+        let page = repository
+            .get_courses_with_tag(tag_id, &by_id)
+            .await
+            .unwrap();
+        assert_eq!(page.paging.total, 2);
+        repository
+            .remove_course_tag(tutor_id, ids[1], tag_id)
+            .await
+            .unwrap();
+        assert_eq!(count(repository.get_tag_counts().await.unwrap()), 1);
+        let tag_row = repository
+            .update_tag(
+                tag_id,
+                UpdateTag {
+                    tag_name: format!("zq{} renamed", tag),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(tag_row, repository.get_tag(tag_id).await.unwrap());
+
+        // Deleting a course takes it out of its categories and tags
+        repository.delete_course(tutor_id, ids[0]).await.unwrap();
+        assert_eq!(count(repository.get_tag_counts().await.unwrap()), 0);
+        let page = repository
+            .get_courses_in_category(categories[0], &by_id)
+            .await
+            .unwrap();
+        assert_eq!(page.paging.total, 1);
+
+        // Deleting a category deletes those below it, but not the courses
+        repository.delete_category(categories[0]).await.unwrap();
+        for category_id in &categories {
+            assert!(matches!(
+                repository.get_category(*category_id).await,
+                Err(EzyTutorError::NotFound(_))
+            ));
+        }
+        assert!(repository
+            .get_course_categories(tutor_id, ids[1])
+            .await
+            .unwrap()
+            .is_empty());
+        repository.delete_tag(tag_id).await.unwrap();
+        assert!(matches!(
+            repository.get_tag(tag_id).await,
+            Err(EzyTutorError::NotFound(_))
+        ));
+        repository.delete_tutor(tutor_id).await.unwrap();
+    }
+
+    // Students, enrollments and waitlists, from any repository. As above,
+    // `tag` keeps the rows of one run apart.
+    async fn check_enrollments<R>(repository: &R, tag: u32)
+    where
+        R: CourseRepository + TutorRepository + StudentRepository + EnrollmentRepository,
+    {
+        let new_tutor = NewTutor {
+            tutor_name: format!("Tutor {}", tag),
+            tutor_pic_url: "http://tutor.s3.com/contract".into(),
+            tutor_profile: "Teaches whatever the tests need".into(),
+            username: None,
+            password: None,
+        };
+        let tutor_id = repository
+            .post_new_tutor(new_tutor, None)
+            .await
+            .unwrap()
+            .tutor_id;
+        let course_id = repository
+            .post_new_course(new_course(tutor_id, "Course one".into(), None))
+            .await
+            .unwrap()
+            .course_id;
+
+        let mut students = vec![];
+        for name in ["Ann", "Ben", "Cat"] {
+            let login = NewUser {
+                username: format!("{}{}", name.to_lowercase(), tag),
+                user_password: "not really a hash".into(),
+            };
+            let new_student = NewStudent {
+                student_name: name.into(),
+                username: login.username.clone(),
+                password: "not a password".into(),
+            };
+            let student = repository
+                .post_new_student(new_student.clone(), login.clone())
+                .await
+                .unwrap();
+            assert_eq!(student.student_name, name);
+            if name == "Ann" {
+                match repository.post_new_student(new_student, login).await {
+                    Err(EzyTutorError::InvalidInput(msg)) => assert!(msg.contains("is taken")),
+                    other => panic!("Username taken twice: {:?}", other),
+                }
+            }
+            students.push(student.student_id);
+        }
+        let user = repository
+            .get_user(&format!("ann{}", tag))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.student_id, Some(students[0]));
+        assert_eq!(user.tutor_id, None);
+        assert!(matches!(
+            repository.get_student(0).await,
+            Err(EzyTutorError::NotFound(_))
+        ));
+
+        // Two seats: the third student waits
+        let course = repository
+            .set_course_capacity(tutor_id, course_id, Some(2))
+            .await
+            .unwrap();
+        assert_eq!(course.course_capacity, Some(2));
+        let mut enrollments = vec![];
+        for student_id in &students {
+            let enrollment = repository
+                .enroll(tutor_id, course_id, *student_id)
+                .await
+                .unwrap();
+            enrollments.push(enrollment);
+        }
+        let statuses: Vec<_> = enrollments.iter().map(|e| e.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                EnrollmentStatus::Active,
+                EnrollmentStatus::Active,
+                EnrollmentStatus::Pending
+            ]
+        );
+        match repository.enroll(tutor_id, course_id, students[0]).await {
+            Err(EzyTutorError::InvalidInput(msg)) => assert!(msg.contains("already enrolled")),
+            other => panic!("Enrolled twice: {:?}", other),
+        }
+        assert!(matches!(
+            repository.enroll(tutor_id, course_id, 0).await,
+            Err(EzyTutorError::NotFound(_))
+        ));
+        assert!(matches!(
+            repository.enroll(0, course_id, students[0]).await,
+            Err(EzyTutorError::NotFound(_))
+        ));
+
+        let pending = EnrollmentQuery {
+            status: Some(EnrollmentStatus::Pending),
+        };
+        let roster = repository
+            .get_course_roster(tutor_id, course_id, &EnrollmentQuery::default())
+            .await
+            .unwrap();
+        let names: Vec<_> = roster.iter().map(|e| e.student_name.as_str()).collect();
+        assert_eq!(names, ["Ann", "Ben", "Cat"]);
+        let waitlist = repository
+            .get_course_roster(tutor_id, course_id, &pending)
+            .await
+            .unwrap();
+        assert_eq!(waitlist.len(), 1);
+        assert_eq!(waitlist[0].enrollment, enrollments[2]);
+
+        // Cancelling a seat gives it to the first student waiting
+        let cancelled = repository
+            .update_enrollment_status(
+                tutor_id,
+                course_id,
+                enrollments[0].enrollment_id,
+                EnrollmentStatus::Cancelled,
+            )
+            .await
+            .unwrap();
+        assert_eq!(cancelled.status, EnrollmentStatus::Cancelled);
+        let promoted = repository
+            .get_enrollment(tutor_id, course_id, enrollments[2].enrollment_id)
+            .await
+            .unwrap();
+        assert_eq!(promoted.status, EnrollmentStatus::Active);
+        assert!(matches!(
+            repository
+                .update_enrollment_status(
+                    tutor_id,
+                    course_id,
+                    enrollments[0].enrollment_id,
+                    EnrollmentStatus::Cancelled,
+                )
+                .await,
+            Err(EzyTutorError::InvalidInput(_))
+        ));
+
+        // A cancelled student may enroll again, at the end of the waitlist
+        let again = repository
+            .enroll(tutor_id, course_id, students[0])
+            .await
+            .unwrap();
+        assert_eq!(again.status, EnrollmentStatus::Pending);
+        assert!(matches!(
+            repository
+                .update_enrollment_status(
+                    tutor_id,
+                    course_id,
+                    again.enrollment_id,
+                    EnrollmentStatus::Completed,
+                )
+                .await,
+            Err(EzyTutorError::InvalidInput(_))
+        ));
+        repository
+            .update_enrollment_status(
+                tutor_id,
+                course_id,
+                enrollments[1].enrollment_id,
+                EnrollmentStatus::Completed,
+            )
+            .await
+            .unwrap();
+        let again = repository
+            .get_enrollment(tutor_id, course_id, again.enrollment_id)
+            .await
+            .unwrap();
+        assert_eq!(again.status, EnrollmentStatus::Active);
+
+        // Lowering the capacity takes no seat away; raising it seats the
+        // waitlist
+        repository
+            .set_course_capacity(tutor_id, course_id, Some(1))
+            .await
+            .unwrap();
+        let late = repository
+            .enroll(tutor_id, course_id, students[1])
+            .await
+            .unwrap();
+        assert_eq!(late.status, EnrollmentStatus::Pending);
+        let active = EnrollmentQuery {
+            status: Some(EnrollmentStatus::Active),
+        };
+        let seated = repository
+            .get_course_roster(tutor_id, course_id, &active)
+            .await
+            .unwrap();
+        assert_eq!(seated.len(), 2);
+        repository
+            .set_course_capacity(tutor_id, course_id, None)
+            .await
+            .unwrap();
+        let seated = repository
+            .get_course_roster(tutor_id, course_id, &active)
+            .await
+            .unwrap();
+        assert_eq!(seated.len(), 3);
+
+        let courses = repository
+            .get_student_courses(students[0], &EnrollmentQuery::default())
+            .await
+            .unwrap();
+        let statuses: Vec<_> = courses.iter().map(|c| c.enrollment.status).collect();
+        assert_eq!(
+            statuses,
+            [EnrollmentStatus::Active, EnrollmentStatus::Cancelled]
+        );
+        assert_eq!(courses[0].course_name, "Course one");
+        assert_eq!(courses[0].tutor_id, tutor_id);
+
+        // Deleting the course deletes its enrollments
+        repository.delete_course(tutor_id, course_id).await.unwrap();
+        assert!(repository
+            .get_student_courses(students[0], &EnrollmentQuery::default())
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(matches!(
+            repository
+                .get_course_roster(tutor_id, course_id, &EnrollmentQuery::default())
+                .await,
+            Err(EzyTutorError::NotFound(_))
+        ));
+        repository.delete_tutor(tutor_id).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn memory_repository_test() {
+        check_repository(&MemoryRepository::with_sample_data(), 1).await;
+        check_categories_and_tags(&MemoryRepository::with_sample_data(), 1).await;
+        check_enrollments(&MemoryRepository::with_sample_data(), 1).await;
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[actix_rt::test]
+    async fn sqlite_repository_test() {
+        use crate::migrate::SQLITE_SCHEMA;
+        let path = env::temp_dir().join(format!("ezytutors-{}.db", rand::random::<u32>()));
+        let pool = sqlite::connect(&format!("sqlite://{}", path.display()))
+            .await
+            .unwrap();
+        SQLITE_SCHEMA.run(&pool).await.unwrap();
+        check_repository(&sqlite::SqliteRepository::new(pool.clone()), 1).await;
+        check_categories_and_tags(&sqlite::SqliteRepository::new(pool.clone()), 1).await;
+        check_enrollments(&sqlite::SqliteRepository::new(pool.clone()), 1).await;
+        pool.close().await;
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn postgres_repository_test() {
+        dotenv().ok();
+        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
+        let pool: PgPool = PgPool::connect(&database_url).await.unwrap();
+        check_repository(&PgRepository::new(pool.clone()), rand::random()).await;
+        check_categories_and_tags(&PgRepository::new(pool.clone()), rand::random()).await;
+        check_enrollments(&PgRepository::new(pool), rand::random()).await;
+    }
+}
@@ -0,0 +1,309 @@
+use crate::dbaccess::{
+    auth::*, category::*, course::*, enrollment::*, student::*, tag::*, tutor::*,
+};
+use crate::errors::EzyTutorError;
+use crate::models::auth::{NewUser, User};
+use crate::models::category::{Category, NewCategory, UpdateCategory};
+use crate::models::course::{
+    Course, CourseQuery, CourseSearchQuery, CourseSearchResult, CreateCourse, UpdateCourse,
+};
+use crate::models::enrollment::{
+    Enrollment, EnrollmentQuery, EnrollmentStatus, RosterEntry, StudentCourse,
+};
+use crate::models::paging::Page;
+use crate::models::student::{NewStudent, Student};
+use crate::models::tag::{NewTag, Tag, TagCount, UpdateTag};
+use crate::models::tutor::{NewTutor, Tutor, TutorQuery, UpdateTutor};
+use crate::repository::{
+    CategoryRepository, CourseRepository, EnrollmentRepository, StudentRepository, TagRepository,
+    TutorRepository,
+};
+use async_trait::async_trait;
+use sqlx::postgres::PgPool;
+
+// Courses, tutors, students and the rest kept in Postgres, through the
+// functions in dbaccess
+pub struct PgRepository {
+    pool: PgPool,
+}
+
+impl PgRepository {
+    pub fn new(pool: PgPool) -> Self {
+        PgRepository { pool }
+    }
+}
+
+#[async_trait]
+impl CourseRepository for PgRepository {
+    async fn get_courses_for_tutor(
+        &self,
+        tutor_id: i32,
+        query: &CourseQuery,
+    ) -> Result<Page<Course>, EzyTutorError> {
+        get_courses_for_tutor_db(&self.pool, tutor_id, query).await
+    }
+
+    async fn search_courses(
+        &self,
+        query: &CourseSearchQuery,
+    ) -> Result<Page<CourseSearchResult>, EzyTutorError> {
+        search_courses_db(&self.pool, query).await
+    }
+
+    async fn get_course_details(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+    ) -> Result<Course, EzyTutorError> {
+        get_course_details_db(&self.pool, tutor_id, course_id).await
+    }
+
+    async fn post_new_course(&self, new_course: CreateCourse) -> Result<Course, EzyTutorError> {
+        post_new_course_db(&self.pool, new_course).await
+    }
+
+    async fn update_course_details(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        update_course: UpdateCourse,
+    ) -> Result<Course, EzyTutorError> {
+        update_course_details_db(&self.pool, tutor_id, course_id, update_course).await
+    }
+
+    async fn delete_course(&self, tutor_id: i32, course_id: i32) -> Result<String, EzyTutorError> {
+        delete_course_db(&self.pool, tutor_id, course_id).await
+    }
+}
+
+#[async_trait]
+impl TutorRepository for PgRepository {
+    async fn get_all_tutors(&self, query: &TutorQuery) -> Result<Page<Tutor>, EzyTutorError> {
+        get_all_tutors_db(&self.pool, query).await
+    }
+
+    async fn get_tutor_details(&self, tutor_id: i32) -> Result<Tutor, EzyTutorError> {
+        get_tutor_details_db(&self.pool, tutor_id).await
+    }
+
+    async fn post_new_tutor(
+        &self,
+        new_tutor: NewTutor,
+        login: Option<NewUser>,
+    ) -> Result<Tutor, EzyTutorError> {
+        post_new_tutor_db(&self.pool, new_tutor, login).await
+    }
+
+    async fn update_tutor_details(
+        &self,
+        tutor_id: i32,
+        update_tutor: UpdateTutor,
+    ) -> Result<Tutor, EzyTutorError> {
+        update_tutor_details_db(&self.pool, tutor_id, update_tutor).await
+    }
+
+    async fn delete_tutor(&self, tutor_id: i32) -> Result<String, EzyTutorError> {
+        delete_tutor_db(&self.pool, tutor_id).await
+    }
+
+    async fn get_user(&self, username: &str) -> Result<Option<User>, EzyTutorError> {
+        get_user_db(&self.pool, username).await
+    }
+}
+
+#[async_trait]
+impl CategoryRepository for PgRepository {
+    async fn get_all_categories(&self) -> Result<Vec<Category>, EzyTutorError> {
+        get_all_categories_db(&self.pool).await
+    }
+
+    async fn get_category(&self, category_id: i32) -> Result<Category, EzyTutorError> {
+        get_category_db(&self.pool, category_id).await
+    }
+
+    async fn post_new_category(
+        &self,
+        new_category: NewCategory,
+    ) -> Result<Category, EzyTutorError> {
+        post_new_category_db(&self.pool, new_category).await
+    }
+
+    async fn update_category(
+        &self,
+        category_id: i32,
+        update_category: UpdateCategory,
+    ) -> Result<Category, EzyTutorError> {
+        update_category_db(&self.pool, category_id, update_category).await
+    }
+
+    async fn delete_category(&self, category_id: i32) -> Result<String, EzyTutorError> {
+        delete_category_db(&self.pool, category_id).await
+    }
+
+    async fn get_courses_in_category(
+        &self,
+        category_id: i32,
+        query: &CourseQuery,
+    ) -> Result<Page<Course>, EzyTutorError> {
+        get_courses_in_category_db(&self.pool, category_id, query).await
+    }
+
+    async fn get_course_categories(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+    ) -> Result<Vec<Category>, EzyTutorError> {
+        get_course_categories_db(&self.pool, tutor_id, course_id).await
+    }
+
+    async fn add_course_category(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        category_id: i32,
+    ) -> Result<(), EzyTutorError> {
+        add_course_category_db(&self.pool, tutor_id, course_id, category_id).await
+    }
+
+    async fn remove_course_category(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        category_id: i32,
+    ) -> Result<(), EzyTutorError> {
+        remove_course_category_db(&self.pool, tutor_id, course_id, category_id).await
+    }
+}
+
+#[async_trait]
+impl TagRepository for PgRepository {
+    async fn get_all_tags(&self) -> Result<Vec<Tag>, EzyTutorError> {
+        get_all_tags_db(&self.pool).await
+    }
+
+    async fn get_tag(&self, tag_id: i32) -> Result<Tag, EzyTutorError> {
+        get_tag_db(&self.pool, tag_id).await
+    }
+
+    async fn get_tag_counts(&self) -> Result<Vec<TagCount>, EzyTutorError> {
+        get_tag_counts_db(&self.pool).await
+    }
+
+    async fn post_new_tag(&self, new_tag: NewTag) -> Result<Tag, EzyTutorError> {
+        post_new_tag_db(&self.pool, new_tag).await
+    }
+
+    async fn update_tag(&self, tag_id: i32, update_tag: UpdateTag) -> Result<Tag, EzyTutorError> {
+        update_tag_db(&self.pool, tag_id, update_tag).await
+    }
+
+    async fn delete_tag(&self, tag_id: i32) -> Result<String, EzyTutorError> {
+        delete_tag_db(&self.pool, tag_id).await
+    }
+
+    async fn get_courses_with_tag(
+        &self,
+        tag_id: i32,
+        query: &CourseQuery,
+    ) -> Result<Page<Course>, EzyTutorError> {
+        get_courses_with_tag_db(&self.pool, tag_id, query).await
+    }
+
+    async fn get_course_tags(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+    ) -> Result<Vec<Tag>, EzyTutorError> {
+        get_course_tags_db(&self.pool, tutor_id, course_id).await
+    }
+
+    async fn add_course_tag(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        tag_id: i32,
+    ) -> Result<(), EzyTutorError> {
+        add_course_tag_db(&self.pool, tutor_id, course_id, tag_id).await
+    }
+
+    async fn remove_course_tag(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        tag_id: i32,
+    ) -> Result<(), EzyTutorError> {
+        remove_course_tag_db(&self.pool, tutor_id, course_id, tag_id).await
+    }
+}
+
+#[async_trait]
+impl StudentRepository for PgRepository {
+    async fn get_student(&self, student_id: i32) -> Result<Student, EzyTutorError> {
+        get_student_db(&self.pool, student_id).await
+    }
+
+    async fn post_new_student(
+        &self,
+        new_student: NewStudent,
+        login: NewUser,
+    ) -> Result<Student, EzyTutorError> {
+        post_new_student_db(&self.pool, new_student, login).await
+    }
+
+    async fn get_student_courses(
+        &self,
+        student_id: i32,
+        query: &EnrollmentQuery,
+    ) -> Result<Vec<StudentCourse>, EzyTutorError> {
+        get_student_courses_db(&self.pool, student_id, query).await
+    }
+}
+
+#[async_trait]
+impl EnrollmentRepository for PgRepository {
+    async fn enroll(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        student_id: i32,
+    ) -> Result<Enrollment, EzyTutorError> {
+        enroll_db(&self.pool, tutor_id, course_id, student_id).await
+    }
+
+    async fn get_enrollment(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        enrollment_id: i32,
+    ) -> Result<Enrollment, EzyTutorError> {
+        get_enrollment_db(&self.pool, tutor_id, course_id, enrollment_id).await
+    }
+
+    async fn get_course_roster(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        query: &EnrollmentQuery,
+    ) -> Result<Vec<RosterEntry>, EzyTutorError> {
+        get_course_roster_db(&self.pool, tutor_id, course_id, query).await
+    }
+
+    async fn update_enrollment_status(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        enrollment_id: i32,
+        status: EnrollmentStatus,
+    ) -> Result<Enrollment, EzyTutorError> {
+        update_enrollment_status_db(&self.pool, tutor_id, course_id, enrollment_id, status).await
+    }
+
+    async fn set_course_capacity(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        capacity: Option<i32>,
+    ) -> Result<Course, EzyTutorError> {
+        set_course_capacity_db(&self.pool, tutor_id, course_id, capacity).await
+    }
+}
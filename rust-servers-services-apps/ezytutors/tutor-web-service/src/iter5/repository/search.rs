@@ -0,0 +1,232 @@
+use crate::errors::EzyTutorError;
+use crate::models::course::{Course, CourseSearchQuery, CourseSearchResult};
+use crate::models::paging::{page_bounds, Page, Paging};
+
+// Words of a search snippet, like MaxWords for ts_headline
+const SNIPPET_WORDS: usize = 25;
+
+// Course search done in process, for storage without full-text search of
+// its own. Results are ranked like the Postgres search, though words are
+// matched more crudely.
+pub struct Search {
+    alternatives: Vec<Alternative>,
+    limit: i64,
+    offset: i64,
+}
+
+impl Search {
+    // Check the search terms, page and filters asked for
+    pub fn new(query: &CourseSearchQuery) -> Result<Self, EzyTutorError> {
+        let alternatives = parse_search(query.terms()?);
+        let (limit, offset) = page_bounds(query.limit, query.offset)?;
+        query.filters().check()?;
+        Ok(Search {
+            alternatives,
+            limit,
+            offset,
+        })
+    }
+
+    // The page of results among `courses`, which must already match the
+    // filters. Best matches come first, then the newest courses.
+    pub fn page<'a>(&self, courses: impl Iterator<Item = &'a Course>) -> Page<CourseSearchResult> {
+        let mut results: Vec<CourseSearchResult> = courses
+            .filter_map(|course| search_result(course, &self.alternatives))
+            .collect();
+        results.sort_by(|a, b| {
+            b.rank
+                .total_cmp(&a.rank)
+                .then(b.course.course_id.cmp(&a.course.course_id))
+        });
+        let total = results.len() as i64;
+        Page {
+            items: results
+                .into_iter()
+                .skip(self.offset as usize)
+                .take(self.limit as usize)
+                .collect(),
+            paging: Paging {
+                limit: self.limit,
+                offset: Some(self.offset),
+                total,
+                next_cursor: None,
+            },
+        }
+    }
+}
+
+// A search in web search syntax, roughly as websearch_to_tsquery reads it:
+// alternatives separated by "or", each with phrases that must all appear
+// and phrases that must not. Words are compared after a crude stemming, and
+// there are no stop words.
+type Phrase = Vec<String>;
+
+#[derive(Default)]
+struct Alternative {
+    include: Vec<Phrase>,
+    exclude: Vec<Phrase>,
+}
+
+fn parse_search(terms: &str) -> Vec<Alternative> {
+    let mut alternatives = vec![Alternative::default()];
+    let mut rest = terms;
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+        let excluded = rest.starts_with('-');
+        if excluded {
+            rest = &rest[1..];
+        }
+        let text = if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            rest = quoted.get(end + 1..).unwrap_or("");
+            &quoted[..end]
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let text = &rest[..end];
+            rest = &rest[end..];
+            text
+        };
+        if !excluded && text.eq_ignore_ascii_case("or") {
+            alternatives.push(Alternative::default());
+            continue;
+        }
+        let phrase = words(text);
+        if phrase.is_empty() {
+            continue;
+        }
+        let alternative = alternatives.last_mut().unwrap();
+        if excluded {
+            alternative.exclude.push(phrase);
+        } else {
+            alternative.include.push(phrase);
+        }
+    }
+    alternatives.retain(|alternative| !alternative.include.is_empty());
+    alternatives
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(stem)
+        .collect()
+}
+
+fn stem(word: &str) -> String {
+    let word = word.to_lowercase();
+    for suffix in ["ing", "ed", "s"] {
+        if let Some(stem) = word.strip_suffix(suffix) {
+            if stem.chars().count() >= 3 {
+                return stem.to_string();
+            }
+        }
+    }
+    word
+}
+
+// How often a phrase appears in a text
+fn occurrences(text: &[String], phrase: &Phrase) -> usize {
+    text.windows(phrase.len())
+        .filter(|window| window == phrase)
+        .count()
+}
+
+// Rank a course the way the search index weighs it: the name most, then
+// the description, then the structure
+fn search_result(course: &Course, search: &[Alternative]) -> Option<CourseSearchResult> {
+    let fields = [
+        (Some(&course.course_name), 1.0),
+        (course.course_description.as_ref(), 0.4),
+        (course.course_structure.as_ref(), 0.2),
+    ]
+    .map(|(text, weight)| (text.map_or_else(Vec::new, |text| words(text)), weight));
+    let count = |phrase: &Phrase| -> f32 {
+        fields
+            .iter()
+            .map(|(text, weight)| occurrences(text, phrase) as f32 * weight)
+            .sum()
+    };
+    let matched: Vec<&Alternative> = search
+        .iter()
+        .filter(|alternative| {
+            alternative.include.iter().all(|phrase| count(phrase) > 0.0)
+                && alternative
+                    .exclude
+                    .iter()
+                    .all(|phrase| count(phrase) == 0.0)
+        })
+        .collect();
+    let rank = matched
+        .iter()
+        .map(|alternative| alternative.include.iter().map(count).sum::<f32>())
+        .reduce(f32::max)?;
+    let marked: Vec<&String> = matched
+        .iter()
+        .flat_map(|alternative| alternative.include.iter().flatten())
+        .collect();
+    Some(CourseSearchResult {
+        course: course.clone(),
+        rank,
+        snippet: Some(snippet(course, &marked)),
+    })
+}
+
+// The course text around the first match, escaped for HTML, with the
+// matching words wrapped in <mark> tags
+fn snippet(course: &Course, marked: &[&String]) -> String {
+    let text: Vec<&str> = [
+        Some(&course.course_name),
+        course.course_description.as_ref(),
+        course.course_structure.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+    .flat_map(|text| text.split_whitespace())
+    .collect();
+    let is_marked = |token: &str| words(token).iter().any(|word| marked.contains(&word));
+    let first = text.iter().position(|token| is_marked(token)).unwrap_or(0);
+    let start = first
+        .saturating_sub(5)
+        .min(text.len().saturating_sub(SNIPPET_WORDS));
+    text.iter()
+        .skip(start)
+        .take(SNIPPET_WORDS)
+        .map(|token| mark_words(token, marked))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// Escape a word of course text for HTML, wrapping the parts that match the
+// search in <mark> tags. Punctuation around them stays outside the tags.
+fn mark_words(token: &str, marked: &[&String]) -> String {
+    let mut out = String::new();
+    let mut rest = token;
+    while let Some(c) = rest.chars().next() {
+        if !c.is_alphanumeric() {
+            match c {
+                '&' => out.push_str("&amp;"),
+                '<' => out.push_str("&lt;"),
+                '>' => out.push_str("&gt;"),
+                '"' => out.push_str("&quot;"),
+                '\'' => out.push_str("&#39;"),
+                c => out.push(c),
+            }
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+        let end = rest
+            .find(|c: char| !c.is_alphanumeric())
+            .unwrap_or(rest.len());
+        let word = &rest[..end];
+        if marked.contains(&&stem(word)) {
+            out.push_str(&format!("<mark>{}</mark>", word));
+        } else {
+            out.push_str(word);
+        }
+        rest = &rest[end..];
+    }
+    out
+}
@@ -0,0 +1,833 @@
+use crate::dbaccess::category::push_in_category;
+use crate::dbaccess::course::push_course_filters;
+use crate::dbaccess::enrollment::{
+    promote_sql, roster_sql, student_courses_sql, ENROLLMENT_COLUMNS,
+};
+use crate::dbaccess::paging::push_page;
+use crate::dbaccess::tag::push_with_tag;
+use crate::errors::EzyTutorError;
+use crate::models::auth::{NewUser, User};
+use crate::models::category::{Category, NewCategory, UpdateCategory};
+use crate::models::course::{
+    Course, CourseQuery, CourseSearchQuery, CourseSearchResult, CreateCourse, UpdateCourse,
+};
+use crate::models::enrollment::{
+    Enrollment, EnrollmentQuery, EnrollmentStatus, RosterEntry, StudentCourse,
+};
+use crate::models::paging::Page;
+use crate::models::student::{NewStudent, Student};
+use crate::models::tag::{NewTag, Tag, TagCount, UpdateTag};
+use crate::models::tutor::{NewTutor, Tutor, TutorQuery, UpdateTutor};
+use crate::repository::search::Search;
+use crate::repository::{
+    CategoryRepository, CourseRepository, EnrollmentRepository, StudentRepository, TagRepository,
+    TutorRepository,
+};
+use async_trait::async_trait;
+use sqlx::sqlite::{Sqlite, SqliteConnectOptions, SqlitePool};
+use sqlx::{QueryBuilder, Transaction};
+use std::str::FromStr;
+
+// SQLite result codes for failed constraints. Statements with RETURNING
+// fail with a plain SQLITE_ERROR instead, so rows they refer to are looked
+// up first.
+//
+// Statements with RETURNING are always read to the end with fetch_all():
+// SQLite only finishes such a statement, and commits its change, once every
+// row has been read, so after fetch_one() other connections in the pool
+// would not see the change yet.
+const PRIMARY_KEY_VIOLATION: &str = "1555";
+const UNIQUE_VIOLATION: &str = "2067";
+const FOREIGN_KEY_VIOLATION: &str = "787";
+
+// Open the SQLite database in `database_url`, creating the file if it is
+// missing. Foreign keys are enforced on every connection, so that deleting
+// a tutor deletes their courses and login.
+pub async fn connect(database_url: &str) -> Result<SqlitePool, sqlx::Error> {
+    let options = SqliteConnectOptions::from_str(database_url)?
+        .create_if_missing(true)
+        .foreign_keys(true);
+    SqlitePool::connect_with(options).await
+}
+
+// Courses, tutors, students and the rest kept in SQLite. Listings are
+// built with the same SQL as for Postgres; searches are ranked in process,
+// as SQLite has no full-text search like Postgres's.
+pub struct SqliteRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        SqliteRepository { pool }
+    }
+}
+
+fn constraint(err: &sqlx::Error) -> Option<String> {
+    err.as_database_error()
+        .and_then(|err| err.code())
+        .map(|code| code.into_owned())
+}
+
+impl SqliteRepository {
+    // Fails when a tag other than `tag_id` has the name
+    async fn check_tag_name(&self, tag_name: &str, tag_id: i32) -> Result<(), EzyTutorError> {
+        let taken: Option<(i32,)> =
+            sqlx::query_as("SELECT tag_id FROM ezy_tag_c6 WHERE tag_name = ? AND tag_id <> ?")
+                .bind(tag_name)
+                .bind(tag_id)
+                .fetch_optional(&self.pool)
+                .await?;
+        match taken {
+            Some(_) => Err(EzyTutorError::InvalidInput(format!(
+                "Tag {} already exists",
+                tag_name
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    // A page of the courses matching the query, among those `push_scope`
+    // keeps to
+    async fn get_courses_where(
+        &self,
+        query: &CourseQuery,
+        push_scope: impl Fn(&mut QueryBuilder<Sqlite>),
+    ) -> Result<Page<Course>, EzyTutorError> {
+        let page = query.page_request()?;
+        let filters = query.filters();
+        filters.check()?;
+
+        let mut count =
+            QueryBuilder::<Sqlite>::new("SELECT count(*) FROM ezy_course_c6 WHERE true");
+        push_course_filters(&mut count, None, &filters);
+        push_scope(&mut count);
+        let (total,): (i64,) = count.build_query_as().fetch_one(&self.pool).await?;
+
+        let mut select = QueryBuilder::<Sqlite>::new("SELECT * FROM ezy_course_c6 WHERE true");
+        push_course_filters(&mut select, None, &filters);
+        push_scope(&mut select);
+        push_page(&mut select, "course_id", &page);
+        let course_rows: Vec<Course> = select.build_query_as().fetch_all(&self.pool).await?;
+
+        Ok(page.into_page(course_rows, total))
+    }
+}
+
+// SQLite has no SELECT ... FOR UPDATE. Writing to the course row instead
+// takes the write lock on the database as the transaction starts, so that
+// seats are still counted and handed out by one transaction at a time.
+// Returns the capacity of the course.
+async fn lock_course(
+    tx: &mut Transaction<'_, Sqlite>,
+    tutor_id: i32,
+    course_id: i32,
+) -> Result<Option<i32>, EzyTutorError> {
+    let capacity: Option<(Option<i32>,)> = sqlx::query_as(
+        "UPDATE ezy_course_c6 SET course_capacity = course_capacity \
+        WHERE tutor_id = ? AND course_id = ? RETURNING course_capacity",
+    )
+    .bind(tutor_id)
+    .bind(course_id)
+    .fetch_all(&mut *tx)
+    .await?
+    .pop();
+    capacity
+        .map(|(capacity,)| capacity)
+        .ok_or_else(|| EzyTutorError::NotFound("Course id not found".into()))
+}
+
+// The seats left in a course, or None if it has no capacity
+async fn free_seats(
+    tx: &mut Transaction<'_, Sqlite>,
+    course_id: i32,
+    capacity: Option<i32>,
+) -> Result<Option<i64>, EzyTutorError> {
+    let Some(capacity) = capacity else {
+        return Ok(None);
+    };
+    let (active,): (i64,) = sqlx::query_as(
+        "SELECT count(*) FROM ezy_enrollment_c6 WHERE course_id = ? AND status = 'active'",
+    )
+    .bind(course_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    Ok(Some((capacity as i64 - active).max(0)))
+}
+
+async fn promote_waitlist(
+    tx: &mut Transaction<'_, Sqlite>,
+    course_id: i32,
+    capacity: Option<i32>,
+) -> Result<(), EzyTutorError> {
+    let seats = free_seats(tx, course_id, capacity).await?;
+    promote_sql(course_id, seats)
+        .build()
+        .execute(&mut *tx)
+        .await?;
+    Ok(())
+}
+
+#[async_trait]
+impl CourseRepository for SqliteRepository {
+    async fn get_courses_for_tutor(
+        &self,
+        tutor_id: i32,
+        query: &CourseQuery,
+    ) -> Result<Page<Course>, EzyTutorError> {
+        let page = query.page_request()?;
+        let filters = query.filters();
+        filters.check()?;
+
+        let mut count =
+            QueryBuilder::<Sqlite>::new("SELECT count(*) FROM ezy_course_c6 WHERE true");
+        push_course_filters(&mut count, Some(tutor_id), &filters);
+        let (total,): (i64,) = count.build_query_as().fetch_one(&self.pool).await?;
+
+        let mut select = QueryBuilder::<Sqlite>::new("SELECT * FROM ezy_course_c6 WHERE true");
+        push_course_filters(&mut select, Some(tutor_id), &filters);
+        push_page(&mut select, "course_id", &page);
+        let course_rows: Vec<Course> = select.build_query_as().fetch_all(&self.pool).await?;
+
+        Ok(page.into_page(course_rows, total))
+    }
+
+    async fn search_courses(
+        &self,
+        query: &CourseSearchQuery,
+    ) -> Result<Page<CourseSearchResult>, EzyTutorError> {
+        let search = Search::new(query)?;
+        let mut select = QueryBuilder::<Sqlite>::new("SELECT * FROM ezy_course_c6 WHERE true");
+        push_course_filters(&mut select, None, &query.filters());
+        let courses: Vec<Course> = select.build_query_as().fetch_all(&self.pool).await?;
+        Ok(search.page(courses.iter()))
+    }
+
+    async fn get_course_details(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+    ) -> Result<Course, EzyTutorError> {
+        sqlx::query_as("SELECT * FROM ezy_course_c6 WHERE tutor_id = ? AND course_id = ?")
+            .bind(tutor_id)
+            .bind(course_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| EzyTutorError::NotFound("Course id not found".into()))
+    }
+
+    async fn post_new_course(&self, new_course: CreateCourse) -> Result<Course, EzyTutorError> {
+        self.get_tutor_details(new_course.tutor_id).await?;
+        let course_row = sqlx::query_as(
+            "INSERT INTO ezy_course_c6 (tutor_id, course_name, course_description, \
+            course_format, course_structure, course_duration, course_price, course_language, \
+            course_level) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING *",
+        )
+        .bind(new_course.tutor_id)
+        .bind(new_course.course_name)
+        .bind(new_course.course_description)
+        .bind(new_course.course_format)
+        .bind(new_course.course_structure)
+        .bind(new_course.course_duration)
+        .bind(new_course.course_price)
+        .bind(new_course.course_language)
+        .bind(new_course.course_level)
+        .fetch_all(&self.pool)
+        .await?
+        .pop()
+        .ok_or(sqlx::Error::RowNotFound)?;
+        Ok(course_row)
+    }
+
+    async fn update_course_details(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        update_course: UpdateCourse,
+    ) -> Result<Course, EzyTutorError> {
+        let current = self.get_course_details(tutor_id, course_id).await?;
+        let course_row = sqlx::query_as(
+            "UPDATE ezy_course_c6 SET course_name = ?, course_description = ?, \
+            course_format = ?, course_structure = ?, course_duration = ?, course_price = ?, \
+            course_language = ?, course_level = ? WHERE tutor_id = ? AND course_id = ? \
+            RETURNING *",
+        )
+        .bind(update_course.course_name.unwrap_or(current.course_name))
+        .bind(
+            update_course
+                .course_description
+                .or(current.course_description),
+        )
+        .bind(update_course.course_format.or(current.course_format))
+        .bind(update_course.course_structure.or(current.course_structure))
+        .bind(update_course.course_duration.or(current.course_duration))
+        .bind(update_course.course_price.or(current.course_price))
+        .bind(update_course.course_language.or(current.course_language))
+        .bind(update_course.course_level.or(current.course_level))
+        .bind(tutor_id)
+        .bind(course_id)
+        .fetch_all(&self.pool)
+        .await?
+        .pop()
+        .ok_or(sqlx::Error::RowNotFound)?;
+        Ok(course_row)
+    }
+
+    async fn delete_course(&self, tutor_id: i32, course_id: i32) -> Result<String, EzyTutorError> {
+        let deleted = sqlx::query("DELETE FROM ezy_course_c6 WHERE tutor_id = ? AND course_id = ?")
+            .bind(tutor_id)
+            .bind(course_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(format!("Deleted {} record", deleted.rows_affected()))
+    }
+}
+
+#[async_trait]
+impl TutorRepository for SqliteRepository {
+    async fn get_all_tutors(&self, query: &TutorQuery) -> Result<Page<Tutor>, EzyTutorError> {
+        let page = query.page_request()?;
+
+        let (total,): (i64,) = sqlx::query_as("SELECT count(*) FROM ezy_tutor_c6")
+            .fetch_one(&self.pool)
+            .await?;
+
+        let mut select = QueryBuilder::<Sqlite>::new("SELECT * FROM ezy_tutor_c6 WHERE true");
+        push_page(&mut select, "tutor_id", &page);
+        let tutor_rows: Vec<Tutor> = select.build_query_as().fetch_all(&self.pool).await?;
+
+        Ok(page.into_page(tutor_rows, total))
+    }
+
+    async fn get_tutor_details(&self, tutor_id: i32) -> Result<Tutor, EzyTutorError> {
+        sqlx::query_as("SELECT * FROM ezy_tutor_c6 WHERE tutor_id = ?")
+            .bind(tutor_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| EzyTutorError::NotFound("Tutor id not found".into()))
+    }
+
+    async fn post_new_tutor(
+        &self,
+        new_tutor: NewTutor,
+        login: Option<NewUser>,
+    ) -> Result<Tutor, EzyTutorError> {
+        let mut tx = self.pool.begin().await?;
+        let tutor_row: Tutor = sqlx::query_as(
+            "INSERT INTO ezy_tutor_c6 (tutor_name, tutor_pic_url, tutor_profile) \
+            VALUES (?, ?, ?) RETURNING *",
+        )
+        .bind(new_tutor.tutor_name)
+        .bind(new_tutor.tutor_pic_url)
+        .bind(new_tutor.tutor_profile)
+        .fetch_all(&mut tx)
+        .await?
+        .pop()
+        .ok_or(sqlx::Error::RowNotFound)?;
+        if let Some(login) = login {
+            sqlx::query(
+                "INSERT INTO ezy_user_c6 (username, tutor_id, user_password) VALUES (?, ?, ?)",
+            )
+            .bind(&login.username)
+            .bind(tutor_row.tutor_id)
+            .bind(&login.user_password)
+            .execute(&mut tx)
+            .await
+            .map_err(|err| match constraint(&err).as_deref() {
+                Some(PRIMARY_KEY_VIOLATION | UNIQUE_VIOLATION) => {
+                    EzyTutorError::InvalidInput(format!("Username {} is taken", login.username))
+                }
+                _ => err.into(),
+            })?;
+        }
+        tx.commit().await?;
+        Ok(tutor_row)
+    }
+
+    async fn update_tutor_details(
+        &self,
+        tutor_id: i32,
+        update_tutor: UpdateTutor,
+    ) -> Result<Tutor, EzyTutorError> {
+        let current = self.get_tutor_details(tutor_id).await?;
+        let tutor_row = sqlx::query_as(
+            "UPDATE ezy_tutor_c6 SET tutor_name = ?, tutor_pic_url = ?, tutor_profile = ? \
+            WHERE tutor_id = ? RETURNING *",
+        )
+        .bind(update_tutor.tutor_name.unwrap_or(current.tutor_name))
+        .bind(update_tutor.tutor_pic_url.unwrap_or(current.tutor_pic_url))
+        .bind(update_tutor.tutor_profile.unwrap_or(current.tutor_profile))
+        .bind(tutor_id)
+        .fetch_all(&self.pool)
+        .await?
+        .pop()
+        .ok_or(sqlx::Error::RowNotFound)?;
+        Ok(tutor_row)
+    }
+
+    async fn delete_tutor(&self, tutor_id: i32) -> Result<String, EzyTutorError> {
+        let deleted = sqlx::query("DELETE FROM ezy_tutor_c6 WHERE tutor_id = ?")
+            .bind(tutor_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(format!("Deleted {} record", deleted.rows_affected()))
+    }
+
+    async fn get_user(&self, username: &str) -> Result<Option<User>, EzyTutorError> {
+        let user = sqlx::query_as(
+            "SELECT username, tutor_id, student_id, user_password, is_admin FROM ezy_user_c6 \
+            WHERE username = ?",
+        )
+        .bind(username)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(user)
+    }
+}
+
+#[async_trait]
+impl CategoryRepository for SqliteRepository {
+    async fn get_all_categories(&self) -> Result<Vec<Category>, EzyTutorError> {
+        let categories = sqlx::query_as("SELECT * FROM ezy_category_c6 ORDER BY category_id")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(categories)
+    }
+
+    async fn get_category(&self, category_id: i32) -> Result<Category, EzyTutorError> {
+        sqlx::query_as("SELECT * FROM ezy_category_c6 WHERE category_id = ?")
+            .bind(category_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| EzyTutorError::NotFound("Category id not found".into()))
+    }
+
+    async fn post_new_category(
+        &self,
+        new_category: NewCategory,
+    ) -> Result<Category, EzyTutorError> {
+        if let Some(parent_id) = new_category.parent_id {
+            self.get_category(parent_id)
+                .await
+                .map_err(|_| EzyTutorError::NotFound("Parent category id not found".into()))?;
+        }
+        let category_row = sqlx::query_as(
+            "INSERT INTO ezy_category_c6 (category_name, parent_id) VALUES (?, ?) RETURNING *",
+        )
+        .bind(new_category.category_name)
+        .bind(new_category.parent_id)
+        .fetch_all(&self.pool)
+        .await?
+        .pop()
+        .ok_or(sqlx::Error::RowNotFound)?;
+        Ok(category_row)
+    }
+
+    async fn update_category(
+        &self,
+        category_id: i32,
+        update_category: UpdateCategory,
+    ) -> Result<Category, EzyTutorError> {
+        sqlx::query_as(
+            "UPDATE ezy_category_c6 SET category_name = ? WHERE category_id = ? RETURNING *",
+        )
+        .bind(update_category.category_name)
+        .bind(category_id)
+        .fetch_all(&self.pool)
+        .await?
+        .pop()
+        .ok_or_else(|| EzyTutorError::NotFound("Category id not found".into()))
+    }
+
+    async fn delete_category(&self, category_id: i32) -> Result<String, EzyTutorError> {
+        let deleted = sqlx::query("DELETE FROM ezy_category_c6 WHERE category_id = ?")
+            .bind(category_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(format!("Deleted {} record", deleted.rows_affected()))
+    }
+
+    async fn get_courses_in_category(
+        &self,
+        category_id: i32,
+        query: &CourseQuery,
+    ) -> Result<Page<Course>, EzyTutorError> {
+        self.get_category(category_id).await?;
+        self.get_courses_where(query, |sql| push_in_category(sql, category_id))
+            .await
+    }
+
+    async fn get_course_categories(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+    ) -> Result<Vec<Category>, EzyTutorError> {
+        self.get_course_details(tutor_id, course_id).await?;
+        let categories = sqlx::query_as(
+            "SELECT category_id, category_name, parent_id FROM ezy_category_c6 \
+            JOIN ezy_course_category_c6 USING (category_id) WHERE course_id = ? \
+            ORDER BY category_id",
+        )
+        .bind(course_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(categories)
+    }
+
+    async fn add_course_category(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        category_id: i32,
+    ) -> Result<(), EzyTutorError> {
+        self.get_course_details(tutor_id, course_id).await?;
+        sqlx::query(
+            "INSERT INTO ezy_course_category_c6 (course_id, category_id) VALUES (?, ?) \
+            ON CONFLICT DO NOTHING",
+        )
+        .bind(course_id)
+        .bind(category_id)
+        .execute(&self.pool)
+        .await
+        .map_err(|err| match constraint(&err).as_deref() {
+            Some(FOREIGN_KEY_VIOLATION) => EzyTutorError::NotFound("Category id not found".into()),
+            _ => err.into(),
+        })?;
+        Ok(())
+    }
+
+    async fn remove_course_category(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        category_id: i32,
+    ) -> Result<(), EzyTutorError> {
+        self.get_course_details(tutor_id, course_id).await?;
+        sqlx::query("DELETE FROM ezy_course_category_c6 WHERE course_id = ? AND category_id = ?")
+            .bind(course_id)
+            .bind(category_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TagRepository for SqliteRepository {
+    async fn get_all_tags(&self) -> Result<Vec<Tag>, EzyTutorError> {
+        let tags = sqlx::query_as("SELECT * FROM ezy_tag_c6 ORDER BY tag_name")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(tags)
+    }
+
+    async fn get_tag(&self, tag_id: i32) -> Result<Tag, EzyTutorError> {
+        sqlx::query_as("SELECT * FROM ezy_tag_c6 WHERE tag_id = ?")
+            .bind(tag_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| EzyTutorError::NotFound("Tag id not found".into()))
+    }
+
+    async fn get_tag_counts(&self) -> Result<Vec<TagCount>, EzyTutorError> {
+        let counts = sqlx::query_as(
+            "SELECT tag_id, tag_name, count(course_id) AS course_count FROM ezy_tag_c6 \
+            LEFT JOIN ezy_course_tag_c6 USING (tag_id) GROUP BY tag_id, tag_name \
+            ORDER BY course_count DESC, tag_name",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(counts)
+    }
+
+    async fn post_new_tag(&self, new_tag: NewTag) -> Result<Tag, EzyTutorError> {
+        self.check_tag_name(&new_tag.tag_name, 0).await?;
+        let tag_row = sqlx::query_as("INSERT INTO ezy_tag_c6 (tag_name) VALUES (?) RETURNING *")
+            .bind(&new_tag.tag_name)
+            .fetch_all(&self.pool)
+            .await?
+            .pop()
+            .ok_or(sqlx::Error::RowNotFound)?;
+        Ok(tag_row)
+    }
+
+    async fn update_tag(&self, tag_id: i32, update_tag: UpdateTag) -> Result<Tag, EzyTutorError> {
+        self.check_tag_name(&update_tag.tag_name, tag_id).await?;
+        sqlx::query_as("UPDATE ezy_tag_c6 SET tag_name = ? WHERE tag_id = ? RETURNING *")
+            .bind(&update_tag.tag_name)
+            .bind(tag_id)
+            .fetch_all(&self.pool)
+            .await?
+            .pop()
+            .ok_or_else(|| EzyTutorError::NotFound("Tag id not found".into()))
+    }
+
+    async fn delete_tag(&self, tag_id: i32) -> Result<String, EzyTutorError> {
+        let deleted = sqlx::query("DELETE FROM ezy_tag_c6 WHERE tag_id = ?")
+            .bind(tag_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(format!("Deleted {} record", deleted.rows_affected()))
+    }
+
+    async fn get_courses_with_tag(
+        &self,
+        tag_id: i32,
+        query: &CourseQuery,
+    ) -> Result<Page<Course>, EzyTutorError> {
+        self.get_tag(tag_id).await?;
+        self.get_courses_where(query, |sql| push_with_tag(sql, tag_id))
+            .await
+    }
+
+    async fn get_course_tags(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+    ) -> Result<Vec<Tag>, EzyTutorError> {
+        self.get_course_details(tutor_id, course_id).await?;
+        let tags = sqlx::query_as(
+            "SELECT tag_id, tag_name FROM ezy_tag_c6 JOIN ezy_course_tag_c6 USING (tag_id) \
+            WHERE course_id = ? ORDER BY tag_name",
+        )
+        .bind(course_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(tags)
+    }
+
+    async fn add_course_tag(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        tag_id: i32,
+    ) -> Result<(), EzyTutorError> {
+        self.get_course_details(tutor_id, course_id).await?;
+        sqlx::query(
+            "INSERT INTO ezy_course_tag_c6 (course_id, tag_id) VALUES (?, ?) \
+            ON CONFLICT DO NOTHING",
+        )
+        .bind(course_id)
+        .bind(tag_id)
+        .execute(&self.pool)
+        .await
+        .map_err(|err| match constraint(&err).as_deref() {
+            Some(FOREIGN_KEY_VIOLATION) => EzyTutorError::NotFound("Tag id not found".into()),
+            _ => err.into(),
+        })?;
+        Ok(())
+    }
+
+    async fn remove_course_tag(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        tag_id: i32,
+    ) -> Result<(), EzyTutorError> {
+        self.get_course_details(tutor_id, course_id).await?;
+        sqlx::query("DELETE FROM ezy_course_tag_c6 WHERE course_id = ? AND tag_id = ?")
+            .bind(course_id)
+            .bind(tag_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl StudentRepository for SqliteRepository {
+    async fn get_student(&self, student_id: i32) -> Result<Student, EzyTutorError> {
+        sqlx::query_as("SELECT * FROM ezy_student_c6 WHERE student_id = ?")
+            .bind(student_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| EzyTutorError::NotFound("Student id not found".into()))
+    }
+
+    async fn post_new_student(
+        &self,
+        new_student: NewStudent,
+        login: NewUser,
+    ) -> Result<Student, EzyTutorError> {
+        let mut tx = self.pool.begin().await?;
+        let student: Student =
+            sqlx::query_as("INSERT INTO ezy_student_c6 (student_name) VALUES (?) RETURNING *")
+                .bind(new_student.student_name)
+                .fetch_all(&mut tx)
+                .await?
+                .pop()
+                .ok_or(sqlx::Error::RowNotFound)?;
+        sqlx::query(
+            "INSERT INTO ezy_user_c6 (username, student_id, user_password) VALUES (?, ?, ?)",
+        )
+        .bind(&login.username)
+        .bind(student.student_id)
+        .bind(&login.user_password)
+        .execute(&mut tx)
+        .await
+        .map_err(|err| match constraint(&err).as_deref() {
+            Some(PRIMARY_KEY_VIOLATION | UNIQUE_VIOLATION) => {
+                EzyTutorError::InvalidInput(format!("Username {} is taken", login.username))
+            }
+            _ => err.into(),
+        })?;
+        tx.commit().await?;
+        Ok(student)
+    }
+
+    async fn get_student_courses(
+        &self,
+        student_id: i32,
+        query: &EnrollmentQuery,
+    ) -> Result<Vec<StudentCourse>, EzyTutorError> {
+        self.get_student(student_id).await?;
+        let courses = student_courses_sql(student_id, query)
+            .build_query_as()
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(courses)
+    }
+}
+
+#[async_trait]
+impl EnrollmentRepository for SqliteRepository {
+    async fn enroll(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        student_id: i32,
+    ) -> Result<Enrollment, EzyTutorError> {
+        let mut tx = self.pool.begin().await?;
+        let capacity = lock_course(&mut tx, tutor_id, course_id).await?;
+        let student: Option<(i32,)> =
+            sqlx::query_as("SELECT student_id FROM ezy_student_c6 WHERE student_id = ?")
+                .bind(student_id)
+                .fetch_optional(&mut tx)
+                .await?;
+        if student.is_none() {
+            return Err(EzyTutorError::NotFound("Student id not found".into()));
+        }
+        let enrolled: Option<(i32,)> = sqlx::query_as(
+            "SELECT enrollment_id FROM ezy_enrollment_c6 WHERE course_id = ? AND student_id = ? \
+            AND status IN ('pending', 'active')",
+        )
+        .bind(course_id)
+        .bind(student_id)
+        .fetch_optional(&mut tx)
+        .await?;
+        if enrolled.is_some() {
+            return Err(EzyTutorError::InvalidInput(format!(
+                "Student {} is already enrolled in course {}",
+                student_id, course_id
+            )));
+        }
+        let status = match free_seats(&mut tx, course_id, capacity).await? {
+            Some(0) => EnrollmentStatus::Pending,
+            _ => EnrollmentStatus::Active,
+        };
+        let enrollment = sqlx::query_as(&format!(
+            "INSERT INTO ezy_enrollment_c6 (course_id, student_id, status) VALUES (?, ?, ?) \
+            RETURNING {}",
+            ENROLLMENT_COLUMNS
+        ))
+        .bind(course_id)
+        .bind(student_id)
+        .bind(status)
+        .fetch_all(&mut tx)
+        .await?
+        .pop()
+        .ok_or(sqlx::Error::RowNotFound)?;
+        tx.commit().await?;
+        Ok(enrollment)
+    }
+
+    async fn get_enrollment(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        enrollment_id: i32,
+    ) -> Result<Enrollment, EzyTutorError> {
+        self.get_course_details(tutor_id, course_id).await?;
+        sqlx::query_as(&format!(
+            "SELECT {} FROM ezy_enrollment_c6 WHERE course_id = ? AND enrollment_id = ?",
+            ENROLLMENT_COLUMNS
+        ))
+        .bind(course_id)
+        .bind(enrollment_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| EzyTutorError::NotFound("Enrollment id not found".into()))
+    }
+
+    async fn get_course_roster(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        query: &EnrollmentQuery,
+    ) -> Result<Vec<RosterEntry>, EzyTutorError> {
+        self.get_course_details(tutor_id, course_id).await?;
+        let roster = roster_sql(course_id, query)
+            .build_query_as()
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(roster)
+    }
+
+    async fn update_enrollment_status(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        enrollment_id: i32,
+        status: EnrollmentStatus,
+    ) -> Result<Enrollment, EzyTutorError> {
+        let mut tx = self.pool.begin().await?;
+        let capacity = lock_course(&mut tx, tutor_id, course_id).await?;
+        let current: Enrollment = sqlx::query_as(&format!(
+            "SELECT {} FROM ezy_enrollment_c6 WHERE course_id = ? AND enrollment_id = ?",
+            ENROLLMENT_COLUMNS
+        ))
+        .bind(course_id)
+        .bind(enrollment_id)
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or_else(|| EzyTutorError::NotFound("Enrollment id not found".into()))?;
+        current.status.check_change(status)?;
+        let enrollment = sqlx::query_as(&format!(
+            "UPDATE ezy_enrollment_c6 SET status = ? WHERE enrollment_id = ? RETURNING {}",
+            ENROLLMENT_COLUMNS
+        ))
+        .bind(status)
+        .bind(enrollment_id)
+        .fetch_all(&mut tx)
+        .await?
+        .pop()
+        .ok_or(sqlx::Error::RowNotFound)?;
+        if current.status == EnrollmentStatus::Active {
+            promote_waitlist(&mut tx, course_id, capacity).await?;
+        }
+        tx.commit().await?;
+        Ok(enrollment)
+    }
+
+    async fn set_course_capacity(
+        &self,
+        tutor_id: i32,
+        course_id: i32,
+        capacity: Option<i32>,
+    ) -> Result<Course, EzyTutorError> {
+        let mut tx = self.pool.begin().await?;
+        lock_course(&mut tx, tutor_id, course_id).await?;
+        let course = sqlx::query_as(
+            "UPDATE ezy_course_c6 SET course_capacity = ? WHERE course_id = ? RETURNING *",
+        )
+        .bind(capacity)
+        .bind(course_id)
+        .fetch_all(&mut tx)
+        .await?
+        .pop()
+        .ok_or(sqlx::Error::RowNotFound)?;
+        promote_waitlist(&mut tx, course_id, capacity).await?;
+        tx.commit().await?;
+        Ok(course)
+    }
+}
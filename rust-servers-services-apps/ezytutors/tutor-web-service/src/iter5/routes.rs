@@ -1,31 +1,284 @@
-use crate::handlers::{course::*, general::*, tutor::*};
-use actix_web::web;
+use crate::handlers::{
+    auth::*, category::*, course::*, enrollment::*, general::*, student::*, tag::*, tutor::*,
+};
+use crate::openapi::openapi_json;
+use actix_web::http::Method;
+use actix_web::{web, Route};
+use swagger_ui_dist::{ApiDefinition, OpenApiSource};
+
+// A route of the API. Every one of them must be described in the OpenAPI
+// document, which the tests in openapi.rs check.
+pub struct ApiRoute {
+    pub method: Method,
+    pub path: &'static str,
+    handler: fn(Route) -> Route,
+}
+
+pub const GENERAL_ROUTES: &[ApiRoute] = &[ApiRoute {
+    method: Method::GET,
+    path: "/health",
+    handler: |route| route.to(health_check_handler),
+}];
+
+pub const AUTH_ROUTES: &[ApiRoute] = &[ApiRoute {
+    method: Method::POST,
+    path: "/auth/token",
+    handler: |route| route.to(issue_token),
+}];
+
+pub const COURSE_ROUTES: &[ApiRoute] = &[
+    ApiRoute {
+        method: Method::POST,
+        path: "/courses",
+        handler: |route| route.to(post_new_course),
+    },
+    // Before /courses/{tutor_id}, which would otherwise take "search" for an id
+    ApiRoute {
+        method: Method::GET,
+        path: "/courses/search",
+        handler: |route| route.to(search_courses),
+    },
+    ApiRoute {
+        method: Method::GET,
+        path: "/courses/{tutor_id}",
+        handler: |route| route.to(get_courses_for_tutor),
+    },
+    ApiRoute {
+        method: Method::GET,
+        path: "/courses/{tutor_id}/{course_id}",
+        handler: |route| route.to(get_course_details),
+    },
+    ApiRoute {
+        method: Method::PUT,
+        path: "/courses/{tutor_id}/{course_id}",
+        handler: |route| route.to(update_course_details),
+    },
+    ApiRoute {
+        method: Method::DELETE,
+        path: "/courses/{tutor_id}/{course_id}",
+        handler: |route| route.to(delete_course),
+    },
+];
+
+pub const CATEGORY_ROUTES: &[ApiRoute] = &[
+    ApiRoute {
+        method: Method::POST,
+        path: "/categories",
+        handler: |route| route.to(post_new_category),
+    },
+    ApiRoute {
+        method: Method::GET,
+        path: "/categories",
+        handler: |route| route.to(get_all_categories),
+    },
+    ApiRoute {
+        method: Method::GET,
+        path: "/categories/{category_id}",
+        handler: |route| route.to(get_category),
+    },
+    ApiRoute {
+        method: Method::PUT,
+        path: "/categories/{category_id}",
+        handler: |route| route.to(update_category),
+    },
+    ApiRoute {
+        method: Method::DELETE,
+        path: "/categories/{category_id}",
+        handler: |route| route.to(delete_category),
+    },
+    ApiRoute {
+        method: Method::GET,
+        path: "/categories/{category_id}/courses",
+        handler: |route| route.to(get_courses_in_category),
+    },
+    ApiRoute {
+        method: Method::GET,
+        path: "/courses/{tutor_id}/{course_id}/categories",
+        handler: |route| route.to(get_course_categories),
+    },
+    ApiRoute {
+        method: Method::PUT,
+        path: "/courses/{tutor_id}/{course_id}/categories/{category_id}",
+        handler: |route| route.to(add_course_category),
+    },
+    ApiRoute {
+        method: Method::DELETE,
+        path: "/courses/{tutor_id}/{course_id}/categories/{category_id}",
+        handler: |route| route.to(remove_course_category),
+    },
+];
+
+pub const TAG_ROUTES: &[ApiRoute] = &[
+    ApiRoute {
+        method: Method::POST,
+        path: "/tags",
+        handler: |route| route.to(post_new_tag),
+    },
+    ApiRoute {
+        method: Method::GET,
+        path: "/tags",
+        handler: |route| route.to(get_all_tags),
+    },
+    // Before /tags/{tag_id}, as with /courses/search
+    ApiRoute {
+        method: Method::GET,
+        path: "/tags/counts",
+        handler: |route| route.to(get_tag_counts),
+    },
+    ApiRoute {
+        method: Method::GET,
+        path: "/tags/{tag_id}",
+        handler: |route| route.to(get_tag),
+    },
+    ApiRoute {
+        method: Method::PUT,
+        path: "/tags/{tag_id}",
+        handler: |route| route.to(update_tag),
+    },
+    ApiRoute {
+        method: Method::DELETE,
+        path: "/tags/{tag_id}",
+        handler: |route| route.to(delete_tag),
+    },
+    ApiRoute {
+        method: Method::GET,
+        path: "/tags/{tag_id}/courses",
+        handler: |route| route.to(get_courses_with_tag),
+    },
+    ApiRoute {
+        method: Method::GET,
+        path: "/courses/{tutor_id}/{course_id}/tags",
+        handler: |route| route.to(get_course_tags),
+    },
+    ApiRoute {
+        method: Method::PUT,
+        path: "/courses/{tutor_id}/{course_id}/tags/{tag_id}",
+        handler: |route| route.to(add_course_tag),
+    },
+    ApiRoute {
+        method: Method::DELETE,
+        path: "/courses/{tutor_id}/{course_id}/tags/{tag_id}",
+        handler: |route| route.to(remove_course_tag),
+    },
+];
+
+pub const TUTOR_ROUTES: &[ApiRoute] = &[
+    ApiRoute {
+        method: Method::POST,
+        path: "/tutors/",
+        handler: |route| route.to(post_new_tutor),
+    },
+    ApiRoute {
+        method: Method::GET,
+        path: "/tutors/",
+        handler: |route| route.to(get_all_tutors),
+    },
+    ApiRoute {
+        method: Method::GET,
+        path: "/tutors/{tutor_id}",
+        handler: |route| route.to(get_tutor_details),
+    },
+    ApiRoute {
+        method: Method::PUT,
+        path: "/tutors/{tutor_id}",
+        handler: |route| route.to(update_tutor_details),
+    },
+    ApiRoute {
+        method: Method::DELETE,
+        path: "/tutors/{tutor_id}",
+        handler: |route| route.to(delete_tutor),
+    },
+];
+
+pub const STUDENT_ROUTES: &[ApiRoute] = &[
+    ApiRoute {
+        method: Method::POST,
+        path: "/students",
+        handler: |route| route.to(post_new_student),
+    },
+    ApiRoute {
+        method: Method::GET,
+        path: "/students/{student_id}",
+        handler: |route| route.to(get_student),
+    },
+    ApiRoute {
+        method: Method::GET,
+        path: "/students/{student_id}/courses",
+        handler: |route| route.to(get_student_courses),
+    },
+];
+
+pub const ENROLLMENT_ROUTES: &[ApiRoute] = &[
+    ApiRoute {
+        method: Method::POST,
+        path: "/courses/{tutor_id}/{course_id}/enrollments",
+        handler: |route| route.to(enroll),
+    },
+    ApiRoute {
+        method: Method::GET,
+        path: "/courses/{tutor_id}/{course_id}/enrollments",
+        handler: |route| route.to(get_course_roster),
+    },
+    ApiRoute {
+        method: Method::PUT,
+        path: "/courses/{tutor_id}/{course_id}/enrollments/{enrollment_id}",
+        handler: |route| route.to(update_enrollment),
+    },
+    ApiRoute {
+        method: Method::PUT,
+        path: "/courses/{tutor_id}/{course_id}/capacity",
+        handler: |route| route.to(set_course_capacity),
+    },
+];
+
+fn register(cfg: &mut web::ServiceConfig, routes: &[ApiRoute]) {
+    for route in routes {
+        cfg.route(
+            route.path,
+            (route.handler)(web::method(route.method.clone())),
+        );
+    }
+}
 
 pub fn general_routes(cfg: &mut web::ServiceConfig) {
-    cfg.route("/health", web::get().to(health_check_handler));
+    register(cfg, GENERAL_ROUTES);
+}
+
+pub fn auth_routes(cfg: &mut web::ServiceConfig) {
+    register(cfg, AUTH_ROUTES);
 }
 
 pub fn course_routes(cfg: &mut web::ServiceConfig) {
-    cfg.service(
-        web::scope("/courses")
-            .route("", web::post().to(post_new_course))
-            .route("/{tutor_id}", web::get().to(get_courses_for_tutor))
-            .route("/{tutor_id}/{course_id}", web::get().to(get_course_details))
-            .route(
-                "/{tutor_id}/{course_id}",
-                web::put().to(update_course_details),
-            )
-            .route("/{tutor_id}/{course_id}", web::delete().to(delete_course)),
-    );
+    register(cfg, COURSE_ROUTES);
 }
 
 pub fn tutor_routes(cfg: &mut web::ServiceConfig) {
-    cfg.service(
-        web::scope("/tutors")
-            .route("/", web::post().to(post_new_tutor))
-            .route("/", web::get().to(get_all_tutors))
-            .route("/{tutor_id}", web::get().to(get_tutor_details))
-            .route("/{tutor_id}", web::put().to(update_tutor_details))
-            .route("/{tutor_id}", web::delete().to(delete_tutor)),
-    );
+    register(cfg, TUTOR_ROUTES);
+}
+
+pub fn category_routes(cfg: &mut web::ServiceConfig) {
+    register(cfg, CATEGORY_ROUTES);
+}
+
+pub fn tag_routes(cfg: &mut web::ServiceConfig) {
+    register(cfg, TAG_ROUTES);
+}
+
+pub fn student_routes(cfg: &mut web::ServiceConfig) {
+    register(cfg, STUDENT_ROUTES);
+}
+
+pub fn enrollment_routes(cfg: &mut web::ServiceConfig) {
+    register(cfg, ENROLLMENT_ROUTES);
+}
+
+// The OpenAPI document at /openapi.json, and the Swagger UI page showing
+// it at /docs/
+pub fn docs_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/openapi.json", web::get().to(openapi_json));
+    cfg.service(swagger_ui_dist::generate_scope(ApiDefinition {
+        uri_prefix: "/docs",
+        api_definition: OpenApiSource::Uri("/openapi.json"),
+        title: Some("EzyTutors API"),
+    }));
 }
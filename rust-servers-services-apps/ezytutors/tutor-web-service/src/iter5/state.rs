@@ -1,8 +1,43 @@
-use sqlx::postgres::PgPool;
-use std::sync::Mutex;
+use crate::repository::{
+    CategoryRepository, CourseRepository, EnrollmentRepository, StudentRepository, TagRepository,
+    TutorRepository,
+};
+use std::sync::{Arc, Mutex};
 
 pub struct AppState {
     pub health_check_response: String,
     pub visit_count: Mutex<u32>,
-    pub db: PgPool,
+    pub courses: Arc<dyn CourseRepository>,
+    pub tutors: Arc<dyn TutorRepository>,
+    pub categories: Arc<dyn CategoryRepository>,
+    pub tags: Arc<dyn TagRepository>,
+    pub students: Arc<dyn StudentRepository>,
+    pub enrollments: Arc<dyn EnrollmentRepository>,
+}
+
+impl AppState {
+    // State for handlers that keep courses, tutors, students and the rest
+    // in `repository`
+    pub fn new<R>(health_check_response: &str, repository: R) -> Self
+    where
+        R: CourseRepository
+            + TutorRepository
+            + CategoryRepository
+            + TagRepository
+            + StudentRepository
+            + EnrollmentRepository
+            + 'static,
+    {
+        let repository = Arc::new(repository);
+        AppState {
+            health_check_response: health_check_response.to_string(),
+            visit_count: Mutex::new(0),
+            courses: repository.clone(),
+            tutors: repository.clone(),
+            categories: repository.clone(),
+            tags: repository.clone(),
+            students: repository.clone(),
+            enrollments: repository,
+        }
+    }
 }